@@ -1,6 +1,13 @@
+#[cfg(not(target_arch = "wasm32"))]
 use std::{sync::Arc, thread, time::Duration};
+#[cfg(not(target_arch = "wasm32"))]
 use xcap::Monitor;
 
+// 浏览器里没有线程，录制器不能移动到其他线程
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
     let monitor = Monitor::from_point(100, 100).unwrap();
 
@@ -0,0 +1,36 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use xcap::{FrameSink, Monitor, StreamSink};
+
+// 浏览器里没有线程，也不能启动 ffmpeg
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let monitor = Monitor::from_point(100, 100).unwrap();
+
+    let video_recorder = Arc::new(monitor.video_recorder().unwrap());
+    let sink = Arc::new(Mutex::new(
+        StreamSink::rtmp("rtmp://localhost/live/xcap").with_frame_rate(monitor.frequency() as u32),
+    ));
+
+    let video_recorder_clone = video_recorder.clone();
+    let sink_clone = sink.clone();
+    thread::spawn(move || {
+        video_recorder_clone
+            .on_frame(move |frame| sink_clone.lock()?.write_frame(&frame))
+            .unwrap();
+    });
+
+    video_recorder.start().unwrap();
+    thread::sleep(Duration::from_secs(10));
+    video_recorder.stop().unwrap();
+
+    sink.lock().unwrap().finish().unwrap();
+}
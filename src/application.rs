@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+
+use crate::{error::XCapResult, Window};
+
+/// A process and its windows, the way a task switcher groups them.
+#[derive(Debug, Clone)]
+pub struct Application {
+    pid: u32,
+    exe_path: Option<PathBuf>,
+    name: String,
+    windows: Vec<Window>,
+}
+
+impl Application {
+    /// The process id, 0 for native Wayland windows which are grouped by app id instead.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+    /// The executable path, `None` when the process cannot be inspected.
+    pub fn exe_path(&self) -> Option<&Path> {
+        self.exe_path.as_deref()
+    }
+    /// The app name of the topmost window.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// The windows of the application, sorted by z coordinate.
+    pub fn windows(&self) -> &[Window] {
+        &self.windows
+    }
+}
+
+/// List the applications with windows, the application with the topmost window first.
+pub fn applications() -> XCapResult<Vec<Application>> {
+    let mut applications: Vec<Application> = Vec::new();
+
+    // Window::all 已经按 z 排序，分组后顺序不变
+    for window in Window::all()? {
+        let application = applications.iter_mut().find(|application| {
+            application.pid == window.pid()
+                && (window.pid() != 0 || application.name == window.app_name())
+        });
+
+        match application {
+            Some(application) => application.windows.push(window),
+            None => applications.push(Application {
+                pid: window.pid(),
+                exe_path: window.exe_path().ok(),
+                name: window.app_name().to_string(),
+                windows: vec![window],
+            }),
+        }
+    }
+
+    Ok(applications)
+}
@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use crate::{error::XCapError, platform::impl_monitor::ImplMonitor};
+
+/// A capture backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// X11 through xcb, also used for XWayland.
+    X11,
+    /// The xdg-desktop-portal Screenshot interface, also without XWayland.
+    Wayland,
+    /// `zwlr_screencopy_manager_v1` on wlroots based compositors, needs the
+    /// `wlr-screencopy` feature.
+    WlrScreencopy,
+    /// `ext_image_copy_capture_manager_v1`, needs the `ext-image-copy-capture` feature.
+    ExtImageCopyCapture,
+    /// NVIDIA NvFBC on X11, needs the `nvfbc` feature.
+    NvFbc,
+    /// KMS framebuffers, when no display server is running.
+    Drm,
+    /// `/dev/fb*` devices.
+    Fbdev,
+    /// GDI `BitBlt` and `PrintWindow`.
+    Gdi,
+    /// CoreGraphics `CGDisplayCreateImage` and `CGWindowListCreateImage`.
+    CoreGraphics,
+    /// `navigator.mediaDevices.getDisplayMedia` in the browser, streaming only.
+    DisplayMedia,
+    /// A [`CustomBackend`](crate::CustomBackend) registered by the application.
+    Custom,
+}
+
+impl FromStr for Backend {
+    type Err = XCapError;
+
+    /// Parse a backend name, e.g. from a config file or an environment variable.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "x11" | "xorg" => Ok(Backend::X11),
+            "wayland" | "portal" => Ok(Backend::Wayland),
+            "wlr-screencopy" | "wlr" => Ok(Backend::WlrScreencopy),
+            "ext-image-copy-capture" | "ext" => Ok(Backend::ExtImageCopyCapture),
+            "nvfbc" => Ok(Backend::NvFbc),
+            "drm" | "kms" => Ok(Backend::Drm),
+            "fbdev" => Ok(Backend::Fbdev),
+            "gdi" => Ok(Backend::Gdi),
+            "coregraphics" | "cg" => Ok(Backend::CoreGraphics),
+            "displaymedia" | "web" => Ok(Backend::DisplayMedia),
+            _ => Err(XCapError::new(format!("Unknown backend {}", s))),
+        }
+    }
+}
+
+/// The backend [`Monitor::all`](crate::Monitor::all) and monitor captures use in the current
+/// session, detected at runtime.
+pub fn backend() -> Backend {
+    ImplMonitor::backend()
+}
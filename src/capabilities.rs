@@ -0,0 +1,92 @@
+use crate::{backend::Backend, error::Remediation, platform::impl_monitor::ImplMonitor};
+
+/// Something that currently makes captures fail or come out black.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inhibition {
+    /// The secure desktop of a UAC prompt or the lock screen is shown, until it closes.
+    SecureDesktop,
+    /// The platform denies capture until the user grants access.
+    PermissionDenied(Remediation),
+    /// The display server does not offer capture, e.g. a Wayland session without
+    /// xdg-desktop-portal.
+    CompositorRefused(String),
+    /// Protected content, e.g. a DRM video, is masked out of captures.
+    ProtectedContent,
+}
+
+/// What capture can do in the current session, see [`capabilities`].
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// The backend monitor captures use, see [`crate::backend`].
+    pub backend: Backend,
+    /// Whether [`crate::Window::all`] lists windows.
+    pub window_enumeration: bool,
+    /// Whether windows can be captured on their own.
+    pub window_capture: bool,
+    /// Whether window captures include the parts covered by other windows.
+    pub occluded_window_capture: bool,
+    /// Whether [`crate::VideoRecorder`] streams frames.
+    pub streaming: bool,
+    /// Whether [`crate::Pointer`] reports the cursor, to draw it onto captures, which never
+    /// include it.
+    pub cursor_position: bool,
+    /// Whether streams report the changed areas of a frame, no backend does yet.
+    pub dirty_rects: bool,
+    /// Whether captures keep HDR content, no backend does yet, HDR monitors are captured tone
+    /// mapped to SDR.
+    pub hdr: bool,
+    /// Why captures would fail or be black right now, empty when nothing is known to be in
+    /// the way.
+    pub inhibitions: Vec<Inhibition>,
+}
+
+impl Capabilities {
+    fn new(backend: Backend, inhibitions: Vec<Inhibition>) -> Capabilities {
+        // 窗口只能通过 X11、GDI 和 CoreGraphics 列出
+        let windows = matches!(
+            backend,
+            Backend::X11 | Backend::NvFbc | Backend::Gdi | Backend::CoreGraphics
+        );
+
+        Capabilities {
+            backend,
+            window_enumeration: windows,
+            window_capture: windows,
+            occluded_window_capture: matches!(backend, Backend::Gdi | Backend::CoreGraphics),
+            streaming: matches!(backend, Backend::Gdi | Backend::DisplayMedia),
+            cursor_position: windows,
+            dirty_rects: false,
+            hdr: false,
+            inhibitions,
+        }
+    }
+
+    pub fn is_inhibited(&self) -> bool {
+        !self.inhibitions.is_empty()
+    }
+}
+
+/// Inspect the capture capabilities of the current session, e.g. to adapt the UI to the
+/// platform, or to warn before a recording starts that would only contain black frames. The
+/// checks are cheap but not free, call it when the user is about to capture rather than per
+/// frame.
+pub fn capabilities() -> Capabilities {
+    Capabilities::new(ImplMonitor::backend(), ImplMonitor::inhibitions())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_capabilities() {
+        let gdi = Capabilities::new(Backend::Gdi, Vec::new());
+        assert!(gdi.occluded_window_capture && gdi.streaming && !gdi.is_inhibited());
+
+        let portal = Capabilities::new(
+            Backend::Wayland,
+            vec![Inhibition::PermissionDenied(Remediation::SharingDialog)],
+        );
+        assert!(!portal.window_enumeration && !portal.streaming && portal.is_inhibited());
+    }
+}
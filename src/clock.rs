@@ -0,0 +1,95 @@
+use std::{
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+use crate::{error::XCapResult, platform::impl_vblank::ImplVblank, Monitor};
+
+/// Paces a capture loop at a frame rate.
+///
+/// A plain clock sleeps to fixed deadlines. A vsync clock wakes up on the vblank of a monitor
+/// closest to every deadline, through DRM vblank events on Linux, `IDXGIOutput::WaitForVBlank`
+/// on Windows and a `CVDisplayLink` on macOS. Frames are then acquired right after the screen
+/// was refreshed, which removes tearing and the judder of a timer beating against the refresh
+/// rate. Frame rates dividing the refresh rate, e.g. 30 on a 60 Hz monitor, pace evenly.
+pub struct FrameClock {
+    interval: Duration,
+    next: Option<Instant>,
+    vblank: Option<(ImplVblank, Duration)>,
+}
+
+impl fmt::Debug for FrameClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameClock")
+            .field("interval", &self.interval)
+            .field("vsync", &self.vblank.is_some())
+            .finish()
+    }
+}
+
+impl FrameClock {
+    pub fn new(frame_rate: u32) -> FrameClock {
+        FrameClock {
+            interval: Duration::from_secs(1) / frame_rate.max(1),
+            next: None,
+            vblank: None,
+        }
+    }
+
+    /// A clock aligned to the vblank of `monitor`, errors when the platform cannot wait for it,
+    /// e.g. without access to the DRM device on Linux.
+    pub fn vsync(monitor: &Monitor, frame_rate: u32) -> XCapResult<FrameClock> {
+        let vblank = ImplVblank::new(&monitor.impl_monitor)?;
+        let refresh_period = match monitor.frequency() {
+            frequency if frequency > 0.0 => Duration::from_secs_f32(1.0 / frequency),
+            _ => Duration::from_millis(16),
+        };
+
+        Ok(FrameClock {
+            vblank: Some((vblank, refresh_period)),
+            ..FrameClock::new(frame_rate)
+        })
+    }
+
+    pub fn is_vsync(&self) -> bool {
+        self.vblank.is_some()
+    }
+
+    /// Wait for the next frame and return when it started. The first call returns immediately,
+    /// a late caller is not made to catch up with the frames it missed.
+    pub fn tick(&mut self) -> Instant {
+        let now = Instant::now();
+        let next = match self.next {
+            Some(next) if next > now => next,
+            _ => {
+                self.next = Some(now + self.interval);
+                return now;
+            }
+        };
+
+        let frame_time = match &self.vblank {
+            Some((vblank, refresh_period)) => {
+                // 提前半个刷新周期醒来，等待离截止时间最近的 vblank
+                thread::sleep(next.saturating_duration_since(now + *refresh_period / 2));
+                match vblank.wait() {
+                    Ok(()) => Instant::now(),
+                    Err(err) => {
+                        log::warn!("Wait for vblank failed, falling back to a timer: {}", err);
+                        self.vblank = None;
+                        thread::sleep(next.saturating_duration_since(Instant::now()));
+                        next
+                    }
+                }
+            }
+            None => {
+                thread::sleep(next - now);
+                next
+            }
+        };
+
+        // vsync 时以实际的 vblank 为基准，避免与刷新率产生拍频
+        self.next = Some(frame_time + self.interval);
+
+        frame_time
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::OnceLock;
+
+use image::{Rgba32FImage, RgbaImage};
+
+/// Color space of the pixels returned by a capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorSpace {
+    Srgb,
+    /// Wide gamut P3 primaries with the sRGB transfer curve, used by most modern Macs.
+    DisplayP3,
+    /// Linear sRGB primaries with extended range, used by HDR sources.
+    ScRgb,
+    /// The platform does not report a color space, most likely sRGB.
+    #[default]
+    Unknown,
+}
+
+impl ColorSpace {
+    pub fn transfer_function(&self) -> TransferFunction {
+        match self {
+            ColorSpace::ScRgb => TransferFunction::Linear,
+            _ => TransferFunction::Srgb,
+        }
+    }
+}
+
+/// Transfer function (EOTF) used to encode the 8-bit pixels returned by a capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferFunction {
+    /// The piecewise sRGB curve.
+    Srgb,
+    /// A pure power curve, e.g. `Gamma(2.2)`.
+    Gamma(f32),
+    /// Already linear light.
+    Linear,
+}
+
+impl TransferFunction {
+    /// Decode an encoded value in `0.0..=1.0` to linear light.
+    pub fn to_linear(&self, value: f32) -> f32 {
+        match self {
+            TransferFunction::Srgb => {
+                if value <= 0.04045 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            TransferFunction::Gamma(gamma) => value.powf(*gamma),
+            TransferFunction::Linear => value,
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+fn srgb_lut() -> &'static [f32; 256] {
+    static SRGB_LUT: OnceLock<[f32; 256]> = OnceLock::new();
+
+    SRGB_LUT.get_or_init(|| {
+        let mut lut = [0.0; 256];
+        for (i, value) in lut.iter_mut().enumerate() {
+            *value = TransferFunction::Srgb.to_linear(i as f32 / 255.0);
+        }
+        lut
+    })
+}
+
+/// Convert an 8-bit capture to linear light floats, alpha is kept as is.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub(crate) fn to_linear_image(
+    image: &RgbaImage,
+    transfer_function: TransferFunction,
+) -> Rgba32FImage {
+    let lut = match transfer_function {
+        TransferFunction::Srgb => *srgb_lut(),
+        _ => {
+            let mut lut = [0.0; 256];
+            for (i, value) in lut.iter_mut().enumerate() {
+                *value = transfer_function.to_linear(i as f32 / 255.0);
+            }
+            lut
+        }
+    };
+
+    let (width, height) = image.dimensions();
+    let mut buffer = Vec::with_capacity((width * height * 4) as usize);
+
+    for pixel in image.pixels() {
+        buffer.push(lut[pixel[0] as usize]);
+        buffer.push(lut[pixel[1] as usize]);
+        buffer.push(lut[pixel[2] as usize]);
+        buffer.push(pixel[3] as f32 / 255.0);
+    }
+
+    // buffer 长度与宽高一致，from_raw 不会失败
+    Rgba32FImage::from_raw(width, height, buffer).unwrap_or_default()
+}
+
+/// The gamma ramps of a monitor, one table per channel mapping framebuffer values to output
+/// levels, both spread over `0..=65535`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GammaRamp {
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+}
+
+impl GammaRamp {
+    /// A table of `size` entries following a power curve, `1.0` is the identity ramp.
+    pub fn from_gamma(gamma: f32, size: usize) -> GammaRamp {
+        let table: Vec<u16> = (0..size)
+            .map(|index| {
+                let value = index as f32 / (size.max(2) - 1) as f32;
+                (value.powf(gamma) * 65535.0).round() as u16
+            })
+            .collect();
+
+        GammaRamp {
+            red: table.clone(),
+            green: table.clone(),
+            blue: table,
+        }
+    }
+
+    /// The exponent of a power curve through the middle of each ramp, averaged over the
+    /// channels. `1.0` for the identity ramp, above it when the ramps darken the picture, e.g.
+    /// a night light that dims blue.
+    pub fn gamma(&self) -> f32 {
+        let channel_gamma = |table: &[u16]| {
+            let middle = *table.get(table.len() / 2)? as f32 / 65535.0;
+            let input = (table.len() / 2) as f32 / (table.len().max(2) - 1) as f32;
+            (middle > 0.0 && input > 0.0 && input < 1.0).then(|| middle.ln() / input.ln())
+        };
+
+        let gammas: Vec<f32> = [&self.red, &self.green, &self.blue]
+            .iter()
+            .filter_map(|table| channel_gamma(table))
+            .collect();
+        if gammas.is_empty() {
+            return 1.0;
+        }
+
+        gammas.iter().sum::<f32>() / gammas.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_of_power_ramps() {
+        assert!((GammaRamp::from_gamma(1.0, 256).gamma() - 1.0).abs() < 0.01);
+        assert!((GammaRamp::from_gamma(2.2, 1024).gamma() - 2.2).abs() < 0.01);
+        assert_eq!(GammaRamp::default().gamma(), 1.0);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use image::{Pixel, Rgba, RgbaImage};
+
+use crate::{
+    error::{XCapError, XCapResult},
+    Monitor, Rect, Window,
+};
+
+fn window_rect(window: &Window) -> Rect {
+    Rect::new(window.x(), window.y(), window.width(), window.height())
+}
+
+/// The pixels of `image` covering `rect`, for an image of a `bounds` sized area.
+fn to_pixels(rect: &Rect, bounds: &Rect, image: &RgbaImage) -> (u32, u32, u32, u32) {
+    let scale_x = image.width() as f32 / bounds.width.max(1) as f32;
+    let scale_y = image.height() as f32 / bounds.height.max(1) as f32;
+
+    let left = ((rect.x - bounds.x) as f32 * scale_x).floor().max(0.0) as u32;
+    let top = ((rect.y - bounds.y) as f32 * scale_y).floor().max(0.0) as u32;
+    let right = ((rect.right() - bounds.x) as f32 * scale_x).ceil() as u32;
+    let bottom = ((rect.bottom() - bounds.y) as f32 * scale_y).ceil() as u32;
+
+    (
+        left,
+        top,
+        right.min(image.width()),
+        bottom.min(image.height()),
+    )
+}
+
+/// Paint the part of `window_image` inside `rect` onto `image`, an image of the `image_rect`
+/// area, blending translucent pixels such as window shadows.
+fn paint(
+    image: &mut RgbaImage,
+    image_rect: &Rect,
+    window_image: &RgbaImage,
+    window_rect: &Rect,
+    rect: &Rect,
+) {
+    let (left, top, right, bottom) = to_pixels(rect, image_rect, image);
+    let scale_x = image.width() as f32 / image_rect.width.max(1) as f32;
+    let scale_y = image.height() as f32 / image_rect.height.max(1) as f32;
+    let window_scale_x = window_image.width() as f32 / window_rect.width.max(1) as f32;
+    let window_scale_y = window_image.height() as f32 / window_rect.height.max(1) as f32;
+
+    for y in top..bottom {
+        // 像素中心对应的全局坐标
+        let global_y = image_rect.y as f32 + (y as f32 + 0.5) / scale_y;
+        let window_y = ((global_y - window_rect.y as f32) * window_scale_y) as i64;
+        if window_y < 0 || window_y >= window_image.height() as i64 {
+            continue;
+        }
+
+        for x in left..right {
+            let global_x = image_rect.x as f32 + (x as f32 + 0.5) / scale_x;
+            let window_x = ((global_x - window_rect.x as f32) * window_scale_x) as i64;
+            if window_x < 0 || window_x >= window_image.width() as i64 {
+                continue;
+            }
+
+            let pixel = window_image.get_pixel(window_x as u32, window_y as u32);
+            match pixel[3] {
+                0 => {}
+                255 => image.put_pixel(x, y, *pixel),
+                _ => image.get_pixel_mut(x, y).blend(pixel),
+            }
+        }
+    }
+}
+
+/// Capture the monitor, then repaint the areas of the excluded windows with the captures of
+/// the windows below and above them, bottom to top. Areas no other window covers are black.
+pub(crate) fn composite_excluding(monitor: &Monitor, window_ids: &[u32]) -> XCapResult<RgbaImage> {
+    let mut image = monitor.capture_image()?;
+    let monitor_rect = Rect::new(monitor.x(), monitor.y(), monitor.width(), monitor.height());
+
+    let mut windows: Vec<Window> = Window::all()?
+        .into_iter()
+        .filter(|window| window.is_visible() && window.width() > 0 && window.height() > 0)
+        .collect();
+    windows.sort_by_key(|window| window.z());
+
+    let excluded_rects: Vec<Rect> = windows
+        .iter()
+        .filter(|window| window_ids.contains(&window.id()))
+        .filter_map(|window| window_rect(window).intersection(&monitor_rect))
+        .collect();
+
+    let mut window_images: HashMap<u32, Option<RgbaImage>> = HashMap::new();
+
+    for excluded_rect in &excluded_rects {
+        let (left, top, right, bottom) = to_pixels(excluded_rect, &monitor_rect, &image);
+        for y in top..bottom {
+            for x in left..right {
+                image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+
+        for window in &windows {
+            if window_ids.contains(&window.id()) {
+                continue;
+            }
+
+            let rect = match window_rect(window).intersection(excluded_rect) {
+                Some(rect) => rect,
+                None => continue,
+            };
+
+            // 每个窗口只截取一次
+            let window_image = window_images.entry(window.id()).or_insert_with(|| {
+                window
+                    .capture_image()
+                    .map_err(|err| log::debug!("Capture window {} failed: {}", window.id(), err))
+                    .ok()
+            });
+
+            if let Some(window_image) = window_image {
+                paint(
+                    &mut image,
+                    &monitor_rect,
+                    window_image,
+                    &window_rect(window),
+                    &rect,
+                );
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+/// Rebuild what the monitor shows from captures of the visible windows on it, painted bottom
+/// to top over the wallpaper. Areas neither covers are black.
+pub(crate) fn composite_monitor(monitor: &Monitor) -> XCapResult<RgbaImage> {
+    let monitor_rect = Rect::new(monitor.x(), monitor.y(), monitor.width(), monitor.height());
+
+    let mut windows: Vec<(Window, Rect)> = Window::all()?
+        .into_iter()
+        .filter(|window| window.is_visible() && !window.is_minimized())
+        .filter_map(|window| {
+            let rect = window_rect(&window);
+            rect.intersection(&monitor_rect)?;
+            Some((window, rect))
+        })
+        .collect();
+    windows.sort_by_key(|(window, _)| window.z());
+
+    let mut window_images = Vec::with_capacity(windows.len());
+    let mut scale_factor: f32 = 1.0;
+    for (window, rect) in &windows {
+        // 截不到的窗口（例如受保护的内容）直接跳过
+        match window.capture_image() {
+            Ok(window_image) => {
+                scale_factor = scale_factor.max(window_image.width() as f32 / rect.width as f32);
+                window_images.push(Some(window_image));
+            }
+            Err(err) => {
+                log::debug!("Capture window {} failed: {}", window.id(), err);
+                window_images.push(None);
+            }
+        }
+    }
+
+    let width = (monitor_rect.width as f32 * scale_factor).ceil() as u32;
+    let height = (monitor_rect.height as f32 * scale_factor).ceil() as u32;
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+
+    match monitor.capture_desktop_only() {
+        Ok(wallpaper) => paint(
+            &mut image,
+            &monitor_rect,
+            &wallpaper,
+            &monitor_rect,
+            &monitor_rect,
+        ),
+        Err(err) => log::debug!("Capture wallpaper failed: {}", err),
+    }
+
+    for ((_, rect), window_image) in windows.iter().zip(&window_images) {
+        if let Some(window_image) = window_image {
+            paint(&mut image, &monitor_rect, window_image, rect, rect);
+        }
+    }
+
+    Ok(image)
+}
+
+/// Capture `windows` and composite them by z coordinate onto a transparent image of their
+/// bounding box, at their screen positions. Unlike a monitor capture nothing else of the
+/// desktop shows up, e.g. to share two applications only.
+///
+/// The image uses the largest pixel density of the windows, so Retina windows keep their
+/// resolution.
+pub fn compose_windows(windows: &[Window]) -> XCapResult<RgbaImage> {
+    let mut windows: Vec<&Window> = windows
+        .iter()
+        .filter(|window| window.width() > 0 && window.height() > 0)
+        .collect();
+    windows.sort_by_key(|window| window.z());
+
+    let bounds = windows
+        .iter()
+        .map(|window| window_rect(window))
+        .reduce(|bounds, rect| bounds.union(&rect))
+        .ok_or_else(|| XCapError::new("No window to compose"))?;
+
+    let mut window_images = Vec::with_capacity(windows.len());
+    let mut scale_factor: f32 = 1.0;
+    for window in &windows {
+        let window_image = window.capture_image()?;
+        scale_factor = scale_factor.max(window_image.width() as f32 / window.width() as f32);
+        window_images.push(window_image);
+    }
+
+    let mut image = RgbaImage::new(
+        (bounds.width as f32 * scale_factor).ceil() as u32,
+        (bounds.height as f32 * scale_factor).ceil() as u32,
+    );
+    for (window, window_image) in windows.iter().zip(&window_images) {
+        let rect = window_rect(window);
+        paint(&mut image, &bounds, window_image, &rect, &rect);
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint_scaled_window() {
+        // 2x 缩放的显示器，窗口覆盖逻辑坐标 (1, 0) 开始的 1x1 区域
+        let mut image = RgbaImage::new(4, 2);
+        let window_image = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+
+        paint(
+            &mut image,
+            &Rect::new(0, 0, 2, 1),
+            &window_image,
+            &Rect::new(1, 0, 1, 1),
+            &Rect::new(1, 0, 1, 1),
+        );
+
+        assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 0, 0]));
+        assert_eq!(image.get_pixel(2, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(3, 1), &Rgba([255, 0, 0, 255]));
+    }
+}
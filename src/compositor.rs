@@ -0,0 +1,457 @@
+#[cfg(feature = "image")]
+use image::RgbaImage;
+
+#[cfg(all(feature = "input-overlay", not(target_arch = "wasm32")))]
+use crate::input_overlay::InputVisualizer;
+use crate::{
+    error::XCapResult,
+    font::{glyph, text_size, GLYPH_ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH},
+    sink::FrameSink,
+    utils::UtcDateTime,
+    video_recorder::Frame,
+    Monitor, Pointer,
+};
+
+/// The corner an overlay is positioned relative to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Appearance of text stamped by an [`Overlay`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextStyle {
+    /// Integer scale of the built-in 5x7 pixel font.
+    pub scale: u32,
+    pub color: [u8; 4],
+    /// Color of the box drawn behind the text, `None` for no box.
+    pub background: Option<[u8; 4]>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            scale: 2,
+            color: [255, 255, 255, 255],
+            background: Some([0, 0, 0, 160]),
+        }
+    }
+}
+
+/// One element stamped onto frames by a [`Compositor`].
+#[derive(Debug, Clone)]
+pub enum Overlay {
+    /// The current UTC time, formatted as `YYYY-MM-DD HH:MM:SS`.
+    Timestamp {
+        anchor: Anchor,
+        margin: u32,
+        style: TextStyle,
+    },
+    Text {
+        text: String,
+        anchor: Anchor,
+        margin: u32,
+        style: TextStyle,
+    },
+    /// An RGBA image alpha blended onto the frame, e.g. a logo.
+    #[cfg(feature = "image")]
+    Image {
+        image: RgbaImage,
+        anchor: Anchor,
+        margin: u32,
+    },
+    /// Click ripples and the keys being typed.
+    #[cfg(all(feature = "input-overlay", not(target_arch = "wasm32")))]
+    Input(InputVisualizer),
+    /// An arrow at the position of `pointer`, mapped to frames of `monitor`, filled with
+    /// `color` to tell the cursors of several seats apart.
+    Pointer {
+        pointer: Pointer,
+        monitor: Monitor,
+        color: [u8; 4],
+    },
+}
+
+/// An arrow cursor, `B` is the outline and `W` the fill.
+const ARROW: [&str; 16] = [
+    "B",
+    "BB",
+    "BWB",
+    "BWWB",
+    "BWWWB",
+    "BWWWWB",
+    "BWWWWWB",
+    "BWWWWWWB",
+    "BWWWWWWWB",
+    "BWWWWWWWWB",
+    "BWWWWWBBBBB",
+    "BWWBWWB",
+    "BWB BWWB",
+    "BB  BWWB",
+    "B    BWWB",
+    "      BB",
+];
+
+pub(crate) fn anchor_position(
+    anchor: Anchor,
+    margin: u32,
+    (width, height): (u32, u32),
+    (target_width, target_height): (u32, u32),
+) -> (i64, i64) {
+    let margin = margin as i64;
+    let right = target_width as i64 - width as i64 - margin;
+    let bottom = target_height as i64 - height as i64 - margin;
+
+    match anchor {
+        Anchor::TopLeft => (margin, margin),
+        Anchor::TopRight => (right, margin),
+        Anchor::BottomLeft => (margin, bottom),
+        Anchor::BottomRight => (right, bottom),
+    }
+}
+
+/// Blend `color` over the pixel at (`x`, `y`) if it lies inside the buffer.
+pub(crate) fn blend_pixel(raw: &mut [u8], width: u32, height: u32, x: i64, y: i64, color: [u8; 4]) {
+    if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+        return;
+    }
+
+    let index = ((y as u32 * width + x as u32) * 4) as usize;
+    let alpha = color[3] as u32;
+
+    if alpha == 255 {
+        raw[index..index + 4].copy_from_slice(&color);
+        return;
+    }
+
+    for channel in 0..3 {
+        let dst = raw[index + channel] as u32;
+        raw[index + channel] = ((color[channel] as u32 * alpha + dst * (255 - alpha)) / 255) as u8;
+    }
+    raw[index + 3] = raw[index + 3].max(color[3]);
+}
+
+pub(crate) fn fill_rect(
+    raw: &mut [u8],
+    width: u32,
+    height: u32,
+    (x, y): (i64, i64),
+    (rect_width, rect_height): (u32, u32),
+    color: [u8; 4],
+) {
+    for row in y.max(0)..(y + rect_height as i64).min(height as i64) {
+        for column in x.max(0)..(x + rect_width as i64).min(width as i64) {
+            blend_pixel(raw, width, height, column, row, color);
+        }
+    }
+}
+
+pub(crate) fn draw_text(
+    raw: &mut [u8],
+    width: u32,
+    height: u32,
+    text: &str,
+    (x, y): (i64, i64),
+    style: &TextStyle,
+) {
+    let scale = style.scale.max(1);
+    let padding = scale as i64;
+
+    if let Some(background) = style.background {
+        let (text_width, text_height) = text_size(text, scale);
+        fill_rect(
+            raw,
+            width,
+            height,
+            (x - padding, y - padding),
+            (text_width + 2 * scale, text_height + 2 * scale),
+            background,
+        );
+    }
+
+    for (i, ch) in text.chars().enumerate() {
+        let origin_x = x + (i as u32 * GLYPH_ADVANCE * scale) as i64;
+
+        for (column, bits) in glyph(ch).iter().enumerate().take(GLYPH_WIDTH as usize) {
+            for row in 0..GLYPH_HEIGHT {
+                if bits >> row & 1 == 0 {
+                    continue;
+                }
+
+                fill_rect(
+                    raw,
+                    width,
+                    height,
+                    (
+                        origin_x + (column as u32 * scale) as i64,
+                        y + (row * scale) as i64,
+                    ),
+                    (scale, scale),
+                    style.color,
+                );
+            }
+        }
+    }
+}
+
+fn draw_pointer(
+    raw: &mut [u8],
+    width: u32,
+    height: u32,
+    pointer: &Pointer,
+    monitor: &Monitor,
+    color: [u8; 4],
+) {
+    let (x, y) = match pointer.position() {
+        Ok(position) => position,
+        Err(err) => {
+            log::debug!("Get pointer {} position failed: {}", pointer.id(), err);
+            return;
+        }
+    };
+
+    // 高分屏上帧的像素比屏幕坐标多，按比例换算
+    let scale_x = width as f64 / monitor.width().max(1) as f64;
+    let scale_y = height as f64 / monitor.height().max(1) as f64;
+    let origin_x = ((x - monitor.x()) as f64 * scale_x) as i64;
+    let origin_y = ((y - monitor.y()) as f64 * scale_y) as i64;
+    let scale = scale_x.round().max(1.0) as u32;
+
+    for (row, line) in ARROW.iter().enumerate() {
+        for (column, pixel) in line.chars().enumerate() {
+            let pixel_color = match pixel {
+                'B' => [0, 0, 0, 255],
+                'W' => color,
+                _ => continue,
+            };
+            fill_rect(
+                raw,
+                width,
+                height,
+                (
+                    origin_x + (column as u32 * scale) as i64,
+                    origin_y + (row as u32 * scale) as i64,
+                ),
+                (scale, scale),
+                pixel_color,
+            );
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+fn draw_image(raw: &mut [u8], width: u32, height: u32, image: &RgbaImage, (x, y): (i64, i64)) {
+    for (column, row, pixel) in image.enumerate_pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+
+        blend_pixel(
+            raw,
+            width,
+            height,
+            x + column as i64,
+            y + row as i64,
+            pixel.0,
+        );
+    }
+}
+
+/// Stamps timestamps, text, images and cursors onto frames before they reach a sink.
+#[derive(Debug, Clone, Default)]
+pub struct Compositor {
+    overlays: Vec<Overlay>,
+}
+
+impl Compositor {
+    pub fn new() -> Compositor {
+        Compositor::default()
+    }
+
+    pub fn with_overlay(mut self, overlay: Overlay) -> Compositor {
+        self.overlays.push(overlay);
+        self
+    }
+
+    /// Stamp a timestamp with the default [`TextStyle`].
+    pub fn with_timestamp(self, anchor: Anchor) -> Compositor {
+        self.with_overlay(Overlay::Timestamp {
+            anchor,
+            margin: 8,
+            style: TextStyle::default(),
+        })
+    }
+
+    /// Stamp text with the default [`TextStyle`].
+    pub fn with_text<T: ToString>(self, text: T, anchor: Anchor) -> Compositor {
+        self.with_overlay(Overlay::Text {
+            text: text.to_string(),
+            anchor,
+            margin: 8,
+            style: TextStyle::default(),
+        })
+    }
+
+    /// Draw clicks and keystrokes, see [`InputVisualizer`].
+    #[cfg(all(feature = "input-overlay", not(target_arch = "wasm32")))]
+    pub fn with_input(self, visualizer: InputVisualizer) -> Compositor {
+        self.with_overlay(Overlay::Input(visualizer))
+    }
+
+    /// Draw the cursor of `pointer` onto frames of `monitor`, e.g. the cursor of one seat.
+    pub fn with_pointer(self, pointer: Pointer, monitor: &Monitor) -> Compositor {
+        self.with_overlay(Overlay::Pointer {
+            pointer,
+            monitor: monitor.clone(),
+            color: [255, 255, 255, 255],
+        })
+    }
+
+    fn apply_raw(&self, raw: &mut [u8], width: u32, height: u32) {
+        for overlay in &self.overlays {
+            match overlay {
+                Overlay::Timestamp {
+                    anchor,
+                    margin,
+                    style,
+                } => {
+                    let now = UtcDateTime::now();
+                    let text = format!(
+                        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                        now.year, now.month, now.day, now.hour, now.minute, now.second
+                    );
+                    let size = text_size(&text, style.scale.max(1));
+                    let position = anchor_position(*anchor, *margin, size, (width, height));
+                    draw_text(raw, width, height, &text, position, style);
+                }
+                Overlay::Text {
+                    text,
+                    anchor,
+                    margin,
+                    style,
+                } => {
+                    let size = text_size(text, style.scale.max(1));
+                    let position = anchor_position(*anchor, *margin, size, (width, height));
+                    draw_text(raw, width, height, text, position, style);
+                }
+                #[cfg(feature = "image")]
+                Overlay::Image {
+                    image,
+                    anchor,
+                    margin,
+                } => {
+                    let position =
+                        anchor_position(*anchor, *margin, image.dimensions(), (width, height));
+                    draw_image(raw, width, height, image, position);
+                }
+                #[cfg(all(feature = "input-overlay", not(target_arch = "wasm32")))]
+                Overlay::Input(visualizer) => visualizer.draw(raw, width, height),
+                Overlay::Pointer {
+                    pointer,
+                    monitor,
+                    color,
+                } => draw_pointer(raw, width, height, pointer, monitor, *color),
+            }
+        }
+    }
+
+    pub fn apply(&self, frame: &mut Frame) {
+        let (width, height) = (frame.width, frame.height);
+        self.apply_raw(&mut frame.raw, width, height);
+    }
+
+    #[cfg(feature = "image")]
+    pub fn apply_image(&self, image: &mut RgbaImage) {
+        let (width, height) = image.dimensions();
+        self.apply_raw(image, width, height);
+    }
+}
+
+/// A [`FrameSink`] that runs a [`Compositor`] on every frame before forwarding it.
+#[derive(Debug)]
+pub struct CompositedSink<S: FrameSink> {
+    compositor: Compositor,
+    sink: S,
+}
+
+impl<S: FrameSink> CompositedSink<S> {
+    pub fn new(compositor: Compositor, sink: S) -> CompositedSink<S> {
+        CompositedSink { compositor, sink }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: FrameSink> FrameSink for CompositedSink<S> {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        let mut frame = frame.clone();
+        self.compositor.apply(&mut frame);
+        self.sink.write_frame(&frame)
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchored_corners() {
+        let size = (10, 4);
+        let target = (100, 50);
+
+        assert_eq!(anchor_position(Anchor::TopLeft, 8, size, target), (8, 8));
+        assert_eq!(anchor_position(Anchor::TopRight, 8, size, target), (82, 8));
+        assert_eq!(
+            anchor_position(Anchor::BottomLeft, 8, size, target),
+            (8, 38)
+        );
+        assert_eq!(
+            anchor_position(Anchor::BottomRight, 8, size, target),
+            (82, 38)
+        );
+        // 比画面还大的内容会移出左上边界
+        assert_eq!(
+            anchor_position(Anchor::BottomRight, 0, (120, 60), target),
+            (-20, -10)
+        );
+    }
+
+    #[test]
+    fn blended_pixels() {
+        let mut raw = vec![0, 0, 0, 255, 200, 100, 0, 128];
+
+        blend_pixel(&mut raw, 2, 1, 0, 0, [255, 255, 255, 255]);
+        assert_eq!(&raw[0..4], &[255, 255, 255, 255]);
+
+        // 半透明按 alpha 混合，alpha 取两者较大值
+        blend_pixel(&mut raw, 2, 1, 1, 0, [0, 0, 255, 255 / 3]);
+        assert_eq!(&raw[4..8], &[133, 66, 85, 128]);
+    }
+
+    #[test]
+    fn clipped_rect() {
+        let mut raw = vec![0; 3 * 3 * 4];
+
+        fill_rect(&mut raw, 3, 3, (-1, 2), (2, 4), [255, 0, 0, 255]);
+        blend_pixel(&mut raw, 3, 3, 3, 0, [0, 255, 0, 255]);
+        blend_pixel(&mut raw, 3, 3, -1, 0, [0, 255, 0, 255]);
+
+        let filled: Vec<usize> = raw
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[3] != 0)
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(filled, vec![6]);
+        assert_eq!(&raw[24..28], &[255, 0, 0, 255]);
+    }
+}
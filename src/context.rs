@@ -0,0 +1,155 @@
+use std::sync::Arc;
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+
+#[cfg(target_os = "windows")]
+use crate::gpu::GpuDevice;
+#[cfg(target_os = "linux")]
+use crate::platform::screencast::{ScreenCastOptions, ScreenCastSession};
+use crate::{
+    backend::Backend,
+    custom::{custom_monitors, CustomBackend},
+    error::{XCapError, XCapResult},
+    platform::{impl_monitor::ImplMonitor, impl_window::ImplWindow},
+    Monitor, VideoRecorder, Window,
+};
+
+/// Entry point to enumerate monitors and windows with a specific backend.
+#[derive(Debug, Clone, Default)]
+pub struct XCapContext {
+    backend: Option<Backend>,
+    custom_backend: Option<Arc<dyn CustomBackend>>,
+    include_override_redirect: bool,
+    #[cfg(target_os = "windows")]
+    gpu_device: Option<GpuDevice>,
+    #[cfg(target_os = "linux")]
+    restore_token: Arc<Mutex<Option<String>>>,
+}
+
+impl XCapContext {
+    pub fn new() -> XCapContext {
+        XCapContext::default()
+    }
+
+    /// Force a backend instead of the detected one.
+    pub fn with_backend(mut self, backend: Backend) -> XCapContext {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Enumerate the monitors of `custom_backend` instead of the platform ones, see
+    /// [`CustomBackend`].
+    #[cfg(feature = "image")]
+    pub fn with_custom_backend(mut self, custom_backend: Arc<dyn CustomBackend>) -> XCapContext {
+        self.custom_backend = Some(custom_backend);
+        self
+    }
+
+    /// Also list the X11 override-redirect windows, such as menus, tooltips and popups, and the
+    /// system tray icons, which `_NET_CLIENT_LIST_STACKING` leaves out. They only exist while
+    /// shown. Other platforms list menus and popups anyway.
+    pub fn with_override_redirect(mut self, include_override_redirect: bool) -> XCapContext {
+        self.include_override_redirect = include_override_redirect;
+        self
+    }
+
+    /// Capture video on a Direct3D 11 device owned by the application, see
+    /// [`XCapContext::video_recorder`].
+    #[cfg(target_os = "windows")]
+    pub fn with_gpu_device(mut self, gpu_device: GpuDevice) -> XCapContext {
+        self.gpu_device = Some(gpu_device);
+        self
+    }
+
+    /// Restore a ScreenCast portal selection saved from [`XCapContext::restore_token`], so
+    /// [`XCapContext::screen_cast`] reconnects without showing the dialog.
+    #[cfg(target_os = "linux")]
+    pub fn with_restore_token<T: Into<String>>(self, restore_token: T) -> XCapContext {
+        XCapContext {
+            restore_token: Arc::new(Mutex::new(Some(restore_token.into()))),
+            ..self
+        }
+    }
+
+    /// The token to save for the next run, updated by every [`XCapContext::screen_cast`].
+    /// The portal only returns one when the options ask for a
+    /// [`crate::linux::PersistMode`].
+    #[cfg(target_os = "linux")]
+    pub fn restore_token(&self) -> XCapResult<Option<String>> {
+        Ok(self.restore_token.lock()?.clone())
+    }
+
+    /// Start a ScreenCast portal session with the restore token of this context, and keep
+    /// the new token the portal returns. Tokens are single use.
+    #[cfg(target_os = "linux")]
+    pub fn screen_cast(&self, options: ScreenCastOptions) -> XCapResult<ScreenCastSession> {
+        let mut restore_token = self.restore_token.lock()?;
+        let options = match restore_token.clone() {
+            Some(token) => options.with_restore_token(token),
+            None => options,
+        };
+
+        let session = ScreenCastSession::start(&options)?;
+        *restore_token = session.restore_token().map(String::from);
+
+        Ok(session)
+    }
+
+    /// The forced backend, or the one detected for the current session.
+    pub fn backend(&self) -> Backend {
+        if self.custom_backend.is_some() {
+            return Backend::Custom;
+        }
+
+        self.backend.unwrap_or_else(ImplMonitor::backend)
+    }
+
+    /// List all monitors, captured with the backend of this context.
+    pub fn monitors(&self) -> XCapResult<Vec<Monitor>> {
+        if let Some(custom_backend) = &self.custom_backend {
+            return custom_monitors(custom_backend);
+        }
+
+        let impl_monitors = match self.backend {
+            Some(backend) => ImplMonitor::all_with_backend(backend)?,
+            None => ImplMonitor::all()?,
+        };
+
+        Ok(impl_monitors.into_iter().map(Monitor::new).collect())
+    }
+
+    /// List all windows, sorted by z coordinate. Windows are only available with the
+    /// X11, GDI and CoreGraphics backends, and on Wayland with the `foreign-toplevel` feature.
+    pub fn windows(&self) -> XCapResult<Vec<Window>> {
+        if self.custom_backend.is_some() {
+            return Err(XCapError::new("Custom backends can not capture windows"));
+        }
+
+        if let Some(backend) = self.backend {
+            if backend != ImplWindow::backend() {
+                return Err(XCapError::new(format!(
+                    "{:?} backend can not capture windows",
+                    backend
+                )));
+            }
+        }
+
+        let impl_windows = match self.include_override_redirect {
+            true => ImplWindow::all_with_override_redirect()?,
+            false => ImplWindow::all()?,
+        };
+
+        Ok(impl_windows.into_iter().map(Window::new).collect())
+    }
+
+    /// A video recorder of `monitor`, on the registered GPU device if there is one, so
+    /// `VideoRecorder::on_texture` delivers textures the application can use directly.
+    pub fn video_recorder(&self, monitor: &Monitor) -> XCapResult<VideoRecorder> {
+        #[cfg(target_os = "windows")]
+        if let Some(gpu_device) = &self.gpu_device {
+            return monitor.video_recorder_on(gpu_device);
+        }
+
+        monitor.video_recorder()
+    }
+}
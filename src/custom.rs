@@ -0,0 +1,63 @@
+use std::{fmt, sync::Arc};
+
+use image::RgbaImage;
+
+use crate::{error::XCapResult, utils::thumbnail, Monitor};
+
+/// A monitor of a [`CustomBackend`], in physical pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomMonitor {
+    /// Unique within the backend, passed back to [`CustomBackend::capture_monitor`].
+    pub id: u32,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    /// The refresh rate in Hz, 0 when unknown.
+    pub frequency: f32,
+    pub is_primary: bool,
+}
+
+/// A capture source provided by the application, e.g. a network framebuffer, an Android
+/// device over adb or a KVM. Register it with [`crate::XCapContext::with_custom_backend`], its
+/// monitors are then regular [`Monitor`]s: captures, encoding, [`crate::CaptureSession`] and
+/// the sinks work on them.
+///
+/// The trait is unstable, methods may be added in minor versions, with default
+/// implementations where possible. Custom backends only provide monitors, there are no
+/// windows, and [`Monitor::video_recorder`] is not available, stream with a
+/// [`crate::CaptureSession`] instead.
+pub trait CustomBackend: fmt::Debug + Send + Sync {
+    /// The monitors of the source.
+    fn monitors(&self) -> XCapResult<Vec<CustomMonitor>>;
+
+    /// Capture the monitor `id` as it is now.
+    fn capture_monitor(&self, id: u32) -> XCapResult<RgbaImage>;
+
+    /// Capture a preview of the monitor `id` that fits into `max_width` x `max_height`,
+    /// defaults to downscaling a full capture.
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    fn capture_thumbnail(&self, id: u32, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        Ok(thumbnail(self.capture_monitor(id)?, max_width, max_height))
+    }
+
+    /// Whether the monitor `id` is still there, defaults to looking it up in
+    /// [`CustomBackend::monitors`].
+    fn is_valid(&self, id: u32) -> bool {
+        self.monitors()
+            .is_ok_and(|monitors| monitors.iter().any(|monitor| monitor.id == id))
+    }
+}
+
+/// The monitors of `backend`, capturing through it.
+pub(crate) fn custom_monitors(backend: &Arc<dyn CustomBackend>) -> XCapResult<Vec<Monitor>> {
+    let monitors = backend
+        .monitors()?
+        .iter()
+        .map(|custom_monitor| Monitor::custom(backend.clone(), custom_monitor))
+        .collect();
+
+    Ok(monitors)
+}
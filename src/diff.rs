@@ -0,0 +1,335 @@
+//! Frame comparison utilities.
+//!
+//! Images are compared tile by tile: identical tiles are skipped with a plain
+//! slice comparison (which the compiler lowers to vectorized `memcmp`), and
+//! only tiles that differ are inspected pixel by pixel.
+
+#[cfg(feature = "image")]
+use image::RgbaImage;
+
+use crate::{region::Region, video_recorder::Frame, XCapError, XCapResult};
+
+/// Options of [`compare_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Per channel difference ignored as noise.
+    pub tolerance: u8,
+    /// Edge length of the square tiles, in pixels.
+    pub tile_size: u32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            tolerance: 0,
+            tile_size: 16,
+        }
+    }
+}
+
+/// The result of comparing two images of the same size.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FrameDiff {
+    pub changed_pixels: u64,
+    pub total_pixels: u64,
+    /// Bounding boxes of connected changed areas.
+    pub regions: Vec<Region>,
+}
+
+impl FrameDiff {
+    /// Changed pixels in percent of the image, between 0 and 100.
+    pub fn changed_percentage(&self) -> f64 {
+        if self.total_pixels == 0 {
+            return 0.0;
+        }
+
+        self.changed_pixels as f64 * 100.0 / self.total_pixels as f64
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.changed_pixels == 0
+    }
+}
+
+#[inline]
+fn pixel_changed(a: &[u8], b: &[u8], tolerance: u8) -> bool {
+    if tolerance == 0 {
+        return a != b;
+    }
+
+    a.iter().zip(b).any(|(a, b)| a.abs_diff(*b) > tolerance)
+}
+
+/// Count the pixels of `region` that differ between two buffers of the same `width` and
+/// `bytes_per_pixel`.
+pub(crate) fn count_changed(
+    a: &[u8],
+    b: &[u8],
+    width: u32,
+    bytes_per_pixel: usize,
+    region: &Region,
+    tolerance: u8,
+) -> u64 {
+    let mut changed = 0;
+
+    for y in region.y..region.y + region.height {
+        let start = (y * width + region.x) as usize * bytes_per_pixel;
+        let end = start + region.width as usize * bytes_per_pixel;
+
+        if a[start..end] == b[start..end] {
+            continue;
+        }
+
+        changed += a[start..end]
+            .chunks_exact(bytes_per_pixel)
+            .zip(b[start..end].chunks_exact(bytes_per_pixel))
+            .filter(|(a, b)| pixel_changed(a, b, tolerance))
+            .count() as u64;
+    }
+
+    changed
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TileDiff {
+    changed: u64,
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
+}
+
+fn diff_tile(
+    a: &[u8],
+    b: &[u8],
+    width: u32,
+    bytes_per_pixel: usize,
+    tile: &Region,
+    tolerance: u8,
+) -> Option<TileDiff> {
+    let mut result: Option<TileDiff> = None;
+
+    for y in tile.y..tile.y + tile.height {
+        let start = (y * width + tile.x) as usize * bytes_per_pixel;
+        let end = start + tile.width as usize * bytes_per_pixel;
+
+        if a[start..end] == b[start..end] {
+            continue;
+        }
+
+        let pixels = a[start..end]
+            .chunks_exact(bytes_per_pixel)
+            .zip(b[start..end].chunks_exact(bytes_per_pixel));
+
+        for (i, (pa, pb)) in pixels.enumerate() {
+            if !pixel_changed(pa, pb, tolerance) {
+                continue;
+            }
+
+            let x = tile.x + i as u32;
+            let diff = result.get_or_insert(TileDiff {
+                changed: 0,
+                min_x: x,
+                min_y: y,
+                max_x: x,
+                max_y: y,
+            });
+            diff.changed += 1;
+            diff.min_x = diff.min_x.min(x);
+            diff.min_y = diff.min_y.min(y);
+            diff.max_x = diff.max_x.max(x);
+            diff.max_y = diff.max_y.max(y);
+        }
+    }
+
+    result
+}
+
+fn compare_raw(
+    a: &[u8],
+    b: &[u8],
+    width: u32,
+    height: u32,
+    bytes_per_pixel: usize,
+    options: &DiffOptions,
+) -> XCapResult<FrameDiff> {
+    let expected = width as usize * height as usize * bytes_per_pixel;
+    if a.len() < expected || b.len() < expected {
+        return Err(XCapError::new(
+            "Image buffer is smaller than its dimensions",
+        ));
+    }
+
+    let tile_size = options.tile_size.max(1);
+    let columns = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+
+    let mut tiles: Vec<Option<TileDiff>> = Vec::with_capacity((columns * rows) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let tile = Region::new(column * tile_size, row * tile_size, tile_size, tile_size)
+                .clamp(width, height)
+                .ok_or_else(|| XCapError::new("Tile out of bounds"))?;
+            tiles.push(diff_tile(
+                a,
+                b,
+                width,
+                bytes_per_pixel,
+                &tile,
+                options.tolerance,
+            ));
+        }
+    }
+
+    let changed_pixels = tiles.iter().flatten().map(|tile| tile.changed).sum();
+
+    // 将相邻的变化块合并为连通区域，计算每个区域的包围盒
+    let mut visited = vec![false; tiles.len()];
+    let mut regions = Vec::new();
+
+    for start in 0..tiles.len() {
+        if visited[start] || tiles[start].is_none() {
+            continue;
+        }
+
+        visited[start] = true;
+        let mut stack = vec![start];
+        let mut bounds: Option<TileDiff> = None;
+
+        while let Some(index) = stack.pop() {
+            if let Some(tile) = tiles[index] {
+                let merged = bounds.get_or_insert(tile);
+                merged.min_x = merged.min_x.min(tile.min_x);
+                merged.min_y = merged.min_y.min(tile.min_y);
+                merged.max_x = merged.max_x.max(tile.max_x);
+                merged.max_y = merged.max_y.max(tile.max_y);
+            }
+
+            let column = index as u32 % columns;
+            let row = index as u32 / columns;
+            let neighbours = [
+                (column > 0).then(|| index - 1),
+                (column + 1 < columns).then(|| index + 1),
+                (row > 0).then(|| index - columns as usize),
+                (row + 1 < rows).then(|| index + columns as usize),
+            ];
+
+            for neighbour in neighbours.into_iter().flatten() {
+                if !visited[neighbour] && tiles[neighbour].is_some() {
+                    visited[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+
+        if let Some(bounds) = bounds {
+            regions.push(Region::new(
+                bounds.min_x,
+                bounds.min_y,
+                bounds.max_x - bounds.min_x + 1,
+                bounds.max_y - bounds.min_y + 1,
+            ));
+        }
+    }
+
+    Ok(FrameDiff {
+        changed_pixels,
+        total_pixels: width as u64 * height as u64,
+        regions,
+    })
+}
+
+fn check_size(a: (u32, u32), b: (u32, u32)) -> XCapResult<()> {
+    if a != b {
+        return Err(XCapError::new(format!(
+            "Can not compare images of different sizes {:?} and {:?}",
+            a, b
+        )));
+    }
+
+    Ok(())
+}
+
+/// Compare two images of the same size with the default [`DiffOptions`].
+#[cfg(feature = "image")]
+pub fn compare(a: &RgbaImage, b: &RgbaImage) -> XCapResult<FrameDiff> {
+    compare_with(a, b, &DiffOptions::default())
+}
+
+#[cfg(feature = "image")]
+pub fn compare_with(a: &RgbaImage, b: &RgbaImage, options: &DiffOptions) -> XCapResult<FrameDiff> {
+    check_size(a.dimensions(), b.dimensions())?;
+    let (width, height) = a.dimensions();
+
+    compare_raw(a, b, width, height, 4, options)
+}
+
+/// Compare two frames of the same size and pixel format.
+pub fn compare_frames(a: &Frame, b: &Frame, options: &DiffOptions) -> XCapResult<FrameDiff> {
+    check_size((a.width, a.height), (b.width, b.height))?;
+    if a.pixel_format != b.pixel_format {
+        return Err(XCapError::new(format!(
+            "Can not compare frames of different pixel formats {:?} and {:?}",
+            a.pixel_format, b.pixel_format
+        )));
+    }
+
+    compare_raw(
+        &a.raw,
+        &b.raw,
+        a.width,
+        a.height,
+        a.pixel_format.bytes_per_pixel(),
+        options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PixelFormat;
+    #[cfg(feature = "image")]
+    use image::Rgba;
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn compare_regions() {
+        let a = RgbaImage::from_pixel(64, 64, Rgba([0, 0, 0, 255]));
+        let mut b = a.clone();
+
+        // 跨越两个块的变化合并为一个区域，远处的变化单独成为一个区域
+        for x in 10..20 {
+            b.put_pixel(x, 5, Rgba([255, 0, 0, 255]));
+        }
+        b.put_pixel(60, 60, Rgba([0, 0, 1, 255]));
+
+        let diff = compare(&a, &b).unwrap();
+        assert_eq!(diff.changed_pixels, 11);
+        assert_eq!(
+            diff.regions,
+            vec![Region::new(10, 5, 10, 1), Region::new(60, 60, 1, 1)]
+        );
+
+        let options = DiffOptions {
+            tolerance: 1,
+            ..DiffOptions::default()
+        };
+        assert_eq!(compare_with(&a, &b, &options).unwrap().changed_pixels, 10);
+        assert!(compare(&a, &a).unwrap().is_identical());
+    }
+
+    #[test]
+    fn compare_gray_frames() {
+        let mut a = Frame::new(4, 2, vec![0; 8]);
+        a.pixel_format = PixelFormat::Gray8;
+        let mut b = a.clone();
+        b.raw[5] = 255;
+
+        let diff = compare_frames(&a, &b, &DiffOptions::default()).unwrap();
+        assert_eq!(diff.changed_pixels, 1);
+        assert_eq!(diff.regions, vec![Region::new(1, 1, 1, 1)]);
+
+        let rgba = Frame::new(4, 2, vec![0; 32]);
+        assert!(compare_frames(&a, &rgba, &DiffOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use egui::{Color32, ColorImage, Context, TextureHandle, TextureOptions};
+
+use crate::{video_recorder::Frame, XCapError, XCapResult};
+
+impl Frame {
+    /// Copy the frame into `color_image`, reusing its pixel buffer.
+    ///
+    /// Frames are unpremultiplied RGBA while egui expects premultiplied colors, the pixels are
+    /// converted on the way.
+    pub fn copy_to_color_image(&self, color_image: &mut ColorImage) -> XCapResult<()> {
+        let len = (self.width * self.height) as usize;
+        if self.raw.len() != len * 4 {
+            return Err(XCapError::new(format!(
+                "Frame size {}x{} does not match its {} bytes",
+                self.width,
+                self.height,
+                self.raw.len()
+            )));
+        }
+
+        color_image.size = [self.width as usize, self.height as usize];
+        color_image.pixels.clear();
+        color_image.pixels.reserve(len);
+        color_image.pixels.extend(
+            self.raw
+                .chunks_exact(4)
+                .map(|p| Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3])),
+        );
+
+        Ok(())
+    }
+
+    pub fn to_color_image(&self) -> XCapResult<ColorImage> {
+        let mut color_image = ColorImage::new([0, 0], Color32::TRANSPARENT);
+        self.copy_to_color_image(&mut color_image)?;
+
+        Ok(color_image)
+    }
+
+    /// Upload the frame to `texture`, creating it on the first call and updating it in place
+    /// afterwards, so a live preview keeps a single texture.
+    pub fn update_texture(
+        &self,
+        ctx: &Context,
+        texture: &mut Option<TextureHandle>,
+        name: &str,
+    ) -> XCapResult<()> {
+        let color_image = self.to_color_image()?;
+
+        match texture {
+            Some(texture) => texture.set(color_image, TextureOptions::LINEAR),
+            None => *texture = Some(ctx.load_texture(name, color_image, TextureOptions::LINEAR)),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiply() {
+        let frame = Frame::new(2, 1, vec![255, 0, 0, 255, 255, 255, 255, 0]);
+        let mut color_image = ColorImage::new([8, 8], Color32::WHITE);
+        frame.copy_to_color_image(&mut color_image).unwrap();
+
+        assert_eq!(color_image.size, [2, 1]);
+        assert_eq!(color_image.pixels, vec![Color32::RED, Color32::TRANSPARENT]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::{fs, io::Cursor, path::Path};
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::{error::XCapResult, XCapError};
+
+/// Encoder settings used when writing captures to files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// JPEG quality between 1 and 100, ignored by lossless formats.
+    pub quality: u8,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions { quality: 90 }
+    }
+}
+
+pub(crate) fn infer_format(path: &Path) -> XCapResult<ImageFormat> {
+    ImageFormat::from_path(path).map_err(|_| {
+        XCapError::new(format!(
+            "Can not infer the image format from {:?}",
+            path.display()
+        ))
+    })
+}
+
+#[cfg_attr(not(feature = "jpeg"), allow(unused_variables))]
+pub(crate) fn write_image<W: std::io::Write + std::io::Seek>(
+    image: &RgbaImage,
+    writer: &mut W,
+    format: ImageFormat,
+    options: &EncodeOptions,
+) -> XCapResult<()> {
+    match format {
+        ImageFormat::Png => image.write_to(writer, ImageFormat::Png)?,
+        #[cfg(feature = "jpeg")]
+        ImageFormat::Jpeg => {
+            // JPEG 不支持透明通道
+            let rgb = image::DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+            image::codecs::jpeg::JpegEncoder::new_with_quality(
+                writer,
+                options.quality.clamp(1, 100),
+            )
+            .encode_image(&rgb)?;
+        }
+        #[cfg(feature = "webp")]
+        ImageFormat::WebP => image.write_to(writer, ImageFormat::WebP)?,
+        #[cfg(feature = "bmp")]
+        ImageFormat::Bmp => image.write_to(writer, ImageFormat::Bmp)?,
+        format => {
+            return Err(XCapError::new(format!(
+                "Image format {:?} is not enabled, enable the matching xcap feature",
+                format
+            )))
+        }
+    };
+
+    Ok(())
+}
+
+pub(crate) fn save_image(
+    image: &RgbaImage,
+    path: &Path,
+    options: &EncodeOptions,
+) -> XCapResult<()> {
+    let format = infer_format(path)?;
+    // 先编码再创建文件，格式未启用或编码失败时不留下空文件
+    let bytes = encode_image(image, format, options)?;
+    fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+pub(crate) fn encode_image(
+    image: &RgbaImage,
+    format: ImageFormat,
+    options: &EncodeOptions,
+) -> XCapResult<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    write_image(image, &mut cursor, format, options)?;
+
+    Ok(cursor.into_inner())
+}
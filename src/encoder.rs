@@ -0,0 +1,372 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::XCapResult,
+    sink::{check_frame, FrameSink},
+    video_recorder::Frame,
+    XCapError,
+};
+
+/// A compressed frame produced by a [`VideoEncoder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedPacket {
+    pub data: Vec<u8>,
+    /// The index of the frame the packet encodes, counting from 0.
+    pub pts: u64,
+    pub is_keyframe: bool,
+}
+
+/// Encodes RGBA frames into a compressed bitstream, see [`EncoderSink`].
+///
+/// Implement it to record with an encoder xcap does not bundle, e.g. a hardware encoder SDK.
+/// Encoders may buffer frames, packets come out in decoding order and can lag behind.
+pub trait VideoEncoder: Send {
+    /// The FourCC the container stores for the codec, e.g. `*b"AV01"`.
+    fn fourcc(&self) -> [u8; 4];
+
+    /// Prepare for frames of `width` x `height` at `frame_rate`, called once before the first
+    /// frame.
+    fn configure(&mut self, width: u32, height: u32, frame_rate: u32) -> XCapResult<()>;
+
+    /// The codec configuration containers store with the track, e.g. the `av1C` record for
+    /// AV1, available once configured. Defaults to none.
+    fn codec_private(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Encode one RGBA frame, returns the packets that are ready.
+    fn encode(&mut self, frame: &Frame) -> XCapResult<Vec<EncodedPacket>>;
+
+    /// Encode the buffered frames, returns the remaining packets.
+    fn finish(&mut self) -> XCapResult<Vec<EncodedPacket>>;
+}
+
+/// Convert RGBA pixels to 8 bit BT.601 limited range I420 planes, the input most encoders
+/// take, for [`VideoEncoder`] implementations.
+pub fn rgba_to_i420(raw: &[u8], width: u32, height: u32) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let (width, height) = (width as usize, height as usize);
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+
+    let y_plane = raw
+        .chunks_exact(4)
+        .map(|pixel| {
+            let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+            (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8
+        })
+        .collect();
+
+    let mut u_plane = Vec::with_capacity(chroma_width * chroma_height);
+    let mut v_plane = Vec::with_capacity(chroma_width * chroma_height);
+    for chroma_y in 0..chroma_height {
+        for chroma_x in 0..chroma_width {
+            // 2x2 像素取平均
+            let (mut r, mut g, mut b, mut count) = (0, 0, 0, 0);
+            for y in chroma_y * 2..(chroma_y * 2 + 2).min(height) {
+                for x in chroma_x * 2..(chroma_x * 2 + 2).min(width) {
+                    let index = (y * width + x) * 4;
+                    r += raw[index] as i32;
+                    g += raw[index + 1] as i32;
+                    b += raw[index + 2] as i32;
+                    count += 1;
+                }
+            }
+            let (r, g, b) = (r / count, g / count, b / count);
+
+            u_plane.push((((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8);
+            v_plane.push((((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8);
+        }
+    }
+
+    (y_plane, u_plane, v_plane)
+}
+
+/// AV1 encoding with the bundled rav1e, needs the `rav1e` feature.
+#[cfg(feature = "rav1e")]
+#[derive(Debug)]
+pub struct Av1Encoder {
+    speed: u8,
+    bitrate: Option<u32>,
+    context: Option<rav1e::Context<u8>>,
+    width: u32,
+    height: u32,
+}
+
+#[cfg(feature = "rav1e")]
+impl Default for Av1Encoder {
+    fn default() -> Self {
+        Av1Encoder {
+            speed: 10,
+            bitrate: None,
+            context: None,
+            width: 0,
+            height: 0,
+        }
+    }
+}
+
+#[cfg(feature = "rav1e")]
+impl Av1Encoder {
+    pub fn new() -> Av1Encoder {
+        Av1Encoder::default()
+    }
+
+    /// The rav1e speed preset between 0 (slowest, smallest) and 10, defaults to 10 which is
+    /// the only one fast enough for real time screen recording.
+    pub fn with_speed(mut self, speed: u8) -> Av1Encoder {
+        self.speed = speed.min(10);
+        self
+    }
+
+    /// The target bitrate in kbit/s, the default encodes at a constant quality instead.
+    pub fn with_bitrate(mut self, bitrate: u32) -> Av1Encoder {
+        self.bitrate = Some(bitrate);
+        self
+    }
+
+    fn receive_packets(&mut self) -> XCapResult<Vec<EncodedPacket>> {
+        use rav1e::prelude::{EncoderStatus, FrameType};
+
+        let context = self
+            .context
+            .as_mut()
+            .ok_or_else(|| XCapError::new("Av1Encoder is not configured"))?;
+
+        let mut packets = Vec::new();
+        loop {
+            match context.receive_packet() {
+                Ok(packet) => packets.push(EncodedPacket {
+                    data: packet.data,
+                    pts: packet.input_frameno,
+                    is_keyframe: packet.frame_type == FrameType::KEY,
+                }),
+                Err(EncoderStatus::Encoded) => continue,
+                Err(EncoderStatus::NeedMoreData | EncoderStatus::LimitReached) => break,
+                Err(err) => return Err(XCapError::new(format!("rav1e failed: {}", err))),
+            }
+        }
+
+        Ok(packets)
+    }
+}
+
+#[cfg(feature = "rav1e")]
+impl VideoEncoder for Av1Encoder {
+    fn fourcc(&self) -> [u8; 4] {
+        *b"AV01"
+    }
+
+    fn configure(&mut self, width: u32, height: u32, frame_rate: u32) -> XCapResult<()> {
+        use rav1e::prelude::{Config, EncoderConfig, Rational, SpeedSettings};
+
+        let mut encoder_config = EncoderConfig {
+            width: width as usize,
+            height: height as usize,
+            time_base: Rational::new(1, frame_rate.max(1) as u64),
+            speed_settings: SpeedSettings::from_preset(self.speed),
+            low_latency: true,
+            max_key_frame_interval: frame_rate.max(1) as u64 * 2,
+            ..EncoderConfig::default()
+        };
+        if let Some(bitrate) = self.bitrate {
+            // rav1e 以 bit/s 计，超出 i32 时取最大值
+            encoder_config.bitrate = (bitrate as i64 * 1000).min(i32::MAX as i64) as i32;
+        }
+
+        let context = Config::new()
+            .with_encoder_config(encoder_config)
+            .new_context()
+            .map_err(|err| XCapError::new(format!("rav1e config invalid: {}", err)))?;
+
+        self.context = Some(context);
+        self.width = width;
+        self.height = height;
+
+        Ok(())
+    }
+
+    fn codec_private(&self) -> Option<Vec<u8>> {
+        self.context
+            .as_ref()
+            .map(|context| context.container_sequence_header())
+    }
+
+    fn encode(&mut self, frame: &Frame) -> XCapResult<Vec<EncodedPacket>> {
+        let context = self
+            .context
+            .as_mut()
+            .ok_or_else(|| XCapError::new("Av1Encoder is not configured"))?;
+        if (frame.width, frame.height) != (self.width, self.height)
+            || frame.raw.len() != self.width as usize * self.height as usize * 4
+        {
+            return Err(XCapError::new(
+                "Frame does not match the configured RGBA size",
+            ));
+        }
+
+        let (y_plane, u_plane, v_plane) = rgba_to_i420(&frame.raw, frame.width, frame.height);
+        let chroma_width = self.width.div_ceil(2) as usize;
+
+        let mut av1_frame = context.new_frame();
+        av1_frame.planes[0].copy_from_raw_u8(&y_plane, self.width as usize, 1);
+        av1_frame.planes[1].copy_from_raw_u8(&u_plane, chroma_width, 1);
+        av1_frame.planes[2].copy_from_raw_u8(&v_plane, chroma_width, 1);
+
+        context
+            .send_frame(av1_frame)
+            .map_err(|err| XCapError::new(format!("rav1e failed: {}", err)))?;
+
+        self.receive_packets()
+    }
+
+    fn finish(&mut self) -> XCapResult<Vec<EncodedPacket>> {
+        match self.context.as_mut() {
+            Some(context) => context.flush(),
+            None => return Ok(Vec::new()),
+        }
+
+        let packets = self.receive_packets();
+        self.context = None;
+
+        packets
+    }
+}
+
+/// Records frames to an IVF file with a [`VideoEncoder`], without a system `ffmpeg`. IVF
+/// plays in ffmpeg, VLC and browsers' media tools, or remux it with
+/// `ffmpeg -i record.ivf -c copy record.mp4`.
+///
+/// The encoder is configured on the first frame, all frames must have its size.
+#[derive(Debug)]
+pub struct EncoderSink<E: VideoEncoder> {
+    path: PathBuf,
+    encoder: E,
+    frame_rate: u32,
+    size: Option<(u32, u32)>,
+    writer: Option<BufWriter<File>>,
+    packet_count: u32,
+}
+
+impl<E: VideoEncoder> EncoderSink<E> {
+    pub fn new<P: AsRef<Path>>(path: P, encoder: E) -> EncoderSink<E> {
+        EncoderSink {
+            path: path.as_ref().to_path_buf(),
+            encoder,
+            frame_rate: 30,
+            size: None,
+            writer: None,
+            packet_count: 0,
+        }
+    }
+
+    /// The frame rate of the video, defaults to 30.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> EncoderSink<E> {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    fn start(&mut self, width: u32, height: u32) -> XCapResult<()> {
+        self.encoder.configure(width, height, self.frame_rate)?;
+
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        // https://wiki.multimedia.cx/index.php/IVF
+        writer.write_all(b"DKIF")?;
+        writer.write_all(&0u16.to_le_bytes())?;
+        writer.write_all(&32u16.to_le_bytes())?;
+        writer.write_all(&self.encoder.fourcc())?;
+        writer.write_all(&(width as u16).to_le_bytes())?;
+        writer.write_all(&(height as u16).to_le_bytes())?;
+        writer.write_all(&self.frame_rate.to_le_bytes())?;
+        writer.write_all(&1u32.to_le_bytes())?;
+        // 帧数在结束时回填
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        self.writer = Some(writer);
+        self.size = Some((width, height));
+        self.packet_count = 0;
+
+        Ok(())
+    }
+
+    fn write_packets(&mut self, packets: Vec<EncodedPacket>) -> XCapResult<()> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| XCapError::new("EncoderSink is finished"))?;
+
+        for packet in packets {
+            writer.write_all(&(packet.data.len() as u32).to_le_bytes())?;
+            writer.write_all(&packet.pts.to_le_bytes())?;
+            writer.write_all(&packet.data)?;
+            self.packet_count += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: VideoEncoder> FrameSink for EncoderSink<E> {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        check_frame(frame)?;
+
+        match self.size {
+            None => self.start(frame.width, frame.height)?,
+            Some(size) if size != (frame.width, frame.height) => {
+                return Err(XCapError::new(format!(
+                    "Frame size {}x{} differs from video size {}x{}",
+                    frame.width, frame.height, size.0, size.1
+                )))
+            }
+            Some(_) => {}
+        }
+
+        let packets = self.encoder.encode(frame)?;
+        self.write_packets(packets)
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+
+        let packets = self.encoder.finish()?;
+        self.write_packets(packets)?;
+
+        if let Some(mut writer) = self.writer.take() {
+            writer.seek(SeekFrom::Start(24))?;
+            writer.write_all(&self.packet_count.to_le_bytes())?;
+            writer.flush()?;
+        }
+        self.size = None;
+
+        Ok(())
+    }
+}
+
+impl<E: VideoEncoder> Drop for EncoderSink<E> {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            log::error!("EncoderSink finish failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_to_i420() {
+        // 白、黑两列，奇数宽度
+        let raw = [255, 255, 255, 255, 0, 0, 0, 255, 255, 255, 255, 255];
+        let (y_plane, u_plane, v_plane) = rgba_to_i420(&raw, 3, 1);
+
+        assert_eq!(y_plane, [235, 16, 235]);
+        assert_eq!((u_plane.len(), v_plane.len()), (2, 2));
+        assert_eq!(u_plane[1], 128);
+    }
+}
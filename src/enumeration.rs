@@ -0,0 +1,53 @@
+use crate::error::XCapError;
+
+/// An item that could not be inspected while enumerating.
+#[derive(Debug)]
+pub struct EnumerationError {
+    /// The id of the item, `None` when a whole group failed, e.g. a screen of an X server.
+    pub id: Option<u32>,
+    pub error: XCapError,
+}
+
+/// The items that could be enumerated, and the errors of those that could not, so callers can
+/// report e.g. "3 windows could not be inspected" instead of silently missing them.
+#[derive(Debug)]
+pub struct Enumeration<T> {
+    pub items: Vec<T>,
+    pub errors: Vec<EnumerationError>,
+}
+
+impl<T> Default for Enumeration<T> {
+    fn default() -> Self {
+        Enumeration {
+            items: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl<T> Enumeration<T> {
+    // 浏览器里没有窗口可以列出
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn push_error<E: Into<XCapError>>(&mut self, id: Option<u32>, error: E) {
+        self.errors.push(EnumerationError {
+            id,
+            error: error.into(),
+        });
+    }
+
+    pub(crate) fn map<U, F: FnMut(T) -> U>(self, f: F) -> Enumeration<U> {
+        Enumeration {
+            items: self.items.into_iter().map(f).collect(),
+            errors: self.errors,
+        }
+    }
+
+    /// The items, logging the errors the way plain enumeration always did.
+    pub(crate) fn into_logged_items(self) -> Vec<T> {
+        for EnumerationError { id, error } in &self.errors {
+            log::error!("Enumerate {:?} failed: {}", id, error);
+        }
+
+        self.items
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::PoisonError;
+use std::{fmt, sync::PoisonError};
 
 use thiserror::Error;
 
@@ -6,8 +6,22 @@ use thiserror::Error;
 pub enum XCapError {
     #[error("{0}")]
     Error(String),
+    /// The window or monitor was destroyed or disconnected since it was enumerated, enumerate
+    /// again to get fresh handles.
+    #[error("The window or monitor no longer exists")]
+    SourceGone,
+    /// The platform refused access, show `remediation` to the user to let them grant it.
+    #[error("Permission denied: {reason}, {remediation}")]
+    PermissionDenied {
+        reason: String,
+        remediation: Remediation,
+    },
     #[error("StdSyncPoisonError {0}")]
     StdSyncPoisonError(String),
+    #[error(transparent)]
+    StdIOError(#[from] std::io::Error),
+    #[error(transparent)]
+    ImageImageError(#[from] image::ImageError),
 
     #[cfg(target_os = "linux")]
     #[error(transparent)]
@@ -17,18 +31,12 @@ pub enum XCapError {
     XcbConnError(#[from] xcb::ConnError),
     #[cfg(target_os = "linux")]
     #[error(transparent)]
-    ImageImageError(#[from] image::ImageError),
-    #[cfg(target_os = "linux")]
-    #[error(transparent)]
     StdStrUtf8Error(#[from] std::str::Utf8Error),
     #[cfg(target_os = "linux")]
     #[error(transparent)]
     DbusError(#[from] dbus::Error),
     #[cfg(target_os = "linux")]
     #[error(transparent)]
-    StdIOError(#[from] std::io::Error),
-    #[cfg(target_os = "linux")]
-    #[error(transparent)]
     StdTimeSystemTimeError(#[from] std::time::SystemTimeError),
 
     #[cfg(target_os = "macos")]
@@ -47,6 +55,82 @@ impl XCapError {
     pub fn new<S: ToString>(err: S) -> Self {
         XCapError::Error(err.to_string())
     }
+
+    /// Whether setting the capture up again is likely to succeed, e.g. after a reset X
+    /// connection or a lost Desktop Duplication.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            XCapError::XcbConnError(_) => true,
+            #[cfg(target_os = "windows")]
+            XCapError::WindowsCoreError(err) => {
+                use windows::Win32::Graphics::Dxgi::{
+                    DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET,
+                    DXGI_ERROR_SESSION_DISCONNECTED,
+                };
+
+                [
+                    DXGI_ERROR_ACCESS_LOST,
+                    DXGI_ERROR_DEVICE_REMOVED,
+                    DXGI_ERROR_DEVICE_RESET,
+                    DXGI_ERROR_SESSION_DISCONNECTED,
+                ]
+                .contains(&err.code())
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn permission_denied<S: ToString>(reason: S, remediation: Remediation) -> Self {
+        XCapError::PermissionDenied {
+            reason: reason.to_string(),
+            remediation,
+        }
+    }
+}
+
+/// What the user can do to grant the access a [`XCapError::PermissionDenied`] lacked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Remediation {
+    /// Allow the app in a Privacy & Security pane of the macOS System Settings, `url` opens the
+    /// pane with `open`.
+    PrivacyPane {
+        name: &'static str,
+        url: &'static str,
+    },
+    /// The user declined the screen sharing dialog of xdg-desktop-portal or the browser,
+    /// capturing again shows it again.
+    SharingDialog,
+    /// The target runs at a higher integrity level, e.g. as administrator or on the secure
+    /// desktop, the app must run elevated too.
+    IntegrityLevel,
+    /// Add the user to a group, e.g. `video` for DRM and framebuffer devices, and log in again.
+    UserGroup(&'static str),
+    /// Grant the executable a Linux capability with `setcap`, e.g. `cap_sys_admin` to read
+    /// the DRM framebuffers of the display server, or run it as root.
+    Capability(&'static str),
+}
+
+impl fmt::Display for Remediation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Remediation::PrivacyPane { name, .. } => write!(
+                f,
+                "allow the app in System Settings > Privacy & Security > {}",
+                name
+            ),
+            Remediation::SharingDialog => write!(f, "accept the screen sharing dialog"),
+            Remediation::IntegrityLevel => write!(f, "run the app as administrator"),
+            Remediation::UserGroup(group) => {
+                write!(f, "add the user to the {} group and log in again", group)
+            }
+            Remediation::Capability(capability) => write!(
+                f,
+                "grant the executable {} with setcap or run it as root",
+                capability
+            ),
+        }
+    }
 }
 
 // #[cfg(target_os = "macos")]
@@ -0,0 +1,139 @@
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex},
+};
+
+use crate::{error::XCapResult, video_recorder::Frame, XCapError};
+
+/// What a stream does with a new frame while the consumer is still busy and the queue of
+/// frames in flight is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DropPolicy {
+    /// Wait for the consumer, the capture slows down to its pace.
+    #[default]
+    Block,
+    /// Drop the oldest queued frame, the consumer always gets the latest ones, e.g. for live
+    /// previews.
+    DropOldest,
+    /// Drop the new frame, the queued frames are delivered without gaps between them.
+    DropNewest,
+}
+
+// 浏览器里没有线程，不需要队列
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+#[derive(Debug, Default)]
+struct QueueState {
+    frames: VecDeque<Frame>,
+    closed: bool,
+    dropped: u64,
+}
+
+/// A bounded queue between the capture thread and a slow consumer.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct FrameQueue {
+    policy: DropPolicy,
+    capacity: usize,
+    state: Mutex<QueueState>,
+    condvar: Condvar,
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+impl FrameQueue {
+    pub fn new(policy: DropPolicy, capacity: usize) -> FrameQueue {
+        FrameQueue {
+            policy,
+            capacity: capacity.max(1),
+            state: Mutex::new(QueueState::default()),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Queue a frame according to the policy, errors once the consumer closed the queue.
+    pub fn push(&self, frame: Frame) -> XCapResult<()> {
+        let mut state = self.state.lock()?;
+
+        if self.policy == DropPolicy::Block {
+            while !state.closed && state.frames.len() >= self.capacity {
+                state = self.condvar.wait(state)?;
+            }
+        }
+        if state.closed {
+            return Err(XCapError::new("Frame consumer stopped"));
+        }
+
+        if state.frames.len() >= self.capacity {
+            state.dropped += 1;
+            match self.policy {
+                DropPolicy::DropNewest => return Ok(()),
+                _ => {
+                    state.frames.pop_front();
+                }
+            }
+        }
+        state.frames.push_back(frame);
+        self.condvar.notify_all();
+
+        Ok(())
+    }
+
+    /// The next frame, `None` once the queue is closed and drained.
+    pub fn pop(&self) -> XCapResult<Option<Frame>> {
+        let mut state = self.state.lock()?;
+
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.condvar.notify_all();
+                return Ok(Some(frame));
+            }
+            if state.closed {
+                return Ok(None);
+            }
+            state = self.condvar.wait(state)?;
+        }
+    }
+
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            self.condvar.notify_all();
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().map(|state| state.dropped).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(index: u8) -> Frame {
+        Frame::new(1, 1, vec![index, 0, 0, 255])
+    }
+
+    #[test]
+    fn drop_policies() {
+        let oldest = FrameQueue::new(DropPolicy::DropOldest, 2);
+        let newest = FrameQueue::new(DropPolicy::DropNewest, 2);
+        for index in 0..4 {
+            oldest.push(frame(index)).unwrap();
+            newest.push(frame(index)).unwrap();
+        }
+        oldest.close();
+        newest.close();
+
+        let drain = |queue: &FrameQueue| {
+            let mut frames = Vec::new();
+            while let Some(frame) = queue.pop().unwrap() {
+                frames.push(frame.raw[0]);
+            }
+            frames
+        };
+        assert_eq!(drain(&oldest), [2, 3]);
+        assert_eq!(drain(&newest), [0, 1]);
+        assert_eq!(oldest.dropped(), 2);
+        assert!(newest.push(frame(4)).is_err());
+    }
+}
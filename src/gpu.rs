@@ -0,0 +1,66 @@
+use std::{ffi::c_void, time::SystemTime};
+
+use windows::{
+    core::Interface,
+    Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11Multithread, ID3D11Texture2D},
+};
+
+use crate::{error::XCapResult, XCapError};
+
+/// A Direct3D 11 device owned by the application, register it with
+/// [`crate::XCapContext::with_gpu_device`] so Desktop Duplication captures on it and frames can
+/// be used as textures without a copy between devices.
+///
+/// Only Direct3D 11 is supported, the macOS and Linux backends deliver frames in system memory,
+/// there is no texture to share with a Metal or OpenGL context.
+#[derive(Debug, Clone)]
+pub struct GpuDevice {
+    pub(crate) d3d11_device: ID3D11Device,
+}
+
+impl GpuDevice {
+    /// Register an `ID3D11Device`, a reference to it is kept. The device must be created on the
+    /// adapter driving the captured monitors, with `D3D11_CREATE_DEVICE_BGRA_SUPPORT`.
+    /// Multithread protection is turned on, capture runs on its own thread.
+    ///
+    /// # Safety
+    ///
+    /// `device` must point to a valid `ID3D11Device`.
+    pub unsafe fn from_d3d11_device(device: *mut c_void) -> XCapResult<GpuDevice> {
+        let d3d11_device = ID3D11Device::from_raw_borrowed(&device)
+            .cloned()
+            .ok_or_else(|| XCapError::new("ID3D11Device is null"))?;
+
+        // 返回值是之前的状态
+        let _ = d3d11_device
+            .cast::<ID3D11Multithread>()?
+            .SetMultithreadProtected(true);
+
+        Ok(GpuDevice { d3d11_device })
+    }
+
+    /// The `ID3D11Device`, without adding a reference.
+    pub fn as_raw(&self) -> *mut c_void {
+        self.d3d11_device.as_raw()
+    }
+}
+
+/// A captured frame as a texture on the registered [`GpuDevice`], see
+/// [`crate::VideoRecorder::on_texture`].
+///
+/// The texture is `DXGI_FORMAT_B8G8R8A8_UNORM`, in the native orientation of the monitor,
+/// bindable as a shader resource and shareable with `IDXGIResource::GetSharedHandle`.
+#[derive(Debug, Clone)]
+pub struct GpuTexture {
+    pub width: u32,
+    pub height: u32,
+    pub timestamp: SystemTime,
+    pub(crate) texture: ID3D11Texture2D,
+}
+
+impl GpuTexture {
+    /// The `ID3D11Texture2D`, without adding a reference, valid as long as `self` lives.
+    pub fn as_raw(&self) -> *mut c_void {
+        self.texture.as_raw()
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rdev::{Button, Event, EventType, Key};
+
+use crate::{
+    compositor::{anchor_position, blend_pixel, draw_text},
+    font::text_size,
+    Anchor, Monitor, TextStyle,
+};
+
+/// How long a click ripple is drawn.
+const RIPPLE_DURATION: Duration = Duration::from_millis(500);
+/// How long the keystroke banner stays after the last key.
+const BANNER_DURATION: Duration = Duration::from_secs(2);
+/// The banner keeps the last keys that fit.
+const BANNER_MAX_CHARS: usize = 32;
+
+#[derive(Debug, Default)]
+struct InputState {
+    cursor: (f64, f64),
+    clicks: VecDeque<(f64, f64, Button, Instant)>,
+    modifiers: Vec<&'static str>,
+    banner: String,
+    last_key: Option<(Instant, bool)>,
+}
+
+fn modifier_label(key: Key) -> Option<&'static str> {
+    match key {
+        Key::ControlLeft | Key::ControlRight => Some("Ctrl"),
+        Key::Alt | Key::AltGr => Some("Alt"),
+        Key::ShiftLeft | Key::ShiftRight => Some("Shift"),
+        Key::MetaLeft | Key::MetaRight => Some("Meta"),
+        _ => None,
+    }
+}
+
+fn key_label(key: Key) -> String {
+    match key {
+        Key::Return | Key::KpReturn => String::from("Enter"),
+        Key::Escape => String::from("Esc"),
+        Key::UpArrow => String::from("Up"),
+        Key::DownArrow => String::from("Down"),
+        Key::LeftArrow => String::from("Left"),
+        Key::RightArrow => String::from("Right"),
+        key => {
+            let name = format!("{:?}", key);
+            // KeyA、Num1 这类按键只显示字符本身
+            match ["Key", "Num", "Kp"]
+                .iter()
+                .find_map(|prefix| name.strip_prefix(prefix))
+            {
+                Some(rest) if rest.chars().count() == 1 => rest.to_string(),
+                _ => name,
+            }
+        }
+    }
+}
+
+impl InputState {
+    fn handle(&mut self, event: Event) {
+        let now = Instant::now();
+
+        match event.event_type {
+            EventType::MouseMove { x, y } => self.cursor = (x, y),
+            EventType::ButtonPress(button) => {
+                self.clicks
+                    .push_back((self.cursor.0, self.cursor.1, button, now));
+            }
+            EventType::KeyPress(key) => {
+                if let Some(modifier) = modifier_label(key) {
+                    if !self.modifiers.contains(&modifier) {
+                        self.modifiers.push(modifier);
+                    }
+                    return;
+                }
+
+                // 按住 Ctrl、Alt、Meta 时显示组合键，否则显示输入的字符
+                let typed = event
+                    .name
+                    .filter(|name| !name.is_empty() && !name.chars().any(char::is_control))
+                    .filter(|_| self.modifiers.iter().all(|modifier| *modifier == "Shift"));
+                let (label, is_char) = match typed {
+                    Some(name) => (name, true),
+                    None => {
+                        let mut parts: Vec<String> =
+                            self.modifiers.iter().map(|m| m.to_string()).collect();
+                        parts.push(key_label(key));
+                        (parts.join("+"), false)
+                    }
+                };
+                self.push_key(&label, is_char, now);
+            }
+            EventType::KeyRelease(key) => {
+                if let Some(modifier) = modifier_label(key) {
+                    self.modifiers.retain(|m| *m != modifier);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push_key(&mut self, label: &str, is_char: bool, now: Instant) {
+        let previous = self
+            .last_key
+            .filter(|(time, _)| now.duration_since(*time) < BANNER_DURATION);
+
+        match previous {
+            None => self.banner.clear(),
+            // 连续输入的字符拼在一起，组合键之间用空格分开
+            Some((_, previous_is_char)) if !(is_char && previous_is_char) => self.banner.push(' '),
+            _ => {}
+        }
+        self.banner.push_str(label);
+        self.last_key = Some((now, is_char));
+
+        let chars = self.banner.chars().count();
+        if chars > BANNER_MAX_CHARS {
+            self.banner = self.banner.chars().skip(chars - BANNER_MAX_CHARS).collect();
+        }
+    }
+}
+
+fn input_state() -> &'static Arc<Mutex<InputState>> {
+    static INPUT_STATE: OnceLock<Arc<Mutex<InputState>>> = OnceLock::new();
+
+    INPUT_STATE.get_or_init(|| {
+        let state = Arc::new(Mutex::new(InputState::default()));
+        let listener_state = state.clone();
+
+        // rdev::listen 会一直阻塞，整个进程只启动一个监听线程
+        thread::spawn(move || {
+            let result = rdev::listen(move |event| {
+                if let Ok(mut state) = listener_state.lock() {
+                    state.handle(event);
+                }
+            });
+
+            if let Err(err) = result {
+                log::error!("Listen input events failed: {:?}", err);
+            }
+        });
+
+        state
+    })
+}
+
+fn draw_ring(
+    raw: &mut [u8],
+    width: u32,
+    height: u32,
+    (center_x, center_y): (f64, f64),
+    radius: f64,
+    thickness: f64,
+    color: [u8; 4],
+) {
+    let outer = radius + thickness / 2.0;
+
+    for y in (center_y - outer).floor() as i64..=(center_y + outer).ceil() as i64 {
+        for x in (center_x - outer).floor() as i64..=(center_x + outer).ceil() as i64 {
+            let distance = (x as f64 - center_x).hypot(y as f64 - center_y);
+            if (distance - radius).abs() <= thickness / 2.0 {
+                blend_pixel(raw, width, height, x, y, color);
+            }
+        }
+    }
+}
+
+/// Draws click ripples and a banner of the keys being typed onto frames, for tutorial videos.
+/// Add it to a [`crate::Compositor`] with [`crate::Compositor::with_input`].
+///
+/// Input is listened to globally with `rdev`, by a thread started on first use that lives as
+/// long as the process. On macOS the process needs the Accessibility permission, on Linux an X
+/// server with the RECORD extension, native Wayland sessions are not supported.
+#[derive(Clone)]
+pub struct InputVisualizer {
+    state: Arc<Mutex<InputState>>,
+    monitor: Option<(i32, i32, u32, u32)>,
+    anchor: Anchor,
+    style: TextStyle,
+}
+
+impl fmt::Debug for InputVisualizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputVisualizer")
+            .field("monitor", &self.monitor)
+            .field("anchor", &self.anchor)
+            .field("style", &self.style)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for InputVisualizer {
+    fn default() -> Self {
+        InputVisualizer::new()
+    }
+}
+
+impl InputVisualizer {
+    pub fn new() -> InputVisualizer {
+        InputVisualizer {
+            state: input_state().clone(),
+            monitor: None,
+            anchor: Anchor::BottomLeft,
+            style: TextStyle {
+                scale: 4,
+                ..TextStyle::default()
+            },
+        }
+    }
+
+    /// Map clicks to frames of `monitor`, without it clicks are drawn at their screen
+    /// coordinates.
+    pub fn with_monitor(mut self, monitor: &Monitor) -> InputVisualizer {
+        self.monitor = Some((monitor.x(), monitor.y(), monitor.width(), monitor.height()));
+        self
+    }
+
+    /// Where the keystroke banner is drawn, defaults to [`Anchor::BottomLeft`].
+    pub fn with_anchor(mut self, anchor: Anchor) -> InputVisualizer {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn with_style(mut self, style: TextStyle) -> InputVisualizer {
+        self.style = style;
+        self
+    }
+
+    pub(crate) fn draw(&self, raw: &mut [u8], width: u32, height: u32) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let now = Instant::now();
+
+        // 高分屏上帧的像素比屏幕坐标多，按比例换算
+        let (origin_x, origin_y, scale_x, scale_y) = match self.monitor {
+            Some((x, y, monitor_width, monitor_height)) => (
+                x as f64,
+                y as f64,
+                width as f64 / monitor_width.max(1) as f64,
+                height as f64 / monitor_height.max(1) as f64,
+            ),
+            None => (0.0, 0.0, 1.0, 1.0),
+        };
+
+        state
+            .clicks
+            .retain(|(.., time)| now.duration_since(*time) < RIPPLE_DURATION);
+        for (x, y, button, time) in &state.clicks {
+            let progress = now.duration_since(*time).as_secs_f64() / RIPPLE_DURATION.as_secs_f64();
+            let [r, g, b] = match button {
+                Button::Right => [0, 160, 255],
+                _ => [255, 200, 0],
+            };
+            let alpha = (220.0 * (1.0 - progress)) as u8;
+
+            draw_ring(
+                raw,
+                width,
+                height,
+                ((x - origin_x) * scale_x, (y - origin_y) * scale_y),
+                (8.0 + 28.0 * progress) * scale_x,
+                3.0 * scale_x,
+                [r, g, b, alpha],
+            );
+        }
+
+        let show_banner = state
+            .last_key
+            .is_some_and(|(time, _)| now.duration_since(time) < BANNER_DURATION);
+        if show_banner && !state.banner.is_empty() {
+            let size = text_size(&state.banner, self.style.scale.max(1));
+            let margin = 8 * self.style.scale.max(1);
+            let position = anchor_position(self.anchor, margin, size, (width, height));
+            draw_text(raw, width, height, &state.banner, position, &self.style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_event(event_type: EventType, name: Option<&str>) -> Event {
+        Event {
+            time: std::time::SystemTime::now(),
+            name: name.map(String::from),
+            event_type,
+        }
+    }
+
+    #[test]
+    fn banner_of_typed_keys() {
+        let mut state = InputState::default();
+
+        state.handle(key_event(EventType::KeyPress(Key::KeyH), Some("h")));
+        state.handle(key_event(EventType::KeyPress(Key::KeyI), Some("i")));
+        state.handle(key_event(EventType::KeyPress(Key::ControlLeft), None));
+        state.handle(key_event(EventType::KeyPress(Key::KeyC), Some("\u{3}")));
+        state.handle(key_event(EventType::KeyRelease(Key::ControlLeft), None));
+        state.handle(key_event(EventType::KeyPress(Key::Return), Some("\r")));
+
+        assert_eq!(state.banner, "hi Ctrl+C Enter");
+    }
+}
@@ -0,0 +1,246 @@
+#[cfg(feature = "image")]
+use image::{imageops, RgbaImage};
+
+use crate::{error::XCapResult, Monitor};
+
+/// A rectangle in virtual screen coordinates, shared by all monitors. The origin is the top
+/// left corner of the primary monitor, monitors left of or above it have negative coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The x coordinate right after the rectangle.
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    /// The y coordinate right below the rectangle.
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The overlapping part of both rectangles, `None` if they do not overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+
+        if left >= right || top >= bottom {
+            return None;
+        }
+
+        Some(Rect::new(
+            left,
+            top,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        ))
+    }
+
+    /// The smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let left = self.x.min(other.x);
+        let top = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+
+        Rect::new(left, top, (right - left) as u32, (bottom - top) as u32)
+    }
+}
+
+fn bounding_rect(rects: &[Rect]) -> Rect {
+    rects
+        .iter()
+        .copied()
+        .reduce(|bounds, rect| bounds.union(&rect))
+        .unwrap_or_default()
+}
+
+/// The monitors and their place in the virtual screen.
+#[derive(Debug, Clone)]
+pub struct ScreenLayout {
+    bounds: Rect,
+    monitors: Vec<Monitor>,
+    rects: Vec<Rect>,
+}
+
+impl ScreenLayout {
+    fn new(monitors: Vec<Monitor>) -> ScreenLayout {
+        let rects: Vec<Rect> = monitors
+            .iter()
+            .map(|monitor| Rect::new(monitor.x(), monitor.y(), monitor.width(), monitor.height()))
+            .collect();
+
+        ScreenLayout {
+            bounds: bounding_rect(&rects),
+            monitors,
+            rects,
+        }
+    }
+
+    /// The bounding box of all monitors.
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn monitors(&self) -> &[Monitor] {
+        &self.monitors
+    }
+
+    /// The rectangle of `monitor` in virtual screen coordinates.
+    pub fn monitor_rect(&self, monitor: &Monitor) -> Option<Rect> {
+        self.monitors
+            .iter()
+            .position(|m| m.id() == monitor.id())
+            .map(|index| self.rects[index])
+    }
+
+    /// The monitor containing the point, `None` in the gaps between monitors.
+    pub fn monitor_at(&self, x: i32, y: i32) -> Option<&Monitor> {
+        self.rects
+            .iter()
+            .position(|rect| rect.contains(x, y))
+            .map(|index| &self.monitors[index])
+    }
+
+    /// Convert a virtual screen point to the monitor containing it and the point relative to
+    /// that monitor's top left corner.
+    pub fn to_monitor(&self, x: i32, y: i32) -> Option<(&Monitor, i32, i32)> {
+        let index = self.rects.iter().position(|rect| rect.contains(x, y))?;
+        let rect = self.rects[index];
+
+        Some((&self.monitors[index], x - rect.x, y - rect.y))
+    }
+
+    /// Convert a point relative to `monitor` to virtual screen coordinates.
+    pub fn to_global(&self, monitor: &Monitor, x: i32, y: i32) -> Option<(i32, i32)> {
+        let rect = self.monitor_rect(monitor)?;
+
+        Some((rect.x + x, rect.y + y))
+    }
+
+    /// Capture all monitors into one image of [`ScreenLayout::bounds`], the gaps between
+    /// monitors of different sizes or offsets stay transparent.
+    #[cfg(feature = "image")]
+    pub fn capture(&self) -> XCapResult<RgbaImage> {
+        self.stitch(None)
+    }
+
+    /// Like [`ScreenLayout::capture`], with the gaps filled from the root window background on
+    /// X11. Elsewhere, or when no wallpaper pixmap is set, the gaps stay transparent.
+    #[cfg(feature = "image")]
+    pub fn capture_with_background(&self) -> XCapResult<RgbaImage> {
+        let background = match self.monitors.first() {
+            Some(monitor) => monitor
+                .impl_monitor
+                .capture_root_background(self.bounds)
+                .unwrap_or_else(|err| {
+                    log::debug!("Capture root background failed: {}", err);
+                    None
+                }),
+            None => None,
+        };
+
+        self.stitch(background)
+    }
+
+    #[cfg(feature = "image")]
+    fn stitch(&self, background: Option<RgbaImage>) -> XCapResult<RgbaImage> {
+        let (width, height) = (self.bounds.width, self.bounds.height);
+        let mut image = match background {
+            Some(background) if background.dimensions() == (width, height) => background,
+            // HiDPI 下背景按物理像素截取
+            Some(background) => {
+                imageops::resize(&background, width, height, imageops::FilterType::Triangle)
+            }
+            None => RgbaImage::new(width, height),
+        };
+
+        for (monitor, rect) in self.monitors.iter().zip(&self.rects) {
+            let mut capture = monitor.capture_rgba()?;
+            if capture.dimensions() != (rect.width, rect.height) {
+                capture = imageops::resize(
+                    &capture,
+                    rect.width,
+                    rect.height,
+                    imageops::FilterType::Triangle,
+                );
+            }
+
+            imageops::replace(
+                &mut image,
+                &capture,
+                (rect.x - self.bounds.x) as i64,
+                (rect.y - self.bounds.y) as i64,
+            );
+        }
+
+        Ok(image)
+    }
+
+    /// Convert a virtual screen point to a pixel of an image stitched from all monitors, whose
+    /// top left corner is the top left corner of [`ScreenLayout::bounds`].
+    pub fn to_bounds(&self, x: i32, y: i32) -> Option<(u32, u32)> {
+        if !self.bounds.contains(x, y) {
+            return None;
+        }
+
+        Some(((x - self.bounds.x) as u32, (y - self.bounds.y) as u32))
+    }
+}
+
+/// The current monitor layout of the virtual screen.
+pub fn screen_layout() -> XCapResult<ScreenLayout> {
+    Ok(ScreenLayout::new(Monitor::all()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rect_intersection_union() {
+        let left = Rect::new(-1920, 0, 1920, 1080);
+        let right = Rect::new(0, -200, 2560, 1440);
+
+        assert_eq!(left.intersection(&right), None);
+        assert_eq!(left.union(&right), Rect::new(-1920, -200, 4480, 1440));
+        assert_eq!(
+            right.intersection(&Rect::new(2000, 1000, 1000, 1000)),
+            Some(Rect::new(2000, 1000, 560, 240))
+        );
+        assert!(left.contains(-1, 1079));
+        assert!(!left.contains(0, 0));
+    }
+
+    #[test]
+    fn bounding_rect_of_monitors() {
+        let rects = [
+            Rect::new(0, 0, 1920, 1080),
+            Rect::new(1920, 0, 1280, 1024),
+            Rect::new(-1080, -400, 1080, 1920),
+        ];
+
+        assert_eq!(bounding_rect(&rects), Rect::new(-1080, -400, 4280, 1920));
+        assert_eq!(bounding_rect(&[]), Rect::default());
+    }
+}
@@ -1,6 +1,53 @@
+mod application;
+mod backend;
+mod capabilities;
+mod clock;
+mod color;
+#[cfg(feature = "image")]
+mod compose;
+mod compositor;
+mod context;
+mod custom;
+pub mod diff;
+#[cfg(feature = "egui")]
+mod egui;
+mod encode;
+mod encoder;
+mod enumeration;
 mod error;
+mod font;
+mod frame_queue;
+#[cfg(target_os = "windows")]
+mod gpu;
+#[cfg(all(feature = "input-overlay", not(target_arch = "wasm32")))]
+mod input_overlay;
+mod layout;
+mod metadata;
 mod monitor;
+mod motion;
+mod mux;
+mod ocr;
+mod pixel_format;
+mod pointer;
+#[cfg(feature = "image")]
+mod popup;
+mod quantize;
+mod region;
+mod replay;
+mod report;
+mod scheduler;
+#[cfg(feature = "image")]
+mod scrolling;
+#[cfg(all(feature = "selector", not(target_arch = "wasm32")))]
+mod selector;
+#[cfg(feature = "server")]
+mod server;
+mod session;
+mod sink;
+mod tile;
+mod utils;
 mod video_recorder;
+mod watcher;
 mod window;
 
 #[cfg(target_os = "macos")]
@@ -15,10 +62,86 @@ mod platform;
 #[path = "linux/mod.rs"]
 mod platform;
 
+#[cfg(target_arch = "wasm32")]
+#[path = "web/mod.rs"]
+mod platform;
+
+#[cfg(feature = "image")]
 pub use image;
 
-pub use error::{XCapError, XCapResult};
-pub use monitor::Monitor;
-pub use window::Window;
+/// Node.js bindings, the addon links against the node runtime and is built as a cdylib.
+#[cfg(feature = "napi")]
+pub mod napi;
+
+/// Linux specific APIs.
+#[cfg(target_os = "linux")]
+pub mod linux {
+    #[cfg(feature = "image")]
+    pub use crate::platform::capture_drawable;
+    pub use crate::platform::screencast::{
+        CursorMode, PersistMode, PortalStream, ScreenCastOptions, ScreenCastSession, SourceType,
+    };
+    pub use crate::platform::x_connection::{release_xcb_connection, use_xcb_connection};
+}
+
+pub use application::{applications, Application};
+pub use backend::{backend, Backend};
+pub use capabilities::{capabilities, Capabilities, Inhibition};
+pub use clock::FrameClock;
+pub use color::{ColorSpace, GammaRamp, TransferFunction};
+#[cfg(feature = "image")]
+pub use compose::compose_windows;
+pub use compositor::{Anchor, CompositedSink, Compositor, Overlay, TextStyle};
+pub use context::XCapContext;
+#[cfg(feature = "image")]
+pub use custom::{CustomBackend, CustomMonitor};
+pub use encode::EncodeOptions;
+#[cfg(feature = "rav1e")]
+pub use encoder::Av1Encoder;
+pub use encoder::{rgba_to_i420, EncodedPacket, EncoderSink, VideoEncoder};
+pub use enumeration::{Enumeration, EnumerationError};
+pub use error::{Remediation, XCapError, XCapResult};
+pub use frame_queue::DropPolicy;
+#[cfg(target_os = "windows")]
+pub use gpu::{GpuDevice, GpuTexture};
+#[cfg(all(feature = "input-overlay", not(target_arch = "wasm32")))]
+pub use input_overlay::InputVisualizer;
+pub use layout::{screen_layout, Rect, ScreenLayout};
+pub use metadata::{CursorPosition, FocusedWindow, FrameMetadata};
+pub use monitor::{Monitor, VideoMode};
+#[cfg(not(target_arch = "wasm32"))]
+pub use motion::watch_region;
+pub use motion::MotionDetector;
+pub use mux::{AudioTrack, MatroskaMuxer, MatroskaSink};
+pub use ocr::{Binarize, OcrPreprocessor};
+pub use pixel_format::{CaptureOptions, PixelFormat};
+pub use pointer::Pointer;
+#[cfg(feature = "image")]
+pub use popup::{PopupCapture, PopupCatcher, PopupCatcherHandle};
+#[cfg(feature = "gif")]
+pub use quantize::GifSink;
+pub use quantize::{IndexedFrame, Quantizer};
+pub use region::Region;
+pub use replay::ReplayBuffer;
+pub use report::{capture_report, clear_capture_report, CaptureReport, Decision, ReportEntry};
+pub use window::{
+    ProcessArch, ProcessInfo, Visibility, Window, WindowFingerprint, WindowKind, WindowShape,
+};
 
-pub use video_recorder::VideoRecorder;
+pub use scheduler::{CaptureTarget, CronSchedule, Schedule, Scheduler, SchedulerHandle};
+#[cfg(feature = "image")]
+pub use scrolling::capture_scrolling;
+#[cfg(all(feature = "selector", not(target_arch = "wasm32")))]
+pub use selector::{select, Selection};
+#[cfg(feature = "server")]
+pub use server::{PreviewServer, PreviewServerHandle};
+pub use session::{CaptureSession, CaptureSessionHandle, FrameBundle};
+pub use sink::{FileSink, FrameSink, H264Encoder, StreamProtocol, StreamSink, VirtualCameraSink};
+pub use tile::{TileCompositor, TileLayout};
+#[cfg(not(target_arch = "wasm32"))]
+pub use video_recorder::Recording;
+pub use video_recorder::{Frame, StreamEvent, VideoRecorder};
+pub use watcher::{
+    DisplayEvent, FocusEvent, FocusStats, WatchEvent, Watcher, WatcherHandle, WindowEvent,
+    WindowState,
+};
@@ -1,14 +1,38 @@
 use image::RgbaImage;
 use std::env::var_os;
+use xcb::x::ScreenBuf;
 
-use crate::error::XCapResult;
+use crate::{
+    backend::Backend,
+    error::{XCapError, XCapResult},
+    layout::Rect,
+    report::capture_path,
+};
+
+#[cfg(any(
+    feature = "ext-image-copy-capture",
+    feature = "wlr-screencopy",
+    feature = "nvfbc"
+))]
+use crate::report::fallback;
 
+#[cfg(feature = "ext-image-copy-capture")]
+use super::ext_capture::{ext_capture_output, ext_capture_toplevel};
+#[cfg(feature = "nvfbc")]
+use super::nvfbc_capture::nvfbc_capture;
+#[cfg(feature = "wlr-screencopy")]
+use super::wlr_capture::wlr_capture;
 use super::{
-    impl_monitor::ImplMonitor, impl_window::ImplWindow, wayland_capture::wayland_capture,
-    xorg_capture::xorg_capture,
+    drm_capture::drm_capture,
+    fbdev_capture::fbdev_capture,
+    impl_monitor::{ImplMonitor, MonitorSource},
+    impl_window::{ImplWindow, WindowSource},
+    wayland_capture::wayland_capture,
+    x_connection::XConnection,
+    xorg_capture::{xorg_capture, xorg_capture_wallpaper},
 };
 
-fn wayland_detect() -> bool {
+pub(super) fn wayland_detect() -> bool {
     let xdg_session_type = var_os("XDG_SESSION_TYPE")
         .unwrap_or_default()
         .to_string_lossy()
@@ -22,24 +46,159 @@ fn wayland_detect() -> bool {
     xdg_session_type.eq("wayland") || wayland_display.to_lowercase().contains("wayland")
 }
 
+fn xorg_capture_monitor(
+    impl_monitor: &ImplMonitor,
+    conn: &XConnection,
+    screen_buf: &ScreenBuf,
+) -> XCapResult<RgbaImage> {
+    let x = ((impl_monitor.x as f32) * impl_monitor.scale_factor) as i32;
+    let y = ((impl_monitor.y as f32) * impl_monitor.scale_factor) as i32;
+    let width = ((impl_monitor.width as f32) * impl_monitor.scale_factor) as u32;
+    let height = ((impl_monitor.height as f32) * impl_monitor.scale_factor) as u32;
+
+    capture_path(
+        "x11-get-image",
+        xorg_capture(conn, screen_buf.root(), x, y, width, height),
+    )
+}
+
 pub fn capture_monitor(impl_monitor: &ImplMonitor) -> XCapResult<RgbaImage> {
+    // 指定 Wayland 时没有 XWayland 的输出也经过 portal 截图
+    if impl_monitor.backend == Some(Backend::Wayland) {
+        return wayland_capture(impl_monitor);
+    }
+
+    let (conn, screen_buf) = match &impl_monitor.source {
+        MonitorSource::Xorg {
+            conn, screen_buf, ..
+        } => (conn, screen_buf),
+        MonitorSource::Drm { card, crtc_id } => {
+            return capture_path("drm", drm_capture(card, *crtc_id))
+        }
+        MonitorSource::Fbdev { device } => return capture_path("fbdev", fbdev_capture(device)),
+        #[cfg(feature = "wlr-screencopy")]
+        MonitorSource::Wlr => return capture_path("wlr-screencopy", wlr_capture(impl_monitor)),
+        MonitorSource::Custom => {
+            return Err(XCapError::new(
+                "Custom monitors are captured by their backend",
+            ))
+        }
+    };
+
+    // XCapContext 指定了 backend 时不做自动选择
+    match impl_monitor.backend {
+        Some(Backend::X11) => return xorg_capture_monitor(impl_monitor, conn, screen_buf),
+        #[cfg(feature = "ext-image-copy-capture")]
+        Some(Backend::ExtImageCopyCapture) => {
+            return capture_path("ext-image-copy-capture", ext_capture_output(impl_monitor))
+        }
+        #[cfg(feature = "nvfbc")]
+        Some(Backend::NvFbc) => return capture_path("nvfbc", nvfbc_capture(impl_monitor)),
+        _ => {}
+    }
+
     if wayland_detect() {
+        #[cfg(feature = "ext-image-copy-capture")]
+        match capture_path("ext-image-copy-capture", ext_capture_output(impl_monitor)) {
+            Ok(image) => return Ok(image),
+            Err(err) => fallback(
+                "ext-image-copy-capture",
+                if cfg!(feature = "wlr-screencopy") {
+                    "wlr-screencopy"
+                } else {
+                    "dbus-screenshot"
+                },
+                &err,
+            ),
+        }
+
+        // wlroots 系的 compositor 可以直接截图，不需要经过 portal
+        #[cfg(feature = "wlr-screencopy")]
+        match capture_path("wlr-screencopy", wlr_capture(impl_monitor)) {
+            Ok(image) => return Ok(image),
+            Err(err) => fallback("wlr-screencopy", "dbus-screenshot", &err),
+        }
+
         wayland_capture(impl_monitor)
     } else {
-        let x = ((impl_monitor.x as f32) * impl_monitor.scale_factor) as i32;
-        let y = ((impl_monitor.y as f32) * impl_monitor.scale_factor) as i32;
-        let width = ((impl_monitor.width as f32) * impl_monitor.scale_factor) as u32;
-        let height = ((impl_monitor.height as f32) * impl_monitor.scale_factor) as u32;
+        #[cfg(feature = "nvfbc")]
+        match capture_path("nvfbc", nvfbc_capture(impl_monitor)) {
+            Ok(image) => return Ok(image),
+            Err(err) => fallback("nvfbc", "x11-get-image", &err),
+        }
 
-        xorg_capture(impl_monitor.screen_buf.root(), x, y, width, height)
+        xorg_capture_monitor(impl_monitor, conn, screen_buf)
     }
 }
 
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub fn capture_wallpaper(impl_monitor: &ImplMonitor) -> XCapResult<RgbaImage> {
+    let (conn, screen_buf) = match &impl_monitor.source {
+        MonitorSource::Xorg {
+            conn, screen_buf, ..
+        } => (conn, screen_buf),
+        _ => {
+            return Err(XCapError::new(
+                "Only X11 publishes the wallpaper, Wayland compositors keep it to themselves",
+            ))
+        }
+    };
+
+    let x = ((impl_monitor.x as f32) * impl_monitor.scale_factor) as i32;
+    let y = ((impl_monitor.y as f32) * impl_monitor.scale_factor) as i32;
+    let width = ((impl_monitor.width as f32) * impl_monitor.scale_factor) as u32;
+    let height = ((impl_monitor.height as f32) * impl_monitor.scale_factor) as u32;
+
+    xorg_capture_wallpaper(conn, screen_buf.root(), x, y, width, height)
+}
+
+/// The root window background under `rect` of the virtual screen, `None` without X11.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub fn capture_root_background(
+    impl_monitor: &ImplMonitor,
+    rect: Rect,
+) -> XCapResult<Option<RgbaImage>> {
+    let MonitorSource::Xorg {
+        conn, screen_buf, ..
+    } = &impl_monitor.source
+    else {
+        return Ok(None);
+    };
+
+    let scale_factor = impl_monitor.scale_factor;
+    let image = xorg_capture_wallpaper(
+        conn,
+        screen_buf.root(),
+        (rect.x as f32 * scale_factor) as i32,
+        (rect.y as f32 * scale_factor) as i32,
+        (rect.width as f32 * scale_factor) as u32,
+        (rect.height as f32 * scale_factor) as u32,
+    )?;
+
+    Ok(Some(image))
+}
+
 pub fn capture_window(impl_window: &ImplWindow) -> XCapResult<RgbaImage> {
+    let (conn, window) =
+        match &impl_window.source {
+            WindowSource::Xorg { conn, window } => (conn, *window),
+            #[cfg(feature = "ext-image-copy-capture")]
+            WindowSource::Wayland {
+                identifier: Some(identifier),
+            } => return capture_path("ext-image-copy-capture", ext_capture_toplevel(identifier)),
+            #[cfg(feature = "foreign-toplevel")]
+            WindowSource::Wayland { .. } => return Err(XCapError::new(
+                "Native Wayland windows can not be captured directly, use the ScreenCast portal",
+            )),
+        };
+
     let width = impl_window.width;
     let height = impl_window.height;
 
-    xorg_capture(impl_window.window, 0, 0, width, height)
+    capture_path(
+        "x11-get-image",
+        xorg_capture(conn, window, 0, 0, width, height),
+    )
 }
 
 // fn capture_screen_area(
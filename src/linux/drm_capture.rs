@@ -0,0 +1,589 @@
+use image::RgbaImage;
+use scopeguard::guard;
+use std::{
+    ffi::c_void,
+    fs::{self, File, OpenOptions},
+    io::ErrorKind,
+    mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
+    ptr, slice,
+};
+
+use crate::error::{Remediation, XCapError, XCapResult};
+
+use super::utils::ioctl;
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/drm/drm_mode.h
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmModeCardRes {
+    fb_id_ptr: u64,
+    crtc_id_ptr: u64,
+    connector_id_ptr: u64,
+    encoder_id_ptr: u64,
+    count_fbs: u32,
+    count_crtcs: u32,
+    count_connectors: u32,
+    count_encoders: u32,
+    min_width: u32,
+    max_width: u32,
+    min_height: u32,
+    max_height: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmModeModeInfo {
+    clock: u32,
+    hdisplay: u16,
+    hsync_start: u16,
+    hsync_end: u16,
+    htotal: u16,
+    hskew: u16,
+    vdisplay: u16,
+    vsync_start: u16,
+    vsync_end: u16,
+    vtotal: u16,
+    vscan: u16,
+    vrefresh: u32,
+    flags: u32,
+    r#type: u32,
+    name: [u8; 32],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmModeCrtc {
+    set_connectors_ptr: u64,
+    count_connectors: u32,
+    crtc_id: u32,
+    fb_id: u32,
+    x: u32,
+    y: u32,
+    gamma_size: u32,
+    mode_valid: u32,
+    mode: DrmModeModeInfo,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmModeGetEncoder {
+    encoder_id: u32,
+    encoder_type: u32,
+    crtc_id: u32,
+    possible_crtcs: u32,
+    possible_clones: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmModeGetConnector {
+    encoders_ptr: u64,
+    modes_ptr: u64,
+    props_ptr: u64,
+    prop_values_ptr: u64,
+    count_modes: u32,
+    count_props: u32,
+    count_encoders: u32,
+    encoder_id: u32,
+    connector_id: u32,
+    connector_type: u32,
+    connector_type_id: u32,
+    connection: u32,
+    mm_width: u32,
+    mm_height: u32,
+    subpixel: u32,
+    pad: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmModeFbCmd2 {
+    fb_id: u32,
+    width: u32,
+    height: u32,
+    pixel_format: u32,
+    flags: u32,
+    handles: [u32; 4],
+    pitches: [u32; 4],
+    offsets: [u32; 4],
+    modifier: [u64; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmModeMapDumb {
+    handle: u32,
+    pad: u32,
+    offset: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmPrimeHandle {
+    handle: u32,
+    flags: u32,
+    fd: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmGemClose {
+    handle: u32,
+    pad: u32,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/drm/drm.h
+// union drm_wait_vblank，request 的 signal 与 reply 的 tval_sec 重叠
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DrmWaitVblank {
+    r#type: u32,
+    sequence: u32,
+    tval_sec: i64,
+    tval_usec: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct DmaBufSync {
+    flags: u64,
+}
+
+const fn ioc(dir: u64, ty: u8, nr: u8, size: usize) -> u64 {
+    dir << 30 | (size as u64) << 16 | (ty as u64) << 8 | nr as u64
+}
+
+const IOC_WRITE: u64 = 1;
+const IOC_READ_WRITE: u64 = 3;
+
+const DRM_IOCTL_WAIT_VBLANK: u64 = ioc(IOC_READ_WRITE, b'd', 0x3a, mem::size_of::<DrmWaitVblank>());
+const DRM_IOCTL_GEM_CLOSE: u64 = ioc(IOC_WRITE, b'd', 0x09, mem::size_of::<DrmGemClose>());
+const DRM_IOCTL_PRIME_HANDLE_TO_FD: u64 =
+    ioc(IOC_READ_WRITE, b'd', 0x2d, mem::size_of::<DrmPrimeHandle>());
+const DRM_IOCTL_MODE_GETRESOURCES: u64 =
+    ioc(IOC_READ_WRITE, b'd', 0xa0, mem::size_of::<DrmModeCardRes>());
+const DRM_IOCTL_MODE_GETCRTC: u64 = ioc(IOC_READ_WRITE, b'd', 0xa1, mem::size_of::<DrmModeCrtc>());
+const DRM_IOCTL_MODE_GETENCODER: u64 = ioc(
+    IOC_READ_WRITE,
+    b'd',
+    0xa6,
+    mem::size_of::<DrmModeGetEncoder>(),
+);
+const DRM_IOCTL_MODE_GETCONNECTOR: u64 = ioc(
+    IOC_READ_WRITE,
+    b'd',
+    0xa7,
+    mem::size_of::<DrmModeGetConnector>(),
+);
+const DRM_IOCTL_MODE_MAP_DUMB: u64 =
+    ioc(IOC_READ_WRITE, b'd', 0xb3, mem::size_of::<DrmModeMapDumb>());
+const DRM_IOCTL_MODE_GETFB2: u64 = ioc(IOC_READ_WRITE, b'd', 0xce, mem::size_of::<DrmModeFbCmd2>());
+const DMA_BUF_IOCTL_SYNC: u64 = ioc(IOC_WRITE, b'b', 0, mem::size_of::<DmaBufSync>());
+
+const DMA_BUF_SYNC_READ: u64 = 1;
+const DMA_BUF_SYNC_END: u64 = 4;
+const DRM_MODE_CONNECTED: u32 = 1;
+const DRM_VBLANK_RELATIVE: u32 = 0x1;
+const DRM_VBLANK_SECONDARY: u32 = 0x2000_0000;
+const DRM_VBLANK_HIGH_CRTC_SHIFT: u32 = 1;
+const DRM_VBLANK_HIGH_CRTC_MASK: u32 = 0x0000_003e;
+const DRM_MODE_FB_MODIFIERS: u32 = 2;
+const DRM_FORMAT_MOD_LINEAR: u64 = 0;
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+const DRM_FORMAT_XRGB8888: u32 = fourcc(b"XR24");
+const DRM_FORMAT_ARGB8888: u32 = fourcc(b"AR24");
+const DRM_FORMAT_XBGR8888: u32 = fourcc(b"XB24");
+const DRM_FORMAT_ABGR8888: u32 = fourcc(b"AB24");
+const DRM_FORMAT_RGB565: u32 = fourcc(b"RG16");
+
+// https://github.com/torvalds/linux/blob/master/drivers/gpu/drm/drm_connector.c
+const CONNECTOR_TYPE_NAMES: [&str; 21] = [
+    "Unknown",
+    "VGA",
+    "DVI-I",
+    "DVI-D",
+    "DVI-A",
+    "Composite",
+    "SVIDEO",
+    "LVDS",
+    "Component",
+    "DIN",
+    "DP",
+    "HDMI-A",
+    "HDMI-B",
+    "TV",
+    "eDP",
+    "Virtual",
+    "DSI",
+    "DPI",
+    "Writeback",
+    "SPI",
+    "USB",
+];
+
+fn open_card(card: &Path) -> XCapResult<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(card)
+        .map_err(|err| match err.kind() {
+            ErrorKind::PermissionDenied => XCapError::permission_denied(
+                format!("open {:?}", card),
+                Remediation::UserGroup("video"),
+            ),
+            _ => err.into(),
+        })
+}
+
+/// One connected display of a DRM card.
+#[derive(Debug, Clone)]
+pub(super) struct DrmOutput {
+    pub card: PathBuf,
+    pub connector_id: u32,
+    pub crtc_id: u32,
+    /// The index of the CRTC in the card resources, vblank requests address CRTCs by it.
+    pub pipe: u32,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub frequency: f32,
+}
+
+fn get_crtc(fd: RawFd, crtc_id: u32) -> XCapResult<DrmModeCrtc> {
+    let mut crtc = DrmModeCrtc {
+        crtc_id,
+        ..Default::default()
+    };
+    ioctl(fd, DRM_IOCTL_MODE_GETCRTC, &mut crtc)?;
+
+    Ok(crtc)
+}
+
+fn get_card_outputs(card: &Path) -> XCapResult<Vec<DrmOutput>> {
+    let file = open_card(card)?;
+    let fd = file.as_raw_fd();
+
+    // 第一次调用获取数量，第二次调用填充 id 列表
+    let mut card_res = DrmModeCardRes::default();
+    ioctl(fd, DRM_IOCTL_MODE_GETRESOURCES, &mut card_res)?;
+
+    let mut connector_ids = vec![0u32; card_res.count_connectors as usize];
+    let mut crtc_ids = vec![0u32; card_res.count_crtcs as usize];
+    let mut encoder_ids = vec![0u32; card_res.count_encoders as usize];
+    card_res = DrmModeCardRes {
+        connector_id_ptr: connector_ids.as_mut_ptr() as u64,
+        crtc_id_ptr: crtc_ids.as_mut_ptr() as u64,
+        encoder_id_ptr: encoder_ids.as_mut_ptr() as u64,
+        count_connectors: card_res.count_connectors,
+        count_crtcs: card_res.count_crtcs,
+        count_encoders: card_res.count_encoders,
+        ..Default::default()
+    };
+    ioctl(fd, DRM_IOCTL_MODE_GETRESOURCES, &mut card_res)?;
+    connector_ids.truncate(card_res.count_connectors as usize);
+    crtc_ids.truncate(card_res.count_crtcs as usize);
+
+    let mut outputs = Vec::new();
+
+    for connector_id in connector_ids {
+        // 只请求一个模式，避免没有 DRM master 时触发耗时的强制探测
+        let mut mode = DrmModeModeInfo::default();
+        let mut connector = DrmModeGetConnector {
+            connector_id,
+            modes_ptr: &mut mode as *mut DrmModeModeInfo as u64,
+            count_modes: 1,
+            ..Default::default()
+        };
+        ioctl(fd, DRM_IOCTL_MODE_GETCONNECTOR, &mut connector)?;
+
+        if connector.connection != DRM_MODE_CONNECTED || connector.encoder_id == 0 {
+            continue;
+        }
+
+        let mut encoder = DrmModeGetEncoder {
+            encoder_id: connector.encoder_id,
+            ..Default::default()
+        };
+        ioctl(fd, DRM_IOCTL_MODE_GETENCODER, &mut encoder)?;
+
+        if encoder.crtc_id == 0 {
+            continue;
+        }
+
+        let crtc = get_crtc(fd, encoder.crtc_id)?;
+        if crtc.mode_valid == 0 {
+            continue;
+        }
+
+        let type_name = CONNECTOR_TYPE_NAMES
+            .get(connector.connector_type as usize)
+            .unwrap_or(&"Unknown");
+
+        outputs.push(DrmOutput {
+            card: card.to_path_buf(),
+            connector_id,
+            crtc_id: crtc.crtc_id,
+            pipe: crtc_ids
+                .iter()
+                .position(|&crtc_id| crtc_id == crtc.crtc_id)
+                .unwrap_or(0) as u32,
+            name: format!("{}-{}", type_name, connector.connector_type_id),
+            x: crtc.x as i32,
+            y: crtc.y as i32,
+            width: crtc.mode.hdisplay as u32,
+            height: crtc.mode.vdisplay as u32,
+            frequency: crtc.mode.vrefresh as f32,
+        });
+    }
+
+    Ok(outputs)
+}
+
+/// Waits for the vertical blanking interval of one CRTC.
+#[derive(Debug)]
+pub(super) struct DrmVblank {
+    file: File,
+    pipe: u32,
+}
+
+impl DrmVblank {
+    pub fn new(card: &Path, pipe: u32) -> XCapResult<DrmVblank> {
+        Ok(DrmVblank {
+            file: open_card(card)?,
+            pipe,
+        })
+    }
+
+    /// Block until the next vblank of the CRTC.
+    pub fn wait(&self) -> XCapResult<()> {
+        // 与 libdrm 的 drmWaitVBlank 相同的 CRTC 编码方式
+        let pipe_flags = match self.pipe {
+            0 => 0,
+            1 => DRM_VBLANK_SECONDARY,
+            pipe => (pipe << DRM_VBLANK_HIGH_CRTC_SHIFT) & DRM_VBLANK_HIGH_CRTC_MASK,
+        };
+
+        let mut wait_vblank = DrmWaitVblank {
+            r#type: DRM_VBLANK_RELATIVE | pipe_flags,
+            sequence: 1,
+            ..Default::default()
+        };
+
+        ioctl(
+            self.file.as_raw_fd(),
+            DRM_IOCTL_WAIT_VBLANK,
+            &mut wait_vblank,
+        )
+    }
+}
+
+/// List the active outputs of every `/dev/dri/card*` device.
+pub(super) fn drm_outputs() -> XCapResult<Vec<DrmOutput>> {
+    let mut cards: Vec<PathBuf> = fs::read_dir("/dev/dri")?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("card"))
+        })
+        .collect();
+    cards.sort();
+
+    let mut outputs = Vec::new();
+    let mut last_error = None;
+
+    for card in cards {
+        match get_card_outputs(&card) {
+            Ok(card_outputs) => outputs.extend(card_outputs),
+            Err(err) => {
+                log::error!("Get outputs of {:?} failed: {}", card, err);
+                last_error = Some(err);
+            }
+        }
+    }
+
+    match last_error {
+        Some(err) if outputs.is_empty() => Err(err),
+        _ => Ok(outputs),
+    }
+}
+
+fn bytes_per_pixel(pixel_format: u32) -> usize {
+    match pixel_format {
+        DRM_FORMAT_RGB565 => 2,
+        _ => 4,
+    }
+}
+
+fn convert_to_rgba(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    pitch: usize,
+    pixel_format: u32,
+) -> XCapResult<RgbaImage> {
+    let mut buffer = Vec::with_capacity((width * height * 4) as usize);
+
+    for row in data.chunks(pitch).take(height as usize) {
+        match pixel_format {
+            DRM_FORMAT_XRGB8888 | DRM_FORMAT_ARGB8888 => {
+                for bgra in row[..width as usize * 4].chunks_exact(4) {
+                    buffer.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+                }
+            }
+            DRM_FORMAT_XBGR8888 | DRM_FORMAT_ABGR8888 => {
+                for rgba in row[..width as usize * 4].chunks_exact(4) {
+                    buffer.extend_from_slice(&[rgba[0], rgba[1], rgba[2], 255]);
+                }
+            }
+            DRM_FORMAT_RGB565 => {
+                for pixel in row[..width as usize * 2].chunks_exact(2) {
+                    let pixel = u16::from_le_bytes([pixel[0], pixel[1]]);
+                    buffer.extend_from_slice(&[
+                        ((pixel >> 11) as u32 * 255 / 31) as u8,
+                        ((pixel >> 5 & 63) as u32 * 255 / 63) as u8,
+                        ((pixel & 31) as u32 * 255 / 31) as u8,
+                        255,
+                    ]);
+                }
+            }
+            _ => {
+                return Err(XCapError::new(format!(
+                    "Unsupported DRM pixel format {:?}",
+                    String::from_utf8_lossy(&pixel_format.to_le_bytes())
+                )))
+            }
+        }
+    }
+
+    RgbaImage::from_raw(width, height, buffer)
+        .ok_or_else(|| XCapError::new("RgbaImage::from_raw failed"))
+}
+
+fn map_framebuffer(
+    card_fd: RawFd,
+    handle: u32,
+    size: usize,
+) -> XCapResult<(*mut c_void, Option<OwnedFd>)> {
+    // 优先导出为 dma-buf 映射，适用于 GPU 分配的缓冲区；失败时再尝试 dumb buffer
+    let mut prime = DrmPrimeHandle {
+        handle,
+        flags: libc::O_CLOEXEC as u32,
+        fd: -1,
+    };
+
+    let (map_fd, offset, dma_buf) =
+        if ioctl(card_fd, DRM_IOCTL_PRIME_HANDLE_TO_FD, &mut prime).is_ok() {
+            let dma_buf = unsafe { OwnedFd::from_raw_fd(prime.fd) };
+            (prime.fd, 0, Some(dma_buf))
+        } else {
+            let mut map_dumb = DrmModeMapDumb {
+                handle,
+                ..Default::default()
+            };
+            ioctl(card_fd, DRM_IOCTL_MODE_MAP_DUMB, &mut map_dumb)?;
+            (card_fd, map_dumb.offset as libc::off_t, None)
+        };
+
+    let addr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            map_fd,
+            offset,
+        )
+    };
+
+    if addr == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok((addr, dma_buf))
+}
+
+/// Read the framebuffer currently scanned out by `crtc_id`.
+pub(super) fn drm_capture(card: &Path, crtc_id: u32) -> XCapResult<RgbaImage> {
+    let file = open_card(card)?;
+    let fd = file.as_raw_fd();
+
+    // 每次截图都重新查询，页面翻转后 CRTC 上的 framebuffer 会变化
+    let crtc = get_crtc(fd, crtc_id)?;
+    if crtc.fb_id == 0 {
+        return Err(XCapError::new(format!(
+            "CRTC {} of {:?} has no framebuffer",
+            crtc_id, card
+        )));
+    }
+
+    let mut fb = DrmModeFbCmd2 {
+        fb_id: crtc.fb_id,
+        ..Default::default()
+    };
+    ioctl(fd, DRM_IOCTL_MODE_GETFB2, &mut fb)?;
+
+    // 没有 CAP_SYS_ADMIN 时内核不会返回 buffer handle
+    if fb.handles[0] == 0 {
+        return Err(XCapError::permission_denied(
+            format!("read the framebuffer of {:?}", card),
+            Remediation::Capability("cap_sys_admin"),
+        ));
+    }
+
+    let handle = guard(fb.handles[0], |handle| {
+        let mut gem_close = DrmGemClose { handle, pad: 0 };
+        if let Err(err) = ioctl(fd, DRM_IOCTL_GEM_CLOSE, &mut gem_close) {
+            log::error!("DRM_IOCTL_GEM_CLOSE {} failed: {}", handle, err);
+        }
+    });
+
+    if fb.flags & DRM_MODE_FB_MODIFIERS != 0 && fb.modifier[0] != DRM_FORMAT_MOD_LINEAR {
+        return Err(XCapError::new(format!(
+            "Tiled framebuffer (modifier {:#x}) is not supported",
+            fb.modifier[0]
+        )));
+    }
+
+    // 多个输出可能共用一个 framebuffer，只读取 CRTC 扫描的区域
+    let x = crtc.x.min(fb.width);
+    let y = crtc.y.min(fb.height);
+    let width = (crtc.mode.hdisplay as u32).min(fb.width - x);
+    let height = (crtc.mode.vdisplay as u32).min(fb.height - y);
+
+    let pitch = fb.pitches[0] as usize;
+    let offset = fb.offsets[0] as usize;
+    let size = offset + pitch * fb.height as usize;
+    let start = offset + pitch * y as usize + x as usize * bytes_per_pixel(fb.pixel_format);
+
+    let (addr, dma_buf) = map_framebuffer(fd, *handle, size)?;
+    let addr = guard(addr, |addr| unsafe {
+        libc::munmap(addr, size);
+    });
+
+    let sync = |flags| {
+        if let Some(dma_buf) = &dma_buf {
+            let mut dma_buf_sync = DmaBufSync { flags };
+            if let Err(err) = ioctl(dma_buf.as_raw_fd(), DMA_BUF_IOCTL_SYNC, &mut dma_buf_sync) {
+                log::error!("DMA_BUF_IOCTL_SYNC failed: {}", err);
+            }
+        }
+    };
+
+    sync(DMA_BUF_SYNC_READ);
+    let data = unsafe { slice::from_raw_parts((*addr as *const u8).add(start), size - start) };
+    let image = convert_to_rgba(data, width, height, pitch, fb.pixel_format);
+    sync(DMA_BUF_SYNC_READ | DMA_BUF_SYNC_END);
+
+    image
+}
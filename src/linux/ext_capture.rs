@@ -0,0 +1,421 @@
+use image::RgbaImage;
+use scopeguard::guard;
+use wayland_client::{
+    event_created_child,
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_output::{self, WlOutput},
+        wl_registry::{self, WlRegistry},
+        wl_shm::{Format, WlShm},
+        wl_shm_pool::WlShmPool,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::{
+        ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+        ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+    },
+    image_capture_source::v1::client::{
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+        ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+    },
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1},
+        ext_image_copy_capture_manager_v1::{ExtImageCopyCaptureManagerV1, Options},
+        ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+    },
+};
+
+use crate::error::{XCapError, XCapResult};
+
+use super::{
+    impl_monitor::ImplMonitor,
+    wayland_shm::{is_supported_format, to_rgba_image, ShmBuffer},
+};
+
+// https://wayland.app/protocols/ext-image-copy-capture-v1
+// https://wayland.app/protocols/ext-image-capture-source-v1
+
+#[derive(Debug)]
+struct ExtOutput {
+    output: WlOutput,
+    name: String,
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, Default)]
+struct SessionState {
+    width: u32,
+    height: u32,
+    formats: Vec<Format>,
+    done: bool,
+    stopped: bool,
+}
+
+#[derive(Debug, Default)]
+struct FrameState {
+    ready: bool,
+    failed: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct ExtState {
+    outputs: Vec<ExtOutput>,
+    toplevels: Vec<(ExtForeignToplevelHandleV1, Option<String>)>,
+    shm: Option<WlShm>,
+    copy_manager: Option<ExtImageCopyCaptureManagerV1>,
+    output_source_manager: Option<ExtOutputImageCaptureSourceManagerV1>,
+    toplevel_source_manager: Option<ExtForeignToplevelImageCaptureSourceManagerV1>,
+    session: SessionState,
+    frame: FrameState,
+}
+
+impl Dispatch<WlRegistry, ()> for ExtState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_output" => {
+                    let output = registry.bind(name, version.min(4), qh, ());
+                    state.outputs.push(ExtOutput {
+                        output,
+                        name: format!("wl_output-{}", name),
+                        x: 0,
+                        y: 0,
+                    });
+                }
+                "wl_shm" => state.shm = Some(registry.bind(name, 1, qh, ())),
+                "ext_foreign_toplevel_list_v1" => {
+                    registry.bind::<ExtForeignToplevelListV1, _, _>(name, 1, qh, ());
+                }
+                "ext_image_copy_capture_manager_v1" => {
+                    state.copy_manager = Some(registry.bind(name, 1, qh, ()))
+                }
+                "ext_output_image_capture_source_manager_v1" => {
+                    state.output_source_manager = Some(registry.bind(name, 1, qh, ()))
+                }
+                "ext_foreign_toplevel_image_capture_source_manager_v1" => {
+                    state.toplevel_source_manager = Some(registry.bind(name, 1, qh, ()))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for ExtState {
+    fn event(
+        state: &mut Self,
+        wl_output: &WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let output = match state
+            .outputs
+            .iter_mut()
+            .find(|output| output.output.id() == wl_output.id())
+        {
+            Some(output) => output,
+            None => return,
+        };
+
+        match event {
+            wl_output::Event::Geometry { x, y, .. } => {
+                output.x = x;
+                output.y = y;
+            }
+            wl_output::Event::Name { name } => output.name = name,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for ExtState {
+    fn event(
+        state: &mut Self,
+        _: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            state.toplevels.push((toplevel, None));
+        }
+    }
+
+    event_created_child!(ExtState, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for ExtState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_handle_v1::Event::Identifier { identifier } = event {
+            if let Some((_, toplevel_identifier)) = state
+                .toplevels
+                .iter_mut()
+                .find(|(toplevel, _)| toplevel.id() == handle.id())
+            {
+                *toplevel_identifier = Some(identifier);
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureSessionV1, ()> for ExtState {
+    fn event(
+        state: &mut Self,
+        _: &ExtImageCopyCaptureSessionV1,
+        event: ext_image_copy_capture_session_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_image_copy_capture_session_v1::Event::BufferSize { width, height } => {
+                state.session.width = width;
+                state.session.height = height;
+            }
+            ext_image_copy_capture_session_v1::Event::ShmFormat {
+                format: WEnum::Value(format),
+            } => state.session.formats.push(format),
+            ext_image_copy_capture_session_v1::Event::Done => state.session.done = true,
+            ext_image_copy_capture_session_v1::Event::Stopped => state.session.stopped = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureFrameV1, ()> for ExtState {
+    fn event(
+        state: &mut Self,
+        _: &ExtImageCopyCaptureFrameV1,
+        event: ext_image_copy_capture_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_image_copy_capture_frame_v1::Event::Ready => state.frame.ready = true,
+            ext_image_copy_capture_frame_v1::Event::Failed { reason } => {
+                state.frame.failed = Some(format!("{:?}", reason))
+            }
+            _ => {}
+        }
+    }
+}
+
+macro_rules! ignore_events {
+    ($($proxy:ty),*) => {
+        $(
+            impl Dispatch<$proxy, ()> for ExtState {
+                fn event(
+                    _: &mut Self,
+                    _: &$proxy,
+                    _: <$proxy as Proxy>::Event,
+                    _: &(),
+                    _: &Connection,
+                    _: &QueueHandle<Self>,
+                ) {
+                }
+            }
+        )*
+    };
+}
+
+ignore_events!(
+    WlShm,
+    WlShmPool,
+    WlBuffer,
+    ExtImageCopyCaptureManagerV1,
+    ExtImageCaptureSourceV1,
+    ExtOutputImageCaptureSourceManagerV1,
+    ExtForeignToplevelImageCaptureSourceManagerV1
+);
+
+fn connect() -> XCapResult<(EventQueue<ExtState>, ExtState)> {
+    let conn = Connection::connect_to_env().map_err(XCapError::new)?;
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+
+    conn.display().get_registry(&qh, ());
+
+    // 依次拿到全局对象、wl_output 属性和 toplevel 对象、toplevel 的属性
+    let mut state = ExtState::default();
+    for _ in 0..3 {
+        event_queue.roundtrip(&mut state).map_err(XCapError::new)?;
+    }
+
+    if state.copy_manager.is_none() {
+        return Err(XCapError::new(
+            "The compositor does not support ext_image_copy_capture_manager_v1",
+        ));
+    }
+
+    Ok((event_queue, state))
+}
+
+/// Whether the compositor advertises `ext_image_copy_capture_manager_v1`.
+pub(super) fn ext_available() -> bool {
+    connect().is_ok()
+}
+
+/// Copy one frame of `source` into a `wl_shm` buffer.
+fn capture_source(
+    event_queue: &mut EventQueue<ExtState>,
+    state: &mut ExtState,
+    source: ExtImageCaptureSourceV1,
+) -> XCapResult<RgbaImage> {
+    let qh = event_queue.handle();
+    let source = guard(source, |source| source.destroy());
+
+    let copy_manager = state
+        .copy_manager
+        .clone()
+        .ok_or_else(|| XCapError::new("ext_image_copy_capture_manager_v1 not found"))?;
+    let shm = state
+        .shm
+        .clone()
+        .ok_or_else(|| XCapError::new("wl_shm not found"))?;
+
+    let session = guard(
+        copy_manager.create_session(&source, Options::PaintCursors, &qh, ()),
+        |session| session.destroy(),
+    );
+
+    // done 之前会发送缓冲区大小和所有支持的格式
+    while !state.session.done && !state.session.stopped {
+        event_queue
+            .blocking_dispatch(state)
+            .map_err(XCapError::new)?;
+    }
+
+    if state.session.stopped {
+        return Err(XCapError::new("Capture session stopped"));
+    }
+
+    let format = state
+        .session
+        .formats
+        .iter()
+        .find(|format| is_supported_format(**format))
+        .copied()
+        .ok_or_else(|| XCapError::new("No supported wl_shm format offered"))?;
+    let width = state.session.width;
+    let height = state.session.height;
+    let stride = width * 4;
+
+    let shm_buffer = ShmBuffer::new((stride * height) as usize)?;
+    let pool = guard(
+        shm.create_pool(shm_buffer.fd(), (stride * height) as i32, &qh, ()),
+        |pool| pool.destroy(),
+    );
+    let buffer = guard(
+        pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            format,
+            &qh,
+            (),
+        ),
+        |buffer| buffer.destroy(),
+    );
+
+    let frame = guard(session.create_frame(&qh, ()), |frame| frame.destroy());
+    frame.attach_buffer(&buffer);
+    frame.damage_buffer(0, 0, width as i32, height as i32);
+    frame.capture();
+
+    while !state.frame.ready && state.frame.failed.is_none() {
+        event_queue
+            .blocking_dispatch(state)
+            .map_err(XCapError::new)?;
+    }
+
+    if let Some(reason) = &state.frame.failed {
+        return Err(XCapError::new(format!("Capture frame failed: {}", reason)));
+    }
+
+    to_rgba_image(shm_buffer.data(), format, width, height, stride, false)
+}
+
+/// Capture the output matching `impl_monitor` with `ext_image_copy_capture_manager_v1`.
+pub(super) fn ext_capture_output(impl_monitor: &ImplMonitor) -> XCapResult<RgbaImage> {
+    let (mut event_queue, mut state) = connect()?;
+    let qh = event_queue.handle();
+
+    // XWayland 枚举的显示器名称与 compositor 不同，名称匹配失败时按位置匹配
+    let outputs = &state.outputs;
+    let output = outputs
+        .iter()
+        .find(|output| output.name == impl_monitor.name)
+        .or_else(|| {
+            outputs
+                .iter()
+                .find(|output| output.x == impl_monitor.x && output.y == impl_monitor.y)
+        })
+        .or(if outputs.len() == 1 {
+            outputs.first()
+        } else {
+            None
+        })
+        .map(|output| output.output.clone())
+        .ok_or_else(|| XCapError::new(format!("Output {} not found", impl_monitor.name)))?;
+
+    let source = state
+        .output_source_manager
+        .as_ref()
+        .ok_or_else(|| XCapError::new("ext_output_image_capture_source_manager_v1 not found"))?
+        .create_source(&output, &qh, ());
+
+    capture_source(&mut event_queue, &mut state, source)
+}
+
+/// Capture the toplevel with the `ext_foreign_toplevel_handle_v1` `identifier`.
+pub(super) fn ext_capture_toplevel(identifier: &str) -> XCapResult<RgbaImage> {
+    let (mut event_queue, mut state) = connect()?;
+    let qh = event_queue.handle();
+
+    let toplevel = state
+        .toplevels
+        .iter()
+        .find(|(_, toplevel_identifier)| toplevel_identifier.as_deref() == Some(identifier))
+        .map(|(toplevel, _)| toplevel.clone())
+        .ok_or_else(|| XCapError::new(format!("Toplevel {} not found", identifier)))?;
+
+    let source = state
+        .toplevel_source_manager
+        .as_ref()
+        .ok_or_else(|| {
+            XCapError::new("ext_foreign_toplevel_image_capture_source_manager_v1 not found")
+        })?
+        .create_source(&toplevel, &qh, ());
+
+    capture_source(&mut event_queue, &mut state, source)
+}
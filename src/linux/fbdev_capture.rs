@@ -0,0 +1,256 @@
+use image::RgbaImage;
+use scopeguard::guard;
+use std::{
+    ffi::{c_ulong, CStr},
+    fs::{self, File},
+    io::ErrorKind,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    ptr, slice,
+};
+
+use crate::error::{Remediation, XCapError, XCapResult};
+
+use super::utils::ioctl;
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/fb.h
+
+const FBIOGET_VSCREENINFO: u64 = 0x4600;
+const FBIOGET_FSCREENINFO: u64 = 0x4602;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct FbBitfield {
+    offset: u32,
+    length: u32,
+    msb_right: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct FbVarScreeninfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: FbBitfield,
+    green: FbBitfield,
+    blue: FbBitfield,
+    transp: FbBitfield,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    pixclock: u32,
+    left_margin: u32,
+    right_margin: u32,
+    upper_margin: u32,
+    lower_margin: u32,
+    hsync_len: u32,
+    vsync_len: u32,
+    sync: u32,
+    vmode: u32,
+    rotate: u32,
+    colorspace: u32,
+    reserved: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct FbFixScreeninfo {
+    id: [u8; 16],
+    smem_start: c_ulong,
+    smem_len: u32,
+    r#type: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: c_ulong,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+/// A framebuffer device, e.g. `/dev/fb0`.
+#[derive(Debug, Clone)]
+pub(super) struct FbdevOutput {
+    pub device: PathBuf,
+    pub index: u32,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub frequency: f32,
+}
+
+fn open_device(device: &Path) -> XCapResult<File> {
+    File::open(device).map_err(|err| match err.kind() {
+        ErrorKind::PermissionDenied => XCapError::permission_denied(
+            format!("open {:?}", device),
+            Remediation::UserGroup("video"),
+        ),
+        _ => err.into(),
+    })
+}
+
+fn get_screen_info(file: &File) -> XCapResult<(FbVarScreeninfo, FbFixScreeninfo)> {
+    let mut var_info = FbVarScreeninfo::default();
+    ioctl(file.as_raw_fd(), FBIOGET_VSCREENINFO, &mut var_info)?;
+
+    let mut fix_info = FbFixScreeninfo::default();
+    ioctl(file.as_raw_fd(), FBIOGET_FSCREENINFO, &mut fix_info)?;
+
+    Ok((var_info, fix_info))
+}
+
+fn get_frequency(var_info: &FbVarScreeninfo) -> f32 {
+    // pixclock 的单位是皮秒
+    let htotal = var_info.left_margin + var_info.xres + var_info.right_margin + var_info.hsync_len;
+    let vtotal = var_info.upper_margin + var_info.yres + var_info.lower_margin + var_info.vsync_len;
+
+    if var_info.pixclock == 0 || htotal == 0 || vtotal == 0 {
+        return 0.0;
+    }
+
+    (1e12 / var_info.pixclock as f64 / (htotal as f64 * vtotal as f64)) as f32
+}
+
+/// List the `/dev/fb*` devices.
+pub(super) fn fbdev_outputs() -> XCapResult<Vec<FbdevOutput>> {
+    let mut outputs = Vec::new();
+    let mut last_error = None;
+
+    for entry in fs::read_dir("/dev")? {
+        let device = entry?.path();
+        let index = match device
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("fb"))
+            .and_then(|index| index.parse::<u32>().ok())
+        {
+            Some(index) => index,
+            None => continue,
+        };
+
+        let (var_info, fix_info) =
+            match open_device(&device).and_then(|file| get_screen_info(&file)) {
+                Ok(info) => info,
+                Err(err) => {
+                    log::error!("Get screen info of {:?} failed: {}", device, err);
+                    last_error = Some(err);
+                    continue;
+                }
+            };
+        let name = CStr::from_bytes_until_nul(&fix_info.id)
+            .map(|id| id.to_string_lossy().to_string())
+            .unwrap_or_else(|_| format!("fb{}", index));
+
+        outputs.push(FbdevOutput {
+            device,
+            index,
+            name,
+            width: var_info.xres,
+            height: var_info.yres,
+            frequency: get_frequency(&var_info),
+        });
+    }
+
+    outputs.sort_by_key(|output| output.index);
+
+    match last_error {
+        Some(err) if outputs.is_empty() => Err(err),
+        _ => Ok(outputs),
+    }
+}
+
+fn get_channel(pixel: u32, bitfield: &FbBitfield) -> u8 {
+    if bitfield.length == 0 {
+        return 0;
+    }
+
+    let length = bitfield.length.min(16);
+    let max = (1u32 << length) - 1;
+    let value = (pixel >> bitfield.offset) & max;
+
+    (value * 255 / max) as u8
+}
+
+/// Read the visible part of the framebuffer of `device`.
+pub(super) fn fbdev_capture(device: &Path) -> XCapResult<RgbaImage> {
+    let file = open_device(device)?;
+    let (var_info, fix_info) = get_screen_info(&file)?;
+
+    let bytes_per_pixel = var_info.bits_per_pixel.div_ceil(8) as usize;
+    if var_info.xres == 0 || var_info.yres == 0 {
+        return Err(XCapError::new(format!("Framebuffer {:?} is empty", device)));
+    }
+    if !(2..=4).contains(&bytes_per_pixel) || var_info.grayscale != 0 {
+        return Err(XCapError::new(format!(
+            "Unsupported framebuffer format: {} bits per pixel, grayscale {}",
+            var_info.bits_per_pixel, var_info.grayscale
+        )));
+    }
+
+    let size = fix_info.smem_len as usize;
+    let line_length = fix_info.line_length as usize;
+    // 双缓冲时可见区域由 xoffset、yoffset 决定
+    let start =
+        var_info.yoffset as usize * line_length + var_info.xoffset as usize * bytes_per_pixel;
+    let end = start
+        + line_length * (var_info.yres as usize - 1)
+        + var_info.xres as usize * bytes_per_pixel;
+    if end > size {
+        return Err(XCapError::new(
+            "Framebuffer visible area exceeds its memory",
+        ));
+    }
+
+    let addr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if addr == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let addr = guard(addr, |addr| unsafe {
+        libc::munmap(addr, size);
+    });
+
+    let data = unsafe { slice::from_raw_parts(*addr as *const u8, size) };
+
+    let mut buffer = Vec::with_capacity((var_info.xres * var_info.yres * 4) as usize);
+    for y in 0..var_info.yres as usize {
+        let row_start = start + y * line_length;
+        let row = &data[row_start..row_start + var_info.xres as usize * bytes_per_pixel];
+
+        for bytes in row.chunks_exact(bytes_per_pixel) {
+            let mut pixel = [0u8; 4];
+            pixel[..bytes_per_pixel].copy_from_slice(bytes);
+            let pixel = u32::from_le_bytes(pixel);
+
+            buffer.extend_from_slice(&[
+                get_channel(pixel, &var_info.red),
+                get_channel(pixel, &var_info.green),
+                get_channel(pixel, &var_info.blue),
+                255,
+            ]);
+        }
+    }
+
+    RgbaImage::from_raw(var_info.xres, var_info.yres, buffer)
+        .ok_or_else(|| XCapError::new("RgbaImage::from_raw failed"))
+}
@@ -0,0 +1,289 @@
+use wayland_client::{
+    backend::ObjectId,
+    event_created_child,
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry::{self, WlRegistry},
+    },
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+use crate::error::{XCapError, XCapResult};
+
+// https://wayland.app/protocols/ext-foreign-toplevel-list-v1
+// https://wayland.app/protocols/wlr-foreign-toplevel-management-unstable-v1
+
+/// A toplevel listed by the compositor.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Toplevel {
+    /// Stable identifier, only provided by `ext_foreign_toplevel_list_v1`.
+    pub identifier: Option<String>,
+    pub title: String,
+    pub app_id: String,
+    /// Position of the output the toplevel is shown on.
+    pub output_position: Option<(i32, i32)>,
+    pub is_minimized: bool,
+    pub is_maximized: bool,
+    pub is_fullscreen: bool,
+    pub is_activated: bool,
+}
+
+#[derive(Debug, Default)]
+struct ToplevelState {
+    outputs: Vec<(ObjectId, (i32, i32))>,
+    ext_toplevels: Vec<(ObjectId, Toplevel)>,
+    wlr_toplevels: Vec<(ObjectId, Toplevel)>,
+    has_ext: bool,
+    has_wlr: bool,
+}
+
+fn find_toplevel(toplevels: &mut [(ObjectId, Toplevel)], id: ObjectId) -> Option<&mut Toplevel> {
+    toplevels
+        .iter_mut()
+        .find(|(toplevel_id, _)| *toplevel_id == id)
+        .map(|(_, toplevel)| toplevel)
+}
+
+impl Dispatch<WlRegistry, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_output" => {
+                    let output: WlOutput = registry.bind(name, version.min(4), qh, ());
+                    state.outputs.push((output.id(), (0, 0)));
+                }
+                "ext_foreign_toplevel_list_v1" => {
+                    registry.bind::<ExtForeignToplevelListV1, _, _>(name, 1, qh, ());
+                    state.has_ext = true;
+                }
+                "zwlr_foreign_toplevel_manager_v1" => {
+                    registry.bind::<ZwlrForeignToplevelManagerV1, _, _>(
+                        name,
+                        version.min(3),
+                        qh,
+                        (),
+                    );
+                    state.has_wlr = true;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Geometry { x, y, .. } = event {
+            if let Some((_, position)) = state.outputs.iter_mut().find(|(id, _)| *id == output.id())
+            {
+                *position = (x, y);
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        _: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            state
+                .ext_toplevels
+                .push((toplevel.id(), Toplevel::default()));
+        }
+    }
+
+    event_created_child!(ToplevelState, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_handle_v1::Event::Closed = event {
+            state.ext_toplevels.retain(|(id, _)| *id != handle.id());
+            return;
+        }
+
+        let toplevel = match find_toplevel(&mut state.ext_toplevels, handle.id()) {
+            Some(toplevel) => toplevel,
+            None => return,
+        };
+
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            ext_foreign_toplevel_handle_v1::Event::Identifier { identifier } => {
+                toplevel.identifier = Some(identifier)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            state
+                .wlr_toplevels
+                .push((toplevel.id(), Toplevel::default()));
+        }
+    }
+
+    event_created_child!(ToplevelState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            state.wlr_toplevels.retain(|(id, _)| *id != handle.id());
+            return;
+        }
+
+        let output_position = match &event {
+            zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => state
+                .outputs
+                .iter()
+                .find(|(id, _)| *id == output.id())
+                .map(|(_, position)| *position),
+            _ => None,
+        };
+
+        let toplevel = match find_toplevel(&mut state.wlr_toplevels, handle.id()) {
+            Some(toplevel) => toplevel,
+            None => return,
+        };
+
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { .. } => {
+                toplevel.output_position = output_position.or(toplevel.output_position)
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                // state 是原生字节序的 u32 数组
+                let states: Vec<u32> = state
+                    .chunks_exact(4)
+                    .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect();
+
+                toplevel.is_maximized = states.contains(&0);
+                toplevel.is_minimized = states.contains(&1);
+                toplevel.is_activated = states.contains(&2);
+                toplevel.is_fullscreen = states.contains(&3);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// List the toplevels of the compositor, with `ext_foreign_toplevel_list_v1` and
+/// `zwlr_foreign_toplevel_manager_v1`.
+pub(super) fn toplevels() -> XCapResult<Vec<Toplevel>> {
+    let conn = Connection::connect_to_env().map_err(XCapError::new)?;
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+
+    conn.display().get_registry(&qh, ());
+
+    // 依次拿到全局对象、toplevel 对象、toplevel 的属性
+    let mut state = ToplevelState::default();
+    for _ in 0..3 {
+        event_queue.roundtrip(&mut state).map_err(XCapError::new)?;
+    }
+
+    if !state.has_ext && !state.has_wlr {
+        return Err(XCapError::new(
+            "The compositor does not support foreign toplevel protocols",
+        ));
+    }
+
+    let mut wlr_toplevels: Vec<Toplevel> = state
+        .wlr_toplevels
+        .into_iter()
+        .map(|(_, toplevel)| toplevel)
+        .collect();
+
+    if !state.has_ext {
+        return Ok(wlr_toplevels);
+    }
+
+    // ext 协议提供稳定的 identifier，但没有窗口状态，状态从 wlr 协议中按标题匹配补充
+    let toplevels = state
+        .ext_toplevels
+        .into_iter()
+        .map(|(_, mut toplevel)| {
+            let index = wlr_toplevels.iter().position(|wlr_toplevel| {
+                wlr_toplevel.app_id == toplevel.app_id && wlr_toplevel.title == toplevel.title
+            });
+
+            if let Some(index) = index {
+                let wlr_toplevel = wlr_toplevels.remove(index);
+                toplevel.output_position = wlr_toplevel.output_position;
+                toplevel.is_minimized = wlr_toplevel.is_minimized;
+                toplevel.is_maximized = wlr_toplevel.is_maximized;
+                toplevel.is_fullscreen = wlr_toplevel.is_fullscreen;
+                toplevel.is_activated = wlr_toplevel.is_activated;
+            }
+
+            toplevel
+        })
+        .collect();
+
+    Ok(toplevels)
+}
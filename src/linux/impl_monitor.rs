@@ -1,23 +1,68 @@
 use image::RgbaImage;
-use std::str;
+use std::{fs, path::PathBuf, str, sync::Arc};
 use xcb::{
     randr::{
-        GetCrtcInfo, GetMonitors, GetOutputInfo, GetScreenResources, Mode, ModeFlag, ModeInfo,
-        MonitorInfo, MonitorInfoBuf, Output, Rotation,
+        GetCrtcGamma, GetCrtcInfo, GetMonitors, GetOutputInfo, GetOutputProperty,
+        GetScreenResources, Mode, ModeFlag, ModeInfo, MonitorInfo, Output, QueryOutputProperty,
+        Rotation,
     },
-    x::{GetProperty, Screen, ScreenBuf, ATOM_RESOURCE_MANAGER, ATOM_STRING, CURRENT_TIME},
-    Connection, Xid,
+    x::{
+        GetProperty, Screen, ScreenBuf, ATOM_CARDINAL, ATOM_INTEGER, ATOM_NONE,
+        ATOM_RESOURCE_MANAGER, ATOM_STRING, CURRENT_TIME,
+    },
+    ConnError, Connection, Xid, XidNew,
 };
 
-use crate::error::{XCapError, XCapResult};
+use crate::{
+    backend::Backend,
+    capabilities::Inhibition,
+    color::{ColorSpace, GammaRamp},
+    custom::CustomMonitor,
+    error::{XCapError, XCapResult},
+    layout::Rect,
+    report::fallback,
+    utils::thumbnail,
+    VideoMode,
+};
 
-use super::{capture::capture_monitor, impl_video_recorder::ImplVideoRecorder};
+#[cfg(feature = "ext-image-copy-capture")]
+use super::ext_capture::ext_available;
+#[cfg(feature = "nvfbc")]
+use super::nvfbc_capture::nvfbc_available;
+#[cfg(feature = "wlr-screencopy")]
+use super::wlr_capture::wlr_outputs;
+use super::{
+    capture::{capture_monitor, capture_root_background, capture_wallpaper, wayland_detect},
+    drm_capture::drm_outputs,
+    fbdev_capture::fbdev_outputs,
+    impl_video_recorder::ImplVideoRecorder,
+    wayland_capture::portal_available,
+    x_connection::{x_connection, XConnection},
+};
+
+/// Where a monitor was enumerated from, and therefore how it is captured.
+#[derive(Debug, Clone)]
+pub(crate) enum MonitorSource {
+    Xorg {
+        conn: Arc<XConnection>,
+        screen_buf: ScreenBuf,
+    },
+    /// A KMS output, used when no display server is running.
+    Drm { card: PathBuf, crtc_id: u32 },
+    /// A `/dev/fb*` device, used when KMS is not available either.
+    Fbdev { device: PathBuf },
+    /// A `wl_output` of a wlroots based compositor running without XWayland.
+    #[cfg(feature = "wlr-screencopy")]
+    Wlr,
+    /// A monitor of a [`CustomBackend`](crate::CustomBackend), captured by it.
+    Custom,
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct ImplMonitor {
-    pub screen_buf: ScreenBuf,
-    #[allow(unused)]
-    pub monitor_info_buf: MonitorInfoBuf,
+    pub source: MonitorSource,
+    /// The backend forced by [`XCapContext`](crate::XCapContext), `None` to detect it when capturing.
+    pub backend: Option<Backend>,
     pub id: u32,
     pub name: String,
     pub x: i32,
@@ -28,6 +73,30 @@ pub(crate) struct ImplMonitor {
     pub scale_factor: f32,
     pub frequency: f32,
     pub is_primary: bool,
+    pub color_space: ColorSpace,
+}
+
+/// Whether connecting to X11 failed because no X server is reachable, other errors like a
+/// refused authorization are real X11 errors.
+fn is_no_display(err: &XCapError) -> bool {
+    matches!(
+        err,
+        XCapError::XcbConnError(ConnError::Connection | ConnError::ClosedParseErr)
+    )
+}
+
+/// Add why X11 was not used to the error of the framebuffer fallback.
+fn with_x11_error(err: XCapError, x11_err: &XCapError) -> XCapError {
+    match err {
+        XCapError::PermissionDenied {
+            reason,
+            remediation,
+        } => XCapError::PermissionDenied {
+            reason: format!("{} (no X11: {})", reason, x11_err),
+            remediation,
+        },
+        err => XCapError::new(format!("{} (no X11: {})", err, x11_err)),
+    }
 }
 
 // per https://gitlab.freedesktop.org/xorg/app/xrandr/-/blob/master/xrandr.c#L576
@@ -117,9 +186,54 @@ fn get_rotation_frequency(
     Ok((rotation, frequency))
 }
 
+/// The base EDID block of a RandR output, `None` when the driver does not expose it.
+fn xorg_edid(conn: &XConnection, output: u32) -> Option<Vec<u8>> {
+    let property = conn.atoms().ok()?.get(conn, "EDID").ok()?;
+    if property.is_none() {
+        return None;
+    }
+
+    let get_output_property_cookie = conn.send_request(&GetOutputProperty {
+        output: Output::new(output),
+        property,
+        r#type: ATOM_INTEGER,
+        long_offset: 0,
+        // 32 位为单位，只取 128 字节的基础块
+        long_length: 32,
+        delete: false,
+        pending: false,
+    });
+    let edid = conn
+        .wait_for_reply(get_output_property_cookie)
+        .ok()?
+        .data::<u8>()
+        .to_vec();
+
+    (!edid.is_empty()).then_some(edid)
+}
+
+/// The EDID of the KMS connector `name`, e.g. `HDMI-A-1`, read from sysfs.
+fn sysfs_edid(name: &str) -> Option<Vec<u8>> {
+    let suffix = format!("-{}", name);
+    fs::read_dir("/sys/class/drm")
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(&suffix))
+        .filter_map(|entry| fs::read(entry.path().join("edid")).ok())
+        .find(|edid| !edid.is_empty())
+        .map(|edid| edid[..edid.len().min(128)].to_vec())
+}
+
+/// FNV-1a, unlike `DefaultHasher` it is the same in every Rust version.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 impl ImplMonitor {
     fn new(
-        conn: &Connection,
+        conn: &Arc<XConnection>,
         screen: &Screen,
         monitor_info: &MonitorInfo,
         output: &Output,
@@ -134,8 +248,11 @@ impl ImplMonitor {
         let get_output_info_reply = conn.wait_for_reply(get_output_info_cookie)?;
 
         Ok(ImplMonitor {
-            screen_buf: screen.to_owned(),
-            monitor_info_buf: monitor_info.to_owned(),
+            backend: None,
+            source: MonitorSource::Xorg {
+                conn: conn.clone(),
+                screen_buf: screen.to_owned(),
+            },
             id: output.resource_id(),
             name: str::from_utf8(get_output_info_reply.name())?.to_string(),
             x: ((monitor_info.x() as f32) / scale_factor) as i32,
@@ -146,17 +263,268 @@ impl ImplMonitor {
             scale_factor,
             frequency,
             is_primary: monitor_info.primary(),
+            // X11 没有色彩管理，无法得知显示器的色彩空间
+            color_space: ColorSpace::Unknown,
         })
     }
 
+    fn all_drm() -> XCapResult<Vec<ImplMonitor>> {
+        let impl_monitors = drm_outputs()?
+            .into_iter()
+            .enumerate()
+            .map(|(index, output)| ImplMonitor {
+                backend: None,
+                source: MonitorSource::Drm {
+                    card: output.card,
+                    crtc_id: output.crtc_id,
+                },
+                id: output.connector_id,
+                name: output.name,
+                x: output.x,
+                y: output.y,
+                width: output.width,
+                height: output.height,
+                rotation: 0.0,
+                scale_factor: 1.0,
+                frequency: output.frequency,
+                // KMS 没有主显示器的概念，把第一个输出作为主显示器
+                is_primary: index == 0,
+                color_space: ColorSpace::Unknown,
+            })
+            .collect();
+
+        Ok(impl_monitors)
+    }
+
+    fn all_fbdev() -> XCapResult<Vec<ImplMonitor>> {
+        let impl_monitors = fbdev_outputs()?
+            .into_iter()
+            .map(|output| ImplMonitor {
+                backend: None,
+                source: MonitorSource::Fbdev {
+                    device: output.device,
+                },
+                id: output.index,
+                name: output.name,
+                x: 0,
+                y: 0,
+                width: output.width,
+                height: output.height,
+                rotation: 0.0,
+                scale_factor: 1.0,
+                frequency: output.frequency,
+                is_primary: output.index == 0,
+                color_space: ColorSpace::Unknown,
+            })
+            .collect();
+
+        Ok(impl_monitors)
+    }
+
+    #[cfg(feature = "wlr-screencopy")]
+    fn all_wlr() -> XCapResult<Vec<ImplMonitor>> {
+        let impl_monitors = wlr_outputs()?
+            .into_iter()
+            .enumerate()
+            .map(|(index, output)| {
+                let (width, height) = output.logical_size();
+
+                ImplMonitor {
+                    backend: None,
+                    source: MonitorSource::Wlr,
+                    id: index as u32,
+                    name: output.name,
+                    x: output.x,
+                    y: output.y,
+                    width,
+                    height,
+                    rotation: output.rotation,
+                    scale_factor: output.scale.max(1) as f32,
+                    frequency: output.frequency,
+                    is_primary: index == 0,
+                    color_space: ColorSpace::Unknown,
+                }
+            })
+            .collect();
+
+        Ok(impl_monitors)
+    }
+
+    /// The outputs of a Wayland session, captured through the portal. XWayland knows the layout
+    /// of the compositor, without it the `wl_output`s and then the KMS connectors are used.
+    fn all_wayland() -> XCapResult<Vec<ImplMonitor>> {
+        let err = match ImplMonitor::all_xorg() {
+            Ok(impl_monitors) => return Ok(impl_monitors),
+            Err(err) => err,
+        };
+
+        #[cfg(feature = "wlr-screencopy")]
+        {
+            fallback("x11", "wlr-screencopy", &err);
+            match ImplMonitor::all_wlr() {
+                Ok(impl_monitors) => return Ok(impl_monitors),
+                Err(err) => fallback("wlr-screencopy", "drm", &err),
+            }
+        }
+        #[cfg(not(feature = "wlr-screencopy"))]
+        fallback("x11", "drm", &err);
+
+        let mut impl_monitors = ImplMonitor::all_drm()?;
+        // compositor 给每个 CRTC 单独的 framebuffer，位置都是 0 时按接口顺序横向排列
+        if impl_monitors.iter().all(|m| m.x == 0 && m.y == 0) {
+            let mut x = 0;
+            for impl_monitor in impl_monitors.iter_mut() {
+                impl_monitor.x = x;
+                x += impl_monitor.width as i32;
+            }
+        }
+
+        Ok(impl_monitors)
+    }
+
+    pub fn backend() -> Backend {
+        if wayland_detect() {
+            #[cfg(feature = "ext-image-copy-capture")]
+            if ext_available() {
+                return Backend::ExtImageCopyCapture;
+            }
+
+            #[cfg(feature = "wlr-screencopy")]
+            if wlr_outputs().is_ok() {
+                return Backend::WlrScreencopy;
+            }
+
+            return Backend::Wayland;
+        }
+
+        if x_connection().is_ok() {
+            #[cfg(feature = "nvfbc")]
+            if nvfbc_available() {
+                return Backend::NvFbc;
+            }
+
+            return Backend::X11;
+        }
+
+        match drm_outputs() {
+            Ok(outputs) if !outputs.is_empty() => Backend::Drm,
+            _ => Backend::Fbdev,
+        }
+    }
+
+    pub fn inhibitions() -> Vec<Inhibition> {
+        let error = match ImplMonitor::backend() {
+            Backend::Wayland => match portal_available() {
+                Ok(true) => return Vec::new(),
+                Ok(false) => {
+                    return vec![Inhibition::CompositorRefused(String::from(
+                        "xdg-desktop-portal is not running",
+                    ))]
+                }
+                Err(err) => err,
+            },
+            Backend::Drm => return Vec::new(),
+            // 没有权限打开 DRM 设备时 backend 也会回退到 Fbdev
+            Backend::Fbdev => match (drm_outputs(), fbdev_outputs()) {
+                (_, Ok(outputs)) if !outputs.is_empty() => return Vec::new(),
+                (Err(err @ XCapError::PermissionDenied { .. }), _) | (_, Err(err)) => err,
+                _ => XCapError::new("No framebuffer device found"),
+            },
+            _ => return Vec::new(),
+        };
+
+        match error {
+            XCapError::PermissionDenied { remediation, .. } => {
+                vec![Inhibition::PermissionDenied(remediation)]
+            }
+            err => vec![Inhibition::CompositorRefused(err.to_string())],
+        }
+    }
+
+    /// A monitor of a custom backend, `Monitor` captures it through the backend.
+    pub fn from_custom(custom_monitor: &CustomMonitor) -> ImplMonitor {
+        ImplMonitor {
+            source: MonitorSource::Custom,
+            backend: Some(Backend::Custom),
+            id: custom_monitor.id,
+            name: custom_monitor.name.clone(),
+            x: custom_monitor.x,
+            y: custom_monitor.y,
+            width: custom_monitor.width,
+            height: custom_monitor.height,
+            rotation: 0.0,
+            scale_factor: custom_monitor.scale_factor,
+            frequency: custom_monitor.frequency,
+            is_primary: custom_monitor.is_primary,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn all_with_backend(backend: Backend) -> XCapResult<Vec<ImplMonitor>> {
+        let mut impl_monitors = match backend {
+            Backend::X11 => ImplMonitor::all_xorg()?,
+            Backend::Wayland => ImplMonitor::all_wayland()?,
+            #[cfg(feature = "nvfbc")]
+            Backend::NvFbc => ImplMonitor::all_xorg()?,
+            #[cfg(feature = "wlr-screencopy")]
+            Backend::WlrScreencopy => ImplMonitor::all_wlr()?,
+            #[cfg(feature = "ext-image-copy-capture")]
+            Backend::ExtImageCopyCapture => ImplMonitor::all()?,
+            Backend::Drm => ImplMonitor::all_drm()?,
+            Backend::Fbdev => ImplMonitor::all_fbdev()?,
+            _ => {
+                return Err(XCapError::new(format!(
+                    "{:?} backend is not available",
+                    backend
+                )))
+            }
+        };
+
+        for impl_monitor in impl_monitors.iter_mut() {
+            impl_monitor.backend = Some(backend);
+        }
+
+        Ok(impl_monitors)
+    }
+
     pub fn all() -> XCapResult<Vec<ImplMonitor>> {
-        let (conn, index) = Connection::connect(None)?;
+        match ImplMonitor::all_xorg() {
+            Ok(impl_monitors) => Ok(impl_monitors),
+            // 既没有 X11 也没有 Wayland 时（如 kiosk、TTY），直接读取 KMS framebuffer
+            Err(err) if !wayland_detect() && is_no_display(&err) => {
+                fallback("x11", "drm", &err);
+
+                // 嵌入式设备可能没有 KMS 驱动，只提供 /dev/fb*
+                let impl_monitors = match ImplMonitor::all_drm() {
+                    Ok(impl_monitors) if !impl_monitors.is_empty() => Ok(impl_monitors),
+                    Ok(_) => ImplMonitor::all_fbdev(),
+                    Err(drm_err) => {
+                        fallback("drm", "fbdev", &drm_err);
+                        ImplMonitor::all_fbdev().map_err(|_| drm_err)
+                    }
+                };
+
+                impl_monitors.map_err(|fallback_err| with_x11_error(fallback_err, &err))
+            }
+            // 没有 XWayland 的 wlroots compositor
+            #[cfg(feature = "wlr-screencopy")]
+            Err(err) => {
+                fallback("x11", "wlr-screencopy", &err);
+                ImplMonitor::all_wlr().map_err(|_| err)
+            }
+            #[cfg(not(feature = "wlr-screencopy"))]
+            Err(err) => Err(err),
+        }
+    }
+
+    fn all_xorg() -> XCapResult<Vec<ImplMonitor>> {
+        let conn = x_connection()?;
 
         let setup = conn.get_setup();
 
         let screen = setup
             .roots()
-            .nth(index as usize)
+            .nth(conn.screen_num as usize)
             .ok_or_else(|| XCapError::new("Not found screen"))?;
 
         let scale_factor = get_scale_factor(&conn, screen).unwrap_or(1.0);
@@ -233,10 +601,249 @@ impl ImplMonitor {
 }
 
 impl ImplMonitor {
+    pub fn is_valid(&self) -> bool {
+        ImplMonitor::all().is_ok_and(|impl_monitors| {
+            impl_monitors
+                .iter()
+                .any(|impl_monitor| impl_monitor.id == self.id)
+        })
+    }
+
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
         capture_monitor(self)
     }
 
+    /// No native exclusion, [`Monitor`](crate::Monitor) composites the window captures instead.
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_excluding(&self, _window_ids: &[u32]) -> XCapResult<Option<RgbaImage>> {
+        Ok(None)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_desktop_only(&self) -> XCapResult<RgbaImage> {
+        capture_wallpaper(self)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_root_background(&self, rect: Rect) -> XCapResult<Option<RgbaImage>> {
+        capture_root_background(self, rect)
+    }
+
+    pub fn workarea(&self) -> XCapResult<Rect> {
+        let rect = Rect::new(self.x, self.y, self.width, self.height);
+        let MonitorSource::Xorg {
+            conn, screen_buf, ..
+        } = &self.source
+        else {
+            // Wayland 不公开面板占用的区域
+            return Ok(rect);
+        };
+
+        let atoms = conn.atoms()?;
+        let workarea_atom = atoms.get(conn, "_NET_WORKAREA")?;
+        let current_desktop_atom = atoms.get(conn, "_NET_CURRENT_DESKTOP")?;
+        if workarea_atom == ATOM_NONE {
+            return Ok(rect);
+        }
+
+        let get_property = |property, long_offset, long_length| {
+            let get_property_cookie = conn.send_request(&GetProperty {
+                delete: false,
+                window: screen_buf.root(),
+                property,
+                r#type: ATOM_CARDINAL,
+                long_offset,
+                long_length,
+            });
+            conn.wait_for_reply(get_property_cookie)
+        };
+
+        let current_desktop = match current_desktop_atom {
+            ATOM_NONE => 0,
+            atom => get_property(atom, 0, 1)?
+                .value::<u32>()
+                .first()
+                .copied()
+                .unwrap_or(0),
+        };
+
+        // _NET_WORKAREA 按桌面依次存放 x, y, width, height，覆盖整个根窗口
+        let reply = get_property(workarea_atom, current_desktop * 4, 4)?;
+        let &[x, y, width, height] = reply.value::<u32>() else {
+            return Ok(rect);
+        };
+
+        let workarea = Rect::new(
+            (x as f32 / self.scale_factor) as i32,
+            (y as f32 / self.scale_factor) as i32,
+            (width as f32 / self.scale_factor) as u32,
+            (height as f32 / self.scale_factor) as u32,
+        );
+
+        Ok(rect.intersection(&workarea).unwrap_or(rect))
+    }
+
+    pub fn stable_id(&self) -> String {
+        let edid = match &self.source {
+            MonitorSource::Xorg { conn, .. } => xorg_edid(conn, self.id),
+            MonitorSource::Drm { .. } => sysfs_edid(&self.name),
+            #[cfg(feature = "wlr-screencopy")]
+            MonitorSource::Wlr => sysfs_edid(&self.name),
+            MonitorSource::Fbdev { .. } | MonitorSource::Custom => None,
+        };
+
+        // 同型号的显示器可能没有序列号，EDID 相同，加上接口名区分
+        match edid {
+            Some(edid) => format!("{}:{:016x}", self.name, fnv1a(&edid)),
+            None => self.name.clone(),
+        }
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        Ok(thumbnail(self.capture_image()?, max_width, max_height))
+    }
+
+    pub fn is_mirror_of(&self, other: &ImplMonitor) -> bool {
+        // RandR 的镜像（共用 CRTC 或 --same-as）在根窗口中占据完全相同的区域
+        self.id != other.id
+            && (self.x, self.y, self.width, self.height)
+                == (other.x, other.y, other.width, other.height)
+    }
+
+    pub fn supported_modes(&self) -> XCapResult<Vec<VideoMode>> {
+        let (conn, screen_buf) = match &self.source {
+            MonitorSource::Xorg {
+                conn, screen_buf, ..
+            } => (conn, screen_buf),
+            // 其他后端无法枚举模式，只返回当前模式
+            _ => {
+                return Ok(vec![VideoMode {
+                    width: (self.width as f32 * self.scale_factor) as u32,
+                    height: (self.height as f32 * self.scale_factor) as u32,
+                    refresh_rate: self.frequency,
+                    is_current: true,
+                    is_native: false,
+                }])
+            }
+        };
+
+        let get_screen_resources_cookie = conn.send_request(&GetScreenResources {
+            window: screen_buf.root(),
+        });
+        let get_screen_resources_reply = conn.wait_for_reply(get_screen_resources_cookie)?;
+        let mode_infos = get_screen_resources_reply.modes();
+
+        let get_output_info_cookie = conn.send_request(&GetOutputInfo {
+            output: Output::new(self.id),
+            config_timestamp: CURRENT_TIME,
+        });
+        let get_output_info_reply = conn.wait_for_reply(get_output_info_cookie)?;
+
+        let current_mode = if get_output_info_reply.crtc().is_none() {
+            None
+        } else {
+            let get_crtc_info_cookie = conn.send_request(&GetCrtcInfo {
+                crtc: get_output_info_reply.crtc(),
+                config_timestamp: CURRENT_TIME,
+            });
+            Some(conn.wait_for_reply(get_crtc_info_cookie)?.mode())
+        };
+
+        // 前 num_preferred 个模式是显示器的首选模式
+        let num_preferred = get_output_info_reply.num_preferred() as usize;
+        let video_modes = get_output_info_reply
+            .modes()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, mode)| {
+                let mode_info = mode_infos.iter().find(|m| m.id == mode.resource_id())?;
+
+                Some(VideoMode {
+                    width: mode_info.width as u32,
+                    height: mode_info.height as u32,
+                    refresh_rate: get_current_frequency(mode_infos, *mode),
+                    is_current: current_mode == Some(*mode),
+                    is_native: index < num_preferred,
+                })
+            })
+            .collect();
+
+        Ok(video_modes)
+    }
+
+    fn x_connection(&self) -> XCapResult<&XConnection> {
+        match &self.source {
+            MonitorSource::Xorg { conn, .. } => Ok(conn),
+            _ => Err(XCapError::new(
+                "Monitor brightness and gamma are only available on X11",
+            )),
+        }
+    }
+
+    pub fn brightness(&self) -> XCapResult<f32> {
+        let conn = self.x_connection()?;
+        let output = Output::new(self.id);
+
+        // 新驱动用 Backlight，旧驱动用 BACKLIGHT
+        for name in ["Backlight", "BACKLIGHT"] {
+            let property = conn.atoms()?.get(conn, name)?;
+            if property.is_none() {
+                continue;
+            }
+
+            let get_output_property_cookie = conn.send_request(&GetOutputProperty {
+                output,
+                property,
+                r#type: ATOM_INTEGER,
+                long_offset: 0,
+                long_length: 1,
+                delete: false,
+                pending: false,
+            });
+            let get_output_property_reply = conn.wait_for_reply(get_output_property_cookie)?;
+            let Some(value) = get_output_property_reply.data::<u32>().first() else {
+                continue;
+            };
+
+            let query_output_property_cookie =
+                conn.send_request(&QueryOutputProperty { output, property });
+            let query_output_property_reply = conn.wait_for_reply(query_output_property_cookie)?;
+            if let [min, max] = query_output_property_reply.valid_values() {
+                if max > min {
+                    return Ok((*value as i32 - min) as f32 / (max - min) as f32);
+                }
+            }
+        }
+
+        Err(XCapError::new(format!(
+            "Output {} has no backlight control",
+            self.name
+        )))
+    }
+
+    pub fn gamma_ramp(&self) -> XCapResult<GammaRamp> {
+        let conn = self.x_connection()?;
+
+        let get_output_info_cookie = conn.send_request(&GetOutputInfo {
+            output: Output::new(self.id),
+            config_timestamp: CURRENT_TIME,
+        });
+        let crtc = conn.wait_for_reply(get_output_info_cookie)?.crtc();
+        if crtc.is_none() {
+            return Err(XCapError::new(format!("Output {} is disabled", self.name)));
+        }
+
+        let get_crtc_gamma_cookie = conn.send_request(&GetCrtcGamma { crtc });
+        let get_crtc_gamma_reply = conn.wait_for_reply(get_crtc_gamma_cookie)?;
+
+        Ok(GammaRamp {
+            red: get_crtc_gamma_reply.red().to_vec(),
+            green: get_crtc_gamma_reply.green().to_vec(),
+            blue: get_crtc_gamma_reply.blue().to_vec(),
+        })
+    }
+
     pub fn video_recorder(&self) -> XCapResult<ImplVideoRecorder> {
         ImplVideoRecorder::new()
     }
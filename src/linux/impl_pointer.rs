@@ -0,0 +1,93 @@
+use xcb::{
+    x::QueryPointer,
+    xinput::{Device, DeviceType, XiQueryDevice, XiQueryPointer, XiQueryVersion},
+    Extension,
+};
+
+use crate::error::{XCapError, XCapResult};
+
+use super::x_connection::{x_connection, XConnection};
+
+/// The id of the core pointer when the X server has no XInput 2.
+const CORE_POINTER_ID: u32 = 0;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ImplPointer {
+    pub id: u32,
+    pub name: String,
+}
+
+/// XInput 2 requests fail until the client announced the version it speaks.
+fn has_xinput2(conn: &XConnection) -> bool {
+    if !conn
+        .active_extensions()
+        .any(|extension| extension == Extension::Input)
+    {
+        return false;
+    }
+
+    let cookie = conn.send_request(&XiQueryVersion {
+        major_version: 2,
+        minor_version: 0,
+    });
+    conn.wait_for_reply(cookie)
+        .is_ok_and(|reply| reply.major_version() >= 2)
+}
+
+impl ImplPointer {
+    /// The XInput 2 master pointers, one per MPX cursor or seat.
+    pub fn all() -> XCapResult<Vec<ImplPointer>> {
+        let conn = x_connection()?;
+        if !has_xinput2(&conn) {
+            return Ok(vec![ImplPointer {
+                id: CORE_POINTER_ID,
+                name: String::from("Core pointer"),
+            }]);
+        }
+
+        let cookie = conn.send_request(&XiQueryDevice {
+            device: Device::AllMaster,
+        });
+        let reply = conn.wait_for_reply(cookie)?;
+
+        let impl_pointers = reply
+            .infos()
+            .filter(|info| info.r#type() == DeviceType::MasterPointer)
+            .filter_map(|info| match info.device() {
+                Device::Id(id) => Some(ImplPointer {
+                    id: id as u32,
+                    name: info.name().to_utf8().into_owned(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        Ok(impl_pointers)
+    }
+
+    pub fn position(&self) -> XCapResult<(i32, i32)> {
+        let conn = x_connection()?;
+        let root = conn
+            .get_setup()
+            .roots()
+            .nth(conn.screen_num as usize)
+            .ok_or_else(|| XCapError::new("Get screen failed"))?
+            .root();
+
+        if self.id == CORE_POINTER_ID || !has_xinput2(&conn) {
+            let cookie = conn.send_request(&QueryPointer { window: root });
+            let reply = conn.wait_for_reply(cookie)?;
+
+            return Ok((reply.root_x() as i32, reply.root_y() as i32));
+        }
+
+        let cookie = conn.send_request(&XiQueryPointer {
+            window: root,
+            device: Device::Id(self.id as u16),
+        });
+        let reply = conn.wait_for_reply(cookie)?;
+
+        // 坐标是 16.16 定点数
+        Ok((reply.root_x() >> 16, reply.root_y() >> 16))
+    }
+}
@@ -0,0 +1,43 @@
+use crate::error::{XCapError, XCapResult};
+
+use super::{
+    drm_capture::{drm_outputs, DrmVblank},
+    impl_monitor::{ImplMonitor, MonitorSource},
+};
+
+/// Waits for the vblank of the DRM CRTC driving a monitor.
+#[derive(Debug)]
+pub(crate) struct ImplVblank {
+    drm_vblank: DrmVblank,
+}
+
+impl ImplVblank {
+    pub fn new(impl_monitor: &ImplMonitor) -> XCapResult<ImplVblank> {
+        let outputs = drm_outputs()?;
+
+        // X11 和 Wayland 的输出名一般就是 DRM 的连接器名，例如 DP-1
+        let output = outputs
+            .iter()
+            .find(|output| match &impl_monitor.source {
+                MonitorSource::Drm { card, crtc_id } => {
+                    &output.card == card && output.crtc_id == *crtc_id
+                }
+                _ => output.name == impl_monitor.name,
+            })
+            .or(match outputs.as_slice() {
+                [output] => Some(output),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                XCapError::new(format!("No DRM CRTC drives monitor {}", impl_monitor.name))
+            })?;
+
+        Ok(ImplVblank {
+            drm_vblank: DrmVblank::new(&output.card, output.pipe)?,
+        })
+    }
+
+    pub fn wait(&self) -> XCapResult<()> {
+        self.drm_vblank.wait()
+    }
+}
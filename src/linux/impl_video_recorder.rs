@@ -1,25 +1,34 @@
 #![allow(unused)]
 
-use crate::{video_recorder::Frame, XCapResult};
+use crate::{video_recorder::Frame, XCapError, XCapResult};
 
 #[derive(Debug, Clone)]
-pub struct ImplVideoRecorder {}
+pub struct ImplVideoRecorder {
+    raw_orientation: bool,
+}
 
 impl ImplVideoRecorder {
     pub fn new() -> XCapResult<Self> {
-        unimplemented!()
+        Err(XCapError::new("Video recording is not supported on Linux"))
     }
 
     pub fn on_frame<F>(&self, on_frame: F) -> XCapResult<()>
     where
         F: Fn(Frame) -> XCapResult<()> + Send + 'static,
     {
-        unimplemented!()
+        Err(XCapError::new("Video recording is not supported on Linux"))
+    }
+    pub fn rebuild(&self) -> XCapResult<Self> {
+        Err(XCapError::new("Rebuild video recorder not supported"))
+    }
+    pub fn with_raw_orientation(mut self, raw_orientation: bool) -> Self {
+        self.raw_orientation = raw_orientation;
+        self
     }
     pub fn start(&self) -> XCapResult<()> {
-        unimplemented!()
+        Err(XCapError::new("Video recording is not supported on Linux"))
     }
     pub fn stop(&self) -> XCapResult<()> {
-        unimplemented!()
+        Err(XCapError::new("Video recording is not supported on Linux"))
     }
 }
@@ -0,0 +1,197 @@
+use std::{
+    collections::HashSet,
+    os::fd::AsRawFd,
+    thread,
+    time::{Duration, Instant},
+};
+use xcb::{
+    randr::{NotifyMask, SelectInput},
+    x::{ChangeWindowAttributes, Cw, EventMask, GetProperty, Window, ATOM_NONE},
+    Event,
+};
+
+use crate::error::XCapResult;
+
+use super::x_connection::XConnection;
+
+/// Wakes the [`Watcher`](crate::Watcher) up when X11 or RandR reports a change.
+pub(crate) struct ImplWatcher {
+    conn: Option<XConnection>,
+    clients: HashSet<Window>,
+}
+
+impl ImplWatcher {
+    pub fn new() -> ImplWatcher {
+        match ImplWatcher::connect() {
+            Ok(impl_watcher) => impl_watcher,
+            Err(err) => {
+                // 没有 X11 时（纯 Wayland 会话）只能定时轮询
+                log::debug!("Watch X11 events failed: {}, fallback to polling", err);
+
+                ImplWatcher {
+                    conn: None,
+                    clients: HashSet::new(),
+                }
+            }
+        }
+    }
+
+    fn connect() -> XCapResult<ImplWatcher> {
+        // 自己的连接，共享的连接上的事件会被别人读走
+        let conn = XConnection::connect()?;
+
+        for screen in conn.get_setup().roots() {
+            conn.send_and_check_request(&ChangeWindowAttributes {
+                window: screen.root(),
+                value_list: &[Cw::EventMask(
+                    EventMask::PROPERTY_CHANGE
+                        | EventMask::STRUCTURE_NOTIFY
+                        | EventMask::SUBSTRUCTURE_NOTIFY,
+                )],
+            })
+            .map_err(xcb::Error::from)?;
+
+            // 显示器的增减、分辨率和旋转变化
+            conn.send_and_check_request(&SelectInput {
+                window: screen.root(),
+                enable: NotifyMask::SCREEN_CHANGE
+                    | NotifyMask::CRTC_CHANGE
+                    | NotifyMask::OUTPUT_CHANGE,
+            })
+            .map_err(xcb::Error::from)?;
+        }
+
+        let mut impl_watcher = ImplWatcher {
+            conn: Some(conn),
+            clients: HashSet::new(),
+        };
+        impl_watcher.select_clients()?;
+
+        Ok(impl_watcher)
+    }
+
+    /// Listen to property and geometry changes of the new client windows.
+    fn select_clients(&mut self) -> XCapResult<()> {
+        let conn = match &self.conn {
+            Some(conn) => conn,
+            None => return Ok(()),
+        };
+
+        // 窗口管理器可能在连接之后才启动
+        let client_list_atom = conn.atoms()?.get(conn, "_NET_CLIENT_LIST")?;
+        if client_list_atom == ATOM_NONE {
+            return Ok(());
+        }
+
+        let mut clients = HashSet::new();
+        for screen in conn.get_setup().roots() {
+            let client_list_cookie = conn.send_request(&GetProperty {
+                delete: false,
+                window: screen.root(),
+                property: client_list_atom,
+                r#type: ATOM_NONE,
+                long_offset: 0,
+                long_length: 1024,
+            });
+            let client_list_reply = conn.wait_for_reply(client_list_cookie)?;
+            clients.extend(client_list_reply.value::<Window>().iter().copied());
+        }
+
+        for client in clients.difference(&self.clients) {
+            // 窗口可能已经销毁，不检查错误
+            conn.send_request(&ChangeWindowAttributes {
+                window: *client,
+                value_list: &[Cw::EventMask(
+                    EventMask::PROPERTY_CHANGE | EventMask::STRUCTURE_NOTIFY,
+                )],
+            });
+        }
+        conn.flush()?;
+
+        self.clients = clients;
+
+        Ok(())
+    }
+
+    /// Read the pending events, returns whether there were any.
+    fn drain(&mut self) -> bool {
+        let mut changed = false;
+        let mut clients_changed = false;
+
+        if let Some(conn) = &self.conn {
+            let client_list_atom = conn
+                .atoms()
+                .and_then(|atoms| atoms.get(conn, "_NET_CLIENT_LIST"))
+                .unwrap_or(ATOM_NONE);
+
+            loop {
+                match conn.poll_for_event() {
+                    Ok(Some(event)) => {
+                        changed = true;
+                        if let Event::X(xcb::x::Event::PropertyNotify(event)) = event {
+                            clients_changed |= event.atom() == client_list_atom;
+                        }
+                    }
+                    Ok(None) => break,
+                    // 已销毁窗口的 BadWindow 等错误也说明窗口有变化
+                    Err(xcb::Error::Protocol(_)) => changed = true,
+                    Err(err) => {
+                        log::error!("Read X11 events failed: {}", err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if clients_changed {
+            if let Err(err) = self.select_clients() {
+                log::error!("Select client window events failed: {}", err);
+            }
+        }
+
+        changed
+    }
+
+    /// Block until X11 reports a change or `timeout` elapses, returns whether something changed.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        let fd = match &self.conn {
+            Some(conn) => conn.as_raw_fd(),
+            None => {
+                thread::sleep(timeout);
+                return false;
+            }
+        };
+
+        if self.drain() {
+            return true;
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut poll_fd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            let result = unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as i32) };
+            // 被信号打断时继续等待
+            if result < 0
+                && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+            {
+                continue;
+            }
+            if result <= 0 {
+                return false;
+            }
+
+            if self.drain() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+        }
+    }
+}
@@ -1,23 +1,61 @@
 use image::RgbaImage;
-use std::str;
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    str,
+    sync::Arc,
+};
 use xcb::{
+    shape::{GetRectangles, Sk},
     x::{
-        Atom, Drawable, GetGeometry, GetProperty, GetPropertyReply, InternAtom, QueryPointer,
-        TranslateCoordinates, Window, ATOM_ATOM, ATOM_CARDINAL, ATOM_NONE, ATOM_STRING,
-        ATOM_WM_CLASS, ATOM_WM_NAME,
+        Atom, ButtonPressEvent, ButtonReleaseEvent, Drawable, GetGeometry, GetProperty,
+        GetPropertyReply, GetSelectionOwner, GetWindowAttributes, MapState, MotionNotifyEvent,
+        QueryExtension, QueryPointer, QueryTree, TranslateCoordinates, Window, WindowClass,
+        ATOM_ATOM, ATOM_CARDINAL, ATOM_NONE, ATOM_STRING, ATOM_WINDOW, ATOM_WM_CLASS, ATOM_WM_NAME,
+        ATOM_WM_TRANSIENT_FOR, CURRENT_TIME,
     },
-    Connection, Xid,
+    xtest::FakeInput,
+    BaseEvent, Connection, Extension, Xid,
+};
+
+use crate::{
+    backend::Backend,
+    enumeration::Enumeration,
+    error::{XCapError, XCapResult},
+    utils::thumbnail,
+    ProcessArch, ProcessInfo, Region, WindowKind, WindowShape,
 };
 
-use crate::error::{XCapError, XCapResult};
+#[cfg(feature = "ext-image-copy-capture")]
+use super::ext_capture::ext_available;
+#[cfg(feature = "foreign-toplevel")]
+use super::foreign_toplevel::toplevels;
+use super::{
+    capture::{capture_window, wayland_detect},
+    impl_monitor::ImplMonitor,
+    utils::Rect,
+    x_connection::{x_connection, XConnection},
+};
 
-use super::{capture::capture_window, impl_monitor::ImplMonitor, utils::Rect};
+/// Where a window was enumerated from, and therefore how it is captured.
+#[derive(Debug, Clone)]
+pub(crate) enum WindowSource {
+    Xorg {
+        conn: Arc<XConnection>,
+        window: Window,
+    },
+    /// A native Wayland toplevel, listed through a foreign toplevel protocol.
+    #[cfg(feature = "foreign-toplevel")]
+    Wayland { identifier: Option<String> },
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct ImplWindow {
-    pub window: Window,
+    pub source: WindowSource,
     pub id: u32,
     pub title: String,
+    pub title_bytes: Vec<u8>,
     pub app_name: String,
     pub pid: u32,
     pub current_monitor: ImplMonitor,
@@ -28,16 +66,18 @@ pub(crate) struct ImplWindow {
     pub height: u32,
     pub is_minimized: bool,
     pub is_maximized: bool,
+    pub is_fullscreen: bool,
     pub is_focused: bool,
+    pub is_xwayland: bool,
+    pub kind: WindowKind,
+    pub skip_taskbar: bool,
+    pub skip_pager: bool,
+    pub is_visible: bool,
+    pub owner_id: Option<u32>,
 }
 
-fn get_atom(conn: &Connection, name: &str) -> XCapResult<Atom> {
-    let atom_cookie = conn.send_request(&InternAtom {
-        only_if_exists: true,
-        name: name.as_bytes(),
-    });
-    let atom_reply = conn.wait_for_reply(atom_cookie)?;
-    let atom = atom_reply.atom();
+fn get_atom(conn: &XConnection, name: &str) -> XCapResult<Atom> {
+    let atom = conn.atoms()?.get(conn, name)?;
 
     if atom.is_none() {
         return Err(XCapError::new(format!("{} not supported", name)));
@@ -46,6 +86,24 @@ fn get_atom(conn: &Connection, name: &str) -> XCapResult<Atom> {
     Ok(atom)
 }
 
+/// The bytes of a text property, empty if it is not 8 bit.
+fn text_property_bytes(reply: &GetPropertyReply) -> &[u8] {
+    match reply.format() {
+        8 => reply.value(),
+        _ => &[],
+    }
+}
+
+/// Decode a text property. `STRING` is Latin-1 by the ICCCM but often holds UTF-8 in practice,
+/// COMPOUND_TEXT of legacy apps is decoded lossily.
+fn decode_text_property(r#type: Atom, bytes: &[u8]) -> String {
+    match str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) if r#type == ATOM_STRING => bytes.iter().map(|byte| *byte as char).collect(),
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
 fn get_window_property(
     conn: &Connection,
     window: Window,
@@ -68,7 +126,68 @@ fn get_window_property(
     Ok(window_property_reply)
 }
 
-pub fn get_window_pid(conn: &Connection, window: &Window) -> XCapResult<u32> {
+/// The kind of a `_NET_WM_WINDOW_TYPE` atom name.
+fn window_kind(name: &str) -> Option<WindowKind> {
+    let kind = match name {
+        "_NET_WM_WINDOW_TYPE_NORMAL" => WindowKind::Normal,
+        "_NET_WM_WINDOW_TYPE_DIALOG" => WindowKind::Dialog,
+        "_NET_WM_WINDOW_TYPE_UTILITY" => WindowKind::Utility,
+        "_NET_WM_WINDOW_TYPE_TOOLBAR" => WindowKind::Toolbar,
+        "_NET_WM_WINDOW_TYPE_MENU"
+        | "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU"
+        | "_NET_WM_WINDOW_TYPE_POPUP_MENU"
+        | "_NET_WM_WINDOW_TYPE_COMBO" => WindowKind::Menu,
+        "_NET_WM_WINDOW_TYPE_DOCK" => WindowKind::Dock,
+        "_NET_WM_WINDOW_TYPE_DESKTOP" => WindowKind::Desktop,
+        "_NET_WM_WINDOW_TYPE_SPLASH" => WindowKind::Splash,
+        "_NET_WM_WINDOW_TYPE_TOOLTIP" => WindowKind::Tooltip,
+        "_NET_WM_WINDOW_TYPE_NOTIFICATION" => WindowKind::Notification,
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+fn get_window_kind(conn: &XConnection, window: &Window) -> XCapResult<WindowKind> {
+    // https://specifications.freedesktop.org/wm-spec/1.5/ar01s05.html#id-1.6.7
+    let wm_window_type_atom = get_atom(conn, "_NET_WM_WINDOW_TYPE")?;
+    let wm_window_type_reply =
+        get_window_property(conn, *window, wm_window_type_atom, ATOM_ATOM, 0, 12)?;
+    let atoms = conn.atoms()?;
+
+    // 按优先级排列，使用第一个认识的类型
+    for atom in wm_window_type_reply.value::<Atom>() {
+        let name = atoms.name(conn, *atom)?;
+        if let Some(kind) = name.as_deref().and_then(window_kind) {
+            return Ok(kind);
+        }
+    }
+
+    // 没有类型时，有 WM_TRANSIENT_FOR 的窗口按对话框处理
+    let transient_for_reply =
+        get_window_property(conn, *window, ATOM_WM_TRANSIENT_FOR, ATOM_WINDOW, 0, 1)?;
+    if transient_for_reply.value::<Window>().is_empty() {
+        Ok(WindowKind::Normal)
+    } else {
+        Ok(WindowKind::Dialog)
+    }
+}
+
+/// The `WM_TRANSIENT_FOR` window, `None` for group transients pointing at the root window.
+fn get_transient_for(conn: &Connection, window: &Window, root: Window) -> XCapResult<Option<u32>> {
+    let transient_for_reply =
+        get_window_property(conn, *window, ATOM_WM_TRANSIENT_FOR, ATOM_WINDOW, 0, 1)?;
+
+    let owner = transient_for_reply
+        .value::<Window>()
+        .first()
+        .filter(|owner| !owner.is_none() && **owner != root && *owner != window)
+        .map(|owner| owner.resource_id());
+
+    Ok(owner)
+}
+
+pub fn get_window_pid(conn: &XConnection, window: &Window) -> XCapResult<u32> {
     let wm_pid_atom = get_atom(conn, "_NET_WM_PID")?;
 
     let reply = get_window_property(conn, *window, wm_pid_atom, ATOM_CARDINAL, 0, 4)?;
@@ -80,7 +199,41 @@ pub fn get_window_pid(conn: &Connection, window: &Window) -> XCapResult<u32> {
         .copied()
 }
 
-fn get_active_window_id(conn: &Connection) -> Option<u32> {
+fn get_cardinal(conn: &XConnection, window: Window, name: &str) -> XCapResult<Option<u32>> {
+    let atom = get_atom(conn, name)?;
+    let reply = get_window_property(conn, window, atom, ATOM_CARDINAL, 0, 4)?;
+
+    Ok(reply.value::<u32>().first().copied())
+}
+
+/// Mapped, not hidden, on the current desktop and not fully transparent.
+fn is_window_visible(
+    conn: &XConnection,
+    window: &Window,
+    root: Window,
+    is_minimized: bool,
+) -> XCapResult<bool> {
+    let get_window_attributes_cookie = conn.send_request(&GetWindowAttributes { window: *window });
+    let get_window_attributes_reply = conn.wait_for_reply(get_window_attributes_cookie)?;
+    if get_window_attributes_reply.map_state() != MapState::Viewable || is_minimized {
+        return Ok(false);
+    }
+
+    // 0xFFFFFFFF 表示窗口出现在所有桌面上
+    let desktop = get_cardinal(conn, *window, "_NET_WM_DESKTOP")?;
+    let current_desktop = get_cardinal(conn, root, "_NET_CURRENT_DESKTOP")?;
+    if let (Some(desktop), Some(current_desktop)) = (desktop, current_desktop) {
+        if desktop != 0xFFFFFFFF && desktop != current_desktop {
+            return Ok(false);
+        }
+    }
+
+    let opacity = get_cardinal(conn, *window, "_NET_WM_WINDOW_OPACITY")?;
+
+    Ok(opacity != Some(0))
+}
+
+fn get_active_window_id(conn: &XConnection) -> Option<u32> {
     let active_window_atom = get_atom(conn, "_NET_ACTIVE_WINDOW").ok()?;
     let setup = conn.get_setup();
 
@@ -96,35 +249,104 @@ fn get_active_window_id(conn: &Connection) -> Option<u32> {
     None
 }
 
+/// The mapped children of `parent`, bottom to top, only the override-redirect ones when
+/// `override_redirect`.
+fn mapped_children(
+    conn: &XConnection,
+    parent: Window,
+    override_redirect: bool,
+) -> XCapResult<Vec<Window>> {
+    let query_tree_cookie = conn.send_request(&QueryTree { window: parent });
+    let children = conn.wait_for_reply(query_tree_cookie)?.children().to_vec();
+
+    // 先发出所有请求再等待回复，窗口很多时只需要一次往返
+    let cookies: Vec<_> = children
+        .iter()
+        .map(|child| conn.send_request(&GetWindowAttributes { window: *child }))
+        .collect();
+
+    let mut windows = Vec::new();
+    for (child, cookie) in children.into_iter().zip(cookies) {
+        // 子窗口可能在查询期间被销毁
+        let Ok(reply) = conn.wait_for_reply(cookie) else {
+            continue;
+        };
+        if reply.map_state() == MapState::Viewable
+            && reply.class() == WindowClass::InputOutput
+            && (reply.override_redirect() || !override_redirect)
+        {
+            windows.push(child);
+        }
+    }
+
+    Ok(windows)
+}
+
+/// The icons docked in the system tray of screen `screen_index`, which are children of the
+/// `_NET_SYSTEM_TRAY_S<n>` selection owner. Empty when there is no tray, or when it reparents
+/// the icons somewhere else.
+fn tray_icons(conn: &XConnection, screen_index: usize) -> XCapResult<Vec<Window>> {
+    let Ok(tray_atom) = get_atom(conn, &format!("_NET_SYSTEM_TRAY_S{}", screen_index)) else {
+        return Ok(Vec::new());
+    };
+
+    let get_selection_owner_cookie = conn.send_request(&GetSelectionOwner {
+        selection: tray_atom,
+    });
+    let owner = conn.wait_for_reply(get_selection_owner_cookie)?.owner();
+    if owner.is_none() {
+        return Ok(Vec::new());
+    }
+
+    mapped_children(conn, owner, false)
+}
+
+/// Whether the X server is XWayland, i.e. the windows are X11 clients of a Wayland session.
+fn is_xwayland(conn: &Connection) -> bool {
+    // Xwayland 23.1 开始提供 XWAYLAND 扩展，旧版本只能根据会话类型判断
+    let query_extension_cookie = conn.send_request(&QueryExtension { name: b"XWAYLAND" });
+    let has_extension = conn
+        .wait_for_reply(query_extension_cookie)
+        .map(|reply| reply.present())
+        .unwrap_or(false);
+
+    has_extension || wayland_detect()
+}
+
 impl ImplWindow {
     fn new(
-        conn: &Connection,
+        conn: &Arc<XConnection>,
         window: &Window,
         pid: u32,
         z: i32,
         is_focused: bool,
+        is_xwayland: bool,
         impl_monitors: &Vec<ImplMonitor>,
     ) -> XCapResult<ImplWindow> {
-        let title = {
+        // 旧程序的 WM_NAME 可能是 COMPOUND_TEXT，不限制类型
+        let (title, title_bytes) = {
             let get_title_reply =
-                get_window_property(conn, *window, ATOM_WM_NAME, ATOM_STRING, 0, 1024)?;
-            str::from_utf8(get_title_reply.value())?.to_string()
+                get_window_property(conn, *window, ATOM_WM_NAME, ATOM_NONE, 0, 1024)?;
+            let title_bytes = text_property_bytes(&get_title_reply).to_vec();
+
+            (
+                decode_text_property(get_title_reply.r#type(), &title_bytes),
+                title_bytes,
+            )
         };
 
         let app_name = {
             let get_class_reply =
                 get_window_property(conn, *window, ATOM_WM_CLASS, ATOM_STRING, 0, 1024)?;
 
-            let class = str::from_utf8(get_class_reply.value())?;
-
-            class
-                .split('\u{0}')
-                .find(|str| !str.is_empty())
-                .unwrap_or("")
-                .to_string()
+            text_property_bytes(&get_class_reply)
+                .split(|byte| *byte == 0)
+                .find(|class| !class.is_empty())
+                .map(|class| decode_text_property(ATOM_STRING, class))
+                .unwrap_or_default()
         };
 
-        let (x, y, width, height) = {
+        let (root, x, y, width, height) = {
             let get_geometry_cookie = conn.send_request(&GetGeometry {
                 drawable: Drawable::Window(*window),
             });
@@ -139,6 +361,7 @@ impl ImplWindow {
             let translate_coordinates_reply = conn.wait_for_reply(translate_coordinates_cookie)?;
 
             (
+                get_geometry_reply.root(),
                 (translate_coordinates_reply.dst_x() - get_geometry_reply.x()) as i32,
                 (translate_coordinates_reply.dst_y() - get_geometry_reply.y()) as i32,
                 get_geometry_reply.width() as u32,
@@ -174,37 +397,60 @@ impl ImplWindow {
             find_result.to_owned()
         };
 
-        let (is_minimized, is_maximized) = {
+        let kind = get_window_kind(conn, window).unwrap_or_else(|err| {
+            log::debug!("Get window {:?} kind failed: {}", window, err);
+            WindowKind::Normal
+        });
+
+        let (is_minimized, is_maximized, is_fullscreen, skip_taskbar, skip_pager) = {
             // https://specifications.freedesktop.org/wm-spec/1.3/ar01s05.html
             let wm_state_atom = get_atom(conn, "_NET_WM_STATE")?;
             let wm_state_hidden_atom = get_atom(conn, "_NET_WM_STATE_HIDDEN")?;
             let wm_state_maximized_vert_atom = get_atom(conn, "_NET_WM_STATE_MAXIMIZED_VERT")?;
             let wm_state_maximized_horz_atom = get_atom(conn, "_NET_WM_STATE_MAXIMIZED_HORZ")?;
+            let wm_state_fullscreen_atom = get_atom(conn, "_NET_WM_STATE_FULLSCREEN")?;
+            let wm_state_skip_taskbar_atom = get_atom(conn, "_NET_WM_STATE_SKIP_TASKBAR")?;
+            let wm_state_skip_pager_atom = get_atom(conn, "_NET_WM_STATE_SKIP_PAGER")?;
 
             let wm_state_reply =
                 get_window_property(conn, *window, wm_state_atom, ATOM_ATOM, 0, 12)?;
             let wm_state = wm_state_reply.value::<Atom>();
 
-            let is_minimized = wm_state.iter().any(|&state| state == wm_state_hidden_atom);
+            let is_minimized = wm_state.contains(&wm_state_hidden_atom);
 
-            let is_maximized_vert = wm_state
-                .iter()
-                .any(|&state| state == wm_state_maximized_vert_atom);
+            let is_maximized_vert = wm_state.contains(&wm_state_maximized_vert_atom);
 
-            let is_maximized_horz = wm_state
-                .iter()
-                .any(|&state| state == wm_state_maximized_horz_atom);
+            let is_maximized_horz = wm_state.contains(&wm_state_maximized_horz_atom);
 
             (
                 is_minimized,
                 !is_minimized && is_maximized_vert && is_maximized_horz,
+                !is_minimized && wm_state.contains(&wm_state_fullscreen_atom),
+                wm_state.contains(&wm_state_skip_taskbar_atom),
+                wm_state.contains(&wm_state_skip_pager_atom),
             )
         };
 
+        let is_visible = width > 0
+            && height > 0
+            && is_window_visible(conn, window, root, is_minimized).unwrap_or_else(|err| {
+                log::debug!("Get window {:?} visibility failed: {}", window, err);
+                !is_minimized
+            });
+
+        let owner_id = get_transient_for(conn, window, root).unwrap_or_else(|err| {
+            log::debug!("Get window {:?} transient for failed: {}", window, err);
+            None
+        });
+
         Ok(ImplWindow {
-            window: *window,
+            source: WindowSource::Xorg {
+                conn: conn.clone(),
+                window: *window,
+            },
             id: window.resource_id(),
             title,
+            title_bytes,
             app_name,
             pid,
             current_monitor,
@@ -215,12 +461,152 @@ impl ImplWindow {
             height,
             is_minimized,
             is_maximized,
+            is_fullscreen,
             is_focused,
+            is_xwayland,
+            kind,
+            skip_taskbar,
+            skip_pager,
+            is_visible,
+            owner_id,
         })
     }
 
+    pub fn backend() -> Backend {
+        // 原生 Wayland 窗口由 foreign toplevel 列出，XWayland 窗口仍然走 X11
+        #[cfg(feature = "foreign-toplevel")]
+        if wayland_detect() {
+            #[cfg(feature = "ext-image-copy-capture")]
+            if ext_available() {
+                return Backend::ExtImageCopyCapture;
+            }
+
+            return Backend::Wayland;
+        }
+
+        Backend::X11
+    }
+
     pub fn all() -> XCapResult<Vec<ImplWindow>> {
-        let (conn, _) = Connection::connect(None)?;
+        Ok(ImplWindow::all_with_errors()?.into_logged_items())
+    }
+
+    pub fn all_with_errors() -> XCapResult<Enumeration<ImplWindow>> {
+        #[cfg(feature = "foreign-toplevel")]
+        if wayland_detect() {
+            return ImplWindow::all_wayland(false);
+        }
+
+        ImplWindow::all_xorg(false)
+    }
+
+    /// Also the override-redirect windows and tray icons the window manager does not list.
+    pub fn all_with_override_redirect() -> XCapResult<Vec<ImplWindow>> {
+        #[cfg(feature = "foreign-toplevel")]
+        if wayland_detect() {
+            return Ok(ImplWindow::all_wayland(true)?.into_logged_items());
+        }
+
+        Ok(ImplWindow::all_xorg(true)?.into_logged_items())
+    }
+
+    /// XWayland windows, followed by the native Wayland toplevels.
+    #[cfg(feature = "foreign-toplevel")]
+    fn all_wayland(include_override_redirect: bool) -> XCapResult<Enumeration<ImplWindow>> {
+        // 没有 XWayland 时只有原生窗口
+        let mut enumeration =
+            ImplWindow::all_xorg(include_override_redirect).unwrap_or_else(|err| {
+                log::debug!("List XWayland windows failed: {}", err);
+                Enumeration::default()
+            });
+
+        let toplevels = match toplevels() {
+            Ok(toplevels) => toplevels,
+            Err(err) if !enumeration.items.is_empty() => {
+                enumeration.push_error(None, err);
+                return Ok(enumeration);
+            }
+            Err(err) => return Err(err),
+        };
+        let impl_windows = &mut enumeration.items;
+
+        let impl_monitors = ImplMonitor::all()?;
+        let primary_monitor = impl_monitors
+            .iter()
+            .find(|impl_monitor| impl_monitor.is_primary)
+            .or(impl_monitors.first())
+            .ok_or(XCapError::new("Get screen info failed"))?;
+
+        // XWayland 只知道 X11 窗口的焦点，原生窗口获得焦点时 X11 窗口都没有焦点
+        if toplevels.iter().any(|toplevel| toplevel.is_activated) {
+            for impl_window in impl_windows.iter_mut() {
+                impl_window.is_focused = false;
+            }
+        }
+
+        let mut z = -1;
+        for (index, toplevel) in toplevels.into_iter().enumerate() {
+            // foreign toplevel 协议也会列出 XWayland 窗口，跳过已经从 X11 拿到的窗口
+            let is_duplicate = impl_windows.iter().any(|impl_window| {
+                impl_window.is_xwayland
+                    && impl_window.title == toplevel.title
+                    && impl_window.app_name.eq_ignore_ascii_case(&toplevel.app_id)
+            });
+            if is_duplicate {
+                continue;
+            }
+
+            let current_monitor = toplevel
+                .output_position
+                .and_then(|(x, y)| {
+                    impl_monitors
+                        .iter()
+                        .find(|impl_monitor| impl_monitor.x == x && impl_monitor.y == y)
+                })
+                .unwrap_or(primary_monitor);
+
+            // 原生窗口没有 X11 的 id，用最高位区分
+            let id = match &toplevel.identifier {
+                Some(identifier) => identifier.bytes().fold(0x811c9dc5u32, |hash, byte| {
+                    (hash ^ byte as u32).wrapping_mul(0x01000193)
+                }),
+                None => index as u32,
+            } | 0x8000_0000;
+
+            impl_windows.push(ImplWindow {
+                source: WindowSource::Wayland {
+                    identifier: toplevel.identifier,
+                },
+                id,
+                title_bytes: toplevel.title.as_bytes().to_vec(),
+                title: toplevel.title,
+                app_name: toplevel.app_id,
+                pid: 0,
+                current_monitor: current_monitor.clone(),
+                x: current_monitor.x,
+                y: current_monitor.y,
+                z,
+                width: 0,
+                height: 0,
+                is_minimized: toplevel.is_minimized,
+                is_maximized: toplevel.is_maximized,
+                is_fullscreen: toplevel.is_fullscreen,
+                is_focused: toplevel.is_activated,
+                is_xwayland: false,
+                kind: WindowKind::Normal,
+                skip_taskbar: false,
+                skip_pager: false,
+                is_visible: !toplevel.is_minimized,
+                owner_id: None,
+            });
+            z -= 1;
+        }
+
+        Ok(enumeration)
+    }
+
+    fn all_xorg(include_override_redirect: bool) -> XCapResult<Enumeration<ImplWindow>> {
+        let conn = x_connection()?;
         let setup = conn.get_setup();
 
         // https://github.com/rust-x-bindings/rust-xcb/blob/main/examples/get_all_windows.rs
@@ -228,12 +614,13 @@ impl ImplWindow {
         // list all windows by stacking order
         let client_list_atom = get_atom(&conn, "_NET_CLIENT_LIST_STACKING")?;
         let active_window_id = get_active_window_id(&conn);
+        let is_xwayland = is_xwayland(&conn);
 
-        let mut impl_windows = Vec::new();
+        let mut enumeration = Enumeration::default();
         let impl_monitors = ImplMonitor::all()?;
 
         let mut z = -1;
-        for screen in setup.roots() {
+        for (screen_index, screen) in setup.roots().enumerate() {
             let root_window = screen.root();
 
             let query_pointer_cookie = conn.send_request(&QueryPointer {
@@ -241,7 +628,10 @@ impl ImplWindow {
             });
             let query_pointer_reply = match conn.wait_for_reply(query_pointer_cookie) {
                 Ok(query_pointer_reply) => query_pointer_reply,
-                _ => continue,
+                Err(err) => {
+                    enumeration.push_error(None, err);
+                    continue;
+                }
             };
 
             if query_pointer_reply.same_screen() {
@@ -254,44 +644,359 @@ impl ImplWindow {
                     1024,
                 ) {
                     Ok(list_window_reply) => list_window_reply,
-                    _ => continue,
+                    Err(err) => {
+                        enumeration.push_error(None, err);
+                        continue;
+                    }
                 };
 
                 for client in list_window_reply.value::<Window>() {
                     z += 1;
                     let pid = match get_window_pid(&conn, client) {
                         Ok(pid) => pid,
-                        err => {
-                            log::error!("{:?}", err);
+                        Err(err) => {
+                            enumeration.push_error(Some(client.resource_id()), err);
                             continue;
                         }
                     };
 
                     let is_focused = active_window_id.eq(&Some(client.resource_id()));
 
-                    if let Ok(impl_window) =
-                        ImplWindow::new(&conn, client, pid, z, is_focused, &impl_monitors)
-                    {
-                        impl_windows.push(impl_window);
-                    } else {
-                        log::error!(
-                            "ImplWindow::new(&conn, {:?}, {:?}) failed",
-                            client,
-                            &impl_monitors
-                        );
+                    match ImplWindow::new(
+                        &conn,
+                        client,
+                        pid,
+                        z,
+                        is_focused,
+                        is_xwayland,
+                        &impl_monitors,
+                    ) {
+                        Ok(impl_window) => enumeration.items.push(impl_window),
+                        Err(err) => enumeration.push_error(Some(client.resource_id()), err),
+                    }
+                }
+
+                if include_override_redirect {
+                    // 菜单和提示框总是显示在普通窗口之上，按根窗口的堆叠顺序排在客户端列表之后
+                    let unmanaged = tray_icons(&conn, screen_index).and_then(|mut windows| {
+                        windows.extend(mapped_children(&conn, root_window, true)?);
+                        Ok(windows)
+                    });
+                    let unmanaged = match unmanaged {
+                        Ok(unmanaged) => unmanaged,
+                        Err(err) => {
+                            enumeration.push_error(None, err);
+                            Vec::new()
+                        }
+                    };
+
+                    for window in unmanaged {
+                        z += 1;
+                        // 弹出菜单通常不设置 _NET_WM_PID
+                        let pid = get_window_pid(&conn, &window).unwrap_or(0);
+
+                        match ImplWindow::new(
+                            &conn,
+                            &window,
+                            pid,
+                            z,
+                            false,
+                            is_xwayland,
+                            &impl_monitors,
+                        ) {
+                            Ok(impl_window) => enumeration.items.push(impl_window),
+                            Err(err) => enumeration.push_error(Some(window.resource_id()), err),
+                        }
                     }
                 }
             }
         }
 
-        impl_windows.reverse();
+        enumeration.items.reverse();
+
+        Ok(enumeration)
+    }
+}
+
+/// Split the NUL separated `/proc/<pid>/cmdline`.
+fn parse_cmdline(cmdline: &[u8]) -> Vec<String> {
+    // 每个参数都以 NUL 结尾，内核线程的 cmdline 为空
+    let cmdline = cmdline.strip_suffix(&[0]).unwrap_or(cmdline);
+    if cmdline.is_empty() {
+        return Vec::new();
+    }
+
+    cmdline
+        .split(|byte| *byte == 0)
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect()
+}
+
+/// The architecture in the header of an ELF executable.
+fn elf_arch(header: &[u8; 20]) -> ProcessArch {
+    if !header.starts_with(b"\x7fELF") {
+        return ProcessArch::Unknown;
+    }
+
+    // e_machine 的字节序由 EI_DATA 决定
+    let machine = match header[5] {
+        2 => u16::from_be_bytes([header[18], header[19]]),
+        _ => u16::from_le_bytes([header[18], header[19]]),
+    };
+
+    match machine {
+        3 => ProcessArch::X86,
+        62 => ProcessArch::X86_64,
+        40 => ProcessArch::Arm,
+        183 => ProcessArch::Arm64,
+        _ => ProcessArch::Unknown,
+    }
+}
+
+impl ImplWindow {
+    fn proc_path(&self, name: &str) -> XCapResult<PathBuf> {
+        // 原生 Wayland 窗口拿不到 pid
+        if self.pid == 0 {
+            return Err(XCapError::new("Window process id is unknown"));
+        }
+
+        Ok(PathBuf::from(format!("/proc/{}/{}", self.pid, name)))
+    }
+
+    pub fn exe_path(&self) -> XCapResult<PathBuf> {
+        Ok(fs::read_link(self.proc_path("exe")?)?)
+    }
 
-        Ok(impl_windows)
+    pub fn cmdline(&self) -> XCapResult<Vec<String>> {
+        Ok(parse_cmdline(&fs::read(self.proc_path("cmdline")?)?))
+    }
+
+    pub fn process_info(&self) -> XCapResult<ProcessInfo> {
+        let mut header = [0u8; 20];
+        File::open(self.proc_path("exe")?)?.read_exact(&mut header)?;
+
+        // Flatpak 在沙箱根目录放置 .flatpak-info，Snap 的进程位于 snap.* cgroup
+        let is_flatpak = Path::new(&format!("/proc/{}/root/.flatpak-info", self.pid)).exists();
+        let is_snap = fs::read_to_string(self.proc_path("cgroup")?)
+            .is_ok_and(|cgroup| cgroup.contains("/snap."));
+
+        Ok(ProcessInfo {
+            arch: elf_arch(&header),
+            is_sandboxed: is_flatpak || is_snap,
+            is_translated: false,
+        })
     }
 }
 
 impl ImplWindow {
+    pub fn is_valid(&self) -> bool {
+        match &self.source {
+            WindowSource::Xorg { conn, window } => {
+                let cookie = conn.send_request(&GetWindowAttributes { window: *window });
+                conn.wait_for_reply(cookie).is_ok()
+            }
+            // 没有 identifier 时无法可靠地找到同一个窗口
+            #[cfg(feature = "foreign-toplevel")]
+            WindowSource::Wayland { identifier } => match identifier {
+                Some(identifier) => toplevels().is_ok_and(|toplevels| {
+                    toplevels
+                        .iter()
+                        .any(|toplevel| toplevel.identifier.as_ref() == Some(identifier))
+                }),
+                None => true,
+            },
+        }
+    }
+
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
         capture_window(self)
     }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        Ok(thumbnail(self.capture_image()?, max_width, max_height))
+    }
+}
+
+impl ImplWindow {
+    /// Scroll with XTest button 4 and 5 events at the center of the window, Wayland does not
+    /// allow clients to synthesize input.
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn scroll(&self, lines: i32) -> XCapResult<()> {
+        let conn = match &self.source {
+            WindowSource::Xorg { conn, .. } => conn,
+            #[cfg(feature = "foreign-toplevel")]
+            WindowSource::Wayland { .. } => {
+                return Err(XCapError::new(
+                    "Input can not be synthesized for native Wayland windows",
+                ))
+            }
+        };
+        if !conn
+            .active_extensions()
+            .any(|extension| extension == Extension::Test)
+        {
+            return Err(XCapError::new("The X server has no XTEST extension"));
+        }
+        let root = conn
+            .get_setup()
+            .roots()
+            .nth(conn.screen_num as usize)
+            .ok_or_else(|| XCapError::new("Get screen failed"))?
+            .root();
+
+        // 滚轮事件发给光标下的窗口，先把光标移到窗口中间
+        conn.send_request(&FakeInput {
+            r#type: MotionNotifyEvent::NUMBER as u8,
+            detail: 0,
+            time: CURRENT_TIME,
+            root,
+            root_x: (self.x + self.width as i32 / 2) as i16,
+            root_y: (self.y + self.height as i32 / 2) as i16,
+            deviceid: 0,
+        });
+
+        // 按钮 4 向上，5 向下
+        let button = if lines > 0 { 5 } else { 4 };
+        for _ in 0..lines.unsigned_abs() {
+            for r#type in [ButtonPressEvent::NUMBER, ButtonReleaseEvent::NUMBER] {
+                conn.send_request(&FakeInput {
+                    r#type: r#type as u8,
+                    detail: button,
+                    time: CURRENT_TIME,
+                    root,
+                    root_x: 0,
+                    root_y: 0,
+                    deviceid: 0,
+                });
+            }
+        }
+        conn.flush()?;
+
+        Ok(())
+    }
+}
+
+/// A SHAPE rectangle clamped to the window, shapes may extend over the border.
+fn shape_region(rectangle: &xcb::x::Rectangle, width: u32, height: u32) -> Option<Region> {
+    let left = rectangle.x.max(0) as u32;
+    let top = rectangle.y.max(0) as u32;
+    let right = (rectangle.x as i32 + rectangle.width as i32).max(0) as u32;
+    let bottom = (rectangle.y as i32 + rectangle.height as i32).max(0) as u32;
+
+    Region::new(
+        left,
+        top,
+        right.saturating_sub(left),
+        bottom.saturating_sub(top),
+    )
+    .clamp(width, height)
+}
+
+impl ImplWindow {
+    /// Windows that were never shaped report their whole rectangle from `GetRectangles` too.
+    pub fn shape(&self) -> XCapResult<WindowShape> {
+        let (conn, window) = match &self.source {
+            WindowSource::Xorg { conn, window } => (conn, *window),
+            #[cfg(feature = "foreign-toplevel")]
+            WindowSource::Wayland { .. } => {
+                return Err(XCapError::new(
+                    "The shape of native Wayland windows is unknown",
+                ))
+            }
+        };
+        if !conn
+            .active_extensions()
+            .any(|extension| extension == Extension::Shape)
+        {
+            return Ok(WindowShape::rectangle(self.width, self.height));
+        }
+
+        let rectangles = |source_kind| -> XCapResult<Vec<Region>> {
+            let cookie = conn.send_request(&GetRectangles {
+                window,
+                source_kind,
+            });
+            let reply = conn.wait_for_reply(cookie)?;
+
+            Ok(reply
+                .rectangles()
+                .iter()
+                .filter_map(|rectangle| shape_region(rectangle, self.width, self.height))
+                .collect())
+        };
+
+        Ok(WindowShape {
+            width: self.width,
+            height: self.height,
+            bounding: rectangles(Sk::Bounding)?,
+            input: rectangles(Sk::Input)?,
+        })
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl ImplWindow {
+    pub fn raw_window_handle(&self) -> XCapResult<raw_window_handle::RawWindowHandle> {
+        use raw_window_handle::{RawWindowHandle, XlibWindowHandle};
+
+        match &self.source {
+            WindowSource::Xorg { window, .. } => Ok(RawWindowHandle::Xlib(XlibWindowHandle::new(
+                window.resource_id() as libc::c_ulong,
+            ))),
+            #[cfg(feature = "foreign-toplevel")]
+            WindowSource::Wayland { .. } => Err(XCapError::new(
+                "Native Wayland windows have no raw window handle",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::x_connection::ATOM_NAMES;
+    use super::*;
+
+    #[test]
+    fn decode_invalid_titles() {
+        assert_eq!(decode_text_property(ATOM_STRING, "标题".as_bytes()), "标题");
+        assert_eq!(decode_text_property(ATOM_STRING, b"caf\xe9"), "café");
+        assert_eq!(decode_text_property(ATOM_NONE, b"a\xffb"), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn read_elf_arch() {
+        let mut header = [0u8; 20];
+        header[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        header[18] = 183;
+        assert_eq!(elf_arch(&header), ProcessArch::Arm64);
+
+        assert_eq!(elf_arch(&[0u8; 20]), ProcessArch::Unknown);
+    }
+
+    #[test]
+    fn map_every_window_type() {
+        let window_types = [
+            ("_NET_WM_WINDOW_TYPE_NORMAL", WindowKind::Normal),
+            ("_NET_WM_WINDOW_TYPE_DIALOG", WindowKind::Dialog),
+            ("_NET_WM_WINDOW_TYPE_UTILITY", WindowKind::Utility),
+            ("_NET_WM_WINDOW_TYPE_TOOLBAR", WindowKind::Toolbar),
+            ("_NET_WM_WINDOW_TYPE_MENU", WindowKind::Menu),
+            ("_NET_WM_WINDOW_TYPE_DROPDOWN_MENU", WindowKind::Menu),
+            ("_NET_WM_WINDOW_TYPE_POPUP_MENU", WindowKind::Menu),
+            ("_NET_WM_WINDOW_TYPE_COMBO", WindowKind::Menu),
+            ("_NET_WM_WINDOW_TYPE_DOCK", WindowKind::Dock),
+            ("_NET_WM_WINDOW_TYPE_DESKTOP", WindowKind::Desktop),
+            ("_NET_WM_WINDOW_TYPE_SPLASH", WindowKind::Splash),
+            ("_NET_WM_WINDOW_TYPE_TOOLTIP", WindowKind::Tooltip),
+            ("_NET_WM_WINDOW_TYPE_NOTIFICATION", WindowKind::Notification),
+        ];
+
+        for (name, kind) in window_types {
+            assert_eq!(window_kind(name), Some(kind), "{}", name);
+            // 不在缓存里的类型每次都要多一次往返
+            assert!(ATOM_NAMES.contains(&name), "{} is not cached", name);
+        }
+        assert_eq!(window_kind("_KDE_NET_WM_WINDOW_TYPE_OVERRIDE"), None);
+    }
 }
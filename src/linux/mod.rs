@@ -1,8 +1,28 @@
 mod capture;
+mod drm_capture;
+#[cfg(feature = "ext-image-copy-capture")]
+mod ext_capture;
+mod fbdev_capture;
+#[cfg(feature = "foreign-toplevel")]
+mod foreign_toplevel;
+#[cfg(feature = "nvfbc")]
+mod nvfbc_capture;
 mod utils;
 mod wayland_capture;
+#[cfg(any(feature = "wlr-screencopy", feature = "ext-image-copy-capture"))]
+mod wayland_shm;
+#[cfg(feature = "wlr-screencopy")]
+mod wlr_capture;
 mod xorg_capture;
 
 pub mod impl_monitor;
+pub mod impl_pointer;
+pub mod impl_vblank;
 pub mod impl_video_recorder;
+pub mod impl_watcher;
 pub mod impl_window;
+pub mod screencast;
+pub mod x_connection;
+
+#[cfg(feature = "image")]
+pub use xorg_capture::capture_drawable;
@@ -0,0 +1,363 @@
+use image::RgbaImage;
+use scopeguard::guard;
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem, ptr, slice,
+    sync::OnceLock,
+};
+
+use crate::error::{XCapError, XCapResult};
+
+use super::impl_monitor::ImplMonitor;
+
+// NVIDIA Capture SDK, NvFBC.h
+// https://developer.nvidia.com/capture-sdk
+
+const NVFBC_VERSION_MAJOR: u32 = 1;
+const NVFBC_VERSION_MINOR: u32 = 8;
+const NVFBC_VERSION: u32 = NVFBC_VERSION_MINOR | NVFBC_VERSION_MAJOR << 8;
+
+const fn nvfbc_struct_version<T>(version: u32) -> u32 {
+    mem::size_of::<T>() as u32 | version << 16 | NVFBC_VERSION << 24
+}
+
+const NVFBC_SUCCESS: u32 = 0;
+const NVFBC_FALSE: u32 = 0;
+const NVFBC_TRUE: u32 = 1;
+const NVFBC_CAPTURE_TO_SYS: u32 = 0;
+const NVFBC_TRACKING_OUTPUT: u32 = 1;
+const NVFBC_BUFFER_FORMAT_RGBA: u32 = 4;
+const NVFBC_TOSYS_GRAB_FLAGS_NOWAIT: u32 = 1;
+const NVFBC_OUTPUT_MAX: usize = 5;
+const NVFBC_OUTPUT_NAME_LEN: usize = 128;
+
+type NvFbcSessionHandle = u64;
+type NvFbcStatus = u32;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct NvFbcBox {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct NvFbcSize {
+    w: u32,
+    h: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct NvFbcFrameGrabInfo {
+    width: u32,
+    height: u32,
+    byte_size: u32,
+    current_frame: u32,
+    is_new_frame: u32,
+    timestamp_us: u64,
+    missed_frames: u32,
+    required_post_processing: u32,
+    direct_capture: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcCreateHandleParams {
+    version: u32,
+    private_data: *const c_void,
+    private_data_size: u32,
+    externally_managed_context: u32,
+    glx_ctx: *mut c_void,
+    glx_fb_config: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcDestroyHandleParams {
+    version: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcRandrOutputInfo {
+    id: u32,
+    name: [c_char; NVFBC_OUTPUT_NAME_LEN],
+    tracked_box: NvFbcBox,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcGetStatusParams {
+    version: u32,
+    is_capture_possible: u32,
+    currently_capturing: u32,
+    can_create_now: u32,
+    screen_size: NvFbcSize,
+    xrandr_available: u32,
+    outputs: [NvFbcRandrOutputInfo; NVFBC_OUTPUT_MAX],
+    output_num: u32,
+    nvfbc_version: u32,
+    in_modeset: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcCreateCaptureSessionParams {
+    version: u32,
+    capture_type: u32,
+    tracking_type: u32,
+    output_id: u32,
+    capture_box: NvFbcBox,
+    frame_size: NvFbcSize,
+    with_cursor: u32,
+    disable_auto_modeset_recovery: u32,
+    round_frame_size: u32,
+    sampling_rate_ms: u32,
+    push_model: u32,
+    allow_direct_capture: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcDestroyCaptureSessionParams {
+    version: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcToSysSetupParams {
+    version: u32,
+    buffer_format: u32,
+    buffer: *mut *mut c_void,
+    with_diff_map: u32,
+    diff_map: *mut *mut c_void,
+    diff_map_scaling_factor: u32,
+    diff_map_size: NvFbcSize,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcToSysGrabFrameParams {
+    version: u32,
+    flags: u32,
+    frame_grab_info: *mut NvFbcFrameGrabInfo,
+    timeout_ms: u32,
+}
+
+type NvFbcFn<T> = Option<unsafe extern "C" fn(NvFbcSessionHandle, *mut T) -> NvFbcStatus>;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NvFbcApiFunctionList {
+    version: u32,
+    get_last_error_str: Option<unsafe extern "C" fn(NvFbcSessionHandle) -> *const c_char>,
+    create_handle: Option<
+        unsafe extern "C" fn(*mut NvFbcSessionHandle, *mut NvFbcCreateHandleParams) -> NvFbcStatus,
+    >,
+    destroy_handle: NvFbcFn<NvFbcDestroyHandleParams>,
+    get_status: NvFbcFn<NvFbcGetStatusParams>,
+    create_capture_session: NvFbcFn<NvFbcCreateCaptureSessionParams>,
+    destroy_capture_session: NvFbcFn<NvFbcDestroyCaptureSessionParams>,
+    to_sys_set_up: NvFbcFn<NvFbcToSysSetupParams>,
+    to_sys_grab_frame: NvFbcFn<NvFbcToSysGrabFrameParams>,
+    reserved: [*mut c_void; 13],
+}
+
+// 函数表里只有函数指针，加载后不会再修改
+unsafe impl Send for NvFbcApiFunctionList {}
+unsafe impl Sync for NvFbcApiFunctionList {}
+
+/// Load `libnvidia-fbc.so.1` once, `None` when the driver does not provide it.
+fn nvfbc_api() -> Option<&'static NvFbcApiFunctionList> {
+    static API: OnceLock<Option<NvFbcApiFunctionList>> = OnceLock::new();
+
+    API.get_or_init(|| unsafe {
+        // 库句柄不关闭，函数表在整个进程生命周期内有效
+        let library = libc::dlopen(c"libnvidia-fbc.so.1".as_ptr(), libc::RTLD_NOW);
+        if library.is_null() {
+            log::debug!("libnvidia-fbc.so.1 not found");
+            return None;
+        }
+
+        let create_instance = libc::dlsym(library, c"NvFBCCreateInstance".as_ptr());
+        if create_instance.is_null() {
+            log::debug!("NvFBCCreateInstance not found");
+            return None;
+        }
+
+        let create_instance: unsafe extern "C" fn(*mut NvFbcApiFunctionList) -> NvFbcStatus =
+            mem::transmute(create_instance);
+
+        let mut api = NvFbcApiFunctionList {
+            version: NVFBC_VERSION,
+            get_last_error_str: None,
+            create_handle: None,
+            destroy_handle: None,
+            get_status: None,
+            create_capture_session: None,
+            destroy_capture_session: None,
+            to_sys_set_up: None,
+            to_sys_grab_frame: None,
+            reserved: [ptr::null_mut(); 13],
+        };
+
+        let status = create_instance(&mut api);
+        if status != NVFBC_SUCCESS {
+            log::debug!("NvFBCCreateInstance failed: {}", status);
+            return None;
+        }
+
+        Some(api)
+    })
+    .as_ref()
+}
+
+/// Whether the NVIDIA driver provides NvFBC.
+pub(super) fn nvfbc_available() -> bool {
+    nvfbc_api().is_some()
+}
+
+fn check_status(
+    api: &NvFbcApiFunctionList,
+    handle: NvFbcSessionHandle,
+    name: &str,
+    status: NvFbcStatus,
+) -> XCapResult<()> {
+    if status == NVFBC_SUCCESS {
+        return Ok(());
+    }
+
+    let message = api
+        .get_last_error_str
+        .map(|get_last_error_str| unsafe { get_last_error_str(handle) })
+        .filter(|message| !message.is_null())
+        .map(|message| {
+            unsafe { CStr::from_ptr(message) }
+                .to_string_lossy()
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    Err(XCapError::new(format!(
+        "{} failed: {} {}",
+        name, status, message
+    )))
+}
+
+fn get_fn<T: Copy>(function: Option<T>, name: &str) -> XCapResult<T> {
+    function.ok_or_else(|| XCapError::new(format!("NvFBC function {} not found", name)))
+}
+
+/// Capture a RandR output with NvFBC, which reads the frame straight from the GPU.
+pub(super) fn nvfbc_capture(impl_monitor: &ImplMonitor) -> XCapResult<RgbaImage> {
+    let api = nvfbc_api().ok_or_else(|| XCapError::new("NvFBC is not available"))?;
+
+    let create_handle = get_fn(api.create_handle, "nvFBCCreateHandle")?;
+    let destroy_handle = get_fn(api.destroy_handle, "nvFBCDestroyHandle")?;
+    let get_status = get_fn(api.get_status, "nvFBCGetStatus")?;
+    let create_capture_session = get_fn(api.create_capture_session, "nvFBCCreateCaptureSession")?;
+    let destroy_capture_session =
+        get_fn(api.destroy_capture_session, "nvFBCDestroyCaptureSession")?;
+    let to_sys_set_up = get_fn(api.to_sys_set_up, "nvFBCToSysSetUp")?;
+    let to_sys_grab_frame = get_fn(api.to_sys_grab_frame, "nvFBCToSysGrabFrame")?;
+
+    unsafe {
+        let mut handle: NvFbcSessionHandle = 0;
+        let mut create_handle_params = NvFbcCreateHandleParams {
+            version: nvfbc_struct_version::<NvFbcCreateHandleParams>(2),
+            private_data: ptr::null(),
+            private_data_size: 0,
+            externally_managed_context: NVFBC_FALSE,
+            glx_ctx: ptr::null_mut(),
+            glx_fb_config: ptr::null_mut(),
+        };
+        let status = create_handle(&mut handle, &mut create_handle_params);
+        check_status(api, handle, "nvFBCCreateHandle", status)?;
+
+        let handle = guard(handle, |handle| {
+            let mut params = NvFbcDestroyHandleParams {
+                version: nvfbc_struct_version::<NvFbcDestroyHandleParams>(1),
+            };
+            destroy_handle(handle, &mut params);
+        });
+
+        let mut status_params: NvFbcGetStatusParams = mem::zeroed();
+        status_params.version = nvfbc_struct_version::<NvFbcGetStatusParams>(2);
+        let status = get_status(*handle, &mut status_params);
+        check_status(api, *handle, "nvFBCGetStatus", status)?;
+
+        if status_params.is_capture_possible == NVFBC_FALSE {
+            return Err(XCapError::new(
+                "NvFBC capture is not possible on this GPU or driver",
+            ));
+        }
+
+        // NvFBC 的 output id 就是 RandR output id
+        let output = status_params.outputs[..status_params.output_num as usize]
+            .iter()
+            .find(|output| output.id == impl_monitor.id)
+            .ok_or_else(|| XCapError::new(format!("NvFBC output {} not found", impl_monitor.id)))?;
+
+        let mut session_params = NvFbcCreateCaptureSessionParams {
+            version: nvfbc_struct_version::<NvFbcCreateCaptureSessionParams>(6),
+            capture_type: NVFBC_CAPTURE_TO_SYS,
+            tracking_type: NVFBC_TRACKING_OUTPUT,
+            output_id: output.id,
+            capture_box: NvFbcBox::default(),
+            frame_size: NvFbcSize::default(),
+            with_cursor: NVFBC_FALSE,
+            disable_auto_modeset_recovery: NVFBC_FALSE,
+            round_frame_size: NVFBC_FALSE,
+            sampling_rate_ms: 0,
+            push_model: NVFBC_FALSE,
+            allow_direct_capture: NVFBC_TRUE,
+        };
+        let status = create_capture_session(*handle, &mut session_params);
+        check_status(api, *handle, "nvFBCCreateCaptureSession", status)?;
+
+        let session = guard(*handle, |handle| {
+            let mut params = NvFbcDestroyCaptureSessionParams {
+                version: nvfbc_struct_version::<NvFbcDestroyCaptureSessionParams>(1),
+            };
+            destroy_capture_session(handle, &mut params);
+        });
+
+        // 缓冲区由 NvFBC 分配，在会话销毁前有效
+        let mut buffer: *mut c_void = ptr::null_mut();
+        let mut setup_params = NvFbcToSysSetupParams {
+            version: nvfbc_struct_version::<NvFbcToSysSetupParams>(3),
+            buffer_format: NVFBC_BUFFER_FORMAT_RGBA,
+            buffer: &mut buffer,
+            with_diff_map: NVFBC_FALSE,
+            diff_map: ptr::null_mut(),
+            diff_map_scaling_factor: 1,
+            diff_map_size: NvFbcSize::default(),
+        };
+        let status = to_sys_set_up(*session, &mut setup_params);
+        check_status(api, *session, "nvFBCToSysSetUp", status)?;
+
+        let mut frame_grab_info = NvFbcFrameGrabInfo::default();
+        let mut grab_params = NvFbcToSysGrabFrameParams {
+            version: nvfbc_struct_version::<NvFbcToSysGrabFrameParams>(2),
+            flags: NVFBC_TOSYS_GRAB_FLAGS_NOWAIT,
+            frame_grab_info: &mut frame_grab_info,
+            timeout_ms: 0,
+        };
+        let status = to_sys_grab_frame(*session, &mut grab_params);
+        check_status(api, *session, "nvFBCToSysGrabFrame", status)?;
+
+        let size = (frame_grab_info.width * frame_grab_info.height * 4) as usize;
+        if buffer.is_null() || (frame_grab_info.byte_size as usize) < size {
+            return Err(XCapError::new("NvFBC returned an invalid frame"));
+        }
+
+        let raw = slice::from_raw_parts(buffer as *const u8, size).to_vec();
+
+        RgbaImage::from_raw(frame_grab_info.width, frame_grab_info.height, raw)
+            .ok_or_else(|| XCapError::new("RgbaImage::from_raw failed"))
+    }
+}
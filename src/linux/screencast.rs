@@ -0,0 +1,500 @@
+use dbus::{
+    arg::{AppendAll, OwnedFd as DBusOwnedFd, PropMap, RefArg, Variant},
+    blocking::Connection,
+    message::MatchRule,
+    Path,
+};
+use std::{
+    collections::HashMap,
+    os::fd::{FromRawFd, OwnedFd},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::error::{Remediation, XCapError, XCapResult};
+
+use super::wayland_capture::OrgFreedesktopPortalRequestResponse;
+
+// https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.ScreenCast.html
+
+const SCREEN_CAST_INTERFACE: &str = "org.freedesktop.portal.ScreenCast";
+
+/// What a ScreenCast portal stream shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceType {
+    Monitor,
+    Window,
+    Virtual,
+}
+
+impl SourceType {
+    fn bits(&self) -> u32 {
+        match self {
+            SourceType::Monitor => 1,
+            SourceType::Window => 2,
+            SourceType::Virtual => 4,
+        }
+    }
+
+    fn from_bits(bits: u32) -> Option<SourceType> {
+        match bits {
+            1 => Some(SourceType::Monitor),
+            2 => Some(SourceType::Window),
+            4 => Some(SourceType::Virtual),
+            _ => None,
+        }
+    }
+}
+
+/// How the cursor appears in the streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CursorMode {
+    Hidden,
+    /// Drawn into the frames.
+    Embedded,
+    /// Sent as PipeWire stream metadata.
+    Metadata,
+}
+
+impl CursorMode {
+    fn bits(&self) -> u32 {
+        match self {
+            CursorMode::Hidden => 1,
+            CursorMode::Embedded => 2,
+            CursorMode::Metadata => 4,
+        }
+    }
+}
+
+/// How long the portal remembers the user's approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PersistMode {
+    /// Ask again every session.
+    #[default]
+    DoNot,
+    /// Remember while the application is running.
+    Application,
+    /// Remember until the user revokes it, across restarts.
+    Persistent,
+}
+
+impl PersistMode {
+    fn bits(&self) -> u32 {
+        match self {
+            PersistMode::DoNot => 0,
+            PersistMode::Application => 1,
+            PersistMode::Persistent => 2,
+        }
+    }
+}
+
+/// One PipeWire stream the user approved in the portal dialog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortalStream {
+    /// The PipeWire node to connect to, e.g. with `pipewiresrc path=<node_id>`.
+    pub node_id: u32,
+    /// An opaque id, stable across sessions restored with the same token.
+    pub id: Option<String>,
+    pub source_type: Option<SourceType>,
+    /// Position in the compositor space, only for monitor streams.
+    pub position: Option<(i32, i32)>,
+    pub size: Option<(i32, i32)>,
+}
+
+/// Options of [`ScreenCastSession::start`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenCastOptions {
+    source_types: Vec<SourceType>,
+    multiple: bool,
+    cursor_mode: Option<CursorMode>,
+    persist_mode: PersistMode,
+    restore_token: Option<String>,
+    timeout: Duration,
+}
+
+impl Default for ScreenCastOptions {
+    fn default() -> Self {
+        ScreenCastOptions {
+            source_types: vec![SourceType::Monitor],
+            multiple: false,
+            cursor_mode: None,
+            persist_mode: PersistMode::DoNot,
+            restore_token: None,
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl ScreenCastOptions {
+    pub fn new() -> ScreenCastOptions {
+        ScreenCastOptions::default()
+    }
+
+    /// The kinds of sources offered in the dialog, defaults to monitors only.
+    pub fn with_source_types(mut self, source_types: &[SourceType]) -> ScreenCastOptions {
+        self.source_types = source_types.to_vec();
+        self
+    }
+
+    /// Let the user select several sources.
+    pub fn with_multiple(mut self, multiple: bool) -> ScreenCastOptions {
+        self.multiple = multiple;
+        self
+    }
+
+    pub fn with_cursor_mode(mut self, cursor_mode: CursorMode) -> ScreenCastOptions {
+        self.cursor_mode = Some(cursor_mode);
+        self
+    }
+
+    /// Ask the portal to remember the selection, the token to restore it is then
+    /// available from [`ScreenCastSession::restore_token`].
+    pub fn with_persist_mode(mut self, persist_mode: PersistMode) -> ScreenCastOptions {
+        self.persist_mode = persist_mode;
+        self
+    }
+
+    /// Restore a previous selection without showing the dialog. The token is single
+    /// use, save the new one returned by [`ScreenCastSession::restore_token`].
+    pub fn with_restore_token<T: Into<String>>(mut self, restore_token: T) -> ScreenCastOptions {
+        self.restore_token = Some(restore_token.into());
+        self
+    }
+
+    /// How long to wait for the user to answer the dialog, defaults to 120 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> ScreenCastOptions {
+        self.timeout = timeout;
+        self
+    }
+}
+
+fn handle_token() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    format!(
+        "xcap_{}_{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn unwrap_variant(arg: &dyn RefArg) -> Option<&dyn RefArg> {
+    if arg.signature().starts_with('v') {
+        arg.as_iter()?.next()
+    } else {
+        Some(arg)
+    }
+}
+
+fn parse_pair(arg: &dyn RefArg) -> Option<(i32, i32)> {
+    let mut values = unwrap_variant(arg)?.as_iter()?;
+
+    Some((
+        values.next()?.as_i64()? as i32,
+        values.next()?.as_i64()? as i32,
+    ))
+}
+
+/// Parse the `a(ua{sv})` streams returned by `Start`.
+fn parse_streams(streams: &dyn RefArg) -> Vec<PortalStream> {
+    let mut portal_streams = Vec::new();
+
+    let items = match unwrap_variant(streams).and_then(|streams| streams.as_iter()) {
+        Some(items) => items,
+        None => return portal_streams,
+    };
+
+    for item in items {
+        let mut fields = match item.as_iter() {
+            Some(fields) => fields,
+            None => continue,
+        };
+
+        let node_id = match fields.next().and_then(|node_id| node_id.as_u64()) {
+            Some(node_id) => node_id as u32,
+            None => continue,
+        };
+
+        let mut portal_stream = PortalStream {
+            node_id,
+            id: None,
+            source_type: None,
+            position: None,
+            size: None,
+        };
+
+        // a{sv} 展开后是 key、value 交替排列
+        if let Some(mut properties) = fields.next().and_then(|properties| properties.as_iter()) {
+            while let (Some(key), Some(value)) = (properties.next(), properties.next()) {
+                match key.as_str() {
+                    Some("id") => {
+                        portal_stream.id = unwrap_variant(value)
+                            .and_then(|id| id.as_str())
+                            .map(|id| id.to_string())
+                    }
+                    Some("source_type") => {
+                        portal_stream.source_type = unwrap_variant(value)
+                            .and_then(|source_type| source_type.as_u64())
+                            .and_then(|bits| SourceType::from_bits(bits as u32))
+                    }
+                    Some("position") => portal_stream.position = parse_pair(value),
+                    Some("size") => portal_stream.size = parse_pair(value),
+                    _ => {}
+                }
+            }
+        }
+
+        portal_streams.push(portal_stream);
+    }
+
+    portal_streams
+}
+
+/// Call a portal method that answers through a `org.freedesktop.portal.Request` object.
+fn portal_request<A: AppendAll>(
+    conn: &Connection,
+    method: &str,
+    args: A,
+    token: &str,
+    timeout: Duration,
+) -> XCapResult<PropMap> {
+    // 在调用之前订阅 Response 信号，避免错过很快返回的结果
+    let sender = conn.unique_name().trim_start_matches(':').replace('.', "_");
+    let request_path = format!(
+        "/org/freedesktop/portal/desktop/request/{}/{}",
+        sender, token
+    );
+
+    let response: Arc<Mutex<Option<(u32, PropMap)>>> = Arc::new(Mutex::new(None));
+    let response_res = response.clone();
+
+    let match_rule = MatchRule::new_signal("org.freedesktop.portal.Request", "Response")
+        .with_path(Path::new(request_path).map_err(XCapError::new)?);
+    let match_token = conn.add_match(
+        match_rule,
+        move |res: OrgFreedesktopPortalRequestResponse, _conn, _msg| {
+            if let Ok(mut response) = response.lock() {
+                *response = Some((res.status, res.results));
+            }
+
+            true
+        },
+    )?;
+
+    let proxy = conn.with_proxy(
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        Duration::from_secs(10),
+    );
+    let result = proxy.method_call::<(Path,), A, &str, &str>(SCREEN_CAST_INTERFACE, method, args);
+
+    let deadline = Instant::now() + timeout;
+    let response = result.map_err(XCapError::from).and_then(|_| loop {
+        if let Some(response) = response_res.lock()?.take() {
+            break Ok(response);
+        }
+
+        if Instant::now() >= deadline {
+            break Err(XCapError::new(format!("{} timed out", method)));
+        }
+
+        conn.process(Duration::from_millis(100))?;
+    });
+
+    conn.remove_match(match_token)?;
+
+    let (status, results) = response?;
+    match status {
+        0 => Ok(results),
+        1 => Err(XCapError::permission_denied(
+            format!("{} was cancelled by the user", method),
+            Remediation::SharingDialog,
+        )),
+        _ => Err(XCapError::new(format!("{} failed", method))),
+    }
+}
+
+/// A ScreenCast portal session, for Wayland compositors.
+///
+/// The streams are PipeWire nodes; connect to them with your own PipeWire or
+/// GStreamer consumer through the remote returned by
+/// [`ScreenCastSession::open_pipewire_remote`].
+pub struct ScreenCastSession {
+    conn: Connection,
+    session: Path<'static>,
+    streams: Vec<PortalStream>,
+    restore_token: Option<String>,
+}
+
+impl std::fmt::Debug for ScreenCastSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScreenCastSession")
+            .field("session", &self.session)
+            .field("streams", &self.streams)
+            .field("restore_token", &self.restore_token)
+            .finish()
+    }
+}
+
+impl ScreenCastSession {
+    /// Create a session and show the portal dialog, blocks until the user answers it.
+    pub fn start(options: &ScreenCastOptions) -> XCapResult<ScreenCastSession> {
+        let conn = Connection::new_session()?;
+
+        let token = handle_token();
+        let mut create_options: PropMap = HashMap::new();
+        create_options.insert(
+            String::from("handle_token"),
+            Variant(Box::new(token.clone())),
+        );
+        create_options.insert(
+            String::from("session_handle_token"),
+            Variant(Box::new(handle_token())),
+        );
+        let results = portal_request(
+            &conn,
+            "CreateSession",
+            (create_options,),
+            &token,
+            Duration::from_secs(10),
+        )?;
+
+        let session = results
+            .get("session_handle")
+            .and_then(|session| session.as_str())
+            .ok_or_else(|| XCapError::new("CreateSession returned no session handle"))?;
+        let session = Path::new(session.to_string()).map_err(XCapError::new)?;
+
+        let mut session = ScreenCastSession {
+            conn,
+            session,
+            streams: Vec::new(),
+            restore_token: None,
+        };
+
+        let token = handle_token();
+        let mut select_options: PropMap = HashMap::new();
+        select_options.insert(
+            String::from("handle_token"),
+            Variant(Box::new(token.clone())),
+        );
+        select_options.insert(
+            String::from("types"),
+            Variant(Box::new(
+                options.source_types.iter().map(|t| t.bits()).sum::<u32>(),
+            )),
+        );
+        select_options.insert(
+            String::from("multiple"),
+            Variant(Box::new(options.multiple)),
+        );
+        if let Some(cursor_mode) = options.cursor_mode {
+            select_options.insert(
+                String::from("cursor_mode"),
+                Variant(Box::new(cursor_mode.bits())),
+            );
+        }
+        // persist_mode 和 restore_token 需要 ScreenCast 接口版本 4，旧版本会忽略它们
+        if options.persist_mode != PersistMode::DoNot {
+            select_options.insert(
+                String::from("persist_mode"),
+                Variant(Box::new(options.persist_mode.bits())),
+            );
+        }
+        if let Some(restore_token) = &options.restore_token {
+            select_options.insert(
+                String::from("restore_token"),
+                Variant(Box::new(restore_token.clone())),
+            );
+        }
+        portal_request(
+            &session.conn,
+            "SelectSources",
+            (session.session.clone(), select_options),
+            &token,
+            Duration::from_secs(10),
+        )?;
+
+        let token = handle_token();
+        let mut start_options: PropMap = HashMap::new();
+        start_options.insert(
+            String::from("handle_token"),
+            Variant(Box::new(token.clone())),
+        );
+        let results = portal_request(
+            &session.conn,
+            "Start",
+            (session.session.clone(), "", start_options),
+            &token,
+            options.timeout,
+        )?;
+
+        session.streams = results
+            .get("streams")
+            .map(|streams| parse_streams(streams))
+            .unwrap_or_default();
+        session.restore_token = results
+            .get("restore_token")
+            .and_then(|restore_token| unwrap_variant(restore_token))
+            .and_then(|restore_token| restore_token.as_str())
+            .map(|restore_token| restore_token.to_string());
+
+        Ok(session)
+    }
+
+    /// The streams the user approved.
+    pub fn streams(&self) -> &[PortalStream] {
+        &self.streams
+    }
+
+    /// The token to pass to [`ScreenCastOptions::with_restore_token`] next time, only
+    /// returned when a [`PersistMode`] was requested and the portal supports it.
+    pub fn restore_token(&self) -> Option<&str> {
+        self.restore_token.as_deref()
+    }
+
+    /// Open a PipeWire remote restricted to the streams of this session.
+    pub fn open_pipewire_remote(&self) -> XCapResult<OwnedFd> {
+        let proxy = self.conn.with_proxy(
+            "org.freedesktop.portal.Desktop",
+            "/org/freedesktop/portal/desktop",
+            Duration::from_secs(10),
+        );
+
+        let (fd,): (DBusOwnedFd,) = proxy.method_call(
+            SCREEN_CAST_INTERFACE,
+            "OpenPipeWireRemote",
+            (self.session.clone(), PropMap::new()),
+        )?;
+
+        Ok(unsafe { OwnedFd::from_raw_fd(fd.into_fd()) })
+    }
+
+    /// Close the session, the PipeWire streams stop.
+    pub fn close(self) -> XCapResult<()> {
+        self.close_session()
+    }
+
+    fn close_session(&self) -> XCapResult<()> {
+        let proxy = self.conn.with_proxy(
+            "org.freedesktop.portal.Desktop",
+            self.session.clone(),
+            Duration::from_secs(10),
+        );
+
+        proxy.method_call::<(), (), &str, &str>("org.freedesktop.portal.Session", "Close", ())?;
+
+        Ok(())
+    }
+}
+
+impl Drop for ScreenCastSession {
+    fn drop(&mut self) {
+        // 会话已经关闭时再次关闭会失败，忽略即可
+        let _ = self.close_session();
+    }
+}
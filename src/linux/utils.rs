@@ -1,4 +1,5 @@
 use image::{open, RgbaImage};
+use std::{ffi::c_void, io::ErrorKind, os::fd::RawFd};
 
 use crate::error::XCapResult;
 
@@ -46,3 +47,19 @@ pub(super) fn png_to_rgba_image(
     dynamic_image = dynamic_image.crop(x as u32, y as u32, width as u32, height as u32);
     Ok(dynamic_image.to_rgba8())
 }
+
+pub(super) fn ioctl<T>(fd: RawFd, request: u64, arg: &mut T) -> XCapResult<()> {
+    loop {
+        let result = unsafe { libc::ioctl(fd, request as _, arg as *mut T as *mut c_void) };
+
+        if result == 0 {
+            return Ok(());
+        }
+
+        let err = std::io::Error::last_os_error();
+        // 被信号中断时内核要求重试
+        if !matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock) {
+            return Err(err.into());
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use dbus::{
-    arg::{AppendAll, Iter, IterAppend, PropMap, ReadAll, RefArg, TypeMismatchError, Variant},
+    arg::{
+        AppendAll, Iter, IterAppend, OwnedFd, PropMap, ReadAll, RefArg, TypeMismatchError, Variant,
+    },
     blocking::Connection,
     message::{MatchRule, SignalArgs},
 };
@@ -8,19 +10,24 @@ use percent_encoding::percent_decode;
 use std::{
     collections::HashMap,
     env::temp_dir,
-    fs::{self},
+    fs::{self, File},
+    io::Read,
+    os::fd::FromRawFd,
     sync::{Arc, Mutex},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use crate::error::{XCapError, XCapResult};
+use crate::{
+    error::{Remediation, XCapError, XCapResult},
+    report::{capture_path, fallback},
+};
 
 use super::{impl_monitor::ImplMonitor, utils::png_to_rgba_image};
 
 #[derive(Debug)]
-struct OrgFreedesktopPortalRequestResponse {
-    status: u32,
-    results: PropMap,
+pub(super) struct OrgFreedesktopPortalRequestResponse {
+    pub status: u32,
+    pub results: PropMap,
 }
 
 impl AppendAll for OrgFreedesktopPortalRequestResponse {
@@ -81,6 +88,98 @@ fn org_gnome_shell_screenshot(
     Ok(rgba_image)
 }
 
+/// KWin's ScreenShot2 interface, writes the raw pixels to a pipe.
+///
+/// KWin only allows it to applications whose desktop file declares
+/// `X-KDE-DBUS-Restricted-Interfaces=org.kde.KWin.ScreenShot2`.
+fn org_kde_kwin_screenshot2(
+    conn: &Connection,
+    impl_monitor: &ImplMonitor,
+) -> XCapResult<RgbaImage> {
+    let proxy = conn.with_proxy(
+        "org.kde.KWin.ScreenShot2",
+        "/org/kde/KWin/ScreenShot2",
+        Duration::from_secs(10),
+    );
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(XCapError::new(format!(
+            "Create pipe failed: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { OwnedFd::from_raw_fd(fds[1]) };
+
+    let mut options: PropMap = HashMap::new();
+    options.insert(String::from("native-resolution"), Variant(Box::new(true)));
+    options.insert(String::from("include-cursor"), Variant(Box::new(false)));
+
+    // 坐标是逻辑坐标，native-resolution 返回物理像素
+    let (results,): (PropMap,) = proxy.method_call(
+        "org.kde.KWin.ScreenShot2",
+        "CaptureArea",
+        (
+            impl_monitor.x,
+            impl_monitor.y,
+            impl_monitor.width,
+            impl_monitor.height,
+            options,
+            writer,
+        ),
+    )?;
+
+    let get_u32 = |key: &str| {
+        results
+            .get(key)
+            .and_then(|value| value.as_u64())
+            .map(|value| value as u32)
+            .ok_or(XCapError::new(format!(
+                "KWin screenshot result has no {}",
+                key
+            )))
+    };
+    let width = get_u32("width")?;
+    let height = get_u32("height")?;
+    let stride = get_u32("stride")? as usize;
+    let format = get_u32("format")?;
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if data.len() < stride * height as usize {
+        return Err(XCapError::new("KWin screenshot data is truncated"));
+    }
+
+    let mut buffer = Vec::with_capacity((width * height * 4) as usize);
+    for row in data.chunks_exact(stride).take(height as usize) {
+        let pixels = row[..width as usize * 4].chunks_exact(4);
+        match format {
+            // QImage::Format_RGB32, Format_ARGB32 和 Format_ARGB32_Premultiplied 在小端序内存中是 BGRA
+            4..=6 => {
+                for bgra in pixels {
+                    buffer.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+                }
+            }
+            // QImage::Format_RGBX8888, Format_RGBA8888 和 Format_RGBA8888_Premultiplied
+            16..=18 => {
+                for rgba in pixels {
+                    buffer.extend_from_slice(&[rgba[0], rgba[1], rgba[2], 255]);
+                }
+            }
+            format => {
+                return Err(XCapError::new(format!(
+                    "Unsupported KWin screenshot format {}",
+                    format
+                )))
+            }
+        }
+    }
+
+    RgbaImage::from_raw(width, height, buffer)
+        .ok_or_else(|| XCapError::new("RgbaImage::from_raw failed"))
+}
+
 fn org_freedesktop_portal_screenshot(
     conn: &Connection,
     x: i32,
@@ -156,6 +255,13 @@ fn org_freedesktop_portal_screenshot(
         if !path.is_empty() {
             fs::remove_file(path)?;
         }
+        // 1 表示用户取消了对话框
+        if status == Some(1) {
+            return Err(XCapError::permission_denied(
+                "the screenshot was cancelled",
+                Remediation::SharingDialog,
+            ));
+        }
         return Err(XCapError::new("Screenshot failed or canceled"));
     }
 
@@ -178,8 +284,25 @@ pub fn wayland_capture(impl_monitor: &ImplMonitor) -> XCapResult<RgbaImage> {
     let lock = DBUS_LOCK.lock();
 
     let conn = Connection::new_session()?;
-    let res = org_gnome_shell_screenshot(&conn, x, y, width, height)
-        .or_else(|_| org_freedesktop_portal_screenshot(&conn, x, y, width, height));
+    // 依次尝试 GNOME Shell、KWin 和 portal，部分 kiosk 部署禁用了 portal
+    let res = capture_path(
+        "gnome-shell-screenshot",
+        org_gnome_shell_screenshot(&conn, x, y, width, height),
+    )
+    .or_else(|err| {
+        fallback("gnome-shell-screenshot", "kwin-screenshot", &err);
+        capture_path(
+            "kwin-screenshot",
+            org_kde_kwin_screenshot2(&conn, impl_monitor),
+        )
+    })
+    .or_else(|err| {
+        fallback("kwin-screenshot", "portal-screenshot", &err);
+        capture_path(
+            "portal-screenshot",
+            org_freedesktop_portal_screenshot(&conn, x, y, width, height),
+        )
+    });
 
     drop(lock);
 
@@ -190,7 +313,7 @@ fn screnshot_multithreaded() {
     fn make_screenshots() {
         let monitors = crate::monitor::Monitor::all().unwrap();
         for monitor in monitors {
-            monitor.capture_image().unwrap();
+            monitor.capture_rgba().unwrap();
         }
     }
     // Try making screenshots in paralel. If this times out, then this means that there is a threading issue.
@@ -207,3 +330,20 @@ fn screnshot_multithreaded() {
         .into_iter()
         .for_each(|handle| handle.join().unwrap());
 }
+
+/// Whether xdg-desktop-portal is running on the session bus.
+pub(super) fn portal_available() -> XCapResult<bool> {
+    let conn = Connection::new_session()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(1000),
+    );
+    let (has_owner,): (bool,) = proxy.method_call(
+        "org.freedesktop.DBus",
+        "NameHasOwner",
+        ("org.freedesktop.portal.Desktop",),
+    )?;
+
+    Ok(has_owner)
+}
@@ -0,0 +1,104 @@
+use image::RgbaImage;
+use std::{
+    ffi::c_void,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    ptr, slice,
+};
+use wayland_client::protocol::wl_shm::Format;
+
+use crate::error::{XCapError, XCapResult};
+
+/// The `wl_shm` formats the capture backends can convert.
+pub(super) fn is_supported_format(format: Format) -> bool {
+    matches!(
+        format,
+        Format::Argb8888 | Format::Xrgb8888 | Format::Abgr8888 | Format::Xbgr8888
+    )
+}
+
+/// A memfd mapped into memory, shared with the compositor through `wl_shm`.
+pub(super) struct ShmBuffer {
+    fd: OwnedFd,
+    addr: *mut c_void,
+    size: usize,
+}
+
+impl ShmBuffer {
+    pub fn new(size: usize) -> XCapResult<ShmBuffer> {
+        let fd = unsafe {
+            let fd = libc::memfd_create(c"xcap-wayland-shm".as_ptr(), libc::MFD_CLOEXEC);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+        std::fs::File::from(fd.try_clone()?).set_len(size as u64)?;
+
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(ShmBuffer { fd, addr, size })
+    }
+
+    pub fn fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.addr as *const u8, self.size) }
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.addr, self.size);
+        }
+    }
+}
+
+/// Convert a `wl_shm` buffer to RGBA, `format` must pass [`is_supported_format`].
+pub(super) fn to_rgba_image(
+    data: &[u8],
+    format: Format,
+    width: u32,
+    height: u32,
+    stride: u32,
+    y_invert: bool,
+) -> XCapResult<RgbaImage> {
+    let mut buffer = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        let row = if y_invert { height - 1 - y } else { y };
+        let start = (row * stride) as usize;
+        let pixels = data[start..start + (width * 4) as usize].chunks_exact(4);
+
+        // wl_shm 的格式是小端序，Argb8888 在内存中是 BGRA
+        match format {
+            Format::Argb8888 | Format::Xrgb8888 => {
+                for bgra in pixels {
+                    buffer.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+                }
+            }
+            _ => {
+                for rgba in pixels {
+                    buffer.extend_from_slice(&[rgba[0], rgba[1], rgba[2], 255]);
+                }
+            }
+        }
+    }
+
+    RgbaImage::from_raw(width, height, buffer)
+        .ok_or_else(|| XCapError::new("RgbaImage::from_raw failed"))
+}
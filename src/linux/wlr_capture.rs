@@ -0,0 +1,334 @@
+use image::RgbaImage;
+use scopeguard::guard;
+use wayland_client::{
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_output::{self, Transform, WlOutput},
+        wl_registry::{self, WlRegistry},
+        wl_shm::{Format, WlShm},
+        wl_shm_pool::WlShmPool,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols_wlr::screencopy::v1::client::{
+    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+};
+
+use crate::{
+    error::{XCapError, XCapResult},
+    utils::rotate_to_screen,
+};
+
+use super::{
+    impl_monitor::ImplMonitor,
+    wayland_shm::{is_supported_format, to_rgba_image, ShmBuffer},
+};
+
+// https://wayland.app/protocols/wlr-screencopy-unstable-v1
+
+/// A `wl_output` advertised by the compositor.
+#[derive(Debug, Clone, Default)]
+pub(super) struct WlrOutput {
+    output: Option<WlOutput>,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    /// The current mode, in physical pixels.
+    pub pixel_width: i32,
+    pub pixel_height: i32,
+    pub frequency: f32,
+    pub scale: i32,
+    pub rotation: f32,
+}
+
+impl WlrOutput {
+    /// The size in the compositor space, taking the transform and scale into account.
+    pub fn logical_size(&self) -> (u32, u32) {
+        let scale = self.scale.max(1);
+        let (width, height) = if self.rotation == 90.0 || self.rotation == 270.0 {
+            (self.pixel_height, self.pixel_width)
+        } else {
+            (self.pixel_width, self.pixel_height)
+        };
+
+        ((width / scale) as u32, (height / scale) as u32)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FrameState {
+    formats: Vec<(Format, u32, u32, u32)>,
+    buffer_done: bool,
+    y_invert: bool,
+    ready: bool,
+    failed: bool,
+}
+
+#[derive(Debug, Default)]
+struct WlrState {
+    outputs: Vec<WlrOutput>,
+    shm: Option<WlShm>,
+    manager: Option<ZwlrScreencopyManagerV1>,
+    frame: FrameState,
+}
+
+impl Dispatch<WlRegistry, ()> for WlrState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_output" => {
+                    let output = registry.bind(name, version.min(4), qh, state.outputs.len());
+                    state.outputs.push(WlrOutput {
+                        output: Some(output),
+                        name: format!("wl_output-{}", name),
+                        scale: 1,
+                        ..Default::default()
+                    });
+                }
+                "wl_shm" => state.shm = Some(registry.bind(name, 1, qh, ())),
+                "zwlr_screencopy_manager_v1" => {
+                    state.manager = Some(registry.bind(name, version.min(3), qh, ()))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<WlOutput, usize> for WlrState {
+    fn event(
+        state: &mut Self,
+        _: &WlOutput,
+        event: wl_output::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let output = match state.outputs.get_mut(*index) {
+            Some(output) => output,
+            None => return,
+        };
+
+        match event {
+            wl_output::Event::Geometry {
+                x, y, transform, ..
+            } => {
+                output.x = x;
+                output.y = y;
+                output.rotation = match transform {
+                    WEnum::Value(Transform::_90 | Transform::Flipped90) => 90.0,
+                    WEnum::Value(Transform::_180 | Transform::Flipped180) => 180.0,
+                    WEnum::Value(Transform::_270 | Transform::Flipped270) => 270.0,
+                    _ => 0.0,
+                };
+            }
+            wl_output::Event::Mode {
+                flags,
+                width,
+                height,
+                refresh,
+            } => {
+                let is_current = match flags {
+                    WEnum::Value(flags) => flags.contains(wl_output::Mode::Current),
+                    _ => false,
+                };
+
+                if is_current {
+                    output.pixel_width = width;
+                    output.pixel_height = height;
+                    // refresh 的单位是 mHz
+                    output.frequency = refresh as f32 / 1000.0;
+                }
+            }
+            wl_output::Event::Scale { factor } => output.scale = factor,
+            wl_output::Event::Name { name } => output.name = name,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for WlrState {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => state.frame.formats.push((format, width, height, stride)),
+            zwlr_screencopy_frame_v1::Event::BufferDone => state.frame.buffer_done = true,
+            zwlr_screencopy_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => state.frame.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert),
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => state.frame.ready = true,
+            zwlr_screencopy_frame_v1::Event::Failed => state.frame.failed = true,
+            _ => {}
+        }
+    }
+}
+
+macro_rules! ignore_events {
+    ($($proxy:ty),*) => {
+        $(
+            impl Dispatch<$proxy, ()> for WlrState {
+                fn event(
+                    _: &mut Self,
+                    _: &$proxy,
+                    _: <$proxy as Proxy>::Event,
+                    _: &(),
+                    _: &Connection,
+                    _: &QueueHandle<Self>,
+                ) {
+                }
+            }
+        )*
+    };
+}
+
+ignore_events!(WlShm, WlShmPool, WlBuffer, ZwlrScreencopyManagerV1);
+
+fn connect() -> XCapResult<(EventQueue<WlrState>, WlrState)> {
+    let conn = Connection::connect_to_env().map_err(XCapError::new)?;
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+
+    conn.display().get_registry(&qh, ());
+
+    // 第一次往返拿到全局对象，第二次往返拿到 wl_output 的属性
+    let mut state = WlrState::default();
+    event_queue.roundtrip(&mut state).map_err(XCapError::new)?;
+    event_queue.roundtrip(&mut state).map_err(XCapError::new)?;
+
+    if state.manager.is_none() {
+        return Err(XCapError::new(
+            "The compositor does not support zwlr_screencopy_manager_v1",
+        ));
+    }
+
+    Ok((event_queue, state))
+}
+
+/// List the outputs of a wlroots based compositor.
+pub(super) fn wlr_outputs() -> XCapResult<Vec<WlrOutput>> {
+    let (_, state) = connect()?;
+
+    Ok(state.outputs)
+}
+
+fn find_output<'a>(outputs: &'a [WlrOutput], impl_monitor: &ImplMonitor) -> Option<&'a WlrOutput> {
+    // XWayland 枚举的显示器名称与 compositor 不同，名称匹配失败时按位置匹配
+    outputs
+        .iter()
+        .find(|output| output.name == impl_monitor.name)
+        .or_else(|| {
+            outputs
+                .iter()
+                .find(|output| output.x == impl_monitor.x && output.y == impl_monitor.y)
+        })
+        .or(if outputs.len() == 1 {
+            outputs.first()
+        } else {
+            None
+        })
+}
+
+/// Capture the output matching `impl_monitor` with `zwlr_screencopy_manager_v1`.
+pub(super) fn wlr_capture(impl_monitor: &ImplMonitor) -> XCapResult<RgbaImage> {
+    let (mut event_queue, mut state) = connect()?;
+    let qh = event_queue.handle();
+
+    let (output, rotation) = find_output(&state.outputs, impl_monitor)
+        .and_then(|output| Some((output.output.clone()?, output.rotation)))
+        .ok_or_else(|| XCapError::new(format!("Output {} not found", impl_monitor.name)))?;
+    let manager = state
+        .manager
+        .clone()
+        .ok_or_else(|| XCapError::new("zwlr_screencopy_manager_v1 not found"))?;
+    let shm = state
+        .shm
+        .clone()
+        .ok_or_else(|| XCapError::new("wl_shm not found"))?;
+
+    let frame = guard(manager.capture_output(0, &output, &qh, ()), |frame| {
+        frame.destroy()
+    });
+
+    // 版本 3 以后 compositor 会在列出所有可用格式后发送 buffer_done
+    let needs_buffer_done = manager.version() >= 3;
+    while !state.frame.failed
+        && (state.frame.formats.is_empty() || (needs_buffer_done && !state.frame.buffer_done))
+    {
+        event_queue
+            .blocking_dispatch(&mut state)
+            .map_err(XCapError::new)?;
+    }
+
+    let (format, width, height, stride) = state
+        .frame
+        .formats
+        .iter()
+        .find(|(format, ..)| is_supported_format(*format))
+        .copied()
+        .ok_or_else(|| XCapError::new("No supported wl_shm format offered"))?;
+
+    let shm_buffer = ShmBuffer::new((stride * height) as usize)?;
+    let pool = guard(
+        shm.create_pool(shm_buffer.fd(), (stride * height) as i32, &qh, ()),
+        |pool| pool.destroy(),
+    );
+    let buffer = guard(
+        pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            format,
+            &qh,
+            (),
+        ),
+        |buffer| buffer.destroy(),
+    );
+
+    frame.copy(&buffer);
+    while !state.frame.ready && !state.frame.failed {
+        event_queue
+            .blocking_dispatch(&mut state)
+            .map_err(XCapError::new)?;
+    }
+
+    if state.frame.failed {
+        return Err(XCapError::new("Screencopy frame failed"));
+    }
+
+    // screencopy 的缓冲区是输出的原始方向，转成用户看到的方向
+    let image = to_rgba_image(
+        shm_buffer.data(),
+        format,
+        width,
+        height,
+        stride,
+        state.frame.y_invert,
+    )?;
+
+    Ok(rotate_to_screen(image, rotation))
+}
@@ -0,0 +1,243 @@
+use std::{
+    borrow::Cow,
+    ffi::c_void,
+    fmt,
+    ops::Deref,
+    sync::{Arc, Mutex, OnceLock, Weak},
+};
+
+use xcb::{
+    x::{Atom, GetAtomName, InternAtom, ATOM_NONE},
+    Connection, Extension,
+};
+
+use crate::error::{XCapError, XCapResult};
+
+/// 用到的扩展，没有时对应的功能才会失败
+const EXTENSIONS: [Extension; 4] = [
+    Extension::Input,
+    Extension::RandR,
+    Extension::Shape,
+    Extension::Test,
+];
+
+/// The atoms xcap reads, the EWMH ones while enumerating windows.
+pub(super) const ATOM_NAMES: [&str; 34] = [
+    "_NET_ACTIVE_WINDOW",
+    "_NET_CLIENT_LIST",
+    "_NET_CLIENT_LIST_STACKING",
+    "_NET_CURRENT_DESKTOP",
+    "_NET_WM_DESKTOP",
+    "_NET_WM_PID",
+    "_NET_WM_STATE",
+    "_NET_WM_STATE_FULLSCREEN",
+    "_NET_WM_STATE_HIDDEN",
+    "_NET_WM_STATE_MAXIMIZED_HORZ",
+    "_NET_WM_STATE_MAXIMIZED_VERT",
+    "_NET_WM_STATE_SKIP_PAGER",
+    "_NET_WM_STATE_SKIP_TASKBAR",
+    "_NET_WM_WINDOW_OPACITY",
+    "_NET_WM_WINDOW_TYPE",
+    "_NET_WM_WINDOW_TYPE_COMBO",
+    "_NET_WM_WINDOW_TYPE_DESKTOP",
+    "_NET_WM_WINDOW_TYPE_DIALOG",
+    "_NET_WM_WINDOW_TYPE_DOCK",
+    "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+    "_NET_WM_WINDOW_TYPE_MENU",
+    "_NET_WM_WINDOW_TYPE_NORMAL",
+    "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+    "_NET_WM_WINDOW_TYPE_POPUP_MENU",
+    "_NET_WM_WINDOW_TYPE_SPLASH",
+    "_NET_WM_WINDOW_TYPE_TOOLBAR",
+    "_NET_WM_WINDOW_TYPE_TOOLTIP",
+    "_NET_WM_WINDOW_TYPE_UTILITY",
+    "_NET_WORKAREA",
+    "_XROOTPMAP_ID",
+    "ESETROOT_PMAP_ID",
+    "Backlight",
+    "BACKLIGHT",
+    "EDID",
+];
+
+/// The atoms of [`ATOM_NAMES`], `ATOM_NONE` for the ones no client interned yet.
+#[derive(Debug)]
+pub(crate) struct Atoms {
+    atoms: Mutex<[Atom; ATOM_NAMES.len()]>,
+}
+
+impl Atoms {
+    fn intern(conn: &Connection) -> XCapResult<Atoms> {
+        // 先发出所有请求再等待回复，只需要一次往返
+        let cookies = ATOM_NAMES.map(|name| {
+            conn.send_request(&InternAtom {
+                only_if_exists: true,
+                name: name.as_bytes(),
+            })
+        });
+
+        let mut atoms = [ATOM_NONE; ATOM_NAMES.len()];
+        for (atom, cookie) in atoms.iter_mut().zip(cookies) {
+            *atom = conn.wait_for_reply(cookie)?.atom();
+        }
+
+        Ok(Atoms {
+            atoms: Mutex::new(atoms),
+        })
+    }
+
+    /// The atom of `name`, `ATOM_NONE` if no client interned it yet. Names that are not cached
+    /// are interned on every call.
+    pub fn get(&self, conn: &Connection, name: &str) -> XCapResult<Atom> {
+        let index = ATOM_NAMES.iter().position(|atom_name| *atom_name == name);
+        if let Some(index) = index {
+            let atom = self.atoms.lock()?[index];
+            if atom != ATOM_NONE {
+                return Ok(atom);
+            }
+        }
+
+        // 没有缓存的，或者之前还不存在的，比如连接之后才启动的窗口管理器创建的
+        let intern_atom_cookie = conn.send_request(&InternAtom {
+            only_if_exists: true,
+            name: name.as_bytes(),
+        });
+        let atom = conn.wait_for_reply(intern_atom_cookie)?.atom();
+
+        if let Some(index) = index.filter(|_| atom != ATOM_NONE) {
+            self.atoms.lock()?[index] = atom;
+        }
+
+        Ok(atom)
+    }
+
+    /// The name of `atom`, asking the X server for the ones that are not cached.
+    pub fn name(&self, conn: &Connection, atom: Atom) -> XCapResult<Option<Cow<'static, str>>> {
+        if atom == ATOM_NONE {
+            return Ok(None);
+        }
+
+        let index = self.atoms.lock()?.iter().position(|cached| *cached == atom);
+        if let Some(index) = index {
+            return Ok(Some(Cow::Borrowed(ATOM_NAMES[index])));
+        }
+
+        let get_atom_name_cookie = conn.send_request(&GetAtomName { atom });
+        let get_atom_name_reply = conn.wait_for_reply(get_atom_name_cookie)?;
+
+        Ok(Some(Cow::Owned(
+            get_atom_name_reply.name().to_utf8().into_owned(),
+        )))
+    }
+}
+
+/// A connection to the X server, shared by the monitors and windows enumerated while it is
+/// alive instead of connecting for every request. xcb connections are thread safe.
+pub(crate) struct XConnection {
+    conn: Connection,
+    /// The screen of `DISPLAY`.
+    pub screen_num: i32,
+    atoms: OnceLock<Atoms>,
+}
+
+impl XConnection {
+    /// Open a connection that is not shared, e.g. to select events on it.
+    pub fn connect() -> XCapResult<XConnection> {
+        let (conn, screen_num) = Connection::connect_with_extensions(None, &[], &EXTENSIONS)?;
+
+        Ok(XConnection {
+            conn,
+            screen_num,
+            atoms: OnceLock::new(),
+        })
+    }
+
+    /// The atoms, interned on first use and kept for the lifetime of the connection.
+    pub fn atoms(&self) -> XCapResult<&Atoms> {
+        if let Some(atoms) = self.atoms.get() {
+            return Ok(atoms);
+        }
+
+        let atoms = Atoms::intern(&self.conn)?;
+
+        Ok(self.atoms.get_or_init(|| atoms))
+    }
+}
+
+impl fmt::Debug for XConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XConnection")
+            .field("screen_num", &self.screen_num)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deref for XConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+/// The connection of the application, see [`use_xcb_connection`].
+static APPLICATION: Mutex<Option<Arc<XConnection>>> = Mutex::new(None);
+
+/// Use a connection owned by the application instead of opening one, e.g. the one of its GUI
+/// toolkit, for X servers that limit the number of clients. `raw_conn` is an
+/// `xcb_connection_t`, from `xcb::Connection::get_raw_conn` or
+/// `x11rb::xcb_ffi::XCBConnection::get_raw_xcb_connection`.
+///
+/// xcap never disconnects it. Monitors and windows enumerated while it is registered keep
+/// using it, drop them before disconnecting. A running [`crate::Watcher`] still opens its own
+/// connection, it would otherwise read the events of the application.
+///
+/// # Safety
+///
+/// `raw_conn` must be a connection without error that stays open until
+/// [`release_xcb_connection`] is called and the monitors and windows are dropped.
+pub unsafe fn use_xcb_connection(raw_conn: *mut c_void, screen_num: i32) -> XCapResult<()> {
+    if raw_conn.is_null() {
+        return Err(XCapError::new("xcb connection is null"));
+    }
+
+    let conn = Connection::from_raw_conn_and_extensions_no_drop(
+        raw_conn as *mut xcb::ffi::xcb_connection_t,
+        &[],
+        &EXTENSIONS,
+    );
+    *APPLICATION.lock()? = Some(Arc::new(XConnection {
+        conn,
+        screen_num,
+        atoms: OnceLock::new(),
+    }));
+
+    Ok(())
+}
+
+/// Stop using the connection registered with [`use_xcb_connection`], xcap connects on its own
+/// again.
+pub fn release_xcb_connection() -> XCapResult<()> {
+    APPLICATION.lock()?.take();
+
+    Ok(())
+}
+
+/// The connection of the application, or the shared one, connecting again once every holder
+/// dropped it or it broke, e.g. because the X server restarted.
+pub(crate) fn x_connection() -> XCapResult<Arc<XConnection>> {
+    static SHARED: Mutex<Weak<XConnection>> = Mutex::new(Weak::new());
+
+    if let Some(conn) = APPLICATION.lock()?.as_ref() {
+        return Ok(conn.clone());
+    }
+
+    let mut shared = SHARED.lock()?;
+    if let Some(conn) = shared.upgrade().filter(|conn| conn.has_error().is_ok()) {
+        return Ok(conn);
+    }
+
+    let conn = Arc::new(XConnection::connect()?);
+    *shared = Arc::downgrade(&conn);
+
+    Ok(conn)
+}
@@ -1,10 +1,18 @@
 use image::RgbaImage;
 use xcb::{
-    x::{Drawable, GetImage, ImageFormat, ImageOrder, Window},
-    Connection,
+    x::{
+        Drawable, GetGeometry, GetImage, GetProperty, ImageFormat, ImageOrder, Pixmap, Window,
+        ATOM_PIXMAP,
+    },
+    Connection, Xid, XidNew,
 };
 
-use crate::error::{XCapError, XCapResult};
+use crate::{
+    error::{XCapError, XCapResult},
+    report::pixel_format,
+};
+
+use super::x_connection::{x_connection, XConnection};
 
 fn get_pixel8_rgba(
     bytes: &[u8],
@@ -70,19 +78,96 @@ fn get_pixel24_32_rgba(
 }
 
 pub fn xorg_capture(
+    conn: &Connection,
     window: Window,
     x: i32,
     y: i32,
     width: u32,
     height: u32,
 ) -> XCapResult<RgbaImage> {
-    let (conn, _) = Connection::connect(None)?;
+    get_image(conn, Drawable::Window(window), x, y, width, height)
+}
+
+/// Capture a window or pixmap by its X11 id, including the ones xcap does not list such as
+/// override-redirect popups, tray icons or pixmaps shared by other clients.
+///
+/// Windows must be mapped and inside the screen, the X server refuses to read them otherwise.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub fn capture_drawable(xid: u32) -> XCapResult<RgbaImage> {
+    let conn = x_connection()?;
+
+    // GetGeometry 和 GetImage 对窗口和 pixmap 都适用，只是 id 的类型不同
+    let drawable = Drawable::Pixmap(Pixmap::new(xid));
+    let get_geometry_cookie = conn.send_request(&GetGeometry { drawable });
+    let get_geometry_reply = conn.wait_for_reply(get_geometry_cookie)?;
+
+    get_image(
+        &conn,
+        drawable,
+        0,
+        0,
+        get_geometry_reply.width() as u32,
+        get_geometry_reply.height() as u32,
+    )
+}
+
+/// Capture the wallpaper pixmap that desktop setters publish on the root window, without the
+/// windows drawn over it.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub fn xorg_capture_wallpaper(
+    conn: &XConnection,
+    root: Window,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+) -> XCapResult<RgbaImage> {
+    // feh、nitrogen 等设置 _XROOTPMAP_ID，Esetroot 还会设置 ESETROOT_PMAP_ID
+    for name in ["_XROOTPMAP_ID", "ESETROOT_PMAP_ID"] {
+        let atom = conn.atoms()?.get(conn, name)?;
+        if atom.is_none() {
+            continue;
+        }
+
+        let get_property_cookie = conn.send_request(&GetProperty {
+            delete: false,
+            window: root,
+            property: atom,
+            r#type: ATOM_PIXMAP,
+            long_offset: 0,
+            long_length: 1,
+        });
+        let get_property_reply = conn.wait_for_reply(get_property_cookie)?;
+        if let Some(&pixmap) = get_property_reply.value::<u32>().first() {
+            return get_image(
+                conn,
+                Drawable::Pixmap(Pixmap::new(pixmap)),
+                x,
+                y,
+                width,
+                height,
+            );
+        }
+    }
+
+    Err(XCapError::new(
+        "No wallpaper pixmap is set on the root window",
+    ))
+}
 
+fn get_image(
+    conn: &Connection,
+    drawable: Drawable,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+) -> XCapResult<RgbaImage> {
     let setup = conn.get_setup();
 
     let get_image_cookie = conn.send_request(&GetImage {
         format: ImageFormat::ZPixmap,
-        drawable: Drawable::Window(window),
+        drawable,
         x: x as i16,
         y: y as i16,
         width: width as u16,
@@ -102,6 +187,13 @@ pub fn xorg_capture(
 
     let bits_per_pixel = pixmap_format.bits_per_pixel() as u32;
     let bit_order = setup.bitmap_format_bit_order();
+    pixel_format(
+        "x11-get-image",
+        format!(
+            "ZPixmap depth {}, {} bits per pixel, {:?}",
+            depth, bits_per_pixel, bit_order
+        ),
+    );
 
     let get_pixel_rgba = match depth {
         8 => get_pixel8_rgba,
@@ -1,32 +1,117 @@
+use std::{ffi::c_void, ptr};
+
 use image::RgbaImage;
-use objc2_core_foundation::CGRect;
+use objc2_core_foundation::{CFArray, CGRect};
 use objc2_core_graphics::{
-    CGDataProviderCopyData, CGImageGetBytesPerRow, CGImageGetDataProvider, CGImageGetHeight,
-    CGImageGetWidth, CGWindowID, CGWindowImageOption, CGWindowListCreateImage, CGWindowListOption,
+    CGDataProviderCopyData, CGImage, CGImageGetBytesPerRow, CGImageGetDataProvider,
+    CGImageGetHeight, CGImageGetWidth, CGPreflightScreenCaptureAccess, CGWindowID,
+    CGWindowImageOption, CGWindowListCreate, CGWindowListCreateImage,
+    CGWindowListCreateImageFromArray, CGWindowListOption,
 };
 
-use crate::error::{XCapError, XCapResult};
+use crate::{
+    error::{Remediation, XCapError, XCapResult},
+    report::{capture_path, pixel_format},
+};
 
 pub fn capture(
     cg_rect: CGRect,
     list_option: CGWindowListOption,
     window_id: CGWindowID,
+    image_option: CGWindowImageOption,
+) -> XCapResult<RgbaImage> {
+    let cg_image =
+        unsafe { CGWindowListCreateImage(cg_rect, list_option, window_id, image_option) };
+
+    capture_path(
+        "cg-window-list-create-image",
+        cg_image_to_rgba_image(cg_image.as_deref()),
+    )
+}
+
+/// Capture `cg_rect` with all on screen windows except `excluded_window_ids`.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub fn capture_excluding(
+    cg_rect: CGRect,
+    excluded_window_ids: &[CGWindowID],
+    image_option: CGWindowImageOption,
 ) -> XCapResult<RgbaImage> {
     unsafe {
-        let cg_image = CGWindowListCreateImage(
-            cg_rect,
-            list_option,
-            window_id,
-            CGWindowImageOption::Default,
-        );
+        let window_list = CGWindowListCreate(CGWindowListOption::OptionOnScreenOnly, 0)
+            .ok_or_else(|| XCapError::new("CGWindowListCreate failed"))?;
+
+        // 数组元素直接是 CGWindowID，而不是 CFNumber
+        let window_ids: Vec<CGWindowID> = (0..window_list.count())
+            .map(|index| window_list.value_at_index(index) as usize as CGWindowID)
+            .filter(|window_id| !excluded_window_ids.contains(window_id))
+            .collect();
+
+        capture_windows(cg_rect, &window_ids, image_option)
+    }
+}
+
+/// Capture `cg_rect` with only the windows `window_ids`, front to back.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub fn capture_windows(
+    cg_rect: CGRect,
+    window_ids: &[CGWindowID],
+    image_option: CGWindowImageOption,
+) -> XCapResult<RgbaImage> {
+    unsafe {
+        let mut window_ids: Vec<*const c_void> = window_ids
+            .iter()
+            .map(|window_id| *window_id as usize as *const c_void)
+            .collect();
+
+        let window_array = CFArray::new(
+            None,
+            window_ids.as_mut_ptr(),
+            window_ids.len() as isize,
+            ptr::null(),
+        )
+        .ok_or_else(|| XCapError::new("CFArrayCreate failed"))?;
+
+        let cg_image = CGWindowListCreateImageFromArray(cg_rect, &window_array, image_option);
+
+        capture_path(
+            "cg-window-list-create-image-from-array",
+            cg_image_to_rgba_image(cg_image.as_deref()),
+        )
+    }
+}
 
-        let width = CGImageGetWidth(cg_image.as_deref());
-        let height = CGImageGetHeight(cg_image.as_deref());
-        let data_provider = CGImageGetDataProvider(cg_image.as_deref());
+pub(super) fn screen_recording_remediation() -> Remediation {
+    Remediation::PrivacyPane {
+        name: "Screen Recording",
+        url: "x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture",
+    }
+}
+
+fn cg_image_to_rgba_image(cg_image: Option<&CGImage>) -> XCapResult<RgbaImage> {
+    // 没有屏幕录制权限时拿不到图片
+    if cg_image.is_none() && !CGPreflightScreenCaptureAccess() {
+        return Err(XCapError::permission_denied(
+            "the app is not allowed to record the screen",
+            screen_recording_remediation(),
+        ));
+    }
+
+    unsafe {
+        let width = CGImageGetWidth(cg_image);
+        let height = CGImageGetHeight(cg_image);
+        let data_provider = CGImageGetDataProvider(cg_image);
         let data = CGDataProviderCopyData(data_provider.as_deref())
             .ok_or_else(|| XCapError::new("Failed to copy data"))?
             .to_vec();
-        let bytes_per_row = CGImageGetBytesPerRow(cg_image.as_deref());
+        let bytes_per_row = CGImageGetBytesPerRow(cg_image);
+        pixel_format(
+            "cg-window-list",
+            format!(
+                "{} bits per pixel, bitmap info {:#x}",
+                CGImage::bits_per_pixel(cg_image),
+                CGImage::bitmap_info(cg_image).0
+            ),
+        );
 
         // Some platforms e.g. MacOS can have extra bytes at the end of each row.
         // See
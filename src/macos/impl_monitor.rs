@@ -1,17 +1,36 @@
+use std::ptr::NonNull;
+
 use image::RgbaImage;
-use objc2::MainThreadMarker;
+use objc2::{rc::Retained, MainThreadMarker};
 use objc2_app_kit::NSScreen;
-use objc2_core_foundation::{CGPoint, CGRect};
+use objc2_core_foundation::{CFRetained, CGPoint, CGRect, CFUUID};
 use objc2_core_graphics::{
-    CGDirectDisplayID, CGDisplayBounds, CGDisplayCopyDisplayMode, CGDisplayIsActive,
-    CGDisplayIsMain, CGDisplayModeGetPixelWidth, CGDisplayModeGetRefreshRate, CGDisplayRotation,
-    CGError, CGGetActiveDisplayList, CGGetDisplaysWithPoint, CGWindowListOption,
+    CGColorSpaceIsWideGamutRGB, CGDirectDisplayID, CGDisplayBounds, CGDisplayCopyAllDisplayModes,
+    CGDisplayCopyColorSpace, CGDisplayCopyDisplayMode, CGDisplayGammaTableCapacity,
+    CGDisplayIsActive, CGDisplayIsMain, CGDisplayIsOnline, CGDisplayMirrorsDisplay, CGDisplayMode,
+    CGDisplayModeGetPixelWidth, CGDisplayModeGetRefreshRate, CGDisplayModelNumber,
+    CGDisplayRotation, CGDisplaySerialNumber, CGDisplayVendorNumber, CGError,
+    CGGetActiveDisplayList, CGGetDisplayTransferByTable, CGGetDisplaysWithPoint,
+    CGPreflightScreenCaptureAccess, CGWindowImageOption, CGWindowListOption,
 };
 use objc2_foundation::{NSNumber, NSString};
 
-use crate::error::{XCapError, XCapResult};
+use crate::{
+    backend::Backend,
+    capabilities::Inhibition,
+    color::{ColorSpace, GammaRamp},
+    custom::CustomMonitor,
+    error::{XCapError, XCapResult},
+    layout::Rect,
+    utils::thumbnail,
+    VideoMode,
+};
 
-use super::{capture::capture, impl_video_recorder::ImplVideoRecorder};
+use super::{
+    capture::{capture, capture_excluding, capture_windows, screen_recording_remediation},
+    impl_video_recorder::ImplVideoRecorder,
+    impl_window::desktop_window_ids,
+};
 
 #[derive(Debug, Clone)]
 pub(crate) struct ImplMonitor {
@@ -26,9 +45,10 @@ pub(crate) struct ImplMonitor {
     pub scale_factor: f32,
     pub frequency: f32,
     pub is_primary: bool,
+    pub color_space: ColorSpace,
 }
 
-fn get_display_friendly_name(display_id: CGDirectDisplayID) -> XCapResult<String> {
+fn get_screen(display_id: CGDirectDisplayID) -> XCapResult<Retained<NSScreen>> {
     let screens = NSScreen::screens(unsafe { MainThreadMarker::new_unchecked() });
     for screen in screens {
         let device_description = screen.deviceDescription();
@@ -42,16 +62,41 @@ fn get_display_friendly_name(display_id: CGDirectDisplayID) -> XCapResult<String
             .unsignedIntValue();
 
         if screen_id == display_id {
-            unsafe { return Ok(screen.localizedName().to_string()) };
+            return Ok(screen);
         }
     }
 
     Err(XCapError::new(format!(
-        "Get display {} friendly name failed",
+        "No NSScreen for display {}",
         display_id
     )))
 }
 
+fn get_display_friendly_name(display_id: CGDirectDisplayID) -> XCapResult<String> {
+    let screen = get_screen(display_id)?;
+
+    unsafe { Ok(screen.localizedName().to_string()) }
+}
+
+#[link(name = "ColorSync", kind = "framework")]
+extern "C" {
+    fn CGDisplayCreateUUIDFromDisplayID(display: CGDirectDisplayID) -> *mut CFUUID;
+}
+
+// IOGraphicsTypes.h
+const K_DISPLAY_MODE_NATIVE_FLAG: u32 = 0x0200_0000;
+
+fn get_color_space(display_id: CGDirectDisplayID) -> ColorSpace {
+    // CGWindowListCreateImage 返回的图像使用显示器的色彩空间
+    let cg_color_space = CGDisplayCopyColorSpace(display_id);
+
+    if CGColorSpaceIsWideGamutRGB(&cg_color_space) {
+        ColorSpace::DisplayP3
+    } else {
+        ColorSpace::Srgb
+    }
+}
+
 impl ImplMonitor {
     pub(super) fn new(id: CGDirectDisplayID) -> XCapResult<ImplMonitor> {
         unsafe {
@@ -77,9 +122,51 @@ impl ImplMonitor {
                 scale_factor,
                 frequency,
                 is_primary,
+                color_space: get_color_space(id),
             })
         }
     }
+    pub fn backend() -> Backend {
+        Backend::CoreGraphics
+    }
+
+    pub fn inhibitions() -> Vec<Inhibition> {
+        if CGPreflightScreenCaptureAccess() {
+            return Vec::new();
+        }
+
+        vec![Inhibition::PermissionDenied(screen_recording_remediation())]
+    }
+
+    /// A monitor of a custom backend, without a display id.
+    pub fn from_custom(custom_monitor: &CustomMonitor) -> ImplMonitor {
+        ImplMonitor {
+            cg_direct_display_id: 0,
+            id: custom_monitor.id,
+            name: custom_monitor.name.clone(),
+            x: custom_monitor.x,
+            y: custom_monitor.y,
+            width: custom_monitor.width,
+            height: custom_monitor.height,
+            rotation: 0.0,
+            scale_factor: custom_monitor.scale_factor,
+            frequency: custom_monitor.frequency,
+            is_primary: custom_monitor.is_primary,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn all_with_backend(backend: Backend) -> XCapResult<Vec<ImplMonitor>> {
+        if backend != Backend::CoreGraphics {
+            return Err(XCapError::new(format!(
+                "{:?} backend is not available",
+                backend
+            )));
+        }
+
+        ImplMonitor::all()
+    }
+
     pub fn all() -> XCapResult<Vec<ImplMonitor>> {
         let max_displays: u32 = 16;
         let mut active_displays: Vec<CGDirectDisplayID> = vec![0; max_displays as usize];
@@ -161,10 +248,188 @@ impl ImplMonitor {
 }
 
 impl ImplMonitor {
+    pub fn is_valid(&self) -> bool {
+        CGDisplayIsOnline(self.cg_direct_display_id)
+    }
+
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
         let cg_rect = unsafe { CGDisplayBounds(self.cg_direct_display_id) };
 
-        capture(cg_rect, CGWindowListOption::OptionAll, 0)
+        capture(
+            cg_rect,
+            CGWindowListOption::OptionAll,
+            0,
+            CGWindowImageOption::Default,
+        )
+    }
+
+    /// WindowServer composites the screen without the excluded windows.
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_excluding(&self, window_ids: &[u32]) -> XCapResult<Option<RgbaImage>> {
+        let cg_rect = unsafe { CGDisplayBounds(self.cg_direct_display_id) };
+
+        capture_excluding(cg_rect, window_ids, CGWindowImageOption::Default).map(Some)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_root_background(&self, _rect: Rect) -> XCapResult<Option<RgbaImage>> {
+        Ok(None)
+    }
+
+    pub fn workarea(&self) -> XCapResult<Rect> {
+        let screen = get_screen(self.cg_direct_display_id)?;
+        let frame = screen.frame();
+        let visible_frame = screen.visibleFrame();
+
+        // AppKit 的原点在左下角，按相对 frame 的边距换算，避免处理全局坐标翻转
+        let left = visible_frame.origin.x - frame.origin.x;
+        let top = (frame.origin.y + frame.size.height)
+            - (visible_frame.origin.y + visible_frame.size.height);
+
+        Ok(Rect::new(
+            self.x + left as i32,
+            self.y + top as i32,
+            visible_frame.size.width as u32,
+            visible_frame.size.height as u32,
+        ))
+    }
+
+    pub fn stable_id(&self) -> String {
+        // UUID 由厂商、型号和序列号生成，重启和重新插拔后保持不变
+        let uuid =
+            NonNull::new(unsafe { CGDisplayCreateUUIDFromDisplayID(self.cg_direct_display_id) })
+                .map(|uuid| unsafe { CFRetained::from_raw(uuid) });
+
+        match uuid.and_then(|uuid| CFUUID::new_string(None, Some(&uuid))) {
+            Some(uuid) => uuid.to_string(),
+            None => format!(
+                "{:04x}-{:04x}-{:08x}",
+                CGDisplayVendorNumber(self.cg_direct_display_id),
+                CGDisplayModelNumber(self.cg_direct_display_id),
+                CGDisplaySerialNumber(self.cg_direct_display_id)
+            ),
+        }
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_desktop_only(&self) -> XCapResult<RgbaImage> {
+        let window_ids = desktop_window_ids()?;
+        if window_ids.is_empty() {
+            return Err(XCapError::new("No desktop window found"));
+        }
+        let cg_rect = unsafe { CGDisplayBounds(self.cg_direct_display_id) };
+
+        capture_windows(cg_rect, &window_ids, CGWindowImageOption::Default)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        let cg_rect = unsafe { CGDisplayBounds(self.cg_direct_display_id) };
+
+        // NominalResolution 让 WindowServer 按逻辑分辨率输出，Retina 屏幕上像素数减少为四分之一
+        let image = capture(
+            cg_rect,
+            CGWindowListOption::OptionAll,
+            0,
+            CGWindowImageOption::NominalResolution,
+        )?;
+
+        Ok(thumbnail(image, max_width, max_height))
+    }
+
+    pub fn is_mirror_of(&self, other: &ImplMonitor) -> bool {
+        if self.id == other.id {
+            return false;
+        }
+
+        // 镜像集合中的显示器都返回同一个主显示器，主显示器和未镜像的显示器返回 kCGNullDirectDisplay
+        let master = |id| match CGDisplayMirrorsDisplay(id) {
+            0 => id,
+            master => master,
+        };
+
+        master(self.cg_direct_display_id) == master(other.cg_direct_display_id)
+    }
+
+    pub fn supported_modes(&self) -> XCapResult<Vec<VideoMode>> {
+        let id = self.cg_direct_display_id;
+        let current_mode = CGDisplayCopyDisplayMode(id);
+        let current_mode_id = CGDisplayMode::io_display_mode_id(current_mode.as_deref());
+
+        let display_modes = unsafe { CGDisplayCopyAllDisplayModes(id, None) }
+            .ok_or(XCapError::new("CGDisplayCopyAllDisplayModes failed"))?;
+
+        let mut video_modes: Vec<VideoMode> = Vec::new();
+        for index in 0..display_modes.count() {
+            let display_mode =
+                unsafe { &*(display_modes.value_at_index(index) as *const CGDisplayMode) };
+            let display_mode = Some(display_mode);
+
+            let video_mode = VideoMode {
+                width: CGDisplayMode::pixel_width(display_mode) as u32,
+                height: CGDisplayMode::pixel_height(display_mode) as u32,
+                refresh_rate: CGDisplayMode::refresh_rate(display_mode) as f32,
+                is_current: CGDisplayMode::io_display_mode_id(display_mode) == current_mode_id,
+                is_native: CGDisplayMode::io_flags(display_mode) & K_DISPLAY_MODE_NATIVE_FLAG != 0,
+            };
+
+            // HiDPI 模式与普通模式的像素尺寸可能相同
+            match video_modes.iter_mut().find(|m| {
+                (m.width, m.height, m.refresh_rate)
+                    == (video_mode.width, video_mode.height, video_mode.refresh_rate)
+            }) {
+                Some(m) => {
+                    m.is_current |= video_mode.is_current;
+                    m.is_native |= video_mode.is_native;
+                }
+                None => video_modes.push(video_mode),
+            }
+        }
+
+        Ok(video_modes)
+    }
+
+    pub fn brightness(&self) -> XCapResult<f32> {
+        Err(XCapError::new(
+            "Monitor brightness is not available on macOS",
+        ))
+    }
+
+    pub fn gamma_ramp(&self) -> XCapResult<GammaRamp> {
+        let capacity = CGDisplayGammaTableCapacity(self.cg_direct_display_id);
+        let mut tables = vec![vec![0.0f32; capacity as usize]; 3];
+        let mut sample_count = 0;
+
+        let cg_error = unsafe {
+            CGGetDisplayTransferByTable(
+                self.cg_direct_display_id,
+                capacity,
+                tables[0].as_mut_ptr(),
+                tables[1].as_mut_ptr(),
+                tables[2].as_mut_ptr(),
+                &mut sample_count,
+            )
+        };
+        if cg_error != CGError::Success {
+            return Err(XCapError::new(format!(
+                "CGGetDisplayTransferByTable failed: {:?}",
+                cg_error
+            )));
+        }
+
+        let to_ramp = |table: &[f32]| {
+            table
+                .iter()
+                .take(sample_count as usize)
+                .map(|value| (value.clamp(0.0, 1.0) * 65535.0).round() as u16)
+                .collect()
+        };
+
+        Ok(GammaRamp {
+            red: to_ramp(&tables[0]),
+            green: to_ramp(&tables[1]),
+            blue: to_ramp(&tables[2]),
+        })
     }
 
     pub fn video_recorder(&self) -> XCapResult<ImplVideoRecorder> {
@@ -0,0 +1,27 @@
+use objc2_core_graphics::CGEvent;
+
+use crate::error::{XCapError, XCapResult};
+
+/// macOS moves one cursor with every mouse and trackpad.
+#[derive(Debug, Clone)]
+pub(crate) struct ImplPointer {
+    pub id: u32,
+    pub name: String,
+}
+
+impl ImplPointer {
+    pub fn all() -> XCapResult<Vec<ImplPointer>> {
+        Ok(vec![ImplPointer {
+            id: 0,
+            name: String::from("Cursor"),
+        }])
+    }
+
+    pub fn position(&self) -> XCapResult<(i32, i32)> {
+        // 新建的空事件带有当前的光标位置
+        let event = CGEvent::new(None).ok_or_else(|| XCapError::new("CGEventCreate failed"))?;
+        let location = CGEvent::location(Some(&event));
+
+        Ok((location.x as i32, location.y as i32))
+    }
+}
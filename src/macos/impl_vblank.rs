@@ -0,0 +1,129 @@
+use std::{
+    ffi::c_void,
+    ptr,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::error::{XCapError, XCapResult};
+
+use super::impl_monitor::ImplMonitor;
+
+type CVDisplayLinkRef = *mut c_void;
+type CVDisplayLinkOutputCallback = extern "C" fn(
+    display_link: CVDisplayLinkRef,
+    in_now: *const c_void,
+    in_output_time: *const c_void,
+    flags_in: u64,
+    flags_out: *mut u64,
+    display_link_context: *mut c_void,
+) -> i32;
+
+#[link(name = "CoreVideo", kind = "framework")]
+extern "C" {
+    fn CVDisplayLinkCreateWithCGDisplay(
+        display_id: u32,
+        display_link_out: *mut CVDisplayLinkRef,
+    ) -> i32;
+    fn CVDisplayLinkSetOutputCallback(
+        display_link: CVDisplayLinkRef,
+        callback: CVDisplayLinkOutputCallback,
+        user_info: *mut c_void,
+    ) -> i32;
+    fn CVDisplayLinkStart(display_link: CVDisplayLinkRef) -> i32;
+    fn CVDisplayLinkStop(display_link: CVDisplayLinkRef) -> i32;
+    fn CVDisplayLinkRelease(display_link: CVDisplayLinkRef);
+}
+
+/// How many vblanks the display link has seen.
+type VblankCounter = (Mutex<u64>, Condvar);
+
+extern "C" fn on_vblank(
+    _display_link: CVDisplayLinkRef,
+    _in_now: *const c_void,
+    _in_output_time: *const c_void,
+    _flags_in: u64,
+    _flags_out: *mut u64,
+    display_link_context: *mut c_void,
+) -> i32 {
+    let counter = unsafe { &*(display_link_context as *const VblankCounter) };
+    if let Ok(mut count) = counter.0.lock() {
+        *count += 1;
+        counter.1.notify_all();
+    }
+
+    0
+}
+
+/// Waits for the vblank of a display through a `CVDisplayLink`, whose callback runs on a
+/// CoreVideo thread once per refresh.
+#[derive(Debug)]
+pub(crate) struct ImplVblank {
+    display_link: CVDisplayLinkRef,
+    counter: Arc<VblankCounter>,
+}
+
+unsafe impl Send for ImplVblank {}
+
+impl ImplVblank {
+    pub fn new(impl_monitor: &ImplMonitor) -> XCapResult<ImplVblank> {
+        unsafe {
+            let mut display_link = ptr::null_mut();
+            let cv_return = CVDisplayLinkCreateWithCGDisplay(
+                impl_monitor.cg_direct_display_id,
+                &mut display_link,
+            );
+            if cv_return != 0 || display_link.is_null() {
+                return Err(XCapError::new(format!(
+                    "CVDisplayLinkCreateWithCGDisplay failed: {}",
+                    cv_return
+                )));
+            }
+
+            let counter: Arc<VblankCounter> = Arc::new((Mutex::new(0), Condvar::new()));
+            // 回调持有的引用在 drop 中停止 display link 后才释放
+            let context = Arc::into_raw(counter.clone()) as *mut c_void;
+            let impl_vblank = ImplVblank {
+                display_link,
+                counter,
+            };
+
+            CVDisplayLinkSetOutputCallback(display_link, on_vblank, context);
+            let cv_return = CVDisplayLinkStart(display_link);
+            if cv_return != 0 {
+                return Err(XCapError::new(format!(
+                    "CVDisplayLinkStart failed: {}",
+                    cv_return
+                )));
+            }
+
+            Ok(impl_vblank)
+        }
+    }
+
+    pub fn wait(&self) -> XCapResult<()> {
+        let (count, condvar) = &*self.counter;
+        let count = count.lock()?;
+        let current = *count;
+
+        let (_count, timeout) =
+            condvar
+                .wait_timeout_while(count, Duration::from_millis(100), |count| *count == current)?;
+        if timeout.timed_out() {
+            return Err(XCapError::new("Wait for vblank timed out"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ImplVblank {
+    fn drop(&mut self) {
+        unsafe {
+            CVDisplayLinkStop(self.display_link);
+            CVDisplayLinkRelease(self.display_link);
+            // 回收回调持有的引用
+            Arc::decrement_strong_count(Arc::as_ptr(&self.counter));
+        }
+    }
+}
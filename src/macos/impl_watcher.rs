@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use objc2_app_kit::{NSApplicationActivationPolicy, NSWorkspace};
+use objc2_core_foundation::{
+    kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopRunResult, CFRunLoopSource, CFString,
+};
+
+use crate::error::{XCapError, XCapResult};
+
+type AXObserverRef = *mut c_void;
+type AXUIElementRef = *mut c_void;
+type AXObserverCallback = extern "C" fn(
+    observer: AXObserverRef,
+    element: AXUIElementRef,
+    notification: *const CFString,
+    refcon: *mut c_void,
+);
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+    fn AXUIElementCreateApplication(pid: i32) -> AXUIElementRef;
+    fn AXObserverCreate(
+        application: i32,
+        callback: AXObserverCallback,
+        out_observer: *mut AXObserverRef,
+    ) -> i32;
+    fn AXObserverAddNotification(
+        observer: AXObserverRef,
+        element: AXUIElementRef,
+        notification: &CFString,
+        refcon: *mut c_void,
+    ) -> i32;
+    fn AXObserverGetRunLoopSource(observer: AXObserverRef) -> *mut CFRunLoopSource;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(cf: *const c_void);
+}
+
+/// The accessibility notifications that change what [`Window::all`](crate::Window::all) returns.
+const NOTIFICATIONS: [&str; 9] = [
+    "AXTitleChanged",
+    "AXFocusedWindowChanged",
+    "AXWindowCreated",
+    "AXUIElementDestroyed",
+    "AXWindowMoved",
+    "AXWindowResized",
+    "AXWindowMiniaturized",
+    "AXWindowDeminiaturized",
+    "AXApplicationActivated",
+];
+
+/// How often the running applications are listed again to observe newly launched ones.
+const APPLICATION_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+extern "C" fn on_notification(
+    _observer: AXObserverRef,
+    _element: AXUIElementRef,
+    _notification: *const CFString,
+    refcon: *mut c_void,
+) {
+    let changed = unsafe { &*(refcon as *const AtomicBool) };
+    changed.store(true, Ordering::Relaxed);
+}
+
+/// The AXObserver of one application, its source is on the run loop of the watcher thread.
+struct AppObserver {
+    observer: AXObserverRef,
+    application: AXUIElementRef,
+}
+
+impl Drop for AppObserver {
+    fn drop(&mut self) {
+        unsafe {
+            let source = AXObserverGetRunLoopSource(self.observer);
+            if let Some(run_loop) = CFRunLoop::current() {
+                run_loop.remove_source(source.as_ref(), kCFRunLoopDefaultMode);
+            }
+            CFRelease(self.observer);
+            CFRelease(self.application);
+        }
+    }
+}
+
+/// Wakes the [`Watcher`](crate::Watcher) up on accessibility notifications of the regular
+/// applications. Without the accessibility permission there are none and the watcher polls.
+pub(crate) struct ImplWatcher {
+    observers: HashMap<i32, AppObserver>,
+    // 回调通过 refcon 写入，Box 保证地址不变
+    changed: Box<AtomicBool>,
+    refreshed: Option<Instant>,
+}
+
+impl ImplWatcher {
+    pub fn new() -> ImplWatcher {
+        ImplWatcher {
+            observers: HashMap::new(),
+            changed: Box::new(AtomicBool::new(false)),
+            refreshed: None,
+        }
+    }
+
+    /// Observe newly launched applications and drop the observers of exited ones.
+    fn refresh(&mut self) {
+        let pids: Vec<i32> = NSWorkspace::sharedWorkspace()
+            .runningApplications()
+            .iter()
+            .filter(|app| app.activationPolicy() == NSApplicationActivationPolicy::Regular)
+            .map(|app| app.processIdentifier())
+            .collect();
+
+        self.observers.retain(|pid, _| pids.contains(pid));
+
+        for pid in pids {
+            if self.observers.contains_key(&pid) {
+                continue;
+            }
+
+            match self.observe(pid) {
+                Ok(app_observer) => {
+                    self.observers.insert(pid, app_observer);
+                }
+                Err(err) => log::debug!("Observe application {} failed: {}", pid, err),
+            }
+        }
+    }
+
+    fn observe(&self, pid: i32) -> XCapResult<AppObserver> {
+        unsafe {
+            let mut observer = ptr::null_mut();
+            let ax_error = AXObserverCreate(pid, on_notification, &mut observer);
+            if ax_error != 0 || observer.is_null() {
+                return Err(XCapError::new(format!(
+                    "AXObserverCreate failed: {}",
+                    ax_error
+                )));
+            }
+
+            let app_observer = AppObserver {
+                observer,
+                application: AXUIElementCreateApplication(pid),
+            };
+
+            let refcon = &*self.changed as *const AtomicBool as *mut c_void;
+            for notification in NOTIFICATIONS {
+                // 有的应用不支持部分通知，忽略单个通知的错误
+                AXObserverAddNotification(
+                    observer,
+                    app_observer.application,
+                    &CFString::from_static_str(notification),
+                    refcon,
+                );
+            }
+
+            let run_loop =
+                CFRunLoop::current().ok_or_else(|| XCapError::new("CFRunLoop::current failed"))?;
+            let source = AXObserverGetRunLoopSource(observer);
+            run_loop.add_source(source.as_ref(), kCFRunLoopDefaultMode);
+
+            Ok(app_observer)
+        }
+    }
+
+    /// Run the run loop for up to `timeout`, whether an application reported a change.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        if !unsafe { AXIsProcessTrusted() } {
+            thread::sleep(timeout);
+            return false;
+        }
+
+        if self
+            .refreshed
+            .is_none_or(|refreshed| refreshed.elapsed() >= APPLICATION_REFRESH_INTERVAL)
+        {
+            self.refresh();
+            self.refreshed = Some(Instant::now());
+        }
+
+        let result =
+            unsafe { CFRunLoop::run_in_mode(kCFRunLoopDefaultMode, timeout.as_secs_f64(), true) };
+        // 没有任何 source 时 run loop 立即返回，退回到定时轮询
+        if result == CFRunLoopRunResult::Finished {
+            thread::sleep(timeout);
+        }
+
+        self.changed.swap(false, Ordering::Relaxed)
+    }
+}
@@ -1,25 +1,57 @@
-use std::ffi::c_void;
+use std::{
+    ffi::{c_char, c_int, c_void, OsString},
+    mem,
+    os::unix::ffi::OsStringExt,
+    path::PathBuf,
+    ptr,
+};
 
 use image::RgbaImage;
-use objc2_app_kit::NSWorkspace;
+use objc2_app_kit::{NSApplicationActivationPolicy, NSRunningApplication, NSWorkspace};
 use objc2_core_foundation::{
     CFArrayGetCount, CFArrayGetValueAtIndex, CFBoolean, CFBooleanGetValue, CFDictionary,
     CFDictionaryGetValue, CFNumber, CFNumberGetValue, CFNumberType, CFString, CGPoint, CGRect,
     CGSize,
 };
 use objc2_core_graphics::{
-    CGDisplayBounds, CGMainDisplayID, CGRectContainsPoint, CGRectIntersectsRect,
-    CGRectMakeWithDictionaryRepresentation, CGWindowListCopyWindowInfo, CGWindowListOption,
+    kCGDesktopWindowLevel, CGDisplayBounds, CGError, CGEvent, CGEventTapLocation, CGMainDisplayID,
+    CGRectContainsPoint, CGRectIntersectsRect, CGRectMakeWithDictionaryRepresentation,
+    CGScrollEventUnit, CGWarpMouseCursorPosition, CGWindowImageOption, CGWindowListCopyWindowInfo,
+    CGWindowListOption,
 };
 
-use crate::{error::XCapResult, XCapError};
+use crate::{
+    backend::Backend, enumeration::Enumeration, error::XCapResult, utils::thumbnail, ProcessArch,
+    ProcessInfo, WindowKind, WindowShape, XCapError,
+};
 
 use super::{capture::capture, impl_monitor::ImplMonitor};
 
+// sys/proc_info.h，PROC_PIDARCHINFO 没有公开，活动监视器也依赖它
+const PROC_PIDARCHINFO: c_int = 19;
+// mach/machine.h
+const CPU_ARCH_ABI64: c_int = 0x0100_0000;
+const CPU_TYPE_X86: c_int = 7;
+const CPU_TYPE_ARM: c_int = 12;
+// sandbox.h
+const SANDBOX_FILTER_NONE: c_int = 0;
+
+#[repr(C)]
+#[derive(Default)]
+struct ProcArchInfo {
+    p_cputype: c_int,
+    p_cpusubtype: c_int,
+}
+
+extern "C" {
+    fn sandbox_check(pid: libc::pid_t, operation: *const c_char, r#type: c_int, ...) -> c_int;
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct ImplWindow {
     pub id: u32,
     pub title: String,
+    pub title_bytes: Vec<u8>,
     pub app_name: String,
     pub pid: u32,
     pub current_monitor: ImplMonitor,
@@ -30,7 +62,14 @@ pub(crate) struct ImplWindow {
     pub height: u32,
     pub is_minimized: bool,
     pub is_maximized: bool,
+    pub is_fullscreen: bool,
     pub is_focused: bool,
+    pub is_xwayland: bool,
+    pub kind: WindowKind,
+    pub skip_taskbar: bool,
+    pub skip_pager: bool,
+    pub is_visible: bool,
+    pub owner_id: Option<u32>,
 }
 
 unsafe impl Send for ImplWindow {}
@@ -78,6 +117,28 @@ fn get_cf_number_i32_value(cf_dictionary: &CFDictionary, key: &str) -> XCapResul
     }
 }
 
+fn get_cf_number_f64_value(cf_dictionary: &CFDictionary, key: &str) -> XCapResult<f64> {
+    unsafe {
+        let cf_number = get_cf_dictionary_get_value(cf_dictionary, key)? as *const CFNumber;
+
+        let mut value: f64 = 0.0;
+        let is_success = CFNumberGetValue(
+            &*cf_number,
+            CFNumberType::DoubleType,
+            &mut value as *mut _ as *mut c_void,
+        );
+
+        if !is_success {
+            return Err(XCapError::new(format!(
+                "Get {} CFNumberGetValue failed",
+                key
+            )));
+        }
+
+        Ok(value)
+    }
+}
+
 fn get_cf_string_value(cf_dictionary: &CFDictionary, key: &str) -> XCapResult<String> {
     let value_ref = get_cf_dictionary_get_value(cf_dictionary, key)? as *const CFString;
     let value = unsafe { (*value_ref).to_string() };
@@ -110,6 +171,58 @@ fn get_window_cg_rect(window_cf_dictionary: &CFDictionary) -> XCapResult<CGRect>
     }
 }
 
+/// The windows of the desktop level, which draw the wallpaper below every other window.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub(super) fn desktop_window_ids() -> XCapResult<Vec<u32>> {
+    unsafe {
+        let cf_array = CGWindowListCopyWindowInfo(CGWindowListOption::OptionOnScreenOnly, 0)
+            .ok_or_else(|| XCapError::new("CGWindowListCopyWindowInfo failed"))?;
+
+        let mut window_ids = Vec::new();
+        for i in 0..CFArrayGetCount(&cf_array) {
+            let window_cf_dictionary_ref =
+                CFArrayGetValueAtIndex(&cf_array, i) as *const CFDictionary;
+            if window_cf_dictionary_ref.is_null() {
+                continue;
+            }
+            let window_cf_dictionary = &*window_cf_dictionary_ref;
+
+            if get_cf_number_i32_value(window_cf_dictionary, "kCGWindowLayer")
+                .is_ok_and(|layer| layer == kCGDesktopWindowLevel)
+            {
+                window_ids
+                    .push(get_cf_number_i32_value(window_cf_dictionary, "kCGWindowNumber")? as u32);
+            }
+        }
+
+        Ok(window_ids)
+    }
+}
+
+/// Classify a window by its `kCGWindowLayer`, the CGWindowLevel of the window.
+fn get_window_kind(window_layer: i32, window_owner_name: &str) -> WindowKind {
+    if window_owner_name == "Notification Center" || window_owner_name == "NotificationCenter" {
+        return WindowKind::Notification;
+    }
+
+    match window_layer {
+        0 => WindowKind::Normal,
+        // kCGFloatingWindowLevel、kCGUtilityWindowLevel
+        3 | 19 => WindowKind::Utility,
+        // kCGModalPanelWindowLevel
+        8 => WindowKind::Dialog,
+        // kCGDockWindowLevel、kCGMainMenuWindowLevel、kCGStatusWindowLevel
+        20 | 24 | 25 => WindowKind::Dock,
+        // kCGPopUpMenuWindowLevel
+        101 => WindowKind::Menu,
+        // kCGHelpWindowLevel
+        200 => WindowKind::Tooltip,
+        // kCGDesktopWindowLevel、kCGDesktopIconWindowLevel
+        layer if layer < 0 => WindowKind::Desktop,
+        _ => WindowKind::Normal,
+    }
+}
+
 impl ImplWindow {
     pub fn new(
         window_cf_dictionary: &CFDictionary,
@@ -126,7 +239,7 @@ impl ImplWindow {
 
         let primary_monitor = ImplMonitor::new(unsafe { CGMainDisplayID() })?;
 
-        let (is_maximized, current_monitor) = {
+        let (is_maximized, is_fullscreen, current_monitor) = {
             // 获取窗口中心点的坐标
             let window_center_x = cg_rect.origin.x + cg_rect.size.width / 2.0;
             let window_center_y = cg_rect.origin.y + cg_rect.size.height / 2.0;
@@ -144,20 +257,39 @@ impl ImplWindow {
                 })
                 .unwrap_or(&primary_monitor);
 
+            let covers_monitor = cg_rect.size.width as u32 >= impl_monitor.width
+                && cg_rect.size.height as u32 >= impl_monitor.height;
+
             (
-                cg_rect.size.width as u32 >= impl_monitor.width
-                    && cg_rect.size.height as u32 >= impl_monitor.height,
+                covers_monitor,
+                // 全屏窗口还盖住菜单栏，从显示器的左上角开始
+                covers_monitor
+                    && cg_rect.origin.x as i32 == impl_monitor.x
+                    && cg_rect.origin.y as i32 == impl_monitor.y,
                 impl_monitor,
             )
         };
 
-        let is_minimized =
-            !get_cf_bool_value(window_cf_dictionary, "kCGWindowIsOnscreen")? && !is_maximized;
+        let is_onscreen = get_cf_bool_value(window_cf_dictionary, "kCGWindowIsOnscreen")?;
+        let is_minimized = !is_onscreen && !is_maximized;
+        // 其他桌面空间上的窗口 kCGWindowIsOnscreen 也是 false
+        let is_visible = is_onscreen
+            && get_cf_number_f64_value(window_cf_dictionary, "kCGWindowAlpha").unwrap_or(1.0) > 0.0;
 
         let is_focused = focused_app_pid.eq(&Some(pid));
 
+        let window_layer = get_cf_number_i32_value(window_cf_dictionary, "kCGWindowLayer")?;
+        let kind = get_window_kind(window_layer, &window_owner_name);
+
+        // 只有普通层级的窗口出现在 Mission Control，只有 Regular 应用出现在 Dock
+        let skip_pager = window_layer != 0;
+        let is_regular_app = NSRunningApplication::runningApplicationWithProcessIdentifier(pid)
+            .is_some_and(|app| app.activationPolicy() == NSApplicationActivationPolicy::Regular);
+        let skip_taskbar = skip_pager || !is_regular_app;
+
         Ok(ImplWindow {
             id,
+            title_bytes: window_name.as_bytes().to_vec(),
             title: window_name,
             app_name: window_owner_name,
             pid: pid as u32,
@@ -169,11 +301,32 @@ impl ImplWindow {
             height: cg_rect.size.height as u32,
             is_minimized,
             is_maximized,
+            is_fullscreen,
             is_focused,
+            is_xwayland: false,
+            kind,
+            skip_taskbar,
+            skip_pager,
+            is_visible,
+            // CoreGraphics 不提供窗口之间的从属关系
+            owner_id: None,
         })
     }
 
+    pub fn backend() -> Backend {
+        Backend::CoreGraphics
+    }
+
     pub fn all() -> XCapResult<Vec<ImplWindow>> {
+        Ok(ImplWindow::all_with_errors()?.into_logged_items())
+    }
+
+    pub fn all_with_override_redirect() -> XCapResult<Vec<ImplWindow>> {
+        // CGWindowList 已经包含菜单和弹出窗口
+        ImplWindow::all()
+    }
+
+    pub fn all_with_errors() -> XCapResult<Enumeration<ImplWindow>> {
         unsafe {
             let impl_monitors = ImplMonitor::all()?;
             let workspace = NSWorkspace::sharedWorkspace();
@@ -181,7 +334,7 @@ impl ImplWindow {
                 .frontmostApplication()
                 .map(|focused_app| focused_app.processIdentifier());
 
-            let mut impl_windows = Vec::new();
+            let mut enumeration = Enumeration::default();
 
             // CGWindowListCopyWindowInfo 返回窗口顺序为从顶层到最底层
             // 即在前面的窗口在数组前面
@@ -190,7 +343,7 @@ impl ImplWindow {
                 0,
             ) {
                 Some(cf_array) => cf_array,
-                None => return Ok(impl_windows),
+                None => return Ok(enumeration),
             };
 
             let num_windows = CFArrayGetCount(&cf_array);
@@ -230,33 +383,169 @@ impl ImplWindow {
                     continue;
                 }
 
-                if let Ok(impl_window) = ImplWindow::new(
+                match ImplWindow::new(
                     window_cf_dictionary,
                     &impl_monitors,
-                    window_name.clone(),
-                    window_owner_name.clone(),
+                    window_name,
+                    window_owner_name,
                     num_windows as i32 - i as i32 - 1,
                     focused_app_pid,
                 ) {
-                    impl_windows.push(impl_window);
-                } else {
-                    log::error!(
-                        "ImplWindow::new({:?}, {:?}, {:?}, {:?}) failed",
-                        window_cf_dictionary,
-                        &impl_monitors,
-                        &window_name,
-                        &window_owner_name
-                    );
+                    Ok(impl_window) => enumeration.items.push(impl_window),
+                    Err(err) => {
+                        let id = get_cf_number_i32_value(window_cf_dictionary, "kCGWindowNumber")
+                            .ok()
+                            .map(|id| id as u32);
+                        enumeration.push_error(id, err);
+                    }
                 }
             }
 
-            Ok(impl_windows)
+            Ok(enumeration)
         }
     }
 }
 
+/// Parse the `KERN_PROCARGS2` buffer: argc, the executable path, NUL padding, then the
+/// NUL separated arguments followed by the environment.
+fn parse_procargs(procargs: &[u8]) -> Vec<String> {
+    let (argc, rest) = match procargs.split_first_chunk::<4>() {
+        Some((argc, rest)) => (i32::from_ne_bytes(*argc).max(0) as usize, rest),
+        None => return Vec::new(),
+    };
+
+    let exec_path_end = rest
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(rest.len());
+    let args_start = rest[exec_path_end..]
+        .iter()
+        .position(|byte| *byte != 0)
+        .map(|offset| exec_path_end + offset)
+        .unwrap_or(rest.len());
+
+    rest[args_start..]
+        .split(|byte| *byte == 0)
+        .take(argc)
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect()
+}
+
 impl ImplWindow {
-    pub fn capture_image(&self) -> XCapResult<RgbaImage> {
+    pub fn exe_path(&self) -> XCapResult<PathBuf> {
+        let mut exe_path = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+        let length = unsafe {
+            libc::proc_pidpath(
+                self.pid as i32,
+                exe_path.as_mut_ptr().cast(),
+                exe_path.len() as u32,
+            )
+        };
+        if length <= 0 {
+            return Err(XCapError::new(format!(
+                "proc_pidpath failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        exe_path.truncate(length as usize);
+
+        Ok(PathBuf::from(OsString::from_vec(exe_path)))
+    }
+
+    pub fn cmdline(&self) -> XCapResult<Vec<String>> {
+        let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, self.pid as i32];
+        let mut size = 0;
+
+        unsafe {
+            // 先获取需要的长度
+            if libc::sysctl(
+                mib.as_mut_ptr(),
+                3,
+                ptr::null_mut(),
+                &mut size,
+                ptr::null_mut(),
+                0,
+            ) != 0
+            {
+                return Err(XCapError::new(format!(
+                    "sysctl KERN_PROCARGS2 failed: {}",
+                    std::io::Error::last_os_error()
+                )));
+            }
+
+            let mut procargs = vec![0u8; size];
+            if libc::sysctl(
+                mib.as_mut_ptr(),
+                3,
+                procargs.as_mut_ptr().cast(),
+                &mut size,
+                ptr::null_mut(),
+                0,
+            ) != 0
+            {
+                return Err(XCapError::new(format!(
+                    "sysctl KERN_PROCARGS2 failed: {}",
+                    std::io::Error::last_os_error()
+                )));
+            }
+            procargs.truncate(size);
+
+            Ok(parse_procargs(&procargs))
+        }
+    }
+
+    pub fn process_info(&self) -> XCapResult<ProcessInfo> {
+        let mut arch_info = ProcArchInfo::default();
+        let size = unsafe {
+            libc::proc_pidinfo(
+                self.pid as i32,
+                PROC_PIDARCHINFO,
+                0,
+                (&mut arch_info as *mut ProcArchInfo).cast(),
+                mem::size_of::<ProcArchInfo>() as c_int,
+            )
+        };
+        if size != mem::size_of::<ProcArchInfo>() as c_int {
+            return Err(XCapError::new(format!(
+                "proc_pidinfo PROC_PIDARCHINFO failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+
+        let arch = match arch_info.p_cputype {
+            CPU_TYPE_X86 => ProcessArch::X86,
+            CPU_TYPE_ARM => ProcessArch::Arm,
+            cputype if cputype == CPU_TYPE_X86 | CPU_ARCH_ABI64 => ProcessArch::X86_64,
+            cputype if cputype == CPU_TYPE_ARM | CPU_ARCH_ABI64 => ProcessArch::Arm64,
+            _ => ProcessArch::Unknown,
+        };
+
+        // 自身也可能运行在 Rosetta 下，所以询问硬件而不是看编译目标
+        let mut is_arm64_host: c_int = 0;
+        let mut length = mem::size_of::<c_int>();
+        let has_arm64 = unsafe {
+            libc::sysctlbyname(
+                b"hw.optional.arm64\0".as_ptr().cast(),
+                (&mut is_arm64_host as *mut c_int).cast(),
+                &mut length,
+                ptr::null_mut(),
+                0,
+            ) == 0
+        };
+
+        let is_sandboxed =
+            unsafe { sandbox_check(self.pid as i32, ptr::null(), SANDBOX_FILTER_NONE) > 0 };
+
+        Ok(ProcessInfo {
+            arch,
+            is_sandboxed,
+            is_translated: has_arm64 && is_arm64_host == 1 && arch == ProcessArch::X86_64,
+        })
+    }
+}
+
+impl ImplWindow {
+    fn capture_with_option(&self, image_option: CGWindowImageOption) -> XCapResult<RgbaImage> {
         capture(
             CGRect::new(
                 CGPoint::new(self.x as f64, self.y as f64),
@@ -264,6 +553,83 @@ impl ImplWindow {
             ),
             CGWindowListOption::OptionIncludingWindow,
             self.id,
+            image_option,
         )
     }
+
+    pub fn is_valid(&self) -> bool {
+        CGWindowListCopyWindowInfo(CGWindowListOption::OptionIncludingWindow, self.id)
+            .is_some_and(|cf_array| cf_array.count() > 0)
+    }
+
+    pub fn capture_image(&self) -> XCapResult<RgbaImage> {
+        self.capture_with_option(CGWindowImageOption::Default)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        let image = self.capture_with_option(CGWindowImageOption::NominalResolution)?;
+
+        Ok(thumbnail(image, max_width, max_height))
+    }
+}
+
+impl ImplWindow {
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn scroll(&self, lines: i32) -> XCapResult<()> {
+        // 滚轮事件发给光标下的窗口，先把光标移到窗口中间
+        let cg_error = CGWarpMouseCursorPosition(CGPoint::new(
+            self.x as f64 + self.width as f64 / 2.0,
+            self.y as f64 + self.height as f64 / 2.0,
+        ));
+        if cg_error != CGError::Success {
+            return Err(XCapError::new(format!(
+                "CGWarpMouseCursorPosition failed: {:?}",
+                cg_error
+            )));
+        }
+
+        let event =
+            CGEvent::new_scroll_wheel_event2(None, CGScrollEventUnit::Line, 1, -lines, 0, 0)
+                .ok_or_else(|| XCapError::new("CGEventCreateScrollWheelEvent2 failed"))?;
+        CGEvent::post(CGEventTapLocation::HIDEventTap, Some(&event));
+
+        Ok(())
+    }
+}
+
+impl ImplWindow {
+    /// Quartz does not expose window shapes, only the window rectangle.
+    pub fn shape(&self) -> XCapResult<WindowShape> {
+        Ok(WindowShape::rectangle(self.width, self.height))
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl ImplWindow {
+    pub fn raw_window_handle(&self) -> XCapResult<raw_window_handle::RawWindowHandle> {
+        use std::ptr::NonNull;
+
+        use objc2::{rc::Retained, MainThreadMarker};
+        use objc2_app_kit::NSApplication;
+        use raw_window_handle::{AppKitWindowHandle, RawWindowHandle};
+
+        // 只能拿到当前进程自己的 NSWindow，其它进程的窗口只有 CGWindowID
+        let mtm = MainThreadMarker::new().ok_or(XCapError::new(
+            "Raw window handle must be got on the main thread",
+        ))?;
+        let ns_window = NSApplication::sharedApplication(mtm)
+            .windowWithWindowNumber(self.id as isize)
+            .ok_or(XCapError::new(
+                "Only windows of the current process have a raw window handle",
+            ))?;
+        let ns_view = ns_window
+            .contentView()
+            .ok_or(XCapError::new("Window has no content view"))?;
+
+        let ns_view = NonNull::new(Retained::as_ptr(&ns_view) as *mut c_void)
+            .ok_or(XCapError::new("Window content view is null"))?;
+
+        Ok(RawWindowHandle::AppKit(AppKitWindowHandle::new(ns_view)))
+    }
 }
@@ -1,5 +1,8 @@
 mod capture;
 
 pub mod impl_monitor;
+pub mod impl_pointer;
+pub mod impl_vblank;
 pub mod impl_video_recorder;
+pub mod impl_watcher;
 pub mod impl_window;
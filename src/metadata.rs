@@ -0,0 +1,106 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+};
+
+/// Values attached to a [`crate::Frame`] when it was captured, one per type, so analytics
+/// downstream get them time-aligned with the pixels. Fill it with
+/// [`crate::VideoRecorder::with_metadata`], applications define their own types for anything
+/// else, e.g. the keyboard layout.
+///
+/// Values are shared between clones of the frame, cloning is cheap.
+#[derive(Clone, Default)]
+pub struct FrameMetadata {
+    values: HashMap<TypeId, (&'static str, Arc<dyn Any + Send + Sync>)>,
+}
+
+impl fmt::Debug for FrameMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.values.values().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+impl FrameMetadata {
+    pub fn new() -> FrameMetadata {
+        FrameMetadata::default()
+    }
+
+    /// Attach `value`, replacing the value of the same type.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.values
+            .insert(TypeId::of::<T>(), (type_name::<T>(), Arc::new(value)));
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|(_, value)| value.downcast_ref())
+    }
+
+    /// Remove the value of type `T`, returns whether there was one.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> bool {
+        self.values.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    pub fn contains<T: Any + Send + Sync>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Where the cursor was when the frame was captured, in the coordinates of
+/// [`crate::Window::x`] and [`crate::Window::y`], see
+/// [`crate::VideoRecorder::with_cursor_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CursorPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The id of the window that had the focus when the frame was captured, see
+/// [`crate::VideoRecorder::with_focus_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FocusedWindow {
+    pub id: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_value_per_type() {
+        struct KeyboardLayout(&'static str);
+
+        let mut metadata = FrameMetadata::new();
+        metadata.insert(CursorPosition { x: 1, y: 2 });
+        metadata.insert(KeyboardLayout("us"));
+        metadata.insert(CursorPosition { x: 3, y: 4 });
+
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(
+            metadata.get::<CursorPosition>(),
+            Some(&CursorPosition { x: 3, y: 4 })
+        );
+        assert_eq!(
+            metadata.get::<KeyboardLayout>().map(|layout| layout.0),
+            Some("us")
+        );
+        assert!(metadata.get::<FocusedWindow>().is_none());
+
+        let cloned = metadata.clone();
+        assert!(metadata.remove::<KeyboardLayout>());
+        assert!(cloned.contains::<KeyboardLayout>());
+    }
+}
@@ -1,15 +1,75 @@
-use image::RgbaImage;
+use std::{path::Path, sync::Arc, time::Duration};
 
-use crate::{error::XCapResult, platform::impl_monitor::ImplMonitor, VideoRecorder};
+#[cfg(feature = "image")]
+use image::{imageops, Rgba32FImage};
+use image::{ImageFormat, RgbaImage};
 
+#[cfg(feature = "image")]
+use crate::{
+    color::to_linear_image,
+    compose::{composite_excluding, composite_monitor},
+    region::Region,
+    report::{capture_path, fallback},
+};
+use crate::{
+    color::{ColorSpace, GammaRamp, TransferFunction},
+    custom::{CustomBackend, CustomMonitor},
+    encode::{encode_image, save_image, EncodeOptions},
+    error::{XCapError, XCapResult},
+    layout::Rect,
+    platform::{impl_monitor::ImplMonitor, impl_vblank::ImplVblank},
+    video_recorder::{capture_burst, Frame},
+    CaptureOptions, VideoRecorder,
+};
+#[cfg(target_os = "windows")]
+use crate::{gpu::GpuDevice, video_recorder::stream_burst};
+
+/// A display mode of a monitor, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoMode {
+    pub width: u32,
+    pub height: u32,
+    /// The refresh rate in Hz, 0 when unknown.
+    pub refresh_rate: f32,
+    /// Whether the monitor currently runs this mode.
+    pub is_current: bool,
+    /// Whether this is the native (preferred) mode of the panel. Windows does not report it,
+    /// the largest mode is marked instead.
+    pub is_native: bool,
+}
+
+/// A monitor, cheap to clone. On X11 every monitor enumerated together shares one connection,
+/// so capturing it again and again does not connect to the X server each time.
 #[derive(Debug, Clone)]
 pub struct Monitor {
     pub(crate) impl_monitor: ImplMonitor,
+    /// The backend capturing a monitor of a [`CustomBackend`].
+    custom_backend: Option<Arc<dyn CustomBackend>>,
 }
 
 impl Monitor {
     pub(crate) fn new(impl_monitor: ImplMonitor) -> Monitor {
-        Monitor { impl_monitor }
+        Monitor {
+            impl_monitor,
+            custom_backend: None,
+        }
+    }
+
+    pub(crate) fn custom(
+        custom_backend: Arc<dyn CustomBackend>,
+        custom_monitor: &CustomMonitor,
+    ) -> Monitor {
+        Monitor {
+            impl_monitor: ImplMonitor::from_custom(custom_monitor),
+            custom_backend: Some(custom_backend),
+        }
+    }
+
+    fn custom_unsupported<T>(&self, what: &str) -> XCapResult<T> {
+        Err(XCapError::new(format!(
+            "{} is not available for custom backend monitors",
+            what
+        )))
     }
 }
 
@@ -28,6 +88,14 @@ impl Monitor {
 
         Ok(Monitor::new(impl_monitor))
     }
+
+    /// Find the monitor a [`Monitor::stable_id`] was taken from, e.g. in a previous run.
+    pub fn from_stable_id(stable_id: &str) -> XCapResult<Monitor> {
+        Monitor::all()?
+            .into_iter()
+            .find(|monitor| monitor.stable_id() == stable_id)
+            .ok_or_else(|| XCapError::new(format!("No monitor with stable id {}", stable_id)))
+    }
 }
 
 impl Monitor {
@@ -35,6 +103,17 @@ impl Monitor {
     pub fn id(&self) -> u32 {
         self.impl_monitor.id
     }
+    /// An identifier that survives reboots and re-enumeration, unlike [`Monitor::id`], to store
+    /// in settings. The connector name and a hash of the EDID on Linux, the device interface
+    /// path on Windows and the display UUID on macOS. Falls back to the name when the platform
+    /// has nothing better, which is not unique for identical monitors.
+    pub fn stable_id(&self) -> String {
+        if self.custom_backend.is_some() {
+            return format!("custom:{}", self.id());
+        }
+
+        self.impl_monitor.stable_id()
+    }
     /// Unique identifier associated with the screen.
     pub fn name(&self) -> &str {
         &self.impl_monitor.name
@@ -55,7 +134,9 @@ impl Monitor {
     pub fn height(&self) -> u32 {
         self.impl_monitor.height
     }
-    /// Can be 0, 90, 180, 270, represents screen rotation in clock-wise degrees.
+    /// Can be 0, 90, 180, 270, represents screen rotation in clock-wise degrees. Captures are
+    /// always rotated to match what the user sees, see [`VideoRecorder::with_raw_orientation`]
+    /// for the untransformed buffer.
     pub fn rotation(&self) -> f32 {
         self.impl_monitor.rotation
     }
@@ -71,17 +152,327 @@ impl Monitor {
     pub fn is_primary(&self) -> bool {
         self.impl_monitor.is_primary
     }
+    /// Whether `other` shows the same content as this monitor, e.g. a projector in
+    /// mirrored / duplicated mode. A monitor is never a mirror of itself.
+    pub fn is_mirror_of(&self, other: &Monitor) -> bool {
+        if self.custom_backend.is_some() || other.custom_backend.is_some() {
+            return false;
+        }
+
+        self.impl_monitor.is_mirror_of(&other.impl_monitor)
+    }
+    /// The color space of the images captured from the screen.
+    pub fn color_space(&self) -> ColorSpace {
+        self.impl_monitor.color_space
+    }
+    /// The transfer function of the images captured from the screen.
+    pub fn transfer_function(&self) -> TransferFunction {
+        self.color_space().transfer_function()
+    }
+    /// The display modes the monitor supports, from RandR on X11, `EnumDisplaySettingsW` on
+    /// Windows and `CGDisplayCopyAllDisplayModes` on macOS. Other Linux backends only report
+    /// the current mode.
+    ///
+    /// A current mode that is not the native one means the panel is scaling the picture.
+    pub fn supported_modes(&self) -> XCapResult<Vec<VideoMode>> {
+        if self.custom_backend.is_some() {
+            return Ok(vec![VideoMode {
+                width: self.width(),
+                height: self.height(),
+                refresh_rate: self.frequency(),
+                is_current: true,
+                is_native: true,
+            }]);
+        }
+
+        self.impl_monitor.supported_modes()
+    }
+    /// The backlight or DDC/CI brightness between 0.0 and 1.0, from the RandR `Backlight`
+    /// property on X11 and DDC/CI on Windows. Errors when the monitor does not report one, e.g.
+    /// on macOS, which has no public API for it.
+    pub fn brightness(&self) -> XCapResult<f32> {
+        if self.custom_backend.is_some() {
+            return self.custom_unsupported("Monitor brightness");
+        }
+
+        self.impl_monitor.brightness()
+    }
+    /// The gamma ramps applied to the picture on its way to the monitor, from RandR on X11,
+    /// `GetDeviceGammaRamp` on Windows and `CGGetDisplayTransferByTable` on macOS. Captures do
+    /// not include them, see [`GammaRamp::gamma`] to normalize captures of different monitors.
+    pub fn gamma_ramp(&self) -> XCapResult<GammaRamp> {
+        if self.custom_backend.is_some() {
+            return self.custom_unsupported("Monitor gamma");
+        }
+
+        self.impl_monitor.gamma_ramp()
+    }
 }
 
 impl Monitor {
+    /// Whether the monitor is still connected. Captures of a disconnected monitor fail with
+    /// [`XCapError::SourceGone`].
+    pub fn is_valid(&self) -> bool {
+        if let Some(custom_backend) = &self.custom_backend {
+            return custom_backend.is_valid(self.id());
+        }
+
+        self.impl_monitor.is_valid()
+    }
+
+    /// The part of the monitor not taken by taskbars, docks and panels, in virtual screen
+    /// coordinates. `_NET_WORKAREA` on X11, which only excludes panels along the edges of the
+    /// whole screen, `rcWork` on Windows and `NSScreen.visibleFrame` on macOS. The full monitor
+    /// where the platform does not tell.
+    pub fn workarea(&self) -> XCapResult<Rect> {
+        if self.custom_backend.is_some() {
+            return Ok(Rect::new(self.x(), self.y(), self.width(), self.height()));
+        }
+
+        self.impl_monitor.workarea()
+    }
+
+    /// Backend errors are opaque, check whether they were caused by a disconnected monitor.
+    fn check_gone(&self, err: XCapError) -> XCapError {
+        match self.is_valid() {
+            true => err,
+            false => XCapError::SourceGone,
+        }
+    }
+
     /// Capture image of the monitor
+    #[cfg(feature = "image")]
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
-        self.impl_monitor.capture_image()
+        self.capture_rgba()
+    }
+
+    pub(crate) fn capture_rgba(&self) -> XCapResult<RgbaImage> {
+        if let Some(custom_backend) = &self.custom_backend {
+            return custom_backend
+                .capture_monitor(self.id())
+                .map_err(|err| self.check_gone(err));
+        }
+
+        self.impl_monitor
+            .capture_image()
+            .map_err(|err| self.check_gone(err))
+    }
+
+    /// Capture only the [`Monitor::workarea`] of the monitor, without taskbars, docks and
+    /// panels, e.g. for clean documentation screenshots.
+    #[cfg(feature = "image")]
+    pub fn capture_workarea(&self) -> XCapResult<RgbaImage> {
+        let workarea = self.workarea()?;
+        let image = self.capture_rgba()?;
+
+        // 截图可能是物理像素，工作区与显示器一样是逻辑坐标
+        let scale_x = image.width() as f32 / self.width().max(1) as f32;
+        let scale_y = image.height() as f32 / self.height().max(1) as f32;
+        let region = Region::new(
+            ((workarea.x - self.x()).max(0) as f32 * scale_x) as u32,
+            ((workarea.y - self.y()).max(0) as f32 * scale_y) as u32,
+            (workarea.width as f32 * scale_x) as u32,
+            (workarea.height as f32 * scale_y) as u32,
+        )
+        .clamp(image.width(), image.height())
+        .ok_or_else(|| XCapError::new("The work area is outside of the monitor"))?;
+
+        Ok(imageops::crop_imm(&image, region.x, region.y, region.width, region.height).to_image())
+    }
+
+    /// Capture image of the monitor without the windows listed in `window_ids`, e.g. to hide
+    /// the notes window of a presenter while sharing the screen.
+    ///
+    /// macOS excludes the windows natively. Elsewhere the areas of the excluded windows are
+    /// repainted from captures of the other windows, which is slower and leaves the areas
+    /// no other window covers black.
+    #[cfg(feature = "image")]
+    pub fn capture_excluding(&self, window_ids: &[u32]) -> XCapResult<RgbaImage> {
+        // 自定义后端没有窗口
+        if window_ids.is_empty() || self.custom_backend.is_some() {
+            return self.capture_image();
+        }
+
+        match self
+            .impl_monitor
+            .capture_excluding(window_ids)
+            .map_err(|err| self.check_gone(err))?
+        {
+            Some(image) => Ok(image),
+            None => {
+                fallback(
+                    "native-exclusion",
+                    "composited-exclusion",
+                    &"not supported by the platform",
+                );
+                capture_path(
+                    "composited-exclusion",
+                    composite_excluding(self, window_ids),
+                )
+            }
+        }
+    }
+
+    /// Rebuild what the monitor shows from captures of its visible windows, composited in
+    /// z-order over the wallpaper, for when grabbing the screen is blocked but capturing
+    /// windows is allowed. Much slower than [`Monitor::capture_image`], and windows that can
+    /// not be captured, e.g. on Wayland, are missing.
+    #[cfg(feature = "image")]
+    pub fn capture_composited(&self) -> XCapResult<RgbaImage> {
+        if self.custom_backend.is_some() {
+            return self.custom_unsupported("Composited capture");
+        }
+
+        capture_path("composited", composite_monitor(self))
+    }
+
+    /// Capture a downscaled preview of the monitor that fits into `max_width` x `max_height`,
+    /// keeping the aspect ratio. The backend scales natively where it can, which is much
+    /// cheaper than capturing the full image and resizing it.
+    #[cfg(feature = "image")]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        if let Some(custom_backend) = &self.custom_backend {
+            return custom_backend
+                .capture_thumbnail(self.id(), max_width, max_height)
+                .map_err(|err| self.check_gone(err));
+        }
+
+        self.impl_monitor
+            .capture_thumbnail(max_width, max_height)
+            .map_err(|err| self.check_gone(err))
+    }
+
+    /// Capture only the wallpaper of the monitor, without any window, e.g. to theme an app after
+    /// it. The root pixmap (`_XROOTPMAP_ID`) on X11, the Explorer desktop window on Windows,
+    /// which includes the desktop icons, and the desktop level windows on macOS. Wayland and
+    /// the browser do not expose the wallpaper.
+    #[cfg(feature = "image")]
+    pub fn capture_desktop_only(&self) -> XCapResult<RgbaImage> {
+        if self.custom_backend.is_some() {
+            return self.custom_unsupported("Desktop capture");
+        }
+
+        self.impl_monitor
+            .capture_desktop_only()
+            .map_err(|err| self.check_gone(err))
+    }
+
+    /// Capture image of the monitor as linear light `f32` values.
+    #[cfg(feature = "image")]
+    pub fn capture_image_linear(&self) -> XCapResult<Rgba32FImage> {
+        Ok(to_linear_image(
+            &self.capture_rgba()?,
+            self.transfer_function(),
+        ))
+    }
+
+    /// Capture image of the monitor and save it, the format is inferred from the file extension.
+    pub fn capture_to_file<P: AsRef<Path>>(&self, path: P) -> XCapResult<()> {
+        self.capture_to_file_with(path, &EncodeOptions::default())
+    }
+
+    pub fn capture_to_file_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &EncodeOptions,
+    ) -> XCapResult<()> {
+        save_image(&self.capture_rgba()?, path.as_ref(), options)
+    }
+
+    /// Capture image of the monitor and encode it as PNG.
+    pub fn capture_png(&self) -> XCapResult<Vec<u8>> {
+        encode_image(
+            &self.capture_rgba()?,
+            ImageFormat::Png,
+            &EncodeOptions::default(),
+        )
+    }
+
+    /// Capture image of the monitor and encode it as JPEG with `quality` between 1 and 100.
+    #[cfg(feature = "jpeg")]
+    pub fn capture_jpeg(&self, quality: u8) -> XCapResult<Vec<u8>> {
+        encode_image(
+            &self.capture_rgba()?,
+            ImageFormat::Jpeg,
+            &EncodeOptions { quality },
+        )
+    }
+
+    /// Capture image of the monitor and encode it as lossless WebP, the bundled encoder has no
+    /// lossy mode.
+    #[cfg(feature = "webp")]
+    pub fn capture_webp_lossless(&self) -> XCapResult<Vec<u8>> {
+        encode_image(
+            &self.capture_rgba()?,
+            ImageFormat::WebP,
+            &EncodeOptions::default(),
+        )
+    }
+
+    /// Capture the monitor as a raw RGBA [`Frame`], without exposing `image` types.
+    pub fn capture_frame(&self) -> XCapResult<Frame> {
+        let image = self.capture_rgba()?;
+        let (width, height) = image.dimensions();
+
+        Ok(Frame::new(width, height, image.into_raw()).with_color_space(self.color_space()))
+    }
+
+    /// Capture the monitor right after its next refresh, so UI tests do not catch a redraw
+    /// half way. Waits for the vblank through DRM on Linux, `IDXGIOutput::WaitForVBlank` on
+    /// Windows and a display link on macOS, errors where the platform cannot wait for it.
+    pub fn capture_next_frame(&self) -> XCapResult<Frame> {
+        // 自定义后端自己决定何时出帧
+        if self.custom_backend.is_none() {
+            ImplVblank::new(&self.impl_monitor)?.wait()?;
+        }
+
+        self.capture_frame()
+    }
+
+    /// Like [`Monitor::capture_frame`], converted as `options` say.
+    pub fn capture_frame_with(&self, options: &CaptureOptions) -> XCapResult<Frame> {
+        options.apply(self.capture_frame()?)
+    }
+
+    /// Capture `count` frames of the monitor, `interval` apart, each stamped with the time it
+    /// was taken. On Windows the frames are sampled from a [`VideoRecorder`] stream, repeating
+    /// the latest frame while the screen does not change. Elsewhere every frame is one
+    /// [`Monitor::capture_frame`]. The browser can not block for a burst, sample
+    /// [`VideoRecorder::on_frame`] there.
+    pub fn capture_burst(&self, count: usize, interval: Duration) -> XCapResult<Vec<Frame>> {
+        #[cfg(target_os = "windows")]
+        if self.custom_backend.is_none() {
+            match self.video_recorder() {
+                Ok(video_recorder) => {
+                    return stream_burst(&video_recorder, count, interval, || self.capture_frame())
+                }
+                Err(err) => {
+                    log::debug!("Stream burst failed, capturing frames one by one: {}", err)
+                }
+            }
+        }
+
+        capture_burst(count, interval, || self.capture_frame())
     }
 
     pub fn video_recorder(&self) -> XCapResult<VideoRecorder> {
+        if self.custom_backend.is_some() {
+            return self.custom_unsupported("VideoRecorder");
+        }
+
         let impl_video_recorder = self.impl_monitor.video_recorder()?;
 
         Ok(VideoRecorder::new(impl_video_recorder))
     }
+
+    #[cfg(target_os = "windows")]
+    pub(crate) fn video_recorder_on(&self, gpu_device: &GpuDevice) -> XCapResult<VideoRecorder> {
+        if self.custom_backend.is_some() {
+            return self.custom_unsupported("VideoRecorder");
+        }
+
+        let impl_video_recorder = self.impl_monitor.video_recorder_on(gpu_device)?;
+
+        Ok(VideoRecorder::new(impl_video_recorder))
+    }
 }
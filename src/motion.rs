@@ -0,0 +1,167 @@
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+use crate::{diff::count_changed, error::XCapResult, region::Region, video_recorder::Frame};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{video_recorder::VideoRecorder, Monitor};
+
+/// Decides whether a frame changed enough since the last reported frame.
+///
+/// A pixel counts as changed when any of its channels differs by more than
+/// `pixel_tolerance`, and a frame is reported when the changed pixels exceed
+/// `threshold` (a ratio between 0 and 1) of the watched area. Without regions
+/// the whole frame is watched.
+#[derive(Debug)]
+pub struct MotionDetector {
+    threshold: f32,
+    pixel_tolerance: u8,
+    regions: Vec<Region>,
+    previous: Mutex<Option<Frame>>,
+}
+
+impl MotionDetector {
+    pub fn new(threshold: f32) -> MotionDetector {
+        MotionDetector {
+            threshold: threshold.clamp(0.0, 1.0),
+            pixel_tolerance: 16,
+            regions: Vec::new(),
+            previous: Mutex::new(None),
+        }
+    }
+
+    /// Per channel difference ignored as noise, defaults to 16.
+    pub fn with_pixel_tolerance(mut self, pixel_tolerance: u8) -> MotionDetector {
+        self.pixel_tolerance = pixel_tolerance;
+        self
+    }
+
+    /// Only watch the given region, can be called several times.
+    pub fn with_region(mut self, region: Region) -> MotionDetector {
+        self.regions.push(region);
+        self
+    }
+
+    fn changed_ratio(&self, previous: &Frame, frame: &Frame) -> f32 {
+        let full_frame = [Region::new(0, 0, frame.width, frame.height)];
+        let regions = if self.regions.is_empty() {
+            &full_frame[..]
+        } else {
+            &self.regions[..]
+        };
+
+        let mut total = 0u64;
+        let mut changed = 0u64;
+
+        for region in regions
+            .iter()
+            .filter_map(|r| r.clamp(frame.width, frame.height))
+        {
+            changed += count_changed(
+                &previous.raw,
+                &frame.raw,
+                frame.width,
+                frame.pixel_format.bytes_per_pixel(),
+                &region,
+                self.pixel_tolerance,
+            );
+            total += region.width as u64 * region.height as u64;
+        }
+
+        if total == 0 {
+            return 0.0;
+        }
+
+        changed as f32 / total as f32
+    }
+
+    /// Returns `true` when `frame` should be reported, the first frame is always reported.
+    pub fn detect(&self, frame: &Frame) -> XCapResult<bool> {
+        let mut previous = self.previous.lock()?;
+
+        let is_motion = match previous.as_ref() {
+            Some(prev)
+                if prev.width == frame.width
+                    && prev.height == frame.height
+                    && prev.pixel_format == frame.pixel_format =>
+            {
+                self.changed_ratio(prev, frame) > self.threshold
+            }
+            _ => true,
+        };
+
+        // 与上一次上报的帧对比，缓慢的变化累积到阈值后也能被检测到
+        if is_motion {
+            *previous = Some(frame.clone());
+        }
+
+        Ok(is_motion)
+    }
+
+    /// Forget the reference frame, so the next frame is reported.
+    pub fn reset(&self) -> XCapResult<()> {
+        *self.previous.lock()? = None;
+
+        Ok(())
+    }
+}
+
+/// Stream `monitor` and call `on_change` on a background thread with the crop of `region`
+/// whenever it changed, see [`VideoRecorder::on_region_change`]. `region` is in the pixels of
+/// the monitor.
+///
+/// Returns the started recorder, [`VideoRecorder::stop`] pauses the watch and
+/// [`VideoRecorder::start`] resumes it. An error returned by `on_change` ends it. In the
+/// browser call [`VideoRecorder::on_region_change`] directly, there are no threads.
+///
+/// Only Windows streams monitors for now, Linux and macOS return an error.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn watch_region<F>(
+    monitor: &Monitor,
+    region: Region,
+    threshold: f32,
+    on_change: F,
+) -> XCapResult<VideoRecorder>
+where
+    F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+{
+    let video_recorder = monitor.video_recorder()?;
+
+    let watching_recorder = video_recorder.clone();
+    thread::spawn(move || {
+        if let Err(err) = watching_recorder.on_region_change(region, threshold, on_change) {
+            log::error!("Watch region {:?} failed: {}", region, err);
+        }
+    });
+    video_recorder.start()?;
+
+    Ok(video_recorder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PixelFormat;
+
+    #[test]
+    fn detect_changed_frames() {
+        let detector = MotionDetector::new(0.2).with_pixel_tolerance(0);
+
+        let frame = Frame::new(4, 4, vec![0; 64]);
+        assert!(detector.detect(&frame).unwrap());
+        assert!(!detector.detect(&frame).unwrap());
+
+        let mut raw = vec![0; 64];
+        raw[..16].fill(255);
+        assert!(detector.detect(&Frame::new(4, 4, raw)).unwrap());
+
+        // 格式变了按新画面处理，之后按每像素 1 字节比较
+        let mut gray = Frame::new(4, 4, vec![0; 16]);
+        gray.pixel_format = PixelFormat::Gray8;
+        assert!(detector.detect(&gray).unwrap());
+        gray.raw[..2].fill(255);
+        assert!(!detector.detect(&gray).unwrap());
+        gray.raw[..4].fill(255);
+        assert!(detector.detect(&gray).unwrap());
+    }
+}
@@ -0,0 +1,568 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    encoder::{EncodedPacket, VideoEncoder},
+    error::XCapResult,
+    pixel_format::PixelFormat,
+    sink::FrameSink,
+    video_recorder::Frame,
+    XCapError,
+};
+
+// Matroska element ids, https://www.matroska.org/technical/elements.html
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549A966;
+const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const CLUSTER: u32 = 0x1F43B675;
+const CLUSTER_TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+const VIDEO_TRACK: u8 = 1;
+const AUDIO_TRACK: u8 = 2;
+
+/// Block timestamps are 16 bit offsets from the cluster, in milliseconds.
+const MAX_CLUSTER_DURATION: u64 = 30_000;
+
+fn write_id(buf: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().position(|byte| *byte != 0).unwrap_or(3);
+    buf.extend_from_slice(&bytes[skip..]);
+}
+
+/// The shortest EBML variable length integer holding `size`.
+fn write_size(buf: &mut Vec<u8>, size: u64) {
+    let length = (1..=8)
+        .find(|length| size < (1 << (7 * length)) - 1)
+        .unwrap_or(8);
+    let marked = size | (1 << (7 * length));
+    buf.extend_from_slice(&marked.to_be_bytes()[8 - length..]);
+}
+
+fn write_element(buf: &mut Vec<u8>, id: u32, body: &[u8]) {
+    write_id(buf, id);
+    write_size(buf, body.len() as u64);
+    buf.extend_from_slice(body);
+}
+
+fn write_uint(buf: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().position(|byte| *byte != 0).unwrap_or(7);
+    write_element(buf, id, &bytes[skip..]);
+}
+
+fn write_float(buf: &mut Vec<u8>, id: u32, value: f64) {
+    write_element(buf, id, &value.to_be_bytes());
+}
+
+/// The Matroska codec of a [`VideoEncoder`] FourCC.
+fn video_codec_id(fourcc: [u8; 4]) -> XCapResult<&'static str> {
+    match &fourcc {
+        b"AV01" => Ok("V_AV1"),
+        b"VP80" => Ok("V_VP8"),
+        b"VP90" => Ok("V_VP9"),
+        b"H264" | b"AVC1" => Ok("V_MPEG4/ISO/AVC"),
+        b"HEVC" | b"HVC1" => Ok("V_MPEGH/ISO/HEVC"),
+        _ => Err(XCapError::new(format!(
+            "No Matroska codec for {}",
+            String::from_utf8_lossy(&fourcc)
+        ))),
+    }
+}
+
+/// An encoded audio track for a [`MatroskaMuxer`]. xcap does not capture audio, the
+/// application encodes it and passes the packets to [`MatroskaMuxer::write_audio`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioTrack {
+    /// The Matroska codec, e.g. `A_OPUS`, `A_AAC` or `A_PCM/INT/LIT`.
+    pub codec_id: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// The codec configuration, e.g. the `OpusHead` for Opus.
+    pub codec_private: Option<Vec<u8>>,
+}
+
+impl AudioTrack {
+    pub fn new<C: ToString>(codec_id: C, sample_rate: u32, channels: u16) -> AudioTrack {
+        AudioTrack {
+            codec_id: codec_id.to_string(),
+            sample_rate,
+            channels,
+            codec_private: None,
+        }
+    }
+
+    pub fn with_codec_private(mut self, codec_private: Vec<u8>) -> AudioTrack {
+        self.codec_private = Some(codec_private);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Block {
+    track: u8,
+    timestamp: u64,
+    is_keyframe: bool,
+    data: Vec<u8>,
+}
+
+/// Writes encoded video and audio packets into a Matroska (`.mkv`) container.
+///
+/// Packets of each track are passed in decoding order with their presentation timestamp,
+/// relative to the start of the recording. The muxer buffers them until the other track has
+/// caught up, and interleaves them by timestamp. With an audio track, video is held back
+/// until audio up to the same time arrives, so both tracks must keep flowing. For MP4, remux
+/// the file with `ffmpeg -i record.mkv -c copy record.mp4`.
+#[derive(Debug)]
+pub struct MatroskaMuxer<W: Write + Seek> {
+    writer: W,
+    has_audio: bool,
+    segment_start: u64,
+    duration_position: u64,
+    video: VecDeque<Block>,
+    audio: VecDeque<Block>,
+    cluster: Option<(u64, Vec<u8>)>,
+    duration: u64,
+}
+
+impl<W: Write + Seek> MatroskaMuxer<W> {
+    /// Write the header for a `width` x `height` video from `encoder`, which must be
+    /// configured already, and an optional audio track.
+    pub fn new(
+        mut writer: W,
+        encoder: &dyn VideoEncoder,
+        width: u32,
+        height: u32,
+        audio: Option<AudioTrack>,
+    ) -> XCapResult<MatroskaMuxer<W>> {
+        let mut header = Vec::new();
+        let mut ebml = Vec::new();
+        write_uint(&mut ebml, EBML_VERSION, 1);
+        write_uint(&mut ebml, EBML_READ_VERSION, 1);
+        write_uint(&mut ebml, EBML_MAX_ID_LENGTH, 4);
+        write_uint(&mut ebml, EBML_MAX_SIZE_LENGTH, 8);
+        write_element(&mut ebml, DOC_TYPE, b"matroska");
+        write_uint(&mut ebml, DOC_TYPE_VERSION, 4);
+        write_uint(&mut ebml, DOC_TYPE_READ_VERSION, 2);
+        write_element(&mut header, EBML, &ebml);
+
+        // Segment 的长度在结束时回填，先写 8 字节的未知长度
+        write_id(&mut header, SEGMENT);
+        header.extend_from_slice(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let segment_start = header.len() as u64;
+
+        let mut info = Vec::new();
+        write_uint(&mut info, TIMESTAMP_SCALE, 1_000_000);
+        let app = concat!("xcap ", env!("CARGO_PKG_VERSION"));
+        write_element(&mut info, MUXING_APP, app.as_bytes());
+        write_element(&mut info, WRITING_APP, app.as_bytes());
+        write_float(&mut info, DURATION, 0.0);
+        write_element(&mut header, INFO, &info);
+        // Duration 是 Info 的最后一个元素
+        let duration_position = header.len() as u64 - 8;
+
+        let mut video_track = Vec::new();
+        write_uint(&mut video_track, TRACK_NUMBER, VIDEO_TRACK as u64);
+        write_uint(&mut video_track, TRACK_UID, VIDEO_TRACK as u64);
+        write_uint(&mut video_track, TRACK_TYPE, 1);
+        write_uint(&mut video_track, FLAG_LACING, 0);
+        write_element(
+            &mut video_track,
+            CODEC_ID,
+            video_codec_id(encoder.fourcc())?.as_bytes(),
+        );
+        if let Some(codec_private) = encoder.codec_private() {
+            write_element(&mut video_track, CODEC_PRIVATE, &codec_private);
+        }
+        let mut video = Vec::new();
+        write_uint(&mut video, PIXEL_WIDTH, width as u64);
+        write_uint(&mut video, PIXEL_HEIGHT, height as u64);
+        write_element(&mut video_track, VIDEO, &video);
+
+        let mut tracks = Vec::new();
+        write_element(&mut tracks, TRACK_ENTRY, &video_track);
+
+        if let Some(audio) = &audio {
+            let mut audio_track = Vec::new();
+            write_uint(&mut audio_track, TRACK_NUMBER, AUDIO_TRACK as u64);
+            write_uint(&mut audio_track, TRACK_UID, AUDIO_TRACK as u64);
+            write_uint(&mut audio_track, TRACK_TYPE, 2);
+            write_uint(&mut audio_track, FLAG_LACING, 0);
+            write_element(&mut audio_track, CODEC_ID, audio.codec_id.as_bytes());
+            if let Some(codec_private) = &audio.codec_private {
+                write_element(&mut audio_track, CODEC_PRIVATE, codec_private);
+            }
+            let mut audio_settings = Vec::new();
+            write_float(
+                &mut audio_settings,
+                SAMPLING_FREQUENCY,
+                audio.sample_rate as f64,
+            );
+            write_uint(&mut audio_settings, CHANNELS, audio.channels as u64);
+            write_element(&mut audio_track, AUDIO, &audio_settings);
+
+            write_element(&mut tracks, TRACK_ENTRY, &audio_track);
+        }
+        write_element(&mut header, TRACKS, &tracks);
+
+        let offset = writer.stream_position()?;
+        writer.write_all(&header)?;
+
+        Ok(MatroskaMuxer {
+            writer,
+            has_audio: audio.is_some(),
+            segment_start: offset + segment_start,
+            duration_position: offset + duration_position,
+            video: VecDeque::new(),
+            audio: VecDeque::new(),
+            cluster: None,
+            duration: 0,
+        })
+    }
+
+    pub fn write_video(&mut self, packet: &EncodedPacket, timestamp: Duration) -> XCapResult<()> {
+        self.video.push_back(Block {
+            track: VIDEO_TRACK,
+            timestamp: timestamp.as_millis() as u64,
+            is_keyframe: packet.is_keyframe,
+            data: packet.data.clone(),
+        });
+
+        self.interleave(false)
+    }
+
+    pub fn write_audio(&mut self, data: &[u8], timestamp: Duration) -> XCapResult<()> {
+        if !self.has_audio {
+            return Err(XCapError::new("The muxer has no audio track"));
+        }
+
+        self.audio.push_back(Block {
+            track: AUDIO_TRACK,
+            timestamp: timestamp.as_millis() as u64,
+            // 音频帧都可以独立解码
+            is_keyframe: true,
+            data: data.to_vec(),
+        });
+
+        self.interleave(false)
+    }
+
+    /// Write the buffered packets and complete the file, returns the writer.
+    pub fn finish(mut self) -> XCapResult<W> {
+        self.interleave(true)?;
+        self.write_cluster()?;
+
+        let end = self.writer.stream_position()?;
+        let segment_size = end - self.segment_start;
+        self.writer.seek(SeekFrom::Start(self.segment_start - 8))?;
+        self.writer
+            .write_all(&(segment_size | (1 << 56)).to_be_bytes())?;
+        self.writer.seek(SeekFrom::Start(self.duration_position))?;
+        self.writer
+            .write_all(&(self.duration as f64).to_be_bytes())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    /// Move the packets to clusters in timestamp order, as far as both tracks are known, or
+    /// all of them when `flush`.
+    fn interleave(&mut self, flush: bool) -> XCapResult<()> {
+        loop {
+            let block = match (self.video.front(), self.audio.front()) {
+                (Some(video), Some(audio)) if audio.timestamp < video.timestamp => {
+                    self.audio.pop_front()
+                }
+                (Some(_), Some(_)) => self.video.pop_front(),
+                (Some(_), None) if flush || !self.has_audio => self.video.pop_front(),
+                (None, Some(_)) if flush => self.audio.pop_front(),
+                _ => None,
+            };
+
+            match block {
+                Some(block) => self.write_block(block)?,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn write_block(&mut self, block: Block) -> XCapResult<()> {
+        let starts_cluster = match &self.cluster {
+            None => true,
+            Some((cluster_timestamp, _)) => {
+                (block.track == VIDEO_TRACK && block.is_keyframe)
+                    || block.timestamp < *cluster_timestamp
+                    || block.timestamp - cluster_timestamp > MAX_CLUSTER_DURATION
+            }
+        };
+        if starts_cluster {
+            self.write_cluster()?;
+            let mut body = Vec::new();
+            write_uint(&mut body, CLUSTER_TIMESTAMP, block.timestamp);
+            self.cluster = Some((block.timestamp, body));
+        }
+
+        if let Some((cluster_timestamp, body)) = &mut self.cluster {
+            let mut simple_block = vec![0x80 | block.track];
+            let offset = (block.timestamp - *cluster_timestamp) as i16;
+            simple_block.extend_from_slice(&offset.to_be_bytes());
+            simple_block.push(if block.is_keyframe { 0x80 } else { 0 });
+            simple_block.extend_from_slice(&block.data);
+            write_element(body, SIMPLE_BLOCK, &simple_block);
+        }
+        self.duration = self.duration.max(block.timestamp);
+
+        Ok(())
+    }
+
+    fn write_cluster(&mut self) -> XCapResult<()> {
+        if let Some((_, body)) = self.cluster.take() {
+            let mut cluster = Vec::new();
+            write_element(&mut cluster, CLUSTER, &body);
+            self.writer.write_all(&cluster)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records frames to a Matroska (`.mkv`) file with a [`VideoEncoder`], optionally with an
+/// audio track the application encodes, without a system `ffmpeg`.
+///
+/// Timestamps come from [`Frame::timestamp`] and the time passed to
+/// [`MatroskaSink::write_audio`], relative to the first frame. Audio before the first frame is
+/// dropped. Share the sink between the capture and the audio thread with a mutex.
+#[derive(Debug)]
+pub struct MatroskaSink<E: VideoEncoder> {
+    path: PathBuf,
+    encoder: E,
+    frame_rate: u32,
+    audio: Option<AudioTrack>,
+    muxer: Option<MatroskaMuxer<BufWriter<File>>>,
+    size: (u32, u32),
+    start: SystemTime,
+    frame_count: u64,
+    frame_timestamps: BTreeMap<u64, Duration>,
+}
+
+impl<E: VideoEncoder> MatroskaSink<E> {
+    pub fn new<P: AsRef<Path>>(path: P, encoder: E) -> MatroskaSink<E> {
+        MatroskaSink {
+            path: path.as_ref().to_path_buf(),
+            encoder,
+            frame_rate: 30,
+            audio: None,
+            muxer: None,
+            size: (0, 0),
+            start: SystemTime::UNIX_EPOCH,
+            frame_count: 0,
+            frame_timestamps: BTreeMap::new(),
+        }
+    }
+
+    /// The frame rate the encoder is configured with, defaults to 30. Frames keep their own
+    /// timestamps.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> MatroskaSink<E> {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    pub fn with_audio(mut self, audio: AudioTrack) -> MatroskaSink<E> {
+        self.audio = Some(audio);
+        self
+    }
+
+    /// Write one encoded audio packet captured at `timestamp`.
+    pub fn write_audio(&mut self, data: &[u8], timestamp: SystemTime) -> XCapResult<()> {
+        let Some(muxer) = &mut self.muxer else {
+            return Ok(());
+        };
+        let Ok(timestamp) = timestamp.duration_since(self.start) else {
+            return Ok(());
+        };
+
+        muxer.write_audio(data, timestamp)
+    }
+
+    fn start(&mut self, frame: &Frame) -> XCapResult<()> {
+        self.encoder
+            .configure(frame.width, frame.height, self.frame_rate)?;
+
+        let writer = BufWriter::new(File::create(&self.path)?);
+        let muxer = MatroskaMuxer::new(
+            writer,
+            &self.encoder,
+            frame.width,
+            frame.height,
+            self.audio.clone(),
+        )?;
+
+        self.muxer = Some(muxer);
+        self.size = (frame.width, frame.height);
+        self.start = frame.timestamp;
+        self.frame_count = 0;
+        self.frame_timestamps.clear();
+
+        Ok(())
+    }
+
+    fn write_packets(&mut self, packets: Vec<EncodedPacket>) -> XCapResult<()> {
+        let muxer = self
+            .muxer
+            .as_mut()
+            .ok_or_else(|| XCapError::new("MatroskaSink is finished"))?;
+
+        for packet in packets {
+            let timestamp = self
+                .frame_timestamps
+                .remove(&packet.pts)
+                .unwrap_or_else(|| Duration::from_secs(packet.pts) / self.frame_rate);
+            muxer.write_video(&packet, timestamp)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: VideoEncoder> FrameSink for MatroskaSink<E> {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        if frame.pixel_format != PixelFormat::Rgba8 {
+            return Err(XCapError::new("Only RGBA frames can be encoded"));
+        }
+
+        if self.muxer.is_none() {
+            self.start(frame)?;
+        } else if self.size != (frame.width, frame.height) {
+            return Err(XCapError::new(format!(
+                "Frame size {}x{} differs from video size {}x{}",
+                frame.width, frame.height, self.size.0, self.size.1
+            )));
+        }
+
+        // 时钟回拨时沿用上一帧的时间
+        let timestamp = frame
+            .timestamp
+            .duration_since(self.start)
+            .unwrap_or_default();
+        self.frame_timestamps.insert(self.frame_count, timestamp);
+        self.frame_count += 1;
+
+        let packets = self.encoder.encode(frame)?;
+        self.write_packets(packets)
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        if self.muxer.is_none() {
+            return Ok(());
+        }
+
+        let packets = self.encoder.finish()?;
+        self.write_packets(packets)?;
+
+        if let Some(muxer) = self.muxer.take() {
+            muxer.finish()?.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: VideoEncoder> Drop for MatroskaSink<E> {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            log::error!("MatroskaSink finish failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[derive(Debug)]
+    struct Vp8;
+
+    impl VideoEncoder for Vp8 {
+        fn fourcc(&self) -> [u8; 4] {
+            *b"VP80"
+        }
+
+        fn configure(&mut self, _: u32, _: u32, _: u32) -> XCapResult<()> {
+            Ok(())
+        }
+
+        fn encode(&mut self, _: &Frame) -> XCapResult<Vec<EncodedPacket>> {
+            Ok(Vec::new())
+        }
+
+        fn finish(&mut self) -> XCapResult<Vec<EncodedPacket>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn interleave_by_timestamp() {
+        let audio = AudioTrack::new("A_OPUS", 48000, 2);
+        let mut muxer =
+            MatroskaMuxer::new(Cursor::new(Vec::new()), &Vp8, 4, 4, Some(audio)).unwrap();
+
+        let packet = |data: &[u8], is_keyframe| EncodedPacket {
+            data: data.to_vec(),
+            pts: 0,
+            is_keyframe,
+        };
+        muxer
+            .write_video(&packet(b"video-0", true), Duration::ZERO)
+            .unwrap();
+        muxer
+            .write_video(&packet(b"video-1", false), Duration::from_millis(40))
+            .unwrap();
+        muxer
+            .write_audio(b"audio-0", Duration::from_millis(20))
+            .unwrap();
+        let file = muxer.finish().unwrap().into_inner();
+
+        let position = |needle: &[u8]| {
+            file.windows(needle.len())
+                .position(|window| window == needle)
+        };
+        assert!(position(b"video-0") < position(b"audio-0"));
+        assert!(position(b"audio-0") < position(b"video-1"));
+
+        // 40 字节的 EBML 头之后是 Segment
+        let segment_size = u64::from_be_bytes(file[44..52].try_into().unwrap()) & !(1 << 56);
+        assert_eq!(segment_size as usize, file.len() - 52);
+    }
+}
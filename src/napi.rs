@@ -0,0 +1,114 @@
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+use crate::{Monitor, Window, XCapError};
+
+fn to_napi_error(err: XCapError) -> napi::Error {
+    napi::Error::from_reason(err.to_string())
+}
+
+#[napi(object, js_name = "Monitor")]
+pub struct JsMonitor {
+    pub id: u32,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub rotation: f64,
+    pub scale_factor: f64,
+    pub frequency: f64,
+    pub is_primary: bool,
+}
+
+impl From<&Monitor> for JsMonitor {
+    fn from(monitor: &Monitor) -> Self {
+        JsMonitor {
+            id: monitor.id(),
+            name: monitor.name().to_string(),
+            x: monitor.x(),
+            y: monitor.y(),
+            width: monitor.width(),
+            height: monitor.height(),
+            rotation: monitor.rotation() as f64,
+            scale_factor: monitor.scale_factor() as f64,
+            frequency: monitor.frequency() as f64,
+            is_primary: monitor.is_primary(),
+        }
+    }
+}
+
+#[napi(object, js_name = "Window")]
+pub struct JsWindow {
+    pub id: u32,
+    pub app_name: String,
+    pub title: String,
+    pub pid: u32,
+    pub monitor_id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub width: u32,
+    pub height: u32,
+    pub is_minimized: bool,
+    pub is_maximized: bool,
+    pub is_focused: bool,
+}
+
+impl From<&Window> for JsWindow {
+    fn from(window: &Window) -> Self {
+        JsWindow {
+            id: window.id(),
+            app_name: window.app_name().to_string(),
+            title: window.title().to_string(),
+            pid: window.pid(),
+            monitor_id: window.current_monitor().id(),
+            x: window.x(),
+            y: window.y(),
+            z: window.z(),
+            width: window.width(),
+            height: window.height(),
+            is_minimized: window.is_minimized(),
+            is_maximized: window.is_maximized(),
+            is_focused: window.is_focused(),
+        }
+    }
+}
+
+#[napi]
+pub fn monitors() -> napi::Result<Vec<JsMonitor>> {
+    let monitors = Monitor::all().map_err(to_napi_error)?;
+
+    Ok(monitors.iter().map(JsMonitor::from).collect())
+}
+
+#[napi]
+pub fn windows() -> napi::Result<Vec<JsWindow>> {
+    let windows = Window::all().map_err(to_napi_error)?;
+
+    Ok(windows.iter().map(JsWindow::from).collect())
+}
+
+/// Capture the monitor `id` as a PNG buffer.
+#[napi]
+pub fn capture_monitor(id: u32) -> napi::Result<Buffer> {
+    let monitor = Monitor::all()
+        .map_err(to_napi_error)?
+        .into_iter()
+        .find(|monitor| monitor.id() == id)
+        .ok_or_else(|| napi::Error::from_reason(format!("Monitor {} not found", id)))?;
+
+    Ok(monitor.capture_png().map_err(to_napi_error)?.into())
+}
+
+/// Capture the window `id` as a PNG buffer.
+#[napi]
+pub fn capture_window(id: u32) -> napi::Result<Buffer> {
+    let window = Window::all()
+        .map_err(to_napi_error)?
+        .into_iter()
+        .find(|window| window.id() == id)
+        .ok_or_else(|| napi::Error::from_reason(format!("Window {} not found", id)))?;
+
+    Ok(window.capture_png().map_err(to_napi_error)?.into())
+}
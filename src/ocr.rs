@@ -0,0 +1,219 @@
+use image::{imageops, GrayImage, RgbaImage};
+
+use crate::{error::XCapResult, pixel_format::PixelFormat, video_recorder::Frame, XCapError};
+
+/// How [`OcrPreprocessor`] turns gray pixels into black and white.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binarize {
+    /// Pick the threshold that separates the histogram best, per image.
+    Otsu,
+    /// Pixels brighter than the value turn white.
+    Threshold(u8),
+}
+
+/// Prepares captures for OCR engines such as tesseract, which read small anti-aliased UI text
+/// much better once it is gray, high contrast and larger.
+///
+/// The stages run in order: grayscale, contrast stretch, upscale, binarize. The output is
+/// always [`PixelFormat::Gray8`]. Use it on single captures with [`OcrPreprocessor::process`],
+/// or per stream with [`crate::CaptureOptions::with_ocr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OcrPreprocessor {
+    contrast_stretch: bool,
+    upscale: u32,
+    binarize: Option<Binarize>,
+}
+
+impl Default for OcrPreprocessor {
+    fn default() -> Self {
+        OcrPreprocessor {
+            contrast_stretch: true,
+            upscale: 2,
+            binarize: None,
+        }
+    }
+}
+
+impl OcrPreprocessor {
+    pub fn new() -> OcrPreprocessor {
+        OcrPreprocessor::default()
+    }
+
+    /// Stretch the gray levels so the darkest and brightest 1% become black and white,
+    /// defaults to `true`.
+    pub fn with_contrast_stretch(mut self, contrast_stretch: bool) -> OcrPreprocessor {
+        self.contrast_stretch = contrast_stretch;
+        self
+    }
+
+    /// Scale the image up by `factor` between 1 and 4, defaults to 2. OCR engines expect glyphs
+    /// of 20 to 30 pixels, screen text is usually about half of that.
+    pub fn with_upscale(mut self, factor: u32) -> OcrPreprocessor {
+        self.upscale = factor.clamp(1, 4);
+        self
+    }
+
+    /// Turn the image into black and white, off by default, most engines binarize on their own.
+    pub fn with_binarize(mut self, binarize: Option<Binarize>) -> OcrPreprocessor {
+        self.binarize = binarize;
+        self
+    }
+
+    #[cfg(feature = "image")]
+    pub fn process(&self, image: &RgbaImage) -> GrayImage {
+        self.preprocess(image)
+    }
+
+    fn preprocess(&self, image: &RgbaImage) -> GrayImage {
+        let mut gray = imageops::grayscale(image);
+
+        if self.contrast_stretch {
+            stretch_contrast(&mut gray);
+        }
+
+        if self.upscale > 1 {
+            gray = imageops::resize(
+                &gray,
+                gray.width() * self.upscale,
+                gray.height() * self.upscale,
+                imageops::FilterType::CatmullRom,
+            );
+        }
+
+        if let Some(binarize) = self.binarize {
+            let threshold = match binarize {
+                Binarize::Otsu => otsu_threshold(&histogram(&gray)),
+                Binarize::Threshold(threshold) => threshold,
+            };
+            for pixel in gray.iter_mut() {
+                *pixel = if *pixel > threshold { 255 } else { 0 };
+            }
+        }
+
+        gray
+    }
+
+    /// Process an RGBA frame into a [`PixelFormat::Gray8`] frame.
+    pub fn process_frame(&self, frame: Frame) -> XCapResult<Frame> {
+        if frame.pixel_format != PixelFormat::Rgba8 {
+            return Err(XCapError::new("Only RGBA frames can be preprocessed"));
+        }
+
+        let image = RgbaImage::from_raw(frame.width, frame.height, frame.raw)
+            .ok_or_else(|| XCapError::new("Frame size does not match its data"))?;
+        let gray = self.preprocess(&image);
+
+        Ok(Frame {
+            width: gray.width(),
+            height: gray.height(),
+            raw: gray.into_raw(),
+            pixel_format: PixelFormat::Gray8,
+            ..frame
+        })
+    }
+}
+
+fn histogram(gray: &GrayImage) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    for pixel in gray.iter() {
+        histogram[*pixel as usize] += 1;
+    }
+
+    histogram
+}
+
+fn stretch_contrast(gray: &mut GrayImage) {
+    let histogram = histogram(gray);
+    let total: u64 = histogram.iter().sum();
+    // 忽略最暗和最亮的 1%，避免个别噪点让拉伸失效
+    let clip = total / 100;
+
+    let mut count = 0;
+    let low = histogram
+        .iter()
+        .position(|bin| {
+            count += bin;
+            count > clip
+        })
+        .unwrap_or(0);
+    let mut count = 0;
+    let high = 255
+        - histogram
+            .iter()
+            .rev()
+            .position(|bin| {
+                count += bin;
+                count > clip
+            })
+            .unwrap_or(0);
+
+    if high <= low {
+        return;
+    }
+
+    let range = (high - low) as u32;
+    for pixel in gray.iter_mut() {
+        let value = (*pixel as usize).clamp(low, high) - low;
+        *pixel = (value as u32 * 255 / range) as u8;
+    }
+}
+
+/// The threshold maximizing the between-class variance.
+fn otsu_threshold(histogram: &[u64; 256]) -> u8 {
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(value, count)| value as f64 * *count as f64)
+        .sum();
+
+    let mut background = 0u64;
+    let mut background_sum = 0.0;
+    let mut best = (0.0, 0u8);
+
+    for (value, count) in histogram.iter().enumerate() {
+        background += count;
+        if background == 0 {
+            continue;
+        }
+        let foreground = total - background;
+        if foreground == 0 {
+            break;
+        }
+
+        background_sum += value as f64 * *count as f64;
+        let background_mean = background_sum / background as f64;
+        let foreground_mean = (sum - background_sum) / foreground as f64;
+        let variance =
+            background as f64 * foreground as f64 * (background_mean - foreground_mean).powi(2);
+
+        if variance > best.0 {
+            best = (variance, value as u8);
+        }
+    }
+
+    best.1
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn binarize_low_contrast_text() {
+        // 浅灰底上的深灰字
+        let mut image = RgbaImage::from_pixel(10, 10, Rgba([150, 150, 150, 255]));
+        for x in 2..8 {
+            image.put_pixel(x, 5, Rgba([110, 110, 110, 255]));
+        }
+
+        let gray = OcrPreprocessor::new()
+            .with_upscale(1)
+            .with_binarize(Some(Binarize::Otsu))
+            .process(&image);
+
+        assert_eq!(gray.get_pixel(4, 5).0, [0]);
+        assert_eq!(gray.get_pixel(4, 2).0, [255]);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{error::XCapResult, ocr::OcrPreprocessor, video_recorder::Frame};
+
+/// The pixel layout of [`crate::Frame::raw`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PixelFormat {
+    /// 4 bytes per pixel, red, green, blue and alpha.
+    #[default]
+    Rgba8,
+    /// 1 byte of luma per pixel, BT.601 weighted, e.g. for OCR.
+    Gray8,
+    /// 2 bytes per pixel, little endian with 5 bits red, 6 bits green and 5 bits blue, e.g. for
+    /// embedded displays.
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8 => 4,
+            PixelFormat::Gray8 => 1,
+            PixelFormat::Rgb565 => 2,
+        }
+    }
+
+    /// Convert RGBA pixels to this format.
+    pub(crate) fn convert_rgba(&self, rgba: Vec<u8>) -> Vec<u8> {
+        match self {
+            PixelFormat::Rgba8 => rgba,
+            // 定点数的 BT.601 权重，77 + 150 + 29 = 256
+            PixelFormat::Gray8 => rgba
+                .chunks_exact(4)
+                .map(|pixel| {
+                    ((77 * pixel[0] as u32 + 150 * pixel[1] as u32 + 29 * pixel[2] as u32) >> 8)
+                        as u8
+                })
+                .collect(),
+            PixelFormat::Rgb565 => rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    let rgb565 = ((pixel[0] as u16 >> 3) << 11)
+                        | ((pixel[1] as u16 >> 2) << 5)
+                        | (pixel[2] as u16 >> 3);
+                    rgb565.to_le_bytes()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Options of the frames a capture produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CaptureOptions {
+    pixel_format: PixelFormat,
+    ocr: Option<OcrPreprocessor>,
+}
+
+impl CaptureOptions {
+    pub fn new() -> CaptureOptions {
+        CaptureOptions::default()
+    }
+
+    /// The layout of the frame pixels, defaults to [`PixelFormat::Rgba8`]. The conversion runs
+    /// once per frame after all other processing.
+    pub fn with_pixel_format(mut self, pixel_format: PixelFormat) -> CaptureOptions {
+        self.pixel_format = pixel_format;
+        self
+    }
+
+    /// Preprocess the frames for OCR, they are [`PixelFormat::Gray8`] then and the pixel
+    /// format is ignored.
+    pub fn with_ocr(mut self, ocr: OcrPreprocessor) -> CaptureOptions {
+        self.ocr = Some(ocr);
+        self
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    pub fn ocr(&self) -> Option<OcrPreprocessor> {
+        self.ocr
+    }
+
+    /// Process a captured RGBA frame as the options say.
+    pub(crate) fn apply(&self, frame: Frame) -> XCapResult<Frame> {
+        match self.ocr {
+            Some(ocr) => ocr.process_frame(frame),
+            None => frame.to_pixel_format(self.pixel_format),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_rgba_pixels() {
+        let rgba = vec![255, 255, 255, 255, 255, 0, 0, 255, 0, 0, 255, 255];
+
+        assert_eq!(PixelFormat::Gray8.convert_rgba(rgba.clone()), [255, 76, 28]);
+        assert_eq!(
+            PixelFormat::Rgb565.convert_rgba(rgba),
+            [0xff, 0xff, 0x00, 0xf8, 0x1f, 0x00]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{error::XCapResult, platform::impl_pointer::ImplPointer};
+
+/// A cursor. X11 has one per XInput 2 master pointer, e.g. one per seat of a multi-seat kiosk
+/// or per MPX pointer, Windows and macOS a single one.
+#[derive(Debug, Clone)]
+pub struct Pointer {
+    impl_pointer: ImplPointer,
+}
+
+impl Pointer {
+    /// List all pointers, empty in the browser.
+    pub fn all() -> XCapResult<Vec<Pointer>> {
+        let pointers = ImplPointer::all()?
+            .into_iter()
+            .map(|impl_pointer| Pointer { impl_pointer })
+            .collect();
+
+        Ok(pointers)
+    }
+
+    /// The XInput 2 device id on X11, 0 for the only pointer elsewhere.
+    pub fn id(&self) -> u32 {
+        self.impl_pointer.id
+    }
+    /// The device name, e.g. `Virtual core pointer`.
+    pub fn name(&self) -> &str {
+        &self.impl_pointer.name
+    }
+    /// The current position, in the coordinates of [`Window::x`](crate::Window::x) and
+    /// [`Window::y`](crate::Window::y).
+    pub fn position(&self) -> XCapResult<(i32, i32)> {
+        self.impl_pointer.position()
+    }
+}
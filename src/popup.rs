@@ -0,0 +1,228 @@
+use std::{
+    collections::HashSet,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use image::RgbaImage;
+
+use crate::{
+    error::{XCapError, XCapResult},
+    platform::impl_watcher::ImplWatcher,
+    Window, WindowKind, XCapContext,
+};
+
+/// A popup captured by [`PopupCatcher`] right after it appeared.
+#[derive(Debug, Clone)]
+pub struct PopupCapture {
+    pub window: Window,
+    pub image: RgbaImage,
+    /// When the popup was found.
+    pub timestamp: SystemTime,
+}
+
+/// Captures menus, tooltips and other popups as soon as they are mapped, before they close
+/// again. Polling [`Window::all`] and capturing later misses them: a context menu is gone once
+/// the user clicks.
+///
+/// Popups are new windows owned by another window (transient for it on X11) or of the
+/// [`WindowKind::Menu`] and [`WindowKind::Tooltip`] kinds, on X11 including the
+/// override-redirect windows, see [`XCapContext::with_override_redirect`]. The windows are
+/// re-enumerated as soon as the platform reports a change, like [`crate::Watcher`] does, and
+/// every poll interval otherwise.
+#[derive(Debug, Clone)]
+pub struct PopupCatcher {
+    poll_interval: Duration,
+    pid: Option<u32>,
+}
+
+impl Default for PopupCatcher {
+    fn default() -> Self {
+        PopupCatcher {
+            poll_interval: Duration::from_millis(50),
+            pid: None,
+        }
+    }
+}
+
+impl PopupCatcher {
+    pub fn new() -> PopupCatcher {
+        PopupCatcher::default()
+    }
+
+    /// How often to re-enumerate when the platform reports nothing, defaults to 50
+    /// milliseconds. macOS only polls.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> PopupCatcher {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Only catch the popups of the process `pid`. Popups without a pid, e.g. X11 menus that
+    /// do not set `_NET_WM_PID`, are caught regardless.
+    pub fn with_pid(mut self, pid: u32) -> PopupCatcher {
+        self.pid = Some(pid);
+        self
+    }
+
+    fn is_popup(&self, window: &Window) -> bool {
+        self.matches(
+            window.owner_id(),
+            window.kind(),
+            window.pid(),
+            window.is_visible(),
+        )
+    }
+
+    fn matches(&self, owner_id: Option<u32>, kind: WindowKind, pid: u32, is_visible: bool) -> bool {
+        let is_popup = owner_id.is_some() || matches!(kind, WindowKind::Menu | WindowKind::Tooltip);
+        let is_pid = match self.pid {
+            Some(filter_pid) => pid == filter_pid || pid == 0,
+            None => true,
+        };
+
+        is_popup && is_pid && is_visible
+    }
+
+    /// The popups that appeared since the last call, `known` holds the ids of the windows
+    /// seen before.
+    fn new_popups(&self, known: &mut HashSet<u32>) -> XCapResult<Vec<Window>> {
+        let windows = XCapContext::new().with_override_redirect(true).windows()?;
+
+        let popups = windows
+            .iter()
+            .filter(|window| !known.contains(&window.id()) && self.is_popup(window))
+            .cloned()
+            .collect();
+        *known = windows.iter().map(|window| window.id()).collect();
+
+        Ok(popups)
+    }
+
+    fn known_windows() -> XCapResult<HashSet<u32>> {
+        let windows = XCapContext::new().with_override_redirect(true).windows()?;
+
+        Ok(windows.iter().map(|window| window.id()).collect())
+    }
+
+    /// Run `trigger`, e.g. a right click sent to the application, then wait for the popup it
+    /// opens and capture it. Errors when no popup appears within `timeout`.
+    pub fn capture_after<T>(&self, trigger: T, timeout: Duration) -> XCapResult<PopupCapture>
+    where
+        T: FnOnce() -> XCapResult<()>,
+    {
+        // 触发之前的窗口不算新弹出的
+        let mut known = PopupCatcher::known_windows()?;
+        let mut impl_watcher = ImplWatcher::new();
+        trigger()?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            for popup in self.new_popups(&mut known)? {
+                match popup.capture_image() {
+                    Ok(image) => {
+                        return Ok(PopupCapture {
+                            window: popup,
+                            image,
+                            timestamp: SystemTime::now(),
+                        })
+                    }
+                    // 弹出窗口可能已经关闭，继续等下一个
+                    Err(err) => log::debug!("Capture popup {} failed: {}", popup.id(), err),
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(XCapError::new("Wait for popup timed out"));
+            }
+            impl_watcher.wait(remaining.min(self.poll_interval));
+        }
+    }
+
+    /// Watch for popups in a background thread and deliver each one to `on_popup` until the
+    /// handle is stopped.
+    pub fn start<F>(self, on_popup: F) -> XCapResult<PopupCatcherHandle>
+    where
+        F: FnMut(PopupCapture) + Send + 'static,
+    {
+        let known = PopupCatcher::known_windows()?;
+        let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+        let join_handle = thread::spawn(move || self.run(known, stop_receiver, on_popup));
+
+        Ok(PopupCatcherHandle {
+            stop_sender,
+            join_handle,
+        })
+    }
+
+    fn run<F>(self, mut known: HashSet<u32>, stop_receiver: Receiver<()>, mut on_popup: F)
+    where
+        F: FnMut(PopupCapture),
+    {
+        let mut impl_watcher = ImplWatcher::new();
+
+        while let Err(TryRecvError::Empty) = stop_receiver.try_recv() {
+            let popups = match self.new_popups(&mut known) {
+                Ok(popups) => popups,
+                Err(err) => {
+                    log::error!("Popup catcher list windows failed: {}", err);
+                    Vec::new()
+                }
+            };
+
+            for popup in popups {
+                match popup.capture_image() {
+                    Ok(image) => on_popup(PopupCapture {
+                        window: popup,
+                        image,
+                        timestamp: SystemTime::now(),
+                    }),
+                    Err(err) => log::debug!("Capture popup {} failed: {}", popup.id(), err),
+                }
+            }
+
+            // 分段等待，保证 stop 能及时生效
+            impl_watcher.wait(self.poll_interval.min(Duration::from_millis(100)));
+        }
+    }
+}
+
+/// Handle of a running [`PopupCatcher`].
+#[derive(Debug)]
+pub struct PopupCatcherHandle {
+    stop_sender: Sender<()>,
+    join_handle: JoinHandle<()>,
+}
+
+impl PopupCatcherHandle {
+    /// Stop catching popups and wait for the thread to exit.
+    pub fn stop(self) -> XCapResult<()> {
+        // 线程已经退出时 send 会失败，忽略即可
+        let _ = self.stop_sender.send(());
+        self.join_handle
+            .join()
+            .map_err(|_| XCapError::new("Popup catcher thread panicked"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_popups() {
+        let catcher = PopupCatcher::new();
+        assert!(catcher.matches(Some(1), WindowKind::Normal, 10, true));
+        assert!(catcher.matches(None, WindowKind::Menu, 10, true));
+        assert!(catcher.matches(None, WindowKind::Tooltip, 10, true));
+        assert!(!catcher.matches(None, WindowKind::Dialog, 10, true));
+        assert!(!catcher.matches(Some(1), WindowKind::Menu, 10, false));
+
+        let catcher = catcher.with_pid(10);
+        assert!(catcher.matches(None, WindowKind::Menu, 10, true));
+        assert!(!catcher.matches(None, WindowKind::Menu, 20, true));
+        // 没有 pid 的弹出窗口无法判断归属，不过滤
+        assert!(catcher.matches(Some(1), WindowKind::Normal, 0, true));
+    }
+}
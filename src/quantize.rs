@@ -0,0 +1,425 @@
+#[cfg(feature = "gif")]
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use std::{sync::Mutex, time::SystemTime};
+
+#[cfg(feature = "gif")]
+use gif::{Encoder, Repeat};
+
+#[cfg(feature = "gif")]
+use crate::sink::{check_frame, FrameSink};
+use crate::{error::XCapResult, pixel_format::PixelFormat, video_recorder::Frame, XCapError};
+
+/// Colors are bucketed to 5 bits per channel for the histogram and the lookup table.
+const BUCKET_BITS: u32 = 5;
+const BUCKETS: usize = 1 << (3 * BUCKET_BITS);
+/// At most this many pixels are sampled for a palette.
+const MAX_SAMPLES: usize = 1 << 16;
+
+/// A frame with up to 256 colors, e.g. for GIF or e-ink displays.
+#[derive(Debug, Clone)]
+pub struct IndexedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 3]>,
+    /// One palette index per pixel.
+    pub indices: Vec<u8>,
+    pub timestamp: SystemTime,
+}
+
+fn bucket(r: u8, g: u8, b: u8) -> usize {
+    let shift = 8 - BUCKET_BITS;
+    ((r as usize >> shift) << (2 * BUCKET_BITS))
+        | ((g as usize >> shift) << BUCKET_BITS)
+        | (b as usize >> shift)
+}
+
+fn bucket_color(bucket: usize) -> [u8; 3] {
+    let mask = (1 << BUCKET_BITS) - 1;
+    let shift = 8 - BUCKET_BITS;
+    // 取桶的中心
+    let center = |value: usize| ((value << shift) | (1 << (shift - 1))) as u8;
+
+    [
+        center(bucket >> (2 * BUCKET_BITS)),
+        center((bucket >> BUCKET_BITS) & mask),
+        center(bucket & mask),
+    ]
+}
+
+/// Median cut over the color histogram: split the box with the widest channel range at its
+/// weighted median until there are `max_colors` boxes, each box becomes its mean color.
+fn median_cut(histogram: &[u32], max_colors: usize) -> Vec<[u8; 3]> {
+    let colors: Vec<(usize, u32)> = histogram
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(bucket, count)| (bucket, *count))
+        .collect();
+    let mut boxes = vec![colors];
+
+    while boxes.len() < max_colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| {
+                let (channel, range) = (0..3)
+                    .map(|channel| {
+                        let values = colors
+                            .iter()
+                            .map(|(bucket, _)| bucket_color(*bucket)[channel]);
+                        let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                        (channel, range)
+                    })
+                    .max_by_key(|(_, range)| *range)
+                    .unwrap_or((0, 0));
+                (index, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range);
+
+        let Some((index, channel, _)) = widest else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|(bucket, _)| bucket_color(*bucket)[channel]);
+        let total: u64 = colors.iter().map(|(_, count)| *count as u64).sum();
+        let mut seen = 0;
+        let median = colors
+            .iter()
+            .position(|(_, count)| {
+                seen += *count as u64;
+                seen * 2 >= total
+            })
+            .unwrap_or(0);
+        // 两边都至少保留一种颜色
+        let split = (median + 1).clamp(1, colors.len() - 1);
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .filter(|colors| !colors.is_empty())
+        .map(|colors| {
+            let mut sum = [0u64; 3];
+            let mut total = 0u64;
+            for (bucket, count) in colors {
+                let color = bucket_color(*bucket);
+                for channel in 0..3 {
+                    sum[channel] += color[channel] as u64 * *count as u64;
+                }
+                total += *count as u64;
+            }
+            sum.map(|channel| (channel / total.max(1)) as u8)
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct PaletteCache {
+    palette: Vec<[u8; 3]>,
+    /// The nearest palette index of every bucket, filled on first use, `u16::MAX` when unknown.
+    lookup: Vec<u16>,
+    frames: u32,
+}
+
+impl PaletteCache {
+    fn new(palette: Vec<[u8; 3]>) -> PaletteCache {
+        PaletteCache {
+            palette,
+            lookup: vec![u16::MAX; BUCKETS],
+            frames: 0,
+        }
+    }
+
+    fn nearest(&mut self, color: [u8; 3]) -> u8 {
+        let bucket = bucket(color[0], color[1], color[2]);
+        if self.lookup[bucket] == u16::MAX {
+            let center = bucket_color(bucket);
+            let distance = |entry: &[u8; 3]| -> u32 {
+                (0..3)
+                    .map(|channel| (entry[channel] as i32 - center[channel] as i32).pow(2) as u32)
+                    .sum()
+            };
+            self.lookup[bucket] = (0..self.palette.len())
+                .min_by_key(|index| distance(&self.palette[*index]))
+                .unwrap_or(0) as u16;
+        }
+
+        self.lookup[bucket] as u8
+    }
+}
+
+/// Reduces frames to a palette of up to 256 colors with median cut, optionally with
+/// Floyd-Steinberg dithering.
+///
+/// The palette and a lookup table of nearest colors are kept between frames and only rebuilt
+/// every few frames, quantizing every frame from scratch is much slower than capturing it.
+/// [`GifSink`] records through one with the `gif` feature.
+#[derive(Debug)]
+pub struct Quantizer {
+    max_colors: usize,
+    dither: bool,
+    palette_interval: u32,
+    cache: Mutex<Option<PaletteCache>>,
+}
+
+impl Default for Quantizer {
+    fn default() -> Self {
+        Quantizer::new(256)
+    }
+}
+
+impl Quantizer {
+    /// `max_colors` is clamped between 2 and 256.
+    pub fn new(max_colors: usize) -> Quantizer {
+        Quantizer {
+            max_colors: max_colors.clamp(2, 256),
+            dither: true,
+            palette_interval: 30,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Diffuse the quantization error to neighbour pixels, defaults to true. Gradients look
+    /// smoother, flat UI colors may get noisy.
+    pub fn with_dither(mut self, dither: bool) -> Quantizer {
+        self.dither = dither;
+        self
+    }
+
+    /// Build a new palette every `frames` frames, defaults to 30. Use 1 for unrelated images.
+    pub fn with_palette_interval(mut self, frames: u32) -> Quantizer {
+        self.palette_interval = frames.max(1);
+        self
+    }
+
+    fn build_palette(&self, frame: &Frame) -> Vec<[u8; 3]> {
+        let mut histogram = vec![0u32; BUCKETS];
+        let pixels = frame.raw.len() / 4;
+        let step = (pixels / MAX_SAMPLES).max(1);
+
+        for pixel in frame.raw.chunks_exact(4).step_by(step) {
+            histogram[bucket(pixel[0], pixel[1], pixel[2])] += 1;
+        }
+
+        median_cut(&histogram, self.max_colors)
+    }
+
+    pub fn quantize(&self, frame: &Frame) -> XCapResult<IndexedFrame> {
+        if frame.pixel_format != PixelFormat::Rgba8 {
+            return Err(XCapError::new("Only RGBA frames can be quantized"));
+        }
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        if frame.raw.len() != width * height * 4 {
+            return Err(XCapError::new("Frame size does not match its data"));
+        }
+        if width == 0 || height == 0 {
+            return Ok(IndexedFrame {
+                width: frame.width,
+                height: frame.height,
+                palette: Vec::new(),
+                indices: Vec::new(),
+                timestamp: frame.timestamp,
+            });
+        }
+
+        let mut cache = self.cache.lock()?;
+        if cache
+            .as_ref()
+            .is_none_or(|cache| cache.frames >= self.palette_interval)
+        {
+            *cache = Some(PaletteCache::new(self.build_palette(frame)));
+        }
+        let cache = cache
+            .as_mut()
+            .ok_or_else(|| XCapError::new("Build palette failed"))?;
+        cache.frames += 1;
+
+        let mut indices = Vec::with_capacity(width * height);
+        if !self.dither {
+            for pixel in frame.raw.chunks_exact(4) {
+                indices.push(cache.nearest([pixel[0], pixel[1], pixel[2]]));
+            }
+        } else {
+            // 当前行和下一行累积的误差
+            let mut errors = vec![[0i16; 3]; width + 2];
+            let mut next_errors = vec![[0i16; 3]; width + 2];
+
+            for row in frame.raw.chunks_exact(width * 4) {
+                for (x, pixel) in row.chunks_exact(4).enumerate() {
+                    let color: [u8; 3] = [0, 1, 2].map(|channel| {
+                        (pixel[channel] as i16 + errors[x + 1][channel] / 16).clamp(0, 255) as u8
+                    });
+                    let index = cache.nearest(color);
+                    indices.push(index);
+
+                    let chosen = cache.palette[index as usize];
+                    for channel in 0..3 {
+                        let error = color[channel] as i16 - chosen[channel] as i16;
+                        errors[x + 2][channel] += error * 7;
+                        next_errors[x][channel] += error * 3;
+                        next_errors[x + 1][channel] += error * 5;
+                        next_errors[x + 2][channel] += error;
+                    }
+                }
+
+                errors = std::mem::replace(&mut next_errors, vec![[0i16; 3]; width + 2]);
+            }
+        }
+
+        Ok(IndexedFrame {
+            width: frame.width,
+            height: frame.height,
+            palette: cache.palette.clone(),
+            indices,
+            timestamp: frame.timestamp,
+        })
+    }
+}
+
+/// Records frames to an animated GIF, each frame quantized by a [`Quantizer`] with its own
+/// palette.
+#[cfg(feature = "gif")]
+pub struct GifSink {
+    path: PathBuf,
+    frame_rate: u32,
+    quantizer: Quantizer,
+    encoder: Option<Encoder<BufWriter<File>>>,
+    size: Option<(u32, u32)>,
+}
+
+#[cfg(feature = "gif")]
+impl fmt::Debug for GifSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GifSink")
+            .field("path", &self.path)
+            .field("frame_rate", &self.frame_rate)
+            .field("quantizer", &self.quantizer)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "gif")]
+impl GifSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> GifSink {
+        GifSink {
+            path: path.as_ref().to_path_buf(),
+            frame_rate: 10,
+            quantizer: Quantizer::default(),
+            encoder: None,
+            size: None,
+        }
+    }
+
+    /// The frame rate of the animation, defaults to 10. GIF delays are in hundredths of a
+    /// second, so rates above 50 play slower than recorded in most viewers.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> GifSink {
+        self.frame_rate = frame_rate.clamp(1, 100);
+        self
+    }
+
+    /// The quantizer for the frames, defaults to 256 colors with dithering.
+    pub fn with_quantizer(mut self, quantizer: Quantizer) -> GifSink {
+        self.quantizer = quantizer;
+        self
+    }
+}
+
+#[cfg(feature = "gif")]
+impl FrameSink for GifSink {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        check_frame(frame)?;
+        if frame.width > u16::MAX as u32 || frame.height > u16::MAX as u32 {
+            return Err(XCapError::new(
+                "GIF frames are at most 65535 pixels wide and high",
+            ));
+        }
+
+        match self.size {
+            None => {
+                let writer = BufWriter::new(File::create(&self.path)?);
+                let mut encoder =
+                    Encoder::new(writer, frame.width as u16, frame.height as u16, &[])
+                        .map_err(XCapError::new)?;
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(XCapError::new)?;
+                self.encoder = Some(encoder);
+                self.size = Some((frame.width, frame.height));
+            }
+            Some(size) if size != (frame.width, frame.height) => {
+                return Err(XCapError::new(format!(
+                    "Frame size changed from {}x{} to {}x{}",
+                    size.0, size.1, frame.width, frame.height
+                )))
+            }
+            Some(_) => {}
+        }
+
+        let encoder = self
+            .encoder
+            .as_mut()
+            .ok_or_else(|| XCapError::new("GifSink is finished"))?;
+        let indexed = self.quantizer.quantize(frame)?;
+        let gif_frame = gif::Frame {
+            width: indexed.width as u16,
+            height: indexed.height as u16,
+            // 单位是 1/100 秒
+            delay: (100 / self.frame_rate) as u16,
+            palette: Some(indexed.palette.concat()),
+            buffer: Cow::Borrowed(&indexed.indices),
+            ..gif::Frame::default()
+        };
+
+        encoder.write_frame(&gif_frame).map_err(XCapError::new)
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.into_inner().map_err(XCapError::new)?.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantize_two_colors() {
+        let raw = (0..16)
+            .flat_map(|index| match index % 2 {
+                0 => [200, 16, 16, 255],
+                _ => [16, 16, 200, 255],
+            })
+            .collect();
+        let frame = Frame::new(4, 4, raw);
+        let indexed = Quantizer::new(4)
+            .with_dither(false)
+            .quantize(&frame)
+            .unwrap();
+
+        assert_eq!(indexed.palette.len(), 2);
+        assert_ne!(indexed.indices[0], indexed.indices[1]);
+        assert_eq!(indexed.indices[0], indexed.indices[2]);
+        let red = indexed.palette[indexed.indices[0] as usize];
+        assert!(red[0] > 190 && red[2] < 30);
+
+        let empty = Quantizer::new(4)
+            .quantize(&Frame::new(0, 3, Vec::new()))
+            .unwrap();
+        assert!(empty.indices.is_empty());
+    }
+}
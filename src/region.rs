@@ -0,0 +1,35 @@
+/// A rectangle in the pixel space of a captured image or [`crate::Frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Clamp the region to an image of `width` x `height`, `None` if nothing remains.
+    pub fn clamp(&self, width: u32, height: u32) -> Option<Region> {
+        let right = self.x.saturating_add(self.width).min(width);
+        let bottom = self.y.saturating_add(self.height).min(height);
+
+        if self.x >= right || self.y >= bottom {
+            return None;
+        }
+
+        Some(Region::new(self.x, self.y, right - self.x, bottom - self.y))
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+}
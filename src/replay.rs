@@ -0,0 +1,247 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::{
+    encode::{encode_image, EncodeOptions},
+    error::XCapResult,
+    pixel_format::PixelFormat,
+    sink::{FileSink, FrameSink},
+    video_recorder::Frame,
+    XCapError,
+};
+
+#[derive(Debug)]
+struct BufferedFrame {
+    /// `raw` is empty when the frame is kept encoded.
+    frame: Frame,
+    encoded: Option<Vec<u8>>,
+}
+
+impl BufferedFrame {
+    fn size(&self) -> usize {
+        self.frame.raw.len() + self.encoded.as_ref().map_or(0, Vec::len)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    frames: VecDeque<BufferedFrame>,
+    bytes: usize,
+}
+
+/// Keeps the frames of the last seconds in memory to save them on demand, e.g. to clip the last
+/// 30 seconds after something interesting happened.
+///
+/// The buffer is a [`FrameSink`], feed it from a [`crate::VideoRecorder`] and keep a clone to
+/// save from. Frames older than the duration are dropped, and so are the oldest frames once the
+/// memory budget is exceeded, the saved clip is shorter then. Raw 4K frames take 33 MB each,
+/// keeping frames encoded as JPEG trades CPU time for a buffer about 10 times longer.
+#[derive(Debug, Clone)]
+pub struct ReplayBuffer {
+    duration: Duration,
+    max_bytes: usize,
+    encoding: Option<(ImageFormat, EncodeOptions)>,
+    frame_rate: u32,
+    ffmpeg: String,
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayBuffer {
+    /// Keep the frames of the last `duration`.
+    pub fn new(duration: Duration) -> ReplayBuffer {
+        ReplayBuffer {
+            duration,
+            max_bytes: 1 << 30,
+            encoding: None,
+            frame_rate: 30,
+            ffmpeg: String::from("ffmpeg"),
+            state: Arc::new(Mutex::new(ReplayState::default())),
+        }
+    }
+
+    /// The memory the buffered frames may take, defaults to 1 GiB.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> ReplayBuffer {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Keep frames encoded as `format` instead of raw, e.g. [`ImageFormat::Jpeg`] with the
+    /// `jpeg` feature. Frames are decoded again when saved.
+    #[cfg(feature = "image")]
+    pub fn with_encoding(mut self, format: ImageFormat, options: EncodeOptions) -> ReplayBuffer {
+        self.encoding = Some((format, options));
+        self
+    }
+
+    /// The frame rate of saved videos, defaults to 30. Use the rate the frames are captured at.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> ReplayBuffer {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    /// The `ffmpeg` executable used by [`ReplayBuffer::save`], defaults to the one found in
+    /// `PATH`.
+    pub fn with_ffmpeg<P: ToString>(mut self, ffmpeg: P) -> ReplayBuffer {
+        self.ffmpeg = ffmpeg.to_string();
+        self
+    }
+
+    /// The length of the buffered video.
+    pub fn buffered_duration(&self) -> Duration {
+        self.state
+            .lock()
+            .ok()
+            .and_then(|state| {
+                let first = state.frames.front()?.frame.timestamp;
+                let last = state.frames.back()?.frame.timestamp;
+                last.duration_since(first).ok()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The memory the buffered frames take.
+    pub fn buffered_bytes(&self) -> usize {
+        self.state.lock().map(|state| state.bytes).unwrap_or(0)
+    }
+
+    pub fn clear(&self) -> XCapResult<()> {
+        let mut state = self.state.lock()?;
+        state.frames.clear();
+        state.bytes = 0;
+
+        Ok(())
+    }
+
+    fn buffer(&self, frame: &Frame) -> XCapResult<BufferedFrame> {
+        let Some((format, options)) = &self.encoding else {
+            return Ok(BufferedFrame {
+                frame: frame.clone(),
+                encoded: None,
+            });
+        };
+        if frame.pixel_format != PixelFormat::Rgba8 {
+            return Err(XCapError::new("Only RGBA frames can be encoded"));
+        }
+
+        let image = RgbaImage::from_raw(frame.width, frame.height, frame.raw.clone())
+            .ok_or_else(|| XCapError::new("Frame size does not match its data"))?;
+
+        Ok(BufferedFrame {
+            frame: Frame {
+                raw: Vec::new(),
+                ..frame.clone()
+            },
+            encoded: Some(encode_image(&image, *format, options)?),
+        })
+    }
+
+    /// The buffered frames, oldest first.
+    pub fn frames(&self) -> XCapResult<Vec<Frame>> {
+        // 先复制出来再解码，不阻塞正在写入的录制线程
+        let buffered: Vec<(Frame, Option<Vec<u8>>)> = {
+            let state = self.state.lock()?;
+            state
+                .frames
+                .iter()
+                .map(|buffered| (buffered.frame.clone(), buffered.encoded.clone()))
+                .collect()
+        };
+
+        buffered
+            .into_iter()
+            .map(|(frame, encoded)| match (encoded, &self.encoding) {
+                (Some(encoded), Some((format, _))) => {
+                    let image = image::load_from_memory_with_format(&encoded, *format)?;
+                    Ok(Frame {
+                        raw: image.into_rgba8().into_raw(),
+                        ..frame
+                    })
+                }
+                _ => Ok(frame),
+            })
+            .collect()
+    }
+
+    /// Write the buffered frames to `sink` and finish it. Recording continues into the buffer.
+    pub fn save_to<S: FrameSink>(&self, sink: &mut S) -> XCapResult<()> {
+        for frame in self.frames()? {
+            sink.write_frame(&frame)?;
+        }
+
+        sink.finish()
+    }
+
+    /// Save the buffered frames as a video file, encoded by `ffmpeg` like [`FileSink`].
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> XCapResult<()> {
+        let mut sink = FileSink::new(path)
+            .with_ffmpeg(&self.ffmpeg)
+            .with_frame_rate(self.frame_rate);
+
+        self.save_to(&mut sink)
+    }
+}
+
+impl FrameSink for ReplayBuffer {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        // 编码在锁外进行
+        let buffered = self.buffer(frame)?;
+        let mut state = self.state.lock()?;
+
+        state.bytes += buffered.size();
+        state.frames.push_back(buffered);
+
+        let newest = frame.timestamp;
+        while let Some(oldest) = state.frames.front() {
+            let expired = newest
+                .duration_since(oldest.frame.timestamp)
+                .is_ok_and(|age| age > self.duration);
+            if !expired && state.bytes <= self.max_bytes {
+                break;
+            }
+
+            let size = oldest.size();
+            state.frames.pop_front();
+            state.bytes -= size;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[test]
+    fn drop_expired_frames() {
+        let mut buffer = ReplayBuffer::new(Duration::from_secs(2)).with_max_bytes(12);
+        let start = SystemTime::now();
+        let frame = |second: u64| Frame {
+            timestamp: start + Duration::from_secs(second),
+            ..Frame::new(1, 1, vec![second as u8, 0, 0, 255])
+        };
+
+        for second in 0..5 {
+            buffer.write_frame(&frame(second)).unwrap();
+        }
+        let seconds: Vec<u8> = buffer.frames().unwrap().iter().map(|f| f.raw[0]).collect();
+        assert_eq!(seconds, [2, 3, 4]);
+        assert_eq!(buffer.buffered_duration(), Duration::from_secs(2));
+
+        let mut buffer = buffer.with_max_bytes(8);
+        buffer.write_frame(&frame(5)).unwrap();
+        assert_eq!(buffer.buffered_bytes(), 8);
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{backend::Backend, error::XCapResult, utils::UtcDateTime};
+
+/// How many decisions [`capture_report`] keeps.
+const CAPACITY: usize = 256;
+
+/// A decision taken by a backend, see [`capture_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// The code path that produced a capture, e.g. `x11-get-image` or `dxgi-duplication`.
+    CapturePath(&'static str),
+    /// `from` failed and `to` was tried next.
+    Fallback {
+        from: &'static str,
+        to: &'static str,
+        error: String,
+    },
+    /// The pixel format `path` received from the platform, as the platform names it.
+    PixelFormat { path: &'static str, format: String },
+}
+
+impl Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::CapturePath(path) => write!(f, "captured with {}", path),
+            Decision::Fallback { from, to, error } => {
+                write!(f, "{} failed: {}, fallback to {}", from, error, to)
+            }
+            Decision::PixelFormat { path, format } => write!(f, "{} delivers {}", path, format),
+        }
+    }
+}
+
+/// A decision and how often it was taken in a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportEntry {
+    pub decision: Decision,
+    /// When the decision was last taken.
+    pub timestamp: SystemTime,
+    /// How many times in a row the decision was taken, a capture loop repeats the same one.
+    pub count: u32,
+}
+
+/// What the backends did recently, to attach to bug reports of end users: which of the many
+/// capture paths actually ran, which ones failed before and which pixel formats the platform
+/// delivered. Printing it gives one line per decision.
+#[derive(Debug, Clone)]
+pub struct CaptureReport {
+    /// The backend detected for the session, see [`crate::backend`].
+    pub backend: Backend,
+    /// The last decisions, oldest first.
+    pub entries: Vec<ReportEntry>,
+}
+
+impl Display for CaptureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "backend: {:?}", self.backend)?;
+        for entry in &self.entries {
+            let duration = entry
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let datetime = UtcDateTime::from_unix(duration.as_secs());
+            write!(
+                f,
+                "{:02}:{:02}:{:02}.{:03} {}",
+                datetime.hour,
+                datetime.minute,
+                datetime.second,
+                duration.subsec_millis(),
+                entry.decision
+            )?;
+            if entry.count > 1 {
+                write!(f, " (x{})", entry.count)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+static ENTRIES: Mutex<VecDeque<ReportEntry>> = Mutex::new(VecDeque::new());
+
+/// The backend decisions taken by this process so far, the last 256 of them.
+pub fn capture_report() -> CaptureReport {
+    let entries = match ENTRIES.lock() {
+        Ok(entries) => entries.iter().cloned().collect(),
+        Err(_) => Vec::new(),
+    };
+
+    CaptureReport {
+        backend: crate::backend(),
+        entries,
+    }
+}
+
+/// Forget the decisions taken so far, e.g. before reproducing an issue.
+pub fn clear_capture_report() {
+    if let Ok(mut entries) = ENTRIES.lock() {
+        entries.clear();
+    }
+}
+
+fn record(entries: &mut VecDeque<ReportEntry>, decision: Decision, timestamp: SystemTime) {
+    if let Some(last) = entries.back_mut() {
+        if last.decision == decision {
+            last.timestamp = timestamp;
+            last.count = last.count.saturating_add(1);
+            return;
+        }
+    }
+
+    if entries.len() == CAPACITY {
+        entries.pop_front();
+    }
+    entries.push_back(ReportEntry {
+        decision,
+        timestamp,
+        count: 1,
+    });
+}
+
+fn now() -> SystemTime {
+    // 浏览器里 SystemTime::now 不可用
+    #[cfg(target_arch = "wasm32")]
+    return UNIX_EPOCH + std::time::Duration::from_secs_f64(js_sys::Date::now() / 1000.0);
+    #[cfg(not(target_arch = "wasm32"))]
+    SystemTime::now()
+}
+
+fn push(decision: Decision) {
+    log::debug!("{}", decision);
+    if let Ok(mut entries) = ENTRIES.lock() {
+        record(&mut entries, decision, now());
+    }
+}
+
+/// Record that `path` produced the capture when `result` is ok, and pass it through.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub(crate) fn capture_path<T>(path: &'static str, result: XCapResult<T>) -> XCapResult<T> {
+    if result.is_ok() {
+        push(Decision::CapturePath(path));
+    }
+
+    result
+}
+
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub(crate) fn fallback(from: &'static str, to: &'static str, error: &dyn Display) {
+    push(Decision::Fallback {
+        from,
+        to,
+        error: error.to_string(),
+    });
+}
+
+pub(crate) fn pixel_format(path: &'static str, format: String) {
+    push(Decision::PixelFormat { path, format });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesce_repeated_decisions() {
+        let mut entries = VecDeque::new();
+        let now = SystemTime::now();
+        for _ in 0..3 {
+            record(&mut entries, Decision::CapturePath("x11-get-image"), now);
+        }
+        record(
+            &mut entries,
+            Decision::CapturePath("portal-screenshot"),
+            now,
+        );
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].count, 3);
+
+        for index in 0..CAPACITY {
+            let format = index.to_string();
+            record(
+                &mut entries,
+                Decision::PixelFormat {
+                    path: "x11-get-image",
+                    format,
+                },
+                now,
+            );
+        }
+        assert_eq!(entries.len(), CAPACITY);
+        assert_eq!(entries[0].count, 1);
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use image::RgbaImage;
+
+use crate::{
+    error::XCapResult, utils::UtcDateTime, video_recorder::Frame, Monitor, Window, XCapError,
+};
+
+/// What a [`Scheduler`] captures on every tick.
+#[derive(Debug, Clone)]
+pub enum CaptureTarget {
+    Monitor(Monitor),
+    Window(Window),
+}
+
+impl CaptureTarget {
+    fn name(&self) -> String {
+        let name = match self {
+            CaptureTarget::Monitor(monitor) => monitor.name().to_string(),
+            CaptureTarget::Window(window) => window.title().to_string(),
+        };
+
+        name.replace(['|', '\\', ':', '/', '*', '?', '"', '<', '>'], "")
+    }
+
+    pub(crate) fn capture_image(&self) -> XCapResult<RgbaImage> {
+        match self {
+            CaptureTarget::Monitor(monitor) => monitor.capture_rgba(),
+            CaptureTarget::Window(window) => window.capture_rgba(),
+        }
+    }
+
+    pub(crate) fn capture_frame(&self) -> XCapResult<Frame> {
+        match self {
+            CaptureTarget::Monitor(monitor) => monitor.capture_frame(),
+            CaptureTarget::Window(window) => window.capture_frame(),
+        }
+    }
+}
+
+/// A cron expression (`minute hour day-of-month month day-of-week`), evaluated in UTC.
+///
+/// Every field accepts `*`, single values, ranges (`1-5`), lists (`1,15,30`) and steps (`*/10`, `0-30/5`).
+/// When both day-of-month and day-of-week are restricted, either of them matches, `0 0 1 * 1`
+/// fires on the 1st and on Mondays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    /// Both day fields are restricted, a day matches when either of them does.
+    either_day: bool,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> XCapResult<Vec<bool>> {
+    let mut allowed = vec![false; (max + 1) as usize];
+    let invalid = || XCapError::new(format!("Invalid cron field {:?}", field));
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                start.parse::<u32>().map_err(|_| invalid())?,
+                end.parse::<u32>().map_err(|_| invalid())?,
+            )
+        } else {
+            let value = range.parse::<u32>().map_err(|_| invalid())?;
+            (value, value)
+        };
+
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+
+    Ok(allowed)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> XCapResult<CronSchedule> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(XCapError::new(format!(
+                "Cron expression {:?} must have 5 fields",
+                expression
+            )));
+        }
+
+        let mut weekdays = parse_cron_field(fields[4], 0, 7)?;
+        // 0 和 7 都表示星期日
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        weekdays.truncate(7);
+
+        Ok(CronSchedule {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            weekdays,
+            // 和 cron 一样，只有一个字段以 * 开头时才要求两个都满足
+            either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        })
+    }
+
+    fn matches(&self, datetime: &UtcDateTime) -> bool {
+        let day = self.days[datetime.day as usize];
+        let weekday = self.weekdays[datetime.weekday as usize];
+        let day_matches = if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        };
+
+        self.minutes[datetime.minute as usize]
+            && self.hours[datetime.hour as usize]
+            && self.months[datetime.month as usize]
+            && day_matches
+    }
+
+    /// The first matching minute strictly after `after`.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let secs = after.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let first = secs / 60 + 1;
+
+        // 最多向后查找 4 年，覆盖 2 月 29 日这类稀有的表达式
+        (first..first + 4 * 366 * 24 * 60)
+            .find(|minute| self.matches(&UtcDateTime::from_unix(minute * 60)))
+            .map(|minute| UNIX_EPOCH + Duration::from_secs(minute * 60))
+    }
+}
+
+/// When a [`Scheduler`] fires.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    pub fn cron(expression: &str) -> XCapResult<Schedule> {
+        Ok(Schedule::Cron(CronSchedule::parse(expression)?))
+    }
+
+    fn next_after(&self, last: SystemTime) -> Option<SystemTime> {
+        match self {
+            Schedule::Every(interval) => Some(last + *interval),
+            Schedule::Cron(cron) => cron.next_after(last),
+        }
+    }
+
+    /// The first capture: right away for intervals, the next matching minute for cron.
+    fn first_after(&self, now: SystemTime) -> Option<SystemTime> {
+        match self {
+            Schedule::Every(_) => Some(now),
+            Schedule::Cron(cron) => cron.next_after(now),
+        }
+    }
+}
+
+/// Captures a target on a [`Schedule`] and writes the images into a directory.
+///
+/// File names are produced from a template where `{name}`, `{index}`, `{timestamp}`
+/// (unix seconds), `{date}` (`YYYY-MM-DD`) and `{time}` (`HH-MM-SS`, UTC) are substituted.
+/// The template may contain `/` to rotate images into sub directories, e.g. `{date}/{time}.png`.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    target: CaptureTarget,
+    schedule: Schedule,
+    directory: PathBuf,
+    filename_template: String,
+    max_files: Option<usize>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Scheduler {
+    pub fn new<P: AsRef<Path>>(
+        target: CaptureTarget,
+        schedule: Schedule,
+        directory: P,
+    ) -> Scheduler {
+        Scheduler {
+            target,
+            schedule,
+            directory: directory.as_ref().to_path_buf(),
+            filename_template: String::from("{name}-{date}-{time}-{index}.png"),
+            max_files: None,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+
+    /// The file name template, relative to the output directory.
+    pub fn with_filename_template<T: ToString>(mut self, template: T) -> Scheduler {
+        self.filename_template = template.to_string();
+        self
+    }
+
+    /// Keep at most `max_files` images, deleting the oldest ones written by this scheduler.
+    pub fn with_max_files(mut self, max_files: usize) -> Scheduler {
+        self.max_files = Some(max_files.max(1));
+        self
+    }
+
+    /// After a failed capture retry after `initial`, doubling the delay on every failure up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Scheduler {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    fn file_path(&self, index: u64, now: SystemTime) -> PathBuf {
+        let timestamp = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let datetime = UtcDateTime::from_unix(timestamp);
+
+        let filename = self
+            .filename_template
+            .replace("{name}", &self.target.name())
+            .replace("{index}", &index.to_string())
+            .replace("{timestamp}", &timestamp.to_string())
+            .replace(
+                "{date}",
+                &format!(
+                    "{:04}-{:02}-{:02}",
+                    datetime.year, datetime.month, datetime.day
+                ),
+            )
+            .replace(
+                "{time}",
+                &format!(
+                    "{:02}-{:02}-{:02}",
+                    datetime.hour, datetime.minute, datetime.second
+                ),
+            );
+
+        self.directory.join(filename)
+    }
+
+    fn capture_to(&self, path: &Path) -> XCapResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        self.target.capture_image()?.save(path)?;
+
+        Ok(())
+    }
+
+    /// Run the scheduler on a background thread.
+    ///
+    /// `on_error` is called for every failed capture before backing off.
+    pub fn start<F>(self, on_error: F) -> SchedulerHandle
+    where
+        F: Fn(&XCapError) + Send + 'static,
+    {
+        let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+
+        let join_handle = thread::spawn(move || {
+            let mut written: VecDeque<PathBuf> = VecDeque::new();
+            let mut index = 0;
+            let mut failures = 0;
+            let Some(mut next) = self.schedule.first_after(SystemTime::now()) else {
+                return;
+            };
+
+            loop {
+                let delay = next.duration_since(SystemTime::now()).unwrap_or_default();
+                match stop_receiver.recv_timeout(delay) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+
+                let now = SystemTime::now();
+                let path = self.file_path(index, now);
+
+                match self.capture_to(&path) {
+                    Ok(()) => {
+                        failures = 0;
+                        index += 1;
+                        written.push_back(path);
+
+                        while self.max_files.is_some_and(|max| written.len() > max) {
+                            if let Some(oldest) = written.pop_front() {
+                                if let Err(err) = fs::remove_file(&oldest) {
+                                    log::error!("Remove {:?} failed: {}", oldest, err);
+                                }
+                            }
+                        }
+
+                        next = match self.schedule.next_after(now) {
+                            Some(next) => next,
+                            None => break,
+                        };
+                    }
+                    Err(err) => {
+                        log::error!("Scheduled capture failed: {}", err);
+                        on_error(&err);
+
+                        let backoff = self
+                            .initial_backoff
+                            .saturating_mul(1 << failures.min(16))
+                            .min(self.max_backoff);
+                        failures += 1;
+                        next = now + backoff;
+                    }
+                }
+            }
+        });
+
+        SchedulerHandle {
+            stop_sender,
+            join_handle,
+        }
+    }
+}
+
+/// Handle of a running [`Scheduler`].
+#[derive(Debug)]
+pub struct SchedulerHandle {
+    stop_sender: Sender<()>,
+    join_handle: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    /// Stop the scheduler and wait for the in-flight capture to complete.
+    pub fn stop(self) -> XCapResult<()> {
+        // 线程已经退出时 send 会失败，忽略即可
+        let _ = self.stop_sender.send(());
+        self.join_handle
+            .join()
+            .map_err(|_| XCapError::new("Scheduler thread panicked"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_next_after() {
+        let cron = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+
+        // 2024-01-06 是星期六，下一次触发时间是 2024-01-08 星期一 09:00
+        let saturday = UNIX_EPOCH + Duration::from_secs(1704531600);
+        let next = cron.next_after(saturday).unwrap();
+        let secs = next.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let datetime = UtcDateTime::from_unix(secs);
+
+        assert_eq!(
+            (
+                datetime.year,
+                datetime.month,
+                datetime.day,
+                datetime.hour,
+                datetime.minute
+            ),
+            (2024, 1, 8, 9, 0)
+        );
+        assert_eq!(datetime.weekday, 1);
+
+        assert!(CronSchedule::parse("61 * * * *").is_err());
+        assert!(CronSchedule::parse("* * *").is_err());
+    }
+    #[test]
+    fn cron_either_day() {
+        let cron = CronSchedule::parse("0 0 1 * 1").unwrap();
+        let next_day = |secs: u64| {
+            let next = cron
+                .next_after(UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+            let datetime =
+                UtcDateTime::from_unix(next.duration_since(UNIX_EPOCH).unwrap().as_secs());
+            (datetime.month, datetime.day)
+        };
+
+        // 2024-01-02 星期二之后的星期一是 8 日，2024-01-29 星期一之后先到 2 月 1 日
+        assert_eq!(next_day(1704153600), (1, 8));
+        assert_eq!(next_day(1706486400), (2, 1));
+    }
+    #[test]
+    fn first_capture() {
+        // 2024-01-06 10:00:30
+        let now = UNIX_EPOCH + Duration::from_secs(1704535230);
+
+        let every = Schedule::Every(Duration::from_secs(60));
+        assert_eq!(every.first_after(now), Some(now));
+
+        let cron = Schedule::cron("0 12 * * *").unwrap();
+        let first = cron.first_after(now).unwrap();
+        assert_eq!(
+            first.duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            1704542400
+        );
+    }
+}
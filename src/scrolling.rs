@@ -0,0 +1,164 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    thread,
+    time::Duration,
+};
+
+use image::RgbaImage;
+
+use crate::{
+    error::{XCapError, XCapResult},
+    Window,
+};
+
+/// Stop after this many steps, infinite feeds never reach the end.
+const MAX_STEPS: usize = 100;
+/// Wait for smooth scrolling animations to finish before capturing.
+const SETTLE_DELAY: Duration = Duration::from_millis(200);
+/// A wheel notch scrolls roughly this many pixels in most toolkits.
+const NOTCH_HEIGHT: u32 = 60;
+
+/// Hash every row, comparing hashes is much cheaper than comparing pixels.
+fn row_hashes(image: &RgbaImage) -> Vec<u64> {
+    image
+        .rows()
+        .map(|row| {
+            let mut hasher = DefaultHasher::new();
+            for pixel in row {
+                pixel.0.hash(&mut hasher);
+            }
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// How many rows the content moved up from `previous` to `next`, and the height of a fixed
+/// footer below it. Fixed headers and footers, e.g. toolbars and input boxes, are the rows that
+/// did not change. `None` when the content does not overlap, `Some((0, 0))` when nothing moved.
+fn find_scroll(previous: &[u64], next: &[u64]) -> Option<(usize, usize)> {
+    let height = previous.len();
+    if height != next.len() {
+        return None;
+    }
+
+    let header = previous
+        .iter()
+        .zip(next)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if header == height {
+        return Some((0, 0));
+    }
+
+    let footer = previous
+        .iter()
+        .rev()
+        .zip(next.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (top, bottom) = (header, height - footer);
+
+    // 重叠太少时匹配不可靠，至少要重叠四分之一
+    let min_overlap = ((bottom - top) / 4).max(1);
+    (1..=(bottom - top).saturating_sub(min_overlap))
+        .find(|&offset| next[top..bottom - offset] == previous[top + offset..bottom])
+        .map(|offset| (offset, footer))
+}
+
+/// Append the `offset` rows that scrolled into view in `next` to `stitched`, which ends with
+/// the previous capture, keeping the footer at the bottom.
+fn stitch(stitched: &mut Vec<u8>, next: &RgbaImage, offset: usize, footer: usize) {
+    let row_len = next.width() as usize * 4;
+    let height = next.height() as usize;
+    let raw = next.as_raw();
+
+    stitched.truncate(stitched.len() - footer * row_len);
+    stitched.extend_from_slice(&raw[(height - footer - offset) * row_len..]);
+}
+
+/// Capture a window taller than the screen, e.g. a whole web page or chat history. The window
+/// is scrolled down with synthesized mouse wheel input at its center, and every step is
+/// stitched to the previous one where their content overlaps, until the content stops moving.
+///
+/// The window should be visible and not covered by other windows. Native Wayland windows can
+/// not be scrolled, as Wayland does not allow clients to synthesize input.
+pub fn capture_scrolling(window: &Window) -> XCapResult<RgbaImage> {
+    let first = window.capture_image()?;
+    let (width, height) = first.dimensions();
+    let mut previous_hashes = row_hashes(&first);
+    let mut stitched = first.into_raw();
+
+    // 每次大约滚动半个窗口
+    let lines = (height / 2 / NOTCH_HEIGHT).max(1) as i32;
+
+    for _ in 0..MAX_STEPS {
+        window.impl_window.scroll(lines)?;
+        thread::sleep(SETTLE_DELAY);
+
+        let next = window.capture_image()?;
+        if next.dimensions() != (width, height) {
+            log::warn!("Window was resized while scrolling, stopping");
+            break;
+        }
+
+        let next_hashes = row_hashes(&next);
+        match find_scroll(&previous_hashes, &next_hashes) {
+            Some((0, _)) => break,
+            Some((offset, footer)) => stitch(&mut stitched, &next, offset, footer),
+            None => {
+                log::warn!("Scrolled content does not overlap, stopping");
+                break;
+            }
+        }
+
+        previous_hashes = next_hashes;
+    }
+
+    let stitched_height = (stitched.len() / (width as usize * 4)) as u32;
+    RgbaImage::from_raw(width, stitched_height, stitched)
+        .ok_or_else(|| XCapError::new("Stitch scrolling capture failed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stitch_with_fixed_header_and_footer() {
+        // 第 0 行是固定的标题栏，最后一行是固定的输入框，中间是滚动的内容
+        let page: Vec<u8> = (0..20).collect();
+        let view = |scroll: usize| {
+            RgbaImage::from_fn(2, 8, |_, y| match y {
+                0 => image::Rgba([255, 0, 0, 255]),
+                7 => image::Rgba([0, 0, 255, 255]),
+                _ => image::Rgba([page[scroll + y as usize - 1], 0, 0, 255]),
+            })
+        };
+
+        let first = view(0);
+        let second = view(4);
+        assert_eq!(
+            find_scroll(&row_hashes(&first), &row_hashes(&second)),
+            Some((4, 1))
+        );
+        assert_eq!(
+            find_scroll(&row_hashes(&second), &row_hashes(&second)),
+            Some((0, 0))
+        );
+        assert_eq!(
+            find_scroll(&row_hashes(&first), &row_hashes(&view(14))),
+            None
+        );
+
+        let mut stitched = first.as_raw().clone();
+        stitch(&mut stitched, &second, 4, 1);
+        let stitched = RgbaImage::from_raw(2, 12, stitched).unwrap();
+
+        assert_eq!(stitched.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        for y in 1..11 {
+            assert_eq!(stitched.get_pixel(1, y).0, [y as u8 - 1, 0, 0, 255]);
+        }
+        assert_eq!(stitched.get_pixel(0, 11).0, [0, 0, 255, 255]);
+    }
+}
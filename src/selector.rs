@@ -0,0 +1,443 @@
+use std::{cell::RefCell, num::NonZeroU32, rc::Rc};
+
+use image::RgbaImage;
+use softbuffer::{Context, Surface};
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalPosition,
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::{Key, NamedKey},
+    monitor::MonitorHandle,
+    platform::run_on_demand::EventLoopExtRunOnDemand,
+    window::{CursorIcon, Fullscreen, Window as WinitWindow, WindowId, WindowLevel},
+};
+
+use crate::{
+    error::{XCapError, XCapResult},
+    Monitor, Rect, Window,
+};
+
+/// What the user picked in [`select`].
+#[derive(Debug, Clone)]
+pub enum Selection {
+    /// A dragged rectangle in virtual screen coordinates, or the whole monitor when the user
+    /// clicked where no window is.
+    Region(Rect),
+    /// A clicked window.
+    Window(Box<Window>),
+}
+
+// winit 每个进程只能创建一次事件循环，重复调用 select 时复用
+thread_local! {
+    static EVENT_LOOP: RefCell<Option<EventLoop<()>>> = const { RefCell::new(None) };
+}
+
+/// Drags shorter than this are clicks.
+const DRAG_THRESHOLD: f64 = 4.0;
+const BORDER_COLOR: u32 = 0x0033_99ff;
+
+/// Show a fullscreen overlay over every monitor with a frozen screenshot, and let the user
+/// drag a region or click a window, the way screenshot tools do. Escape or a right click
+/// cancels and returns `None`.
+///
+/// A region is dragged within one monitor. Like any winit event loop this must run on the
+/// main thread on macOS.
+pub fn select() -> XCapResult<Option<Selection>> {
+    let screenshots = Monitor::all()?
+        .into_iter()
+        .map(|monitor| {
+            let image = monitor.capture_rgba()?;
+            Ok((monitor, image))
+        })
+        .collect::<XCapResult<Vec<_>>>()?;
+
+    // 在显示覆盖层之前列出窗口，这样列表里不会有覆盖层自己
+    let windows = Window::all()?
+        .into_iter()
+        .filter(|window| window.is_visible() && window.width() > 0 && window.height() > 0)
+        .collect();
+
+    let mut selector = Selector {
+        screenshots,
+        windows,
+        overlays: Vec::new(),
+        cursor: None,
+        drag_start: None,
+        selection: None,
+        error: None,
+    };
+
+    EVENT_LOOP.with(|event_loop| {
+        let mut event_loop = event_loop.borrow_mut();
+        if event_loop.is_none() {
+            *event_loop = Some(EventLoop::new().map_err(XCapError::new)?);
+        }
+
+        event_loop
+            .as_mut()
+            .ok_or_else(|| XCapError::new("Create event loop failed"))?
+            .run_app_on_demand(&mut selector)
+            .map_err(XCapError::new)
+    })?;
+
+    match selector.error {
+        Some(err) => Err(err),
+        None => Ok(selector.selection),
+    }
+}
+
+/// The rectangle spanned by two corners.
+fn rect_between(a: (f64, f64), b: (f64, f64)) -> (f64, f64, f64, f64) {
+    (a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1))
+}
+
+/// Scale `image` to `width` x `height` 0RGB pixels, halving the brightness when `dim`.
+fn to_buffer(image: &RgbaImage, width: u32, height: u32, dim: bool) -> Vec<u32> {
+    let shift = if dim { 1 } else { 0 };
+    let mut buffer = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height {
+        let image_y = (y as u64 * image.height() as u64 / height as u64) as u32;
+        for x in 0..width {
+            let image_x = (x as u64 * image.width() as u64 / width as u64) as u32;
+            let [r, g, b, _] = image.get_pixel(image_x, image_y).0;
+            buffer.push(
+                ((r >> shift) as u32) << 16 | ((g >> shift) as u32) << 8 | (b >> shift) as u32,
+            );
+        }
+    }
+
+    buffer
+}
+
+struct Overlay {
+    monitor: Monitor,
+    screenshot: RgbaImage,
+    window: Rc<WinitWindow>,
+    surface: Surface<Rc<WinitWindow>, Rc<WinitWindow>>,
+    size: (u32, u32),
+    bright: Vec<u32>,
+    dimmed: Vec<u32>,
+}
+
+impl Overlay {
+    fn resize(&mut self, width: u32, height: u32) -> XCapResult<()> {
+        let (Some(non_zero_width), Some(non_zero_height)) =
+            (NonZeroU32::new(width), NonZeroU32::new(height))
+        else {
+            return Ok(());
+        };
+
+        self.surface
+            .resize(non_zero_width, non_zero_height)
+            .map_err(XCapError::new)?;
+        self.size = (width, height);
+        self.bright = to_buffer(&self.screenshot, width, height, false);
+        self.dimmed = to_buffer(&self.screenshot, width, height, true);
+
+        Ok(())
+    }
+
+    /// Convert a position in the overlay to virtual screen coordinates.
+    fn to_virtual(&self, x: f64, y: f64) -> (i32, i32) {
+        let (width, height) = self.size;
+        (
+            self.monitor.x() + (x * self.monitor.width() as f64 / width.max(1) as f64) as i32,
+            self.monitor.y() + (y * self.monitor.height() as f64 / height.max(1) as f64) as i32,
+        )
+    }
+
+    /// Convert a virtual screen rectangle to the overlay.
+    fn rect_to_overlay(&self, rect: &Rect) -> (f64, f64, f64, f64) {
+        let (width, height) = self.size;
+        let scale_x = width as f64 / self.monitor.width().max(1) as f64;
+        let scale_y = height as f64 / self.monitor.height().max(1) as f64;
+
+        (
+            (rect.x - self.monitor.x()) as f64 * scale_x,
+            (rect.y - self.monitor.y()) as f64 * scale_y,
+            (rect.right() - self.monitor.x()) as f64 * scale_x,
+            (rect.bottom() - self.monitor.y()) as f64 * scale_y,
+        )
+    }
+
+    /// Draw the dimmed screenshot with `highlight` bright and framed.
+    fn draw(&mut self, highlight: Option<(f64, f64, f64, f64)>) -> XCapResult<()> {
+        let (width, height) = self.size;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let mut buffer = self.surface.buffer_mut().map_err(XCapError::new)?;
+        buffer.copy_from_slice(&self.dimmed);
+
+        if let Some((left, top, right, bottom)) = highlight {
+            let left = left.clamp(0.0, width as f64) as usize;
+            let top = top.clamp(0.0, height as f64) as usize;
+            let right = right.clamp(0.0, width as f64) as usize;
+            let bottom = bottom.clamp(0.0, height as f64) as usize;
+
+            for y in top..bottom {
+                let row = y * width as usize;
+                buffer[row + left..row + right]
+                    .copy_from_slice(&self.bright[row + left..row + right]);
+
+                if y == top || y + 1 == bottom {
+                    buffer[row + left..row + right].fill(BORDER_COLOR);
+                } else if right > left {
+                    buffer[row + left] = BORDER_COLOR;
+                    buffer[row + right - 1] = BORDER_COLOR;
+                }
+            }
+        }
+
+        buffer.present().map_err(XCapError::new)
+    }
+}
+
+struct Selector {
+    screenshots: Vec<(Monitor, RgbaImage)>,
+    windows: Vec<Window>,
+    overlays: Vec<Overlay>,
+    cursor: Option<(WindowId, PhysicalPosition<f64>)>,
+    drag_start: Option<(WindowId, PhysicalPosition<f64>)>,
+    selection: Option<Selection>,
+    error: Option<XCapError>,
+}
+
+impl Selector {
+    fn find_monitor_handle(
+        event_loop: &ActiveEventLoop,
+        monitor: &Monitor,
+    ) -> Option<MonitorHandle> {
+        // xcap 的坐标在不同平台上可能是逻辑坐标，两种换算都比较一下
+        event_loop.available_monitors().min_by_key(|handle| {
+            let position = handle.position();
+            let scale_factor = monitor.scale_factor() as f64;
+            let logical = (position.x - monitor.x()).abs() + (position.y - monitor.y()).abs();
+            let physical = (position.x as f64 - monitor.x() as f64 * scale_factor).abs()
+                + (position.y as f64 - monitor.y() as f64 * scale_factor).abs();
+
+            logical.min(physical as i32)
+        })
+    }
+
+    fn create_overlays(&mut self, event_loop: &ActiveEventLoop) -> XCapResult<()> {
+        for (monitor, screenshot) in self.screenshots.drain(..) {
+            let attributes = WinitWindow::default_attributes()
+                .with_title("xcap selector")
+                .with_decorations(false)
+                .with_window_level(WindowLevel::AlwaysOnTop)
+                .with_fullscreen(Some(Fullscreen::Borderless(Selector::find_monitor_handle(
+                    event_loop, &monitor,
+                ))));
+
+            let window = Rc::new(
+                event_loop
+                    .create_window(attributes)
+                    .map_err(XCapError::new)?,
+            );
+            window.set_cursor(CursorIcon::Crosshair);
+
+            let context = Context::new(window.clone()).map_err(XCapError::new)?;
+            let surface = Surface::new(&context, window.clone()).map_err(XCapError::new)?;
+
+            let mut overlay = Overlay {
+                monitor,
+                screenshot,
+                window,
+                surface,
+                size: (0, 0),
+                bright: Vec::new(),
+                dimmed: Vec::new(),
+            };
+            let size = overlay.window.inner_size();
+            overlay.resize(size.width, size.height)?;
+            self.overlays.push(overlay);
+        }
+
+        Ok(())
+    }
+
+    /// The topmost window under a virtual screen point.
+    fn window_at(&self, x: i32, y: i32) -> Option<&Window> {
+        self.windows
+            .iter()
+            .filter(|window| {
+                Rect::new(window.x(), window.y(), window.width(), window.height()).contains(x, y)
+            })
+            .max_by_key(|window| window.z())
+    }
+
+    fn highlight(&self, overlay: &Overlay) -> Option<(f64, f64, f64, f64)> {
+        let (cursor_id, cursor) = self.cursor?;
+
+        // 拖动时只在开始拖动的显示器上画选区
+        if let Some((drag_id, drag_start)) = self.drag_start {
+            return (drag_id == overlay.window.id() && cursor_id == drag_id)
+                .then(|| rect_between((drag_start.x, drag_start.y), (cursor.x, cursor.y)));
+        }
+
+        let cursor_overlay = self
+            .overlays
+            .iter()
+            .find(|overlay| overlay.window.id() == cursor_id)?;
+        let (x, y) = cursor_overlay.to_virtual(cursor.x, cursor.y);
+        let window = self.window_at(x, y)?;
+
+        Some(overlay.rect_to_overlay(&Rect::new(
+            window.x(),
+            window.y(),
+            window.width(),
+            window.height(),
+        )))
+    }
+
+    fn finish(&mut self, event_loop: &ActiveEventLoop, selection: Option<Selection>) {
+        self.selection = selection;
+        // 退出前销毁窗口，否则覆盖层会留在屏幕上
+        self.overlays.clear();
+        event_loop.exit();
+    }
+
+    fn on_release(&mut self, window_id: WindowId) -> Option<Selection> {
+        let (cursor_id, cursor) = self.cursor?;
+        let overlay = self
+            .overlays
+            .iter()
+            .find(|overlay| overlay.window.id() == window_id)?;
+
+        if let Some((drag_id, drag_start)) = self.drag_start.take() {
+            let (left, top, right, bottom) =
+                rect_between((drag_start.x, drag_start.y), (cursor.x, cursor.y));
+
+            if drag_id == cursor_id
+                && (right - left >= DRAG_THRESHOLD || bottom - top >= DRAG_THRESHOLD)
+            {
+                let (x, y) = overlay.to_virtual(left, top);
+                let (right, bottom) = overlay.to_virtual(right, bottom);
+
+                return Some(Selection::Region(Rect::new(
+                    x,
+                    y,
+                    (right - x) as u32,
+                    (bottom - y) as u32,
+                )));
+            }
+        }
+
+        let (x, y) = overlay.to_virtual(cursor.x, cursor.y);
+        match self.window_at(x, y) {
+            Some(window) => Some(Selection::Window(Box::new(window.clone()))),
+            None => Some(Selection::Region(Rect::new(
+                overlay.monitor.x(),
+                overlay.monitor.y(),
+                overlay.monitor.width(),
+                overlay.monitor.height(),
+            ))),
+        }
+    }
+}
+
+impl ApplicationHandler for Selector {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if let Err(err) = self.create_overlays(event_loop) {
+            self.error = Some(err);
+            self.finish(event_loop, None);
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::Escape),
+                        ..
+                    },
+                ..
+            }
+            | WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => self.finish(event_loop, None),
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((window_id, position));
+                for overlay in &self.overlays {
+                    overlay.window.request_redraw();
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.drag_start = self.cursor.filter(|(id, _)| *id == window_id);
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let selection = self.on_release(window_id);
+                if selection.is_some() {
+                    self.finish(event_loop, selection);
+                }
+            }
+            WindowEvent::Resized(size) => {
+                if let Some(overlay) = self
+                    .overlays
+                    .iter_mut()
+                    .find(|overlay| overlay.window.id() == window_id)
+                {
+                    if let Err(err) = overlay.resize(size.width, size.height) {
+                        log::error!("Resize selector overlay failed: {}", err);
+                    }
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                let Some(index) = self
+                    .overlays
+                    .iter()
+                    .position(|overlay| overlay.window.id() == window_id)
+                else {
+                    return;
+                };
+
+                let highlight = self.highlight(&self.overlays[index]);
+                if let Err(err) = self.overlays[index].draw(highlight) {
+                    log::error!("Draw selector overlay failed: {}", err);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dim_and_scale_buffer() {
+        let image = RgbaImage::from_fn(2, 1, |x, _| match x {
+            0 => image::Rgba([255, 0, 0, 255]),
+            _ => image::Rgba([0, 0, 200, 255]),
+        });
+
+        assert_eq!(
+            to_buffer(&image, 4, 1, false),
+            vec![0xff0000, 0xff0000, 0x0000c8, 0x0000c8]
+        );
+        assert_eq!(to_buffer(&image, 1, 1, true), vec![0x7f0000]);
+        assert_eq!(rect_between((10.0, 2.0), (4.0, 8.0)), (4.0, 2.0, 10.0, 8.0));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use image::ImageFormat;
+
+use crate::{
+    clock::FrameClock,
+    encode::{encode_image, EncodeOptions},
+    error::XCapResult,
+    scheduler::CaptureTarget,
+    XCapError,
+};
+
+const BOUNDARY: &str = "xcapframe";
+
+/// The path of a `GET` request line, `None` for other methods.
+fn request_path(request_line: &str) -> Option<&str> {
+    let mut parts = request_line.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+
+    let path = parts.next()?;
+    Some(path.split('?').next().unwrap_or(path))
+}
+
+/// A tiny HTTP server previewing a monitor or a window in the browser.
+///
+/// `/` and `/stream` serve a `multipart/x-mixed-replace` MJPEG stream, `/snapshot.jpg` a single
+/// JPEG. Every client captures on its own thread, there is no authentication: bind it to a
+/// trusted interface.
+#[derive(Debug, Clone)]
+pub struct PreviewServer {
+    target: CaptureTarget,
+    frame_rate: u32,
+    vsync: bool,
+    options: EncodeOptions,
+}
+
+impl PreviewServer {
+    pub fn new(target: CaptureTarget) -> PreviewServer {
+        PreviewServer {
+            target,
+            frame_rate: 10,
+            vsync: false,
+            options: EncodeOptions { quality: 80 },
+        }
+    }
+
+    /// The stream frame rate, defaults to 10.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> PreviewServer {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    /// Align the frames of a monitor stream to its vblank, see [`FrameClock::vsync`]. Falls back
+    /// to a timer for windows or when the vblank is not available.
+    pub fn with_vsync(mut self, vsync: bool) -> PreviewServer {
+        self.vsync = vsync;
+        self
+    }
+
+    /// The JPEG quality between 1 and 100, defaults to 80.
+    pub fn with_quality(mut self, quality: u8) -> PreviewServer {
+        self.options.quality = quality;
+        self
+    }
+
+    /// Listen on `addr` and serve on a background thread.
+    pub fn bind<A: ToSocketAddrs>(self, addr: A) -> XCapResult<PreviewServerHandle> {
+        let listener = TcpListener::bind(addr)?;
+        // 非阻塞 accept，才能及时响应 stop
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let server_stopped = stopped.clone();
+        let join_handle = thread::spawn(move || self.run(listener, server_stopped));
+
+        Ok(PreviewServerHandle {
+            local_addr,
+            stopped,
+            join_handle,
+        })
+    }
+
+    fn run(self, listener: TcpListener, stopped: Arc<AtomicBool>) {
+        while !stopped.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let server = self.clone();
+                    let stopped = stopped.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = server.handle(stream, &stopped) {
+                            log::debug!("Preview client disconnected: {}", err);
+                        }
+                    });
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(err) => log::error!("Preview server accept failed: {}", err),
+            }
+        }
+    }
+
+    fn capture_jpeg(&self) -> XCapResult<Vec<u8>> {
+        let image = self.target.capture_image()?;
+
+        encode_image(&image, ImageFormat::Jpeg, &self.options)
+    }
+
+    fn handle(&self, stream: TcpStream, stopped: &AtomicBool) -> XCapResult<()> {
+        stream.set_nonblocking(false)?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // 丢弃剩余的请求头
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut stream = stream;
+        match request_path(&request_line) {
+            Some("/") | Some("/stream") => self.stream(&mut stream, stopped),
+            Some("/snapshot.jpg") => {
+                let jpeg = self.capture_jpeg()?;
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+                    jpeg.len()
+                )?;
+                stream.write_all(&jpeg)?;
+
+                Ok(())
+            }
+            _ => {
+                stream.write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )?;
+
+                Ok(())
+            }
+        }
+    }
+
+    fn stream(&self, stream: &mut TcpStream, stopped: &AtomicBool) -> XCapResult<()> {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+            BOUNDARY
+        )?;
+
+        let mut clock = match &self.target {
+            CaptureTarget::Monitor(monitor) if self.vsync => {
+                FrameClock::vsync(monitor, self.frame_rate).unwrap_or_else(|err| {
+                    log::warn!("Preview vsync is not available: {}", err);
+                    FrameClock::new(self.frame_rate)
+                })
+            }
+            _ => FrameClock::new(self.frame_rate),
+        };
+
+        while !stopped.load(Ordering::Relaxed) {
+            clock.tick();
+
+            let jpeg = match self.capture_jpeg() {
+                Ok(jpeg) => jpeg,
+                Err(err) => {
+                    log::error!("Preview capture failed: {}", err);
+                    continue;
+                }
+            };
+
+            write!(
+                stream,
+                "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                BOUNDARY,
+                jpeg.len()
+            )?;
+            stream.write_all(&jpeg)?;
+            stream.write_all(b"\r\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Handle of a running [`PreviewServer`].
+#[derive(Debug)]
+pub struct PreviewServerHandle {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    join_handle: JoinHandle<()>,
+}
+
+impl PreviewServerHandle {
+    /// The address the server listens on, useful after binding port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting clients and end the running streams.
+    pub fn stop(self) -> XCapResult<()> {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.join_handle
+            .join()
+            .map_err(|_| XCapError::new("Preview server thread panicked"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_request_path() {
+        assert_eq!(request_path("GET /stream HTTP/1.1\r\n"), Some("/stream"));
+        assert_eq!(
+            request_path("GET /snapshot.jpg?t=1 HTTP/1.1\r\n"),
+            Some("/snapshot.jpg")
+        );
+        assert_eq!(request_path("POST / HTTP/1.1\r\n"), None);
+    }
+}
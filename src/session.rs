@@ -0,0 +1,189 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::SystemTime,
+};
+
+use crate::{
+    clock::FrameClock, error::XCapResult, scheduler::CaptureTarget, video_recorder::Frame,
+    XCapError,
+};
+
+/// The frames of all sources of a [`CaptureSession`] for one tick.
+#[derive(Debug)]
+pub struct FrameBundle {
+    /// Counts the ticks from 0.
+    pub index: u64,
+    /// When the tick started, every frame of the bundle carries this timestamp.
+    pub timestamp: SystemTime,
+    /// One result per source, in the order the sources were given.
+    pub frames: Vec<XCapResult<Frame>>,
+    /// The downscaled copies of `frames`, see [`CaptureSession::with_preview`], empty without a
+    /// preview.
+    pub previews: Vec<XCapResult<Frame>>,
+}
+
+/// Captures several monitors and windows from one clock.
+///
+/// Every tick captures all sources in parallel and emits them together as a [`FrameBundle`],
+/// so multi-monitor recordings stay in step instead of drifting apart like free-running
+/// streams.
+#[derive(Debug, Clone)]
+pub struct CaptureSession {
+    targets: Vec<CaptureTarget>,
+    frame_rate: u32,
+    vsync: bool,
+    output_size: Option<(u32, u32)>,
+    preview_size: Option<(u32, u32)>,
+}
+
+impl CaptureSession {
+    pub fn new(targets: Vec<CaptureTarget>) -> CaptureSession {
+        CaptureSession {
+            targets,
+            frame_rate: 30,
+            vsync: false,
+            output_size: None,
+            preview_size: None,
+        }
+    }
+
+    /// The bundle rate, defaults to 30.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> CaptureSession {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    /// Tick on the vblank of the first monitor source, see [`FrameClock::vsync`].
+    pub fn with_vsync(mut self, vsync: bool) -> CaptureSession {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Letterbox every frame to exactly `width` x `height`, see [`Frame::letterbox`].
+    pub fn with_output_size(mut self, width: u32, height: u32) -> CaptureSession {
+        self.output_size = Some((width, height));
+        self
+    }
+
+    /// Also emit every frame downscaled to fit into `max_width` x `max_height` in
+    /// [`FrameBundle::previews`], e.g. to show a live preview next to a full resolution
+    /// recording without capturing twice.
+    pub fn with_preview(mut self, max_width: u32, max_height: u32) -> CaptureSession {
+        self.preview_size = Some((max_width, max_height));
+        self
+    }
+
+    fn clock(&self) -> FrameClock {
+        let monitor = self.targets.iter().find_map(|target| match target {
+            CaptureTarget::Monitor(monitor) if self.vsync => Some(monitor),
+            _ => None,
+        });
+
+        match monitor {
+            Some(monitor) => FrameClock::vsync(monitor, self.frame_rate).unwrap_or_else(|err| {
+                log::warn!("Capture session vsync is not available: {}", err);
+                FrameClock::new(self.frame_rate)
+            }),
+            None => FrameClock::new(self.frame_rate),
+        }
+    }
+
+    fn capture_bundle(&self, index: u64) -> FrameBundle {
+        let timestamp = SystemTime::now();
+
+        // 所有源并行截图，缩短同一组帧之间的时间差
+        let frames: Vec<XCapResult<Frame>> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .targets
+                .iter()
+                .map(|target| {
+                    // Windows 上的窗口句柄只是 Send，每个线程持有自己的副本
+                    let target = target.clone();
+                    let output_size = self.output_size;
+                    scope.spawn(move || {
+                        let frame = target.capture_frame()?;
+                        match output_size {
+                            Some((width, height)) => frame.letterbox(width, height),
+                            None => Ok(frame),
+                        }
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(XCapError::new("Capture thread panicked")))
+                        .map(|frame| Frame { timestamp, ..frame })
+                })
+                .collect()
+        });
+
+        // 预览由同一次截图缩小得到，与完整帧严格同步
+        let previews = match self.preview_size {
+            Some((max_width, max_height)) => frames
+                .iter()
+                .map(|result| match result {
+                    Ok(frame) => frame.thumbnail(max_width, max_height),
+                    Err(err) => Err(XCapError::new(format!("Capture failed: {}", err))),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        FrameBundle {
+            index,
+            timestamp,
+            frames,
+            previews,
+        }
+    }
+
+    /// Run the session on a background thread, `on_bundle` is called on it for every tick.
+    pub fn start<F>(self, mut on_bundle: F) -> CaptureSessionHandle
+    where
+        F: FnMut(FrameBundle) + Send + 'static,
+    {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let session_stopped = stopped.clone();
+
+        let join_handle = thread::spawn(move || {
+            let mut clock = self.clock();
+            let mut index = 0;
+
+            while !session_stopped.load(Ordering::Relaxed) {
+                clock.tick();
+                on_bundle(self.capture_bundle(index));
+                index += 1;
+            }
+        });
+
+        CaptureSessionHandle {
+            stopped,
+            join_handle,
+        }
+    }
+}
+
+/// Handle of a running [`CaptureSession`].
+#[derive(Debug)]
+pub struct CaptureSessionHandle {
+    stopped: Arc<AtomicBool>,
+    join_handle: JoinHandle<()>,
+}
+
+impl CaptureSessionHandle {
+    /// Stop the session after the in-flight bundle.
+    pub fn stop(self) -> XCapResult<()> {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.join_handle
+            .join()
+            .map_err(|_| XCapError::new("Capture session thread panicked"))
+    }
+}
@@ -0,0 +1,600 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    time::Duration,
+};
+
+use crate::{error::XCapResult, pixel_format::PixelFormat, video_recorder::Frame, XCapError};
+
+/// A destination that consumes the frames produced by a [`crate::VideoRecorder`].
+pub trait FrameSink: Send {
+    /// Push one frame into the sink.
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()>;
+    /// Flush pending data and release the underlying resources.
+    fn finish(&mut self) -> XCapResult<()>;
+}
+
+/// The protocol used by a [`StreamSink`] to push the encoded video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamProtocol {
+    Rtmp,
+    Rtsp,
+}
+
+impl StreamProtocol {
+    fn muxer_args(&self) -> &'static [&'static str] {
+        match self {
+            StreamProtocol::Rtmp => &["-f", "flv"],
+            StreamProtocol::Rtsp => &["-f", "rtsp", "-rtsp_transport", "tcp"],
+        }
+    }
+}
+
+/// The H.264 encoder used by `ffmpeg`.
+///
+/// Hardware encoders only take the encoding off the CPU. Frames are not zero-copy, each one is
+/// read back to system memory, piped to `ffmpeg` and uploaded to the GPU again, so high
+/// resolutions still cost a readback and a copy per frame.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum H264Encoder {
+    /// `libx264`, available everywhere but expensive at high resolutions.
+    #[default]
+    Software,
+    /// VA-API on Linux, `device` is a DRM render node such as `/dev/dri/renderD128`.
+    Vaapi { device: String },
+    /// Media Foundation on Windows.
+    MediaFoundation,
+    /// VideoToolbox on macOS.
+    VideoToolbox,
+    /// NVENC on NVIDIA GPUs.
+    Nvenc,
+}
+
+impl H264Encoder {
+    /// The hardware encoder of the current platform.
+    pub fn platform_default() -> H264Encoder {
+        if cfg!(target_os = "linux") {
+            H264Encoder::Vaapi {
+                device: String::from("/dev/dri/renderD128"),
+            }
+        } else if cfg!(target_os = "windows") {
+            H264Encoder::MediaFoundation
+        } else if cfg!(target_os = "macos") {
+            H264Encoder::VideoToolbox
+        } else {
+            H264Encoder::Software
+        }
+    }
+
+    /// Options placed before the input.
+    fn input_args(&self) -> Vec<&str> {
+        match self {
+            H264Encoder::Vaapi { device } => vec!["-vaapi_device", device],
+            _ => Vec::new(),
+        }
+    }
+
+    fn encoder_args(&self) -> &'static [&'static str] {
+        match self {
+            H264Encoder::Software => &[
+                "-c:v",
+                "libx264",
+                "-preset",
+                "veryfast",
+                "-tune",
+                "zerolatency",
+                "-pix_fmt",
+                "yuv420p",
+            ],
+            // 帧先在 CPU 上转换为 nv12，再上传到 VA-API surface
+            H264Encoder::Vaapi { .. } => &["-vf", "format=nv12,hwupload", "-c:v", "h264_vaapi"],
+            H264Encoder::MediaFoundation => &[
+                "-c:v",
+                "h264_mf",
+                "-hw_encoding",
+                "1",
+                "-scenario",
+                "display_remoting",
+                "-pix_fmt",
+                "nv12",
+            ],
+            H264Encoder::VideoToolbox => &[
+                "-c:v",
+                "h264_videotoolbox",
+                "-realtime",
+                "1",
+                "-pix_fmt",
+                "nv12",
+            ],
+            H264Encoder::Nvenc => &[
+                "-c:v",
+                "h264_nvenc",
+                "-preset",
+                "p1",
+                "-tune",
+                "ll",
+                "-pix_fmt",
+                "yuv420p",
+            ],
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FfmpegProcess {
+    child: Child,
+    stdin: ChildStdin,
+    width: u32,
+    height: u32,
+}
+
+impl FfmpegProcess {
+    /// Spawn `command` with the raw RGBA frames of `width`x`height` piped to its stdin.
+    fn spawn(command: &mut Command, width: u32, height: u32) -> XCapResult<FfmpegProcess> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| XCapError::new("Open ffmpeg stdin failed"))?;
+
+        Ok(FfmpegProcess {
+            child,
+            stdin,
+            width,
+            height,
+        })
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        if self.width != frame.width || self.height != frame.height {
+            return Err(XCapError::new(format!(
+                "Frame size {}x{} differs from stream size {}x{}",
+                frame.width, frame.height, self.width, self.height
+            )));
+        }
+
+        self.stdin.write_all(&frame.raw)?;
+
+        Ok(())
+    }
+
+    fn finish(self) -> XCapResult<()> {
+        let mut child = {
+            // 关闭 stdin 后 ffmpeg 才会结束编码并退出
+            let FfmpegProcess { child, stdin, .. } = self;
+            let _stdin = stdin;
+            child
+        };
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(XCapError::new(format!("ffmpeg exited with {}", status)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Sinks take RGBA pixels, reject other layouts by name instead of a misleading size error.
+pub(crate) fn check_frame(frame: &Frame) -> XCapResult<()> {
+    if frame.pixel_format != PixelFormat::Rgba8 {
+        return Err(XCapError::new(format!(
+            "{:?} frames can not be written, capture them as Rgba8",
+            frame.pixel_format
+        )));
+    }
+
+    if frame.raw.len() != frame.width as usize * frame.height as usize * 4 {
+        return Err(XCapError::new(
+            "Frame buffer size does not match its dimensions",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Pushes frames to a RTMP or RTSP endpoint, encoded as H.264 by an `ffmpeg` child process.
+///
+/// The encoder is spawned lazily on the first frame, so the stream resolution
+/// always matches the captured source.
+#[derive(Debug)]
+pub struct StreamSink {
+    protocol: StreamProtocol,
+    url: String,
+    ffmpeg: String,
+    frame_rate: u32,
+    bitrate: Option<String>,
+    encoder: H264Encoder,
+    process: Option<FfmpegProcess>,
+}
+
+impl StreamSink {
+    pub fn new<U: ToString>(protocol: StreamProtocol, url: U) -> StreamSink {
+        StreamSink {
+            protocol,
+            url: url.to_string(),
+            ffmpeg: String::from("ffmpeg"),
+            frame_rate: 30,
+            bitrate: None,
+            encoder: H264Encoder::Software,
+            process: None,
+        }
+    }
+
+    pub fn rtmp<U: ToString>(url: U) -> StreamSink {
+        StreamSink::new(StreamProtocol::Rtmp, url)
+    }
+
+    pub fn rtsp<U: ToString>(url: U) -> StreamSink {
+        StreamSink::new(StreamProtocol::Rtsp, url)
+    }
+
+    /// The `ffmpeg` executable to use, defaults to the one found in `PATH`.
+    pub fn with_ffmpeg<P: ToString>(mut self, ffmpeg: P) -> StreamSink {
+        self.ffmpeg = ffmpeg.to_string();
+        self
+    }
+
+    /// The frame rate announced to the encoder, defaults to 30.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> StreamSink {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    /// The target video bitrate in ffmpeg notation, e.g. `"4M"`.
+    pub fn with_bitrate<B: ToString>(mut self, bitrate: B) -> StreamSink {
+        self.bitrate = Some(bitrate.to_string());
+        self
+    }
+
+    /// The H.264 encoder, defaults to [`H264Encoder::Software`].
+    pub fn with_encoder(mut self, encoder: H264Encoder) -> StreamSink {
+        self.encoder = encoder;
+        self
+    }
+
+    fn spawn(&self, width: u32, height: u32) -> XCapResult<FfmpegProcess> {
+        let size = format!("{}x{}", width, height);
+        let frame_rate = self.frame_rate.to_string();
+        let gop = (self.frame_rate * 2).to_string();
+
+        let mut command = Command::new(&self.ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(self.encoder.input_args())
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &size, "-r", &frame_rate, "-i", "-"])
+            .args(self.encoder.encoder_args())
+            .args(["-g", &gop]);
+
+        if let Some(bitrate) = &self.bitrate {
+            command.args(["-b:v", bitrate]);
+        }
+
+        command.args(self.protocol.muxer_args()).arg(&self.url);
+
+        FfmpegProcess::spawn(&mut command, width, height)
+    }
+}
+
+impl FrameSink for StreamSink {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        check_frame(frame)?;
+
+        let process = match &mut self.process {
+            Some(process) => process,
+            None => self.process.insert(self.spawn(frame.width, frame.height)?),
+        };
+
+        process.write_frame(frame)
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        match self.process.take() {
+            Some(process) => process.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for StreamSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            log::error!("StreamSink finish failed: {}", err);
+        }
+    }
+}
+
+/// `record.mp4` becomes `record.0001.mp4` for the first segment.
+fn segment_path(path: &Path, index: usize) -> PathBuf {
+    let mut file_name = path.file_stem().map(OsString::from).unwrap_or_default();
+    file_name.push(format!(".{:04}", index));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+
+    path.with_file_name(file_name)
+}
+
+/// Records frames to a video file, encoded as H.264 by an `ffmpeg` child process. The container
+/// is inferred from the file extension.
+///
+/// Long recordings can roll over to a new file after a duration or a size. Segments are
+/// numbered after the file name, `record.mp4` is written as `record.0001.mp4`,
+/// `record.0002.mp4`, ..., and every segment plays on its own.
+#[derive(Debug)]
+pub struct FileSink {
+    path: PathBuf,
+    ffmpeg: String,
+    frame_rate: u32,
+    bitrate: Option<String>,
+    encoder: H264Encoder,
+    segment_duration: Option<Duration>,
+    segment_size: Option<u64>,
+    segments: Vec<PathBuf>,
+    segment_frames: u64,
+    process: Option<FfmpegProcess>,
+}
+
+impl FileSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> FileSink {
+        FileSink {
+            path: path.as_ref().to_path_buf(),
+            ffmpeg: String::from("ffmpeg"),
+            frame_rate: 30,
+            bitrate: None,
+            encoder: H264Encoder::Software,
+            segment_duration: None,
+            segment_size: None,
+            segments: Vec::new(),
+            segment_frames: 0,
+            process: None,
+        }
+    }
+
+    /// The `ffmpeg` executable to use, defaults to the one found in `PATH`.
+    pub fn with_ffmpeg<P: ToString>(mut self, ffmpeg: P) -> FileSink {
+        self.ffmpeg = ffmpeg.to_string();
+        self
+    }
+
+    /// The frame rate of the video, defaults to 30. Frames are stored one after another at this
+    /// rate, whatever rate they are written at.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> FileSink {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    /// The target video bitrate in ffmpeg notation, e.g. `"4M"`.
+    pub fn with_bitrate<B: ToString>(mut self, bitrate: B) -> FileSink {
+        self.bitrate = Some(bitrate.to_string());
+        self
+    }
+
+    /// The H.264 encoder, defaults to [`H264Encoder::Software`].
+    pub fn with_encoder(mut self, encoder: H264Encoder) -> FileSink {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Start a new segment after `duration` of video.
+    pub fn with_segment_duration(mut self, duration: Duration) -> FileSink {
+        self.segment_duration = Some(duration);
+        self
+    }
+
+    /// Start a new segment once the current one exceeds `size` bytes. The size is checked once
+    /// per second of video, and ffmpeg buffers some data, so segments end up slightly larger.
+    pub fn with_segment_size(mut self, size: u64) -> FileSink {
+        self.segment_size = Some(size);
+        self
+    }
+
+    /// The files written so far, the last one is still being written to.
+    pub fn segments(&self) -> &[PathBuf] {
+        &self.segments
+    }
+
+    fn is_segment_full(&self) -> bool {
+        if let Some(duration) = self.segment_duration {
+            if self.segment_frames as f64 >= duration.as_secs_f64() * self.frame_rate as f64 {
+                return true;
+            }
+        }
+
+        match (self.segment_size, self.segments.last()) {
+            (Some(size), Some(path))
+                if self.segment_frames.is_multiple_of(self.frame_rate as u64) =>
+            {
+                fs::metadata(path).is_ok_and(|metadata| metadata.len() >= size)
+            }
+            _ => false,
+        }
+    }
+
+    fn spawn(&mut self, width: u32, height: u32) -> XCapResult<FfmpegProcess> {
+        let path = if self.segment_duration.is_some() || self.segment_size.is_some() {
+            segment_path(&self.path, self.segments.len() + 1)
+        } else {
+            self.path.clone()
+        };
+
+        let size = format!("{}x{}", width, height);
+        let frame_rate = self.frame_rate.to_string();
+        let gop = (self.frame_rate * 2).to_string();
+
+        let mut command = Command::new(&self.ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(self.encoder.input_args())
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &size, "-r", &frame_rate, "-i", "-"])
+            .args(self.encoder.encoder_args())
+            .args(["-g", &gop]);
+
+        if let Some(bitrate) = &self.bitrate {
+            command.args(["-b:v", bitrate]);
+        }
+
+        command.arg(&path);
+
+        let process = FfmpegProcess::spawn(&mut command, width, height)?;
+        self.segments.push(path);
+        self.segment_frames = 0;
+
+        Ok(process)
+    }
+}
+
+impl FrameSink for FileSink {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        check_frame(frame)?;
+
+        if self.process.is_some() && self.is_segment_full() {
+            self.finish()?;
+        }
+
+        if self.process.is_none() {
+            self.process = Some(self.spawn(frame.width, frame.height)?);
+        }
+
+        if let Some(process) = &mut self.process {
+            process.write_frame(frame)?;
+            self.segment_frames += 1;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        match self.process.take() {
+            Some(process) => process.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            log::error!("FileSink finish failed: {}", err);
+        }
+    }
+}
+
+/// Publishes frames as a virtual webcam, so video call applications can pick the capture as a
+/// camera.
+///
+/// Only Linux is supported, through a v4l2loopback device fed by an `ffmpeg` child process.
+/// Windows and macOS need a camera driver or system extension registered with the system, which
+/// a library cannot install. There the sink is a stub whose [`FrameSink::write_frame`] returns an
+/// "unsupported on this platform" error.
+#[derive(Debug)]
+pub struct VirtualCameraSink {
+    device: String,
+    ffmpeg: String,
+    frame_rate: u32,
+    process: Option<FfmpegProcess>,
+}
+
+impl VirtualCameraSink {
+    /// `device` is the v4l2loopback device, e.g. `/dev/video10` after
+    /// `modprobe v4l2loopback video_nr=10 exclusive_caps=1`.
+    pub fn new<D: ToString>(device: D) -> VirtualCameraSink {
+        VirtualCameraSink {
+            device: device.to_string(),
+            ffmpeg: String::from("ffmpeg"),
+            frame_rate: 30,
+            process: None,
+        }
+    }
+
+    /// The `ffmpeg` executable to use, defaults to the one found in `PATH`.
+    pub fn with_ffmpeg<P: ToString>(mut self, ffmpeg: P) -> VirtualCameraSink {
+        self.ffmpeg = ffmpeg.to_string();
+        self
+    }
+
+    /// The frame rate announced to the camera consumers, defaults to 30.
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> VirtualCameraSink {
+        self.frame_rate = frame_rate.max(1);
+        self
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn spawn(&self, _width: u32, _height: u32) -> XCapResult<FfmpegProcess> {
+        Err(XCapError::new(format!(
+            "Virtual camera {} is unsupported on this platform, only v4l2loopback on Linux is",
+            self.device
+        )))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn spawn(&self, width: u32, height: u32) -> XCapResult<FfmpegProcess> {
+        let size = format!("{}x{}", width, height);
+        let frame_rate = self.frame_rate.to_string();
+
+        let mut command = Command::new(&self.ffmpeg);
+        command
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &size, "-r", &frame_rate, "-i", "-"])
+            // 大多数视频会议软件只接受 yuv420p 的摄像头
+            .args(["-pix_fmt", "yuv420p", "-f", "v4l2"])
+            .arg(&self.device);
+
+        FfmpegProcess::spawn(&mut command, width, height)
+    }
+}
+
+impl FrameSink for VirtualCameraSink {
+    fn write_frame(&mut self, frame: &Frame) -> XCapResult<()> {
+        check_frame(frame)?;
+
+        let process = match &mut self.process {
+            Some(process) => process,
+            None => self.process.insert(self.spawn(frame.width, frame.height)?),
+        };
+
+        process.write_frame(frame)
+    }
+
+    fn finish(&mut self) -> XCapResult<()> {
+        match self.process.take() {
+            Some(process) => process.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for VirtualCameraSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            log::error!("VirtualCameraSink finish failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbered_segment_paths() {
+        assert_eq!(
+            segment_path(Path::new("/tmp/record.mp4"), 1),
+            PathBuf::from("/tmp/record.0001.mp4")
+        );
+        assert_eq!(
+            segment_path(Path::new("record"), 12),
+            PathBuf::from("record.0012")
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::{thread, time::SystemTime};
+
+use image::RgbaImage;
+
+use crate::{
+    compositor::anchor_position, pixel_format::PixelFormat, session::FrameBundle, utils::letterbox,
+    video_recorder::Frame, Anchor, Region,
+};
+
+/// How a [`TileCompositor`] arranges its sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileLayout {
+    /// Rows of equal cells filled left to right, `columns` defaults to the smallest square
+    /// grid that holds all sources.
+    Grid { columns: Option<u32> },
+    /// The first source fills the output, the others are insets of `scale` times the output
+    /// size, stacked from `anchor`.
+    PictureInPicture { anchor: Anchor, scale: f32 },
+}
+
+impl Default for TileLayout {
+    fn default() -> Self {
+        TileLayout::Grid { columns: None }
+    }
+}
+
+impl TileLayout {
+    /// The cell of every source in a `width` x `height` output.
+    fn cells(&self, count: usize, width: u32, height: u32, gap: u32) -> Vec<Region> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        match *self {
+            TileLayout::Grid { columns } => {
+                let columns = columns
+                    .unwrap_or_else(|| (count as f64).sqrt().ceil() as u32)
+                    .clamp(1, count as u32);
+                let rows = (count as u32).div_ceil(columns);
+                let cell_width = width.saturating_sub(gap * (columns - 1)) / columns;
+                let cell_height = height.saturating_sub(gap * (rows - 1)) / rows;
+
+                (0..count as u32)
+                    .map(|index| {
+                        Region::new(
+                            (index % columns) * (cell_width + gap),
+                            (index / columns) * (cell_height + gap),
+                            cell_width,
+                            cell_height,
+                        )
+                    })
+                    .collect()
+            }
+            TileLayout::PictureInPicture { anchor, scale } => {
+                let scale = scale.clamp(0.0, 1.0);
+                let inset_width = (width as f32 * scale) as u32;
+                let inset_height = (height as f32 * scale) as u32;
+
+                let mut cells = vec![Region::new(0, 0, width, height)];
+                for index in 0..count as u32 - 1 {
+                    // 画中画沿着竖直方向从角落依次排开
+                    let (x, y) =
+                        anchor_position(anchor, gap, (inset_width, inset_height), (width, height));
+                    let offset = (index * (inset_height + gap)) as i64;
+                    let y = match anchor {
+                        Anchor::TopLeft | Anchor::TopRight => y + offset,
+                        Anchor::BottomLeft | Anchor::BottomRight => y - offset,
+                    };
+                    cells.push(Region::new(
+                        x.max(0) as u32,
+                        y.max(0) as u32,
+                        inset_width,
+                        inset_height,
+                    ));
+                }
+
+                cells
+            }
+        }
+    }
+}
+
+/// Arranges the frames of several sources, e.g. the windows of a [`crate::CaptureSession`],
+/// into one output frame of a fixed size.
+///
+/// Sources are scaled in parallel. A source without a new frame, e.g. one whose capture
+/// failed, keeps showing its last tile.
+#[derive(Debug, Clone)]
+pub struct TileCompositor {
+    width: u32,
+    height: u32,
+    layout: TileLayout,
+    gap: u32,
+    background: [u8; 4],
+    tiles: Vec<Option<RgbaImage>>,
+}
+
+impl TileCompositor {
+    pub fn new(width: u32, height: u32) -> TileCompositor {
+        TileCompositor {
+            width,
+            height,
+            layout: TileLayout::default(),
+            gap: 0,
+            background: [0, 0, 0, 255],
+            tiles: Vec::new(),
+        }
+    }
+
+    pub fn with_layout(mut self, layout: TileLayout) -> TileCompositor {
+        self.layout = layout;
+        self
+    }
+
+    /// The space between cells, and between insets and the output edge.
+    pub fn with_gap(mut self, gap: u32) -> TileCompositor {
+        self.gap = gap;
+        self
+    }
+
+    /// The color shown where no source is, defaults to black.
+    pub fn with_background(mut self, background: [u8; 4]) -> TileCompositor {
+        self.background = background;
+        self
+    }
+
+    /// Compose one output frame, `None` or non RGBA frames keep the last tile of their source.
+    pub fn compose(&mut self, frames: &[Option<&Frame>]) -> Frame {
+        let cells = self
+            .layout
+            .cells(frames.len(), self.width, self.height, self.gap);
+        self.tiles.resize(frames.len(), None);
+
+        let scaled: Vec<Option<RgbaImage>> = thread::scope(|scope| {
+            let handles: Vec<_> = frames
+                .iter()
+                .zip(&cells)
+                .map(|(frame, cell)| {
+                    let frame = frame.filter(|frame| frame.pixel_format == PixelFormat::Rgba8);
+                    scope.spawn(move || {
+                        let frame = frame?;
+                        let image =
+                            RgbaImage::from_raw(frame.width, frame.height, frame.raw.clone())?;
+                        Some(letterbox(image, cell.width, cell.height))
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().ok().flatten())
+                .collect()
+        });
+
+        for (tile, scaled) in self.tiles.iter_mut().zip(scaled) {
+            if scaled.is_some() {
+                *tile = scaled;
+            }
+        }
+
+        let mut raw: Vec<u8> = self
+            .background
+            .iter()
+            .copied()
+            .cycle()
+            .take((self.width * self.height * 4) as usize)
+            .collect();
+        let row_len = self.width as usize * 4;
+
+        // 后面的源画在前面的上面
+        for (tile, cell) in self.tiles.iter().zip(&cells) {
+            let (Some(tile), Some(cell)) = (tile, cell.clamp(self.width, self.height)) else {
+                continue;
+            };
+            let tile_row_len = tile.width() as usize * 4;
+            let copy_len = cell.width.min(tile.width()) as usize * 4;
+
+            for (y, tile_row) in tile
+                .as_raw()
+                .chunks_exact(tile_row_len)
+                .take(cell.height as usize)
+                .enumerate()
+            {
+                let start = (cell.y as usize + y) * row_len + cell.x as usize * 4;
+                raw[start..start + copy_len].copy_from_slice(&tile_row[..copy_len]);
+            }
+        }
+
+        let timestamp = frames
+            .iter()
+            .flatten()
+            .map(|frame| frame.timestamp)
+            .max()
+            .unwrap_or_else(SystemTime::now);
+
+        Frame {
+            timestamp,
+            ..Frame::new(self.width, self.height, raw)
+        }
+    }
+
+    /// Compose the frames of a [`FrameBundle`], failed captures keep their last tile.
+    pub fn compose_bundle(&mut self, bundle: &FrameBundle) -> Frame {
+        let frames: Vec<Option<&Frame>> = bundle
+            .frames
+            .iter()
+            .map(|frame| frame.as_ref().ok())
+            .collect();
+
+        Frame {
+            timestamp: bundle.timestamp,
+            ..self.compose(&frames)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_and_inset_cells() {
+        let grid = TileLayout::default().cells(3, 100, 50, 2);
+        assert_eq!(grid[1], Region::new(51, 0, 49, 24));
+        assert_eq!(grid[2], Region::new(0, 26, 49, 24));
+
+        let pip = TileLayout::PictureInPicture {
+            anchor: Anchor::BottomRight,
+            scale: 0.25,
+        }
+        .cells(3, 100, 100, 4);
+        assert_eq!(pip[0], Region::new(0, 0, 100, 100));
+        assert_eq!(pip[1], Region::new(71, 71, 25, 25));
+        assert_eq!(pip[2], Region::new(71, 42, 25, 25));
+    }
+}
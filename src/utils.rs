@@ -0,0 +1,134 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::{
+    imageops::{self, FilterType},
+    Rgba, RgbaImage,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct UtcDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub weekday: u32,
+}
+
+impl UtcDateTime {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    pub fn from_unix(secs: u64) -> UtcDateTime {
+        let days = (secs / 86400) as i64;
+        let secs_of_day = secs % 86400;
+
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        UtcDateTime {
+            year,
+            month,
+            day,
+            hour: (secs_of_day / 3600) as u32,
+            minute: (secs_of_day % 3600 / 60) as u32,
+            second: (secs_of_day % 60) as u32,
+            // 1970-01-01 是星期四
+            weekday: ((days + 4).rem_euclid(7)) as u32,
+        }
+    }
+
+    pub fn now() -> UtcDateTime {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        UtcDateTime::from_unix(secs)
+    }
+}
+
+/// The largest size with the aspect ratio of `width` x `height` that fits into `max_width` x `max_height`.
+pub(crate) fn thumbnail_size(
+    width: u32,
+    height: u32,
+    max_width: u32,
+    max_height: u32,
+) -> (u32, u32) {
+    if width <= max_width && height <= max_height {
+        return (width, height);
+    }
+
+    let scale = f64::min(
+        max_width as f64 / width as f64,
+        max_height as f64 / height as f64,
+    );
+
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Downscale `image` to fit into `max_width` x `max_height`, keeping its aspect ratio.
+pub(crate) fn thumbnail(image: RgbaImage, max_width: u32, max_height: u32) -> RgbaImage {
+    let (width, height) = thumbnail_size(image.width(), image.height(), max_width, max_height);
+    if (width, height) == image.dimensions() {
+        return image;
+    }
+
+    imageops::thumbnail(&image, width, height)
+}
+
+/// Scale `image` to fit into `width` x `height` keeping its aspect ratio, and pad the rest with
+/// black bars on both sides.
+pub(crate) fn letterbox(image: RgbaImage, width: u32, height: u32) -> RgbaImage {
+    if image.dimensions() == (width, height) {
+        return image;
+    }
+
+    let mut output = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    if image.width() == 0 || image.height() == 0 || width == 0 || height == 0 {
+        return output;
+    }
+
+    let scale = f64::min(
+        width as f64 / image.width() as f64,
+        height as f64 / image.height() as f64,
+    );
+    let scaled_width = ((image.width() as f64 * scale).round() as u32).clamp(1, width);
+    let scaled_height = ((image.height() as f64 * scale).round() as u32).clamp(1, height);
+
+    // 缩小用 thumbnail 更快，放大才需要插值
+    let scaled = if scale < 1.0 {
+        imageops::thumbnail(&image, scaled_width, scaled_height)
+    } else {
+        imageops::resize(&image, scaled_width, scaled_height, FilterType::Triangle)
+    };
+    imageops::replace(
+        &mut output,
+        &scaled,
+        ((width - scaled_width) / 2) as i64,
+        ((height - scaled_height) / 2) as i64,
+    );
+
+    output
+}
+
+/// Rotate a buffer in the native orientation of a monitor rotated `rotation` degrees clockwise,
+/// so the image matches what the user sees.
+#[cfg(any(target_os = "windows", feature = "wlr-screencopy"))]
+pub(crate) fn rotate_to_screen(image: RgbaImage, rotation: f32) -> RgbaImage {
+    match (rotation.round() as i32).rem_euclid(360) {
+        90 => imageops::rotate90(&image),
+        180 => imageops::rotate180(&image),
+        270 => imageops::rotate270(&image),
+        _ => image,
+    }
+}
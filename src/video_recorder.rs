@@ -1,20 +1,250 @@
-use std::sync::{Condvar, Mutex};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
-use crate::{platform::impl_video_recorder::ImplVideoRecorder, XCapResult};
+use image::RgbaImage;
+
+#[cfg(all(feature = "rav1e", not(target_arch = "wasm32")))]
+use std::path::Path;
+
+#[cfg(all(feature = "rav1e", not(target_arch = "wasm32")))]
+use crate::encoder::{Av1Encoder, EncoderSink};
+#[cfg(target_os = "windows")]
+use crate::gpu::GpuTexture;
+use crate::{
+    color::ColorSpace,
+    frame_queue::DropPolicy,
+    metadata::{CursorPosition, FocusedWindow, FrameMetadata},
+    motion::MotionDetector,
+    pixel_format::{CaptureOptions, PixelFormat},
+    platform::impl_video_recorder::ImplVideoRecorder,
+    utils::{letterbox, thumbnail},
+    watcher::focused_window,
+    Pointer, Region, Visibility, XCapError, XCapResult,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{frame_queue::FrameQueue, sink::FrameSink};
 
 #[derive(Debug, Clone)]
 pub struct Frame {
     pub width: u32,
     pub height: u32,
     pub raw: Vec<u8>,
+    /// The color space of `raw`, derived from the source monitor.
+    pub color_space: ColorSpace,
+    /// When the frame was acquired.
+    pub timestamp: SystemTime,
+    /// Whether the captured window was on screen, always visible for monitors.
+    pub visibility: Visibility,
+    /// The layout of `raw`.
+    pub pixel_format: PixelFormat,
+    /// Values attached when the frame was captured, see [`VideoRecorder::with_metadata`].
+    pub metadata: FrameMetadata,
 }
 
 impl Frame {
     pub fn new(width: u32, height: u32, raw: Vec<u8>) -> Self {
-        Self { width, height, raw }
+        Self {
+            width,
+            height,
+            raw,
+            color_space: ColorSpace::Unknown,
+            timestamp: SystemTime::now(),
+            visibility: Visibility::Visible,
+            pixel_format: PixelFormat::Rgba8,
+            metadata: FrameMetadata::default(),
+        }
+    }
+
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Convert the pixels of an RGBA frame to `pixel_format`.
+    pub fn to_pixel_format(self, pixel_format: PixelFormat) -> XCapResult<Frame> {
+        if self.pixel_format == pixel_format {
+            return Ok(self);
+        }
+        if self.pixel_format != PixelFormat::Rgba8 {
+            return Err(XCapError::new("Only RGBA frames can be converted"));
+        }
+
+        Ok(Frame {
+            raw: pixel_format.convert_rgba(self.raw),
+            pixel_format,
+            ..self
+        })
+    }
+
+    /// A copy of the frame downscaled to fit into `max_width` x `max_height`, keeping the
+    /// aspect ratio, e.g. for a preview in the UI.
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<Frame> {
+        if self.pixel_format != PixelFormat::Rgba8 {
+            return Err(XCapError::new("Only RGBA frames can be scaled"));
+        }
+
+        let image = RgbaImage::from_raw(self.width, self.height, self.raw.clone())
+            .ok_or_else(|| XCapError::new("Frame size does not match its data"))?;
+        let image = thumbnail(image, max_width, max_height);
+
+        Ok(Frame {
+            width: image.width(),
+            height: image.height(),
+            raw: image.into_raw(),
+            ..self.clone_without_raw()
+        })
+    }
+
+    /// The frame scaled to fit into `width` x `height` and padded with black bars to exactly
+    /// that size, for encoders that need one frame size for a whole session.
+    pub fn letterbox(self, width: u32, height: u32) -> XCapResult<Frame> {
+        if (self.width, self.height) == (width, height) {
+            return Ok(self);
+        }
+        if self.pixel_format != PixelFormat::Rgba8 {
+            return Err(XCapError::new("Only RGBA frames can be letterboxed"));
+        }
+
+        let image = RgbaImage::from_raw(self.width, self.height, self.raw)
+            .ok_or_else(|| XCapError::new("Frame size does not match its data"))?;
+
+        Ok(Frame {
+            width,
+            height,
+            raw: letterbox(image, width, height).into_raw(),
+            ..self
+        })
+    }
+
+    /// A copy of the part of the frame inside `region`, clamped to the frame, `None` if nothing
+    /// of it is inside.
+    pub fn crop(&self, region: Region) -> Option<Frame> {
+        let region = region.clamp(self.width, self.height)?;
+        let bytes_per_pixel = self.pixel_format.bytes_per_pixel();
+        let row_len = self.width as usize * bytes_per_pixel;
+        let start = region.x as usize * bytes_per_pixel;
+        let end = start + region.width as usize * bytes_per_pixel;
+
+        let raw = self
+            .raw
+            .chunks_exact(row_len)
+            .skip(region.y as usize)
+            .take(region.height as usize)
+            .flat_map(|row| &row[start..end])
+            .copied()
+            .collect();
+
+        Some(Frame {
+            width: region.width,
+            height: region.height,
+            raw,
+            ..self.clone_without_raw()
+        })
+    }
+
+    /// A copy of everything but the pixels, for building a derived frame.
+    fn clone_without_raw(&self) -> Frame {
+        Frame {
+            width: self.width,
+            height: self.height,
+            raw: Vec::new(),
+            color_space: self.color_space,
+            timestamp: self.timestamp,
+            visibility: self.visibility,
+            pixel_format: self.pixel_format,
+            metadata: self.metadata.clone(),
+        }
     }
 }
 
+/// Capture `count` frames spaced `interval` apart.
+///
+/// Every capture is scheduled against a fixed deadline computed from the start
+/// time, so a slow capture does not shift the following ones. Frames are stamped
+/// with the time their capture started.
+pub(crate) fn capture_burst<F>(
+    count: usize,
+    interval: Duration,
+    capture: F,
+) -> XCapResult<Vec<Frame>>
+where
+    F: Fn() -> XCapResult<Frame>,
+{
+    let mut frames = Vec::with_capacity(count);
+    let start = Instant::now();
+
+    for i in 0..count {
+        let deadline = start + interval * i as u32;
+        let now = Instant::now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+
+        // 截图前记录时间，截图耗时不算进时间戳
+        let timestamp = SystemTime::now();
+        let mut frame = capture()?;
+        frame.timestamp = timestamp;
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
+/// Like [`capture_burst`], sampling the latest frame of `video_recorder` instead of capturing.
+/// The stream only delivers a frame when the screen changes, so samples repeat the latest one
+/// and `capture` stands in until the first frame arrives.
+#[cfg(target_os = "windows")]
+pub(crate) fn stream_burst<F>(
+    video_recorder: &VideoRecorder,
+    count: usize,
+    interval: Duration,
+    capture: F,
+) -> XCapResult<Vec<Frame>>
+where
+    F: Fn() -> XCapResult<Frame>,
+{
+    let latest: Arc<Mutex<Option<Frame>>> = Arc::new(Mutex::new(None));
+    let done = Arc::new(AtomicBool::new(false));
+
+    let stream_recorder = video_recorder.clone();
+    let stream_latest = latest.clone();
+    let stream_done = done.clone();
+    thread::spawn(move || {
+        let result = stream_recorder.on_frame(move |frame| {
+            if stream_done.load(Ordering::Relaxed) {
+                return Err(XCapError::new("Burst is done"));
+            }
+            *stream_latest.lock()? = Some(frame);
+            Ok(())
+        });
+        if let Err(err) = result {
+            log::debug!("Burst stream ended: {}", err);
+        }
+    });
+    video_recorder.start()?;
+
+    let frames = capture_burst(count, interval, || match latest.lock()?.clone() {
+        Some(frame) => Ok(frame),
+        None => capture(),
+    });
+    done.store(true, Ordering::Relaxed);
+    video_recorder.stop()?;
+
+    frames
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub(crate) struct RecorderWaker {
@@ -56,30 +286,479 @@ impl RecorderWaker {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Lifecycle events of a [`VideoRecorder`] stream, see [`VideoRecorder::on_event`].
+#[derive(Debug)]
+pub enum StreamEvent {
+    /// A transient error interrupted the stream, it is rebuilt after `delay`.
+    Interrupted {
+        error: String,
+        attempt: u32,
+        delay: Duration,
+    },
+    /// The capture source was rebuilt after `attempts` tries and delivers frames again.
+    Recovered { attempts: u32 },
+}
+
+type OnEvent = Arc<dyn Fn(&StreamEvent) + Send + Sync>;
+type MetadataProvider = Arc<dyn Fn(&mut FrameMetadata) + Send + Sync>;
+
+#[derive(Clone)]
 pub struct VideoRecorder {
     impl_video_recorder: ImplVideoRecorder,
+    paused: Arc<AtomicBool>,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    on_event: Arc<Mutex<Option<OnEvent>>>,
+    drop_policy: Option<(DropPolicy, usize)>,
+    dropped_frames: Arc<AtomicU64>,
+    output_size: Option<(u32, u32)>,
+    options: CaptureOptions,
+    metadata_providers: Vec<MetadataProvider>,
+}
+
+impl fmt::Debug for VideoRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VideoRecorder")
+            .field("impl_video_recorder", &self.impl_video_recorder)
+            .field("paused", &self.paused)
+            .field("max_retries", &self.max_retries)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("drop_policy", &self.drop_policy)
+            .field("output_size", &self.output_size)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
 }
 
 impl VideoRecorder {
     pub(crate) fn new(impl_video_recorder: ImplVideoRecorder) -> VideoRecorder {
         VideoRecorder {
             impl_video_recorder,
+            paused: Arc::new(AtomicBool::new(false)),
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            on_event: Arc::new(Mutex::new(None)),
+            drop_policy: None,
+            dropped_frames: Arc::new(AtomicU64::new(0)),
+            output_size: None,
+            options: CaptureOptions::default(),
+            metadata_providers: Vec::new(),
         }
     }
-}
 
+    /// Convert the frames as `options` say, e.g. to [`PixelFormat::Gray8`].
+    pub fn with_options(mut self, options: CaptureOptions) -> VideoRecorder {
+        self.options = options;
+        self
+    }
+
+    /// Deliver frames in the native orientation of a rotated monitor, as the backend captured
+    /// them, instead of rotated to match the screen. Rotate them by [`crate::Monitor::rotation`]
+    /// degrees clockwise to get what the user sees, GPU consumers can do that for free.
+    pub fn with_raw_orientation(mut self, raw_orientation: bool) -> VideoRecorder {
+        self.impl_video_recorder = self
+            .impl_video_recorder
+            .with_raw_orientation(raw_orientation);
+        self
+    }
+
+    /// Deliver every frame letterboxed to exactly `width` x `height`, see [`Frame::letterbox`].
+    /// With a drop policy the frames are scaled on the delivery thread.
+    pub fn with_output_size(mut self, width: u32, height: u32) -> VideoRecorder {
+        self.output_size = Some((width, height));
+        self
+    }
+
+    /// Deliver frames to [`VideoRecorder::on_frame`] from a separate thread, with at most
+    /// `max_in_flight` frames queued for it. Without it `on_frame` runs on the capture thread,
+    /// and a slow consumer delays the capture. Ignored in the browser, which has no threads.
+    pub fn with_drop_policy(mut self, policy: DropPolicy, max_in_flight: usize) -> VideoRecorder {
+        self.drop_policy = Some((policy, max_in_flight.max(1)));
+        self
+    }
+
+    /// How often the capture source is rebuilt after a transient error before
+    /// [`VideoRecorder::on_frame`] gives up, defaults to 5. The count restarts once frames
+    /// arrive again.
+    pub fn with_max_retries(mut self, max_retries: u32) -> VideoRecorder {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Rebuild the capture source after `initial`, doubling the delay on every failed attempt up
+    /// to `max`. Defaults to 100ms and 5s.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> VideoRecorder {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Call `provider` on the capture thread as soon as a frame arrives, to attach values to
+    /// [`Frame::metadata`] while they still match the pixels. Providers run in the order they
+    /// were added and delay the capture, keep them cheap.
+    pub fn with_metadata<F>(mut self, provider: F) -> VideoRecorder
+    where
+        F: Fn(&mut FrameMetadata) + Send + Sync + 'static,
+    {
+        self.metadata_providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Attach the [`CursorPosition`] of the first [`Pointer`] to every frame.
+    pub fn with_cursor_metadata(self) -> VideoRecorder {
+        let pointer = Pointer::all()
+            .ok()
+            .and_then(|pointers| pointers.into_iter().next());
+
+        self.with_metadata(move |metadata| {
+            if let Some(Ok((x, y))) = pointer.as_ref().map(Pointer::position) {
+                metadata.insert(CursorPosition { x, y });
+            }
+        })
+    }
+
+    /// Attach the [`FocusedWindow`] to every frame. Enumerating windows per frame is too slow,
+    /// the focus is taken from a running [`crate::Watcher`], without one nothing is attached.
+    pub fn with_focus_metadata(self) -> VideoRecorder {
+        self.with_metadata(|metadata| {
+            if let Some(id) = focused_window() {
+                metadata.insert(FocusedWindow { id });
+            }
+        })
+    }
+
+    fn emit(&self, event: StreamEvent) {
+        let on_event = self
+            .on_event
+            .lock()
+            .ok()
+            .and_then(|on_event| on_event.clone());
+        match on_event {
+            Some(on_event) => on_event(&event),
+            None => log::info!("Video recorder {:?}", event),
+        }
+    }
+}
 impl VideoRecorder {
+    /// Deliver frames to `on_frame` until the stream stops, see
+    /// [`VideoRecorder::with_drop_policy`] for slow consumers. A lost Desktop Duplication, e.g.
+    /// after a resolution change, is recreated transparently. Other transient errors, e.g. a
+    /// reset X connection, rebuild the capture source with backoff instead of ending the
+    /// stream, see [`VideoRecorder::with_backoff`].
     pub fn on_frame<F>(&self, on_frame: F) -> XCapResult<()>
     where
         F: Fn(Frame) -> XCapResult<()> + Send + 'static,
     {
-        self.impl_video_recorder.on_frame(on_frame)
+        let output_size = self.output_size;
+        let options = self.options;
+        let on_frame = move |frame: Frame| {
+            let frame = match output_size {
+                Some((width, height)) => frame.letterbox(width, height)?,
+                None => frame,
+            };
+
+            on_frame(options.apply(frame)?)
+        };
+
+        match self.drop_policy {
+            #[cfg(not(target_arch = "wasm32"))]
+            Some((policy, max_in_flight)) => self.deliver_queued(policy, max_in_flight, on_frame),
+            _ => self.deliver(on_frame),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn deliver_queued<F>(
+        &self,
+        policy: DropPolicy,
+        max_in_flight: usize,
+        on_frame: F,
+    ) -> XCapResult<()>
+    where
+        F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+    {
+        let queue = Arc::new(FrameQueue::new(policy, max_in_flight));
+
+        let consumer_queue = queue.clone();
+        let consumer = thread::spawn(move || {
+            while let Some(frame) = consumer_queue.pop()? {
+                if let Err(err) = on_frame(frame) {
+                    // 让截图线程的下一次 push 失败，结束录制
+                    consumer_queue.close();
+                    return Err(err);
+                }
+            }
+
+            Ok(())
+        });
+
+        let producer_queue = queue.clone();
+        let result = self.deliver(move |frame| producer_queue.push(frame));
+        queue.close();
+        self.dropped_frames
+            .fetch_add(queue.dropped(), Ordering::Relaxed);
+
+        let consumer_result = consumer
+            .join()
+            .unwrap_or_else(|_| Err(XCapError::new("Frame consumer thread panicked")));
+        // 消费者的错误才是录制结束的原因
+        consumer_result.and(result)
+    }
+
+    /// How many frames the drop policy dropped in finished [`VideoRecorder::on_frame`] calls.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    fn deliver<F>(&self, on_frame: F) -> XCapResult<()>
+    where
+        F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+    {
+        // 重建后还要继续回调同一个 on_frame
+        let on_frame = Arc::new(Mutex::new(on_frame));
+        let delivered = Arc::new(AtomicBool::new(false));
+        let mut failures = 0;
+        // 重建的录制器和 self 共享启停状态，start、stop 仍然作用于它
+        let mut impl_video_recorder = self.impl_video_recorder.clone();
+
+        loop {
+            let paused = self.paused.clone();
+            let frame_delivered = delivered.clone();
+            let frame_on_frame = on_frame.clone();
+            let metadata_providers = self.metadata_providers.clone();
+
+            let result = impl_video_recorder.on_frame(move |mut frame| {
+                frame_delivered.store(true, Ordering::Relaxed);
+                if paused.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+
+                // 在截图线程上附加，排队之后再取就和画面对不上了
+                for provider in &metadata_providers {
+                    provider(&mut frame.metadata);
+                }
+
+                let on_frame = frame_on_frame.lock()?;
+                (*on_frame)(frame)
+            });
+
+            let mut error = match result {
+                Err(error) if error.is_transient() => error,
+                result => return result,
+            };
+            if delivered.swap(false, Ordering::Relaxed) {
+                failures = 0;
+            }
+
+            // 重建也可能失败，失败次数一起计算
+            loop {
+                if failures >= self.max_retries {
+                    return Err(error);
+                }
+                let delay = self
+                    .initial_backoff
+                    .saturating_mul(1 << failures.min(16))
+                    .min(self.max_backoff);
+                failures += 1;
+                log::warn!("Video recorder interrupted: {}", error);
+                self.emit(StreamEvent::Interrupted {
+                    error: error.to_string(),
+                    attempt: failures,
+                    delay,
+                });
+                thread::sleep(delay);
+
+                match impl_video_recorder.rebuild() {
+                    Ok(rebuilt) => {
+                        impl_video_recorder = rebuilt;
+                        self.emit(StreamEvent::Recovered { attempts: failures });
+                        break;
+                    }
+                    Err(err) if err.is_transient() => error = err,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+    /// Crop every frame to the named `regions` and call `on_region` for each of them, e.g. to
+    /// watch several widgets on one monitor with a single capture instead of one per widget.
+    /// Regions are in the pixel space of the frames, those outside a frame are skipped.
+    pub fn on_regions<F>(&self, regions: Vec<(String, Region)>, on_region: F) -> XCapResult<()>
+    where
+        F: Fn(&str, Frame) -> XCapResult<()> + Send + 'static,
+    {
+        self.on_frame(move |frame| {
+            for (name, region) in &regions {
+                if let Some(cropped) = frame.crop(*region) {
+                    on_region(name, cropped)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+    /// Receive the [`StreamEvent`]s of the stream, they are logged otherwise.
+    pub fn on_event<F>(&self, on_event: F) -> XCapResult<()>
+    where
+        F: Fn(&StreamEvent) + Send + Sync + 'static,
+    {
+        *self.on_event.lock()? = Some(Arc::new(on_event));
+
+        Ok(())
+    }
+    /// Like [`VideoRecorder::on_frame`], but only called for frames in which `motion_detector` detects motion.
+    pub fn on_motion<F>(&self, motion_detector: MotionDetector, on_frame: F) -> XCapResult<()>
+    where
+        F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+    {
+        self.on_frame(move |frame| {
+            if motion_detector.detect(&frame)? {
+                on_frame(frame)
+            } else {
+                Ok(())
+            }
+        })
+    }
+    /// Crop every frame to `region` and call `on_change` with the crop when more than
+    /// `threshold` (a ratio between 0 and 1) of it changed since the last reported crop, e.g. to
+    /// watch a build status light or a dashboard tile. The first crop is always reported.
+    pub fn on_region_change<F>(
+        &self,
+        region: Region,
+        threshold: f32,
+        on_change: F,
+    ) -> XCapResult<()>
+    where
+        F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+    {
+        let motion_detector = MotionDetector::new(threshold);
+
+        self.on_frame(move |frame| match frame.crop(region) {
+            Some(cropped) if motion_detector.detect(&cropped)? => on_change(cropped),
+            _ => Ok(()),
+        })
+    }
+    /// Deliver frames as textures on the [`crate::GpuDevice`] registered with the
+    /// [`crate::XCapContext`] the recorder was created by, without copying them to system
+    /// memory. Frame processing such as [`VideoRecorder::with_output_size`] does not apply.
+    #[cfg(target_os = "windows")]
+    pub fn on_texture<F>(&self, on_texture: F) -> XCapResult<()>
+    where
+        F: Fn(GpuTexture) -> XCapResult<()> + Send + 'static,
+    {
+        self.impl_video_recorder.on_texture(on_texture)
     }
+
     pub fn start(&self) -> XCapResult<()> {
         self.impl_video_recorder.start()
     }
     pub fn stop(&self) -> XCapResult<()> {
         self.impl_video_recorder.stop()
     }
+    /// Stop delivering frames while the capture keeps running, so [`VideoRecorder::resume`]
+    /// continues without reopening the capture or restarting a [`crate::FrameSink`]. The
+    /// recording has no gap, paused time is simply cut out.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Write every frame into `sink` on a background thread and start the recorder.
+    /// [`Recording::stop`] stops the recorder and finishes the sink.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn record_to<S: FrameSink + 'static>(&self, sink: S) -> XCapResult<Recording> {
+        let sink: SharedSink = Arc::new(Mutex::new(Some(Box::new(sink))));
+
+        let video_recorder = self.clone();
+        let frame_sink = sink.clone();
+        thread::spawn(move || {
+            let result = video_recorder.on_frame(move |frame| match frame_sink.lock()?.as_mut() {
+                Some(sink) => sink.write_frame(&frame),
+                None => Err(XCapError::new("Recording is stopped")),
+            });
+            if let Err(err) = result {
+                log::debug!("Recording ended: {}", err);
+            }
+        });
+        self.start()?;
+
+        Ok(Recording {
+            video_recorder: self.clone(),
+            sink,
+        })
+    }
+
+    /// Record into an AV1 IVF file at `path` with [`Av1Encoder`], see [`VideoRecorder::record_to`].
+    #[cfg(all(feature = "rav1e", not(target_arch = "wasm32")))]
+    pub fn record_to_file<P: AsRef<Path>>(&self, path: P) -> XCapResult<Recording> {
+        self.record_to(EncoderSink::new(path, Av1Encoder::new()))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type SharedSink = Arc<Mutex<Option<Box<dyn FrameSink>>>>;
+
+/// A recording started by [`VideoRecorder::record_to`].
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Recording {
+    video_recorder: VideoRecorder,
+    sink: SharedSink,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl fmt::Debug for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recording")
+            .field("video_recorder", &self.video_recorder)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Recording {
+    /// Stop the recorder and finish the sink, e.g. flush the encoder and close the file.
+    pub fn stop(self) -> XCapResult<()> {
+        self.video_recorder.stop()?;
+        match self.sink.lock()?.take() {
+            Some(mut sink) => sink.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_clamped_region() {
+        let raw = (0..4 * 3)
+            .flat_map(|index| [index as u8, 0, 0, 255])
+            .collect();
+        let frame = Frame::new(4, 3, raw);
+
+        let cropped = frame.crop(Region::new(2, 1, 5, 5)).unwrap();
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        let reds: Vec<u8> = cropped.raw.chunks_exact(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(reds, [6, 7, 10, 11]);
+        assert!(frame.crop(Region::new(4, 0, 1, 1)).is_none());
+    }
+
+    #[test]
+    fn letterbox_wide_frame() {
+        let frame = Frame::new(4, 2, [255; 4 * 2 * 4].to_vec());
+        let boxed = frame.letterbox(4, 4).unwrap();
+
+        assert_eq!((boxed.width, boxed.height), (4, 4));
+        let alpha_rows: Vec<u8> = boxed.raw.chunks_exact(16).map(|row| row[0]).collect();
+        assert_eq!(alpha_rows, [0, 255, 255, 0]);
+    }
 }
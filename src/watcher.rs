@@ -0,0 +1,754 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex, OnceLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{error::XCapResult, platform::impl_watcher::ImplWatcher, Monitor, Window, XCapError};
+
+/// A change of a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowEvent {
+    TitleChanged {
+        id: u32,
+        title: String,
+    },
+    /// The window position changed, with its new rect.
+    Moved {
+        id: u32,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    /// The window size changed, with its new rect.
+    Resized {
+        id: u32,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    /// The stacking order changed, e.g. a window was raised. `ids` are all windows from top to
+    /// bottom, `raised` the ones that moved above a window they were below before.
+    Restacked {
+        ids: Vec<u32>,
+        raised: Vec<u32>,
+    },
+    /// The window was minimized, maximized, made full screen or restored.
+    StateChanged {
+        id: u32,
+        state: WindowState,
+        previous: WindowState,
+    },
+}
+
+/// Whether a window is minimized, maximized or full screen, see [`WindowEvent::StateChanged`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowState {
+    Normal,
+    Minimized,
+    Maximized,
+    Fullscreen,
+}
+
+impl From<&Window> for WindowState {
+    fn from(window: &Window) -> Self {
+        // 最小化的全屏窗口算最小化
+        if window.is_minimized() {
+            WindowState::Minimized
+        } else if window.is_fullscreen() {
+            WindowState::Fullscreen
+        } else if window.is_maximized() {
+            WindowState::Maximized
+        } else {
+            WindowState::Normal
+        }
+    }
+}
+
+/// A change of the display configuration.
+#[derive(Debug, Clone)]
+pub enum DisplayEvent {
+    Added(Monitor),
+    /// The monitor as it was last seen.
+    Removed(Monitor),
+    /// The position, resolution, rotation, scale factor or refresh rate changed.
+    ModeChanged(Monitor),
+}
+
+/// The focused window changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FocusEvent {
+    /// The focused window, `None` when no window has the focus.
+    pub id: Option<u32>,
+    /// The window focused before, as last reported.
+    pub previous: Option<u32>,
+    /// When the focus change was first seen.
+    pub timestamp: SystemTime,
+}
+
+/// An event reported by [`Watcher`].
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    Window(WindowEvent),
+    Display(DisplayEvent),
+    Focus(FocusEvent),
+}
+
+/// What the watcher remembers of a window between two updates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WindowSnapshot {
+    id: u32,
+    title: String,
+    x: i32,
+    y: i32,
+    z: i32,
+    width: u32,
+    height: u32,
+    state: WindowState,
+}
+
+impl From<&Window> for WindowSnapshot {
+    fn from(window: &Window) -> Self {
+        WindowSnapshot {
+            id: window.id(),
+            title: window.title().to_string(),
+            x: window.x(),
+            y: window.y(),
+            z: window.z(),
+            width: window.width(),
+            height: window.height(),
+            state: WindowState::from(window),
+        }
+    }
+}
+
+fn diff_windows(
+    previous: &HashMap<u32, WindowSnapshot>,
+    current: &[WindowSnapshot],
+) -> Vec<WindowEvent> {
+    let mut events = Vec::new();
+
+    for window in current {
+        if let Some(previous_window) = previous.get(&window.id) {
+            if previous_window.title != window.title {
+                events.push(WindowEvent::TitleChanged {
+                    id: window.id,
+                    title: window.title.clone(),
+                });
+            }
+
+            if (previous_window.x, previous_window.y) != (window.x, window.y) {
+                events.push(WindowEvent::Moved {
+                    id: window.id,
+                    x: window.x,
+                    y: window.y,
+                    width: window.width,
+                    height: window.height,
+                });
+            }
+
+            if (previous_window.width, previous_window.height) != (window.width, window.height) {
+                events.push(WindowEvent::Resized {
+                    id: window.id,
+                    x: window.x,
+                    y: window.y,
+                    width: window.width,
+                    height: window.height,
+                });
+            }
+
+            if previous_window.state != window.state {
+                events.push(WindowEvent::StateChanged {
+                    id: window.id,
+                    state: window.state,
+                    previous: previous_window.state,
+                });
+            }
+        }
+    }
+
+    if let Some(event) = diff_stacking(previous, current) {
+        events.push(event);
+    }
+
+    events
+}
+
+/// Compare the order of the windows that existed before and still exist, windows mapped on
+/// top or destroyed do not restack the others.
+fn diff_stacking(
+    previous: &HashMap<u32, WindowSnapshot>,
+    current: &[WindowSnapshot],
+) -> Option<WindowEvent> {
+    let mut ids: Vec<&WindowSnapshot> = current.iter().collect();
+    ids.sort_by_key(|window| -window.z);
+
+    let mut previous_order: Vec<&WindowSnapshot> = previous
+        .values()
+        .filter(|window| current.iter().any(|current| current.id == window.id))
+        .collect();
+    previous_order.sort_by_key(|window| -window.z);
+    let current_order: Vec<u32> = ids
+        .iter()
+        .filter(|window| previous.contains_key(&window.id))
+        .map(|window| window.id)
+        .collect();
+
+    if previous_order
+        .iter()
+        .map(|window| window.id)
+        .eq(current_order.iter().copied())
+    {
+        return None;
+    }
+
+    // 比之前在它上面的某个窗口更靠上的就是被提升的窗口
+    let position = |order: &[u32], id: u32| order.iter().position(|item| *item == id);
+    let previous_ids: Vec<u32> = previous_order.iter().map(|window| window.id).collect();
+    let raised = current_order
+        .iter()
+        .enumerate()
+        .filter(|(index, id)| {
+            previous_ids[..position(&previous_ids, **id).unwrap_or(0)]
+                .iter()
+                .any(|above| position(&current_order, *above) > Some(*index))
+        })
+        .map(|(_, id)| *id)
+        .collect();
+
+    Some(WindowEvent::Restacked {
+        ids: ids.iter().map(|window| window.id).collect(),
+        raised,
+    })
+}
+
+/// Debounces focus transitions, a window has to keep the focus for `debounce` to be reported.
+#[derive(Debug)]
+struct FocusTracker {
+    debounce: Duration,
+    reported: Option<u32>,
+    pending: Option<(Option<u32>, Instant, SystemTime)>,
+}
+
+impl FocusTracker {
+    fn new(focused: Option<u32>, debounce: Duration) -> FocusTracker {
+        FocusTracker {
+            debounce,
+            reported: focused,
+            pending: None,
+        }
+    }
+
+    fn update(&mut self, focused: Option<u32>, now: Instant) {
+        if focused == self.reported {
+            // 快速 alt-tab 后又回到原窗口，丢弃中间的切换
+            self.pending = None;
+        } else if self.pending.map(|(id, ..)| id) != Some(focused) {
+            self.pending = Some((focused, now, SystemTime::now()));
+        }
+    }
+
+    fn poll(&mut self, now: Instant) -> Option<FocusEvent> {
+        let (id, since, timestamp) = self.pending?;
+        if now.duration_since(since) < self.debounce {
+            return None;
+        }
+
+        self.pending = None;
+        let previous = std::mem::replace(&mut self.reported, id);
+        Some(FocusEvent {
+            id,
+            previous,
+            timestamp,
+        })
+    }
+}
+
+/// How long a window had the focus while a [`Watcher`] ran, see [`Window::focus_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FocusStats {
+    /// When the window last got the focus.
+    pub last_focused: Option<SystemTime>,
+    /// The total time the window had the focus, including the current stretch.
+    pub focused_duration: Duration,
+    /// How many times the window got the focus.
+    pub focus_count: u32,
+}
+
+/// The focus stats of all windows, fed by the reported [`FocusEvent`]s.
+#[derive(Debug, Default)]
+struct FocusBook {
+    stats: HashMap<u32, FocusStats>,
+    focused: Option<(u32, SystemTime)>,
+}
+
+impl FocusBook {
+    fn record(&mut self, id: Option<u32>, timestamp: SystemTime) {
+        if let Some((focused, since)) = self.focused.take() {
+            let stats = self.stats.entry(focused).or_default();
+            stats.focused_duration += timestamp.duration_since(since).unwrap_or_default();
+        }
+
+        if let Some(id) = id {
+            let stats = self.stats.entry(id).or_default();
+            stats.last_focused = Some(timestamp);
+            stats.focus_count += 1;
+            self.focused = Some((id, timestamp));
+        }
+    }
+
+    fn stats(&self, id: u32, now: SystemTime) -> Option<FocusStats> {
+        let mut stats = *self.stats.get(&id)?;
+        if let Some((_, since)) = self.focused.filter(|(focused, _)| *focused == id) {
+            stats.focused_duration += now.duration_since(since).unwrap_or_default();
+        }
+
+        Some(stats)
+    }
+}
+
+/// 所有 Watcher 共用，Window::focus_stats 不需要拿到 WatcherHandle
+fn focus_book() -> &'static Mutex<FocusBook> {
+    static FOCUS_BOOK: OnceLock<Mutex<FocusBook>> = OnceLock::new();
+
+    FOCUS_BOOK.get_or_init(|| Mutex::new(FocusBook::default()))
+}
+
+fn record_focus(id: Option<u32>, timestamp: SystemTime) {
+    if let Ok(mut focus_book) = focus_book().lock() {
+        focus_book.record(id, timestamp);
+    }
+}
+
+pub(crate) fn focus_stats(id: u32) -> Option<FocusStats> {
+    focus_book().lock().ok()?.stats(id, SystemTime::now())
+}
+
+/// The window a running [`Watcher`] last reported focused.
+pub(crate) fn focused_window() -> Option<u32> {
+    focus_book().lock().ok()?.focused.map(|(id, _)| id)
+}
+
+type PidIndex = Arc<Mutex<HashMap<u32, Vec<Window>>>>;
+
+fn index_by_pid(windows: &[Window]) -> HashMap<u32, Vec<Window>> {
+    let mut pid_index: HashMap<u32, Vec<Window>> = HashMap::new();
+    for window in windows {
+        pid_index
+            .entry(window.pid())
+            .or_default()
+            .push(window.clone());
+    }
+
+    pid_index
+}
+
+fn is_mode_changed(previous: &Monitor, current: &Monitor) -> bool {
+    previous.x() != current.x()
+        || previous.y() != current.y()
+        || previous.width() != current.width()
+        || previous.height() != current.height()
+        || previous.rotation() != current.rotation()
+        || previous.scale_factor() != current.scale_factor()
+        || previous.frequency() != current.frequency()
+}
+
+fn diff_monitors(previous: &[Monitor], current: &[Monitor]) -> Vec<DisplayEvent> {
+    let mut events = Vec::new();
+
+    for monitor in current {
+        match previous
+            .iter()
+            .find(|previous| previous.id() == monitor.id())
+        {
+            Some(previous) if is_mode_changed(previous, monitor) => {
+                events.push(DisplayEvent::ModeChanged(monitor.clone()))
+            }
+            Some(_) => {}
+            None => events.push(DisplayEvent::Added(monitor.clone())),
+        }
+    }
+
+    for monitor in previous {
+        if !current.iter().any(|current| current.id() == monitor.id()) {
+            events.push(DisplayEvent::Removed(monitor.clone()));
+        }
+    }
+
+    events
+}
+
+/// Watch windows and monitors for changes in a background thread.
+///
+/// Windows and monitors are re-enumerated as soon as the platform reports a change
+/// (PropertyNotify, ConfigureNotify and RandR notifications on X11, WinEvents on Windows,
+/// accessibility notifications on macOS) and every poll interval otherwise. Restacking is
+/// reported by `_NET_CLIENT_LIST_STACKING` changes on X11 and `EVENT_OBJECT_REORDER` on
+/// Windows, state changes by `_NET_WM_STATE` changes and
+/// `EVENT_SYSTEM_MINIMIZESTART`/`EVENT_SYSTEM_MINIMIZEEND`. macOS without the accessibility
+/// permission and Wayland sessions without XWayland only poll.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    poll_interval: Duration,
+    coalesce_interval: Duration,
+    focus_debounce: Duration,
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Watcher {
+            poll_interval: Duration::from_secs(1),
+            coalesce_interval: Duration::from_millis(16),
+            focus_debounce: Duration::from_millis(200),
+        }
+    }
+}
+
+impl Watcher {
+    pub fn new() -> Watcher {
+        Watcher::default()
+    }
+
+    /// How often to re-enumerate when the platform reports nothing, defaults to 1 second.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Watcher {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// The minimum time between two re-enumerations, defaults to 16 milliseconds (60Hz).
+    ///
+    /// Changes reported within it are coalesced: a window dragged across the screen yields a
+    /// single [`WindowEvent::Moved`] per interval instead of one per ConfigureNotify or WinEvent,
+    /// and the events of one interval are delivered together, see [`WatcherHandle::recv_batch`].
+    pub fn with_coalesce_interval(mut self, coalesce_interval: Duration) -> Watcher {
+        self.coalesce_interval = coalesce_interval;
+        self
+    }
+
+    /// How long a window has to keep the focus before [`FocusEvent`] reports it, defaults to
+    /// 200 milliseconds. Focus that returns to the reported window within it is not reported.
+    pub fn with_focus_debounce(mut self, focus_debounce: Duration) -> Watcher {
+        self.focus_debounce = focus_debounce;
+        self
+    }
+
+    pub fn start(self) -> XCapResult<WatcherHandle> {
+        let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+        let (event_sender, event_receiver) = mpsc::channel::<WatchEvent>();
+
+        let windows = Window::all()?;
+        let monitors = Monitor::all()?;
+        let pid_index = Arc::new(Mutex::new(index_by_pid(&windows)));
+        let thread_pid_index = pid_index.clone();
+        let join_handle = thread::spawn(move || {
+            self.run(
+                windows,
+                monitors,
+                thread_pid_index,
+                stop_receiver,
+                event_sender,
+            );
+            // 停止后不再累计前台时间
+            record_focus(None, SystemTime::now());
+        });
+
+        Ok(WatcherHandle {
+            pid_index,
+            stop_sender,
+            join_handle,
+            event_receiver,
+        })
+    }
+
+    fn run(
+        self,
+        windows: Vec<Window>,
+        mut monitors: Vec<Monitor>,
+        pid_index: PidIndex,
+        stop_receiver: Receiver<()>,
+        event_sender: Sender<WatchEvent>,
+    ) {
+        let mut impl_watcher = ImplWatcher::new();
+        let mut previous: HashMap<u32, WindowSnapshot> = windows
+            .iter()
+            .map(|window| (window.id(), WindowSnapshot::from(window)))
+            .collect();
+        let focused = windows
+            .iter()
+            .find(|window| window.is_focused())
+            .map(|window| window.id());
+        let mut focus_tracker = FocusTracker::new(focused, self.focus_debounce);
+        record_focus(focused, SystemTime::now());
+        let mut last_update = Instant::now();
+        let mut changed = false;
+
+        while let Err(TryRecvError::Empty) = stop_receiver.try_recv() {
+            // 分段等待，保证 stop 能及时生效；有未处理的变化时只等到合并间隔结束
+            let timeout = if changed {
+                self.coalesce_interval.saturating_sub(last_update.elapsed())
+            } else {
+                self.poll_interval
+            };
+            changed |= impl_watcher.wait(timeout.min(Duration::from_millis(100)));
+
+            if let Some(event) = focus_tracker.poll(Instant::now()) {
+                record_focus(event.id, event.timestamp);
+                if event_sender.send(WatchEvent::Focus(event)).is_err() {
+                    return;
+                }
+            }
+
+            let elapsed = last_update.elapsed();
+            if elapsed < self.coalesce_interval || (!changed && elapsed < self.poll_interval) {
+                continue;
+            }
+            last_update = Instant::now();
+            changed = false;
+
+            // 先报告显示器变化，窗口的位置变化通常是它引起的
+            match Monitor::all() {
+                Ok(current) => {
+                    for event in diff_monitors(&monitors, &current) {
+                        if event_sender.send(WatchEvent::Display(event)).is_err() {
+                            return;
+                        }
+                    }
+                    monitors = current;
+                }
+                Err(err) => log::error!("Watcher list monitors failed: {}", err),
+            }
+
+            let current: Vec<WindowSnapshot> = match Window::all() {
+                Ok(windows) => {
+                    let focused = windows
+                        .iter()
+                        .find(|window| window.is_focused())
+                        .map(|window| window.id());
+                    focus_tracker.update(focused, Instant::now());
+
+                    if let Ok(mut pid_index) = pid_index.lock() {
+                        *pid_index = index_by_pid(&windows);
+                    }
+
+                    windows.iter().map(WindowSnapshot::from).collect()
+                }
+                Err(err) => {
+                    log::error!("Watcher list windows failed: {}", err);
+                    continue;
+                }
+            };
+
+            for event in diff_windows(&previous, &current) {
+                if event_sender.send(WatchEvent::Window(event)).is_err() {
+                    return;
+                }
+            }
+
+            previous = current
+                .into_iter()
+                .map(|window| (window.id, window))
+                .collect();
+        }
+    }
+}
+
+/// Handle of a running [`Watcher`], receives its events.
+#[derive(Debug)]
+pub struct WatcherHandle {
+    pid_index: PidIndex,
+    stop_sender: Sender<()>,
+    join_handle: JoinHandle<()>,
+    event_receiver: Receiver<WatchEvent>,
+}
+
+impl WatcherHandle {
+    /// Block until the next event.
+    pub fn recv(&self) -> XCapResult<WatchEvent> {
+        self.event_receiver
+            .recv()
+            .map_err(|_| XCapError::new("Watcher stopped"))
+    }
+
+    /// Block until the next event, `None` when `timeout` elapses first.
+    pub fn recv_timeout(&self, timeout: Duration) -> XCapResult<Option<WatchEvent>> {
+        match self.event_receiver.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(XCapError::new("Watcher stopped")),
+        }
+    }
+
+    /// Block until the next event, then return it with every event already pending, `None`
+    /// when `timeout` elapses first. The events of one coalesce interval arrive together.
+    pub fn recv_batch(&self, timeout: Duration) -> XCapResult<Option<Vec<WatchEvent>>> {
+        let event = match self.recv_timeout(timeout)? {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+
+        let mut events = vec![event];
+        events.extend(self.event_receiver.try_iter());
+
+        Ok(Some(events))
+    }
+
+    /// The next event if one is pending.
+    pub fn try_recv(&self) -> Option<WatchEvent> {
+        self.event_receiver.try_recv().ok()
+    }
+
+    /// Iterate over the events, blocks between them.
+    pub fn iter(&self) -> impl Iterator<Item = WatchEvent> + '_ {
+        self.event_receiver.iter()
+    }
+
+    /// Iterate over the focus changes only, the other events are dropped.
+    pub fn focus_events(&self) -> impl Iterator<Item = FocusEvent> + '_ {
+        self.event_receiver.iter().filter_map(|event| match event {
+            WatchEvent::Focus(event) => Some(event),
+            _ => None,
+        })
+    }
+
+    /// The windows of the process `pid` as of the last update, without enumerating again.
+    pub fn windows_by_pid(&self, pid: u32) -> Vec<Window> {
+        self.pid_index
+            .lock()
+            .ok()
+            .and_then(|pid_index| pid_index.get(&pid).cloned())
+            .unwrap_or_default()
+    }
+
+    /// Stop the watcher and wait for its thread to exit.
+    pub fn stop(self) -> XCapResult<()> {
+        // 线程已经退出时 send 会失败，忽略即可
+        let _ = self.stop_sender.send(());
+        self.join_handle
+            .join()
+            .map_err(|_| XCapError::new("Watcher thread panicked"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(id: u32, title: &str, x: i32, width: u32) -> WindowSnapshot {
+        WindowSnapshot {
+            id,
+            title: String::from(title),
+            x,
+            y: 0,
+            z: 0,
+            width,
+            height: 100,
+            state: WindowState::Normal,
+        }
+    }
+
+    #[test]
+    fn diff_titles() {
+        let previous = HashMap::from([(1, snapshot(1, "Inbox", 0, 100))]);
+        let current = vec![snapshot(1, "Drafts", 0, 100), snapshot(2, "New", 0, 100)];
+
+        assert_eq!(
+            diff_windows(&previous, &current),
+            vec![WindowEvent::TitleChanged {
+                id: 1,
+                title: String::from("Drafts"),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_geometry() {
+        let previous = HashMap::from([(1, snapshot(1, "Inbox", 0, 100))]);
+        let current = vec![snapshot(1, "Inbox", 10, 200)];
+
+        assert_eq!(
+            diff_windows(&previous, &current),
+            vec![
+                WindowEvent::Moved {
+                    id: 1,
+                    x: 10,
+                    y: 0,
+                    width: 200,
+                    height: 100,
+                },
+                WindowEvent::Resized {
+                    id: 1,
+                    x: 10,
+                    y: 0,
+                    width: 200,
+                    height: 100,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_raised_window() {
+        let stacked = |id: u32, z: i32| WindowSnapshot {
+            z,
+            ..snapshot(id, "", 0, 100)
+        };
+        let previous = HashMap::from([(1, stacked(1, 3)), (2, stacked(2, 2)), (3, stacked(3, 1))]);
+
+        // 新窗口出现在最上层不算重新排列
+        let mapped = vec![stacked(4, 4), stacked(1, 3), stacked(2, 2), stacked(3, 1)];
+        assert_eq!(diff_windows(&previous, &mapped), vec![]);
+
+        let raised = vec![stacked(3, 3), stacked(1, 2), stacked(2, 1)];
+        assert_eq!(
+            diff_windows(&previous, &raised),
+            vec![WindowEvent::Restacked {
+                ids: vec![3, 1, 2],
+                raised: vec![3],
+            }]
+        );
+    }
+
+    #[test]
+    fn focus_debounce() {
+        let start = Instant::now();
+        let debounce = Duration::from_millis(200);
+        let mut focus_tracker = FocusTracker::new(Some(1), debounce);
+
+        // 1 -> 2 -> 1 在防抖时间内，不报告
+        focus_tracker.update(Some(2), start);
+        focus_tracker.update(Some(1), start + Duration::from_millis(50));
+        assert_eq!(focus_tracker.poll(start + debounce * 2), None);
+
+        focus_tracker.update(Some(3), start);
+        assert_eq!(focus_tracker.poll(start + Duration::from_millis(50)), None);
+
+        let event = focus_tracker.poll(start + debounce).unwrap();
+        assert_eq!((event.id, event.previous), (Some(3), Some(1)));
+        assert_eq!(focus_tracker.poll(start + debounce * 2), None);
+    }
+
+    #[test]
+    fn focus_time() {
+        let start = SystemTime::now();
+        let mut focus_book = FocusBook::default();
+
+        focus_book.record(Some(1), start);
+        focus_book.record(Some(2), start + Duration::from_secs(3));
+        focus_book.record(Some(1), start + Duration::from_secs(4));
+
+        let stats = focus_book.stats(1, start + Duration::from_secs(6)).unwrap();
+        assert_eq!(stats.focused_duration, Duration::from_secs(5));
+        assert_eq!(stats.focus_count, 2);
+        assert_eq!(stats.last_focused, Some(start + Duration::from_secs(4)));
+
+        focus_book.record(None, start + Duration::from_secs(6));
+        let stats = focus_book.stats(2, start + Duration::from_secs(9)).unwrap();
+        assert_eq!(stats.focused_duration, Duration::from_secs(1));
+    }
+}
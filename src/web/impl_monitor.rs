@@ -0,0 +1,180 @@
+use image::RgbaImage;
+
+use crate::{
+    backend::Backend,
+    capabilities::Inhibition,
+    color::{ColorSpace, GammaRamp},
+    custom::CustomMonitor,
+    error::{XCapError, XCapResult},
+    layout::Rect,
+    utils::thumbnail,
+    VideoMode,
+};
+
+use super::impl_video_recorder::ImplVideoRecorder;
+
+/// The browser does not expose the monitors, the user picks what to share in the
+/// `getDisplayMedia` dialog. This pseudo monitor stands for that choice.
+#[derive(Debug, Clone)]
+pub(crate) struct ImplMonitor {
+    pub id: u32,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub rotation: f32,
+    pub scale_factor: f32,
+    pub frequency: f32,
+    pub is_primary: bool,
+    pub color_space: ColorSpace,
+}
+
+impl ImplMonitor {
+    fn user_choice() -> ImplMonitor {
+        let (width, height, scale_factor) = web_sys::window()
+            .map(|window| {
+                let scale_factor = window.device_pixel_ratio() as f32;
+                let size = window
+                    .screen()
+                    .map(|screen| {
+                        (
+                            screen.width().unwrap_or_default(),
+                            screen.height().unwrap_or_default(),
+                        )
+                    })
+                    .unwrap_or_default();
+
+                (size.0.max(0) as u32, size.1.max(0) as u32, scale_factor)
+            })
+            .unwrap_or((0, 0, 1.0));
+
+        ImplMonitor {
+            id: 0,
+            name: String::from("User choice"),
+            x: 0,
+            y: 0,
+            width,
+            height,
+            rotation: 0.0,
+            scale_factor,
+            frequency: 0.0,
+            is_primary: true,
+            color_space: ColorSpace::Unknown,
+        }
+    }
+
+    pub fn backend() -> Backend {
+        Backend::DisplayMedia
+    }
+
+    pub fn inhibitions() -> Vec<Inhibition> {
+        // 用户在共享对话框里做出选择之前，浏览器不透露任何信息
+        Vec::new()
+    }
+
+    /// A monitor of a custom backend.
+    pub fn from_custom(custom_monitor: &CustomMonitor) -> ImplMonitor {
+        ImplMonitor {
+            id: custom_monitor.id,
+            name: custom_monitor.name.clone(),
+            x: custom_monitor.x,
+            y: custom_monitor.y,
+            width: custom_monitor.width,
+            height: custom_monitor.height,
+            rotation: 0.0,
+            scale_factor: custom_monitor.scale_factor,
+            frequency: custom_monitor.frequency,
+            is_primary: custom_monitor.is_primary,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn all_with_backend(backend: Backend) -> XCapResult<Vec<ImplMonitor>> {
+        if backend != Backend::DisplayMedia {
+            return Err(XCapError::new(format!(
+                "{:?} backend is not available",
+                backend
+            )));
+        }
+
+        ImplMonitor::all()
+    }
+
+    pub fn all() -> XCapResult<Vec<ImplMonitor>> {
+        Ok(vec![ImplMonitor::user_choice()])
+    }
+
+    pub fn from_point(_x: i32, _y: i32) -> XCapResult<ImplMonitor> {
+        Ok(ImplMonitor::user_choice())
+    }
+}
+
+impl ImplMonitor {
+    /// The screen of the browser never goes away.
+    pub fn is_valid(&self) -> bool {
+        true
+    }
+
+    pub fn capture_image(&self) -> XCapResult<RgbaImage> {
+        // getDisplayMedia 是异步的，浏览器里不能阻塞等待
+        Err(XCapError::new(
+            "Synchronous capture is not supported in the browser, use Monitor::video_recorder",
+        ))
+    }
+
+    /// No native exclusion, [`Monitor`](crate::Monitor) composites the window captures instead.
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_excluding(&self, _window_ids: &[u32]) -> XCapResult<Option<RgbaImage>> {
+        Ok(None)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_desktop_only(&self) -> XCapResult<RgbaImage> {
+        Err(XCapError::new("The browser does not expose the wallpaper"))
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_root_background(&self, _rect: Rect) -> XCapResult<Option<RgbaImage>> {
+        Ok(None)
+    }
+
+    pub fn workarea(&self) -> XCapResult<Rect> {
+        // 浏览器只共享画面，不知道任务栏的位置
+        Ok(Rect::new(self.x, self.y, self.width, self.height))
+    }
+
+    pub fn stable_id(&self) -> String {
+        // 共享的画面没有硬件信息
+        self.name.clone()
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        Ok(thumbnail(self.capture_image()?, max_width, max_height))
+    }
+
+    pub fn is_mirror_of(&self, _other: &ImplMonitor) -> bool {
+        false
+    }
+
+    pub fn supported_modes(&self) -> XCapResult<Vec<VideoMode>> {
+        Ok(Vec::new())
+    }
+
+    pub fn brightness(&self) -> XCapResult<f32> {
+        Err(XCapError::new(
+            "Monitor brightness is not available in the browser",
+        ))
+    }
+
+    pub fn gamma_ramp(&self) -> XCapResult<GammaRamp> {
+        Err(XCapError::new(
+            "Monitor gamma is not available in the browser",
+        ))
+    }
+
+    pub fn video_recorder(&self) -> XCapResult<ImplVideoRecorder> {
+        ImplVideoRecorder::new()
+    }
+}
@@ -0,0 +1,18 @@
+use crate::error::{XCapError, XCapResult};
+
+/// The browser only reports the pointer over the page, not over the shared surface.
+#[derive(Debug, Clone)]
+pub(crate) struct ImplPointer {
+    pub id: u32,
+    pub name: String,
+}
+
+impl ImplPointer {
+    pub fn all() -> XCapResult<Vec<ImplPointer>> {
+        Ok(Vec::new())
+    }
+
+    pub fn position(&self) -> XCapResult<(i32, i32)> {
+        Err(XCapError::new("The pointer is not visible in the browser"))
+    }
+}
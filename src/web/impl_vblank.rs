@@ -0,0 +1,17 @@
+use crate::error::{XCapError, XCapResult};
+
+use super::impl_monitor::ImplMonitor;
+
+#[derive(Debug)]
+pub(crate) struct ImplVblank;
+
+impl ImplVblank {
+    pub fn new(_impl_monitor: &ImplMonitor) -> XCapResult<ImplVblank> {
+        // 浏览器里只能在 requestAnimationFrame 中对齐刷新
+        Err(XCapError::new("Vblank is not available in the browser"))
+    }
+
+    pub fn wait(&self) -> XCapResult<()> {
+        Ok(())
+    }
+}
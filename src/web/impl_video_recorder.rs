@@ -0,0 +1,240 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::Rc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use wasm_bindgen::{closure::Closure, JsCast};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlVideoElement, MediaStream};
+
+use crate::{
+    color::ColorSpace,
+    error::{XCapError, XCapResult},
+    metadata::FrameMetadata,
+    report::pixel_format,
+    video_recorder::Frame,
+    PixelFormat, Visibility,
+};
+
+use super::js_error;
+
+type OnFrame = Rc<dyn Fn(Frame) -> XCapResult<()>>;
+
+#[derive(Default)]
+struct RecorderState {
+    on_frame: Option<OnFrame>,
+    running: bool,
+    stream: Option<MediaStream>,
+    interval_id: Option<i32>,
+    tick: Option<Closure<dyn FnMut()>>,
+}
+
+/// Streams the surface chosen in the `getDisplayMedia` dialog, frames are read back from a
+/// `<video>` element through a canvas.
+#[derive(Clone)]
+pub struct ImplVideoRecorder {
+    state: Rc<RefCell<RecorderState>>,
+}
+
+impl fmt::Debug for ImplVideoRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImplVideoRecorder").finish_non_exhaustive()
+    }
+}
+
+fn create_element<T: JsCast>(name: &str) -> XCapResult<T> {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or(XCapError::new("No document"))?
+        .create_element(name)
+        .map_err(js_error)?
+        .dyn_into::<T>()
+        .map_err(|_| XCapError::new(format!("Create <{}> failed", name)))
+}
+
+fn read_frame(
+    video: &HtmlVideoElement,
+    canvas: &HtmlCanvasElement,
+    context: &CanvasRenderingContext2d,
+) -> XCapResult<Option<Frame>> {
+    let width = video.video_width();
+    let height = video.video_height();
+    // 视频还没有第一帧
+    if width == 0 || height == 0 {
+        return Ok(None);
+    }
+
+    if canvas.width() != width || canvas.height() != height {
+        canvas.set_width(width);
+        canvas.set_height(height);
+    }
+
+    context
+        .draw_image_with_html_video_element(video, 0.0, 0.0)
+        .map_err(js_error)?;
+    let image_data = context
+        .get_image_data(0.0, 0.0, width as f64, height as f64)
+        .map_err(js_error)?;
+
+    // 浏览器里 SystemTime::now 不可用
+    let timestamp = UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1000.0);
+
+    Ok(Some(Frame {
+        width,
+        height,
+        raw: image_data.data().0,
+        color_space: ColorSpace::Unknown,
+        timestamp,
+        visibility: Visibility::Visible,
+        pixel_format: PixelFormat::Rgba8,
+        metadata: FrameMetadata::default(),
+    }))
+}
+
+impl ImplVideoRecorder {
+    pub fn new() -> XCapResult<Self> {
+        Ok(ImplVideoRecorder {
+            state: Rc::new(RefCell::new(RecorderState::default())),
+        })
+    }
+
+    pub fn on_frame<F>(&self, on_frame: F) -> XCapResult<()>
+    where
+        F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+    {
+        self.state.borrow_mut().on_frame = Some(Rc::new(on_frame));
+
+        Ok(())
+    }
+
+    /// The browser ends the stream itself, there is nothing to rebuild.
+    pub fn rebuild(&self) -> XCapResult<Self> {
+        Ok(self.clone())
+    }
+
+    /// The browser always delivers what the user sees.
+    pub fn with_raw_orientation(self, _raw_orientation: bool) -> Self {
+        self
+    }
+
+    fn start_stream(state: &Rc<RefCell<RecorderState>>, stream: MediaStream) -> XCapResult<()> {
+        let video: HtmlVideoElement = create_element("video")?;
+        video.set_muted(true);
+        video.set_src_object(Some(&stream));
+        let _ = video.play().map_err(js_error)?;
+
+        let canvas: HtmlCanvasElement = create_element("canvas")?;
+        let context = canvas
+            .get_context("2d")
+            .map_err(js_error)?
+            .ok_or(XCapError::new("Get canvas 2d context failed"))?
+            .dyn_into::<CanvasRenderingContext2d>()
+            .map_err(|_| XCapError::new("Get canvas 2d context failed"))?;
+
+        let tick_state = state.clone();
+        let tick = Closure::<dyn FnMut()>::new(move || {
+            let on_frame = match tick_state.borrow().on_frame.clone() {
+                Some(on_frame) => on_frame,
+                None => return,
+            };
+
+            let result = read_frame(&video, &canvas, &context).and_then(|frame| match frame {
+                Some(frame) => on_frame(frame),
+                None => Ok(()),
+            });
+            if let Err(err) = result {
+                log::error!("Video recorder frame failed: {}", err);
+            }
+        });
+
+        let interval_id = web_sys::window()
+            .ok_or(XCapError::new("No window"))?
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                tick.as_ref().unchecked_ref(),
+                1000 / 30,
+            )
+            .map_err(js_error)?;
+
+        // 浏览器不公开原始帧的格式，canvas 总是给出 RGBA8
+        pixel_format(
+            "display-media",
+            String::from("RGBA8 read back through a 2d canvas"),
+        );
+
+        let mut state = state.borrow_mut();
+        state.stream = Some(stream);
+        state.interval_id = Some(interval_id);
+        state.tick = Some(tick);
+
+        Ok(())
+    }
+
+    /// Show the `getDisplayMedia` dialog, frames start once the user picked a surface.
+    ///
+    /// Browsers only allow this from a user gesture such as a click handler.
+    pub fn start(&self) -> XCapResult<()> {
+        if self.state.borrow().running {
+            return Ok(());
+        }
+
+        let promise = web_sys::window()
+            .ok_or(XCapError::new("No window"))?
+            .navigator()
+            .media_devices()
+            .map_err(js_error)?
+            .get_display_media()
+            .map_err(js_error)?;
+        self.state.borrow_mut().running = true;
+
+        let state = self.state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let stream = match JsFuture::from(promise).await {
+                Ok(stream) => stream.unchecked_into::<MediaStream>(),
+                Err(err) => {
+                    log::error!("getDisplayMedia failed: {}", js_error(err));
+                    state.borrow_mut().running = false;
+                    return;
+                }
+            };
+
+            // 用户选择期间已经调用了 stop
+            if !state.borrow().running {
+                stop_tracks(&stream);
+                return;
+            }
+
+            if let Err(err) = ImplVideoRecorder::start_stream(&state, stream) {
+                log::error!("Start video recorder failed: {}", err);
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn stop(&self) -> XCapResult<()> {
+        let mut state = self.state.borrow_mut();
+        state.running = false;
+
+        if let Some(interval_id) = state.interval_id.take() {
+            if let Some(window) = web_sys::window() {
+                window.clear_interval_with_handle(interval_id);
+            }
+        }
+        if let Some(stream) = state.stream.take() {
+            stop_tracks(&stream);
+        }
+        state.tick = None;
+
+        Ok(())
+    }
+}
+
+fn stop_tracks(stream: &MediaStream) {
+    for track in stream.get_tracks().iter() {
+        if let Ok(track) = track.dyn_into::<web_sys::MediaStreamTrack>() {
+            track.stop();
+        }
+    }
+}
@@ -0,0 +1,14 @@
+/// The [`Watcher`](crate::Watcher) needs a thread, which the browser main thread can not
+/// block on. Nothing changes between enumerations in the browser anyway.
+pub(crate) struct ImplWatcher;
+
+impl ImplWatcher {
+    pub fn new() -> ImplWatcher {
+        ImplWatcher
+    }
+
+    /// Never reports a change.
+    pub fn wait(&mut self, _timeout: std::time::Duration) -> bool {
+        false
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+
+use image::RgbaImage;
+
+use crate::{
+    backend::Backend,
+    enumeration::Enumeration,
+    error::{XCapError, XCapResult},
+    utils::thumbnail,
+    ProcessInfo, WindowKind, WindowShape,
+};
+
+use super::impl_monitor::ImplMonitor;
+
+/// Windows can only be shared through the `getDisplayMedia` dialog, the browser does not
+/// enumerate them.
+#[derive(Debug, Clone)]
+pub(crate) struct ImplWindow {
+    pub id: u32,
+    pub title: String,
+    pub title_bytes: Vec<u8>,
+    pub app_name: String,
+    pub pid: u32,
+    pub current_monitor: ImplMonitor,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub width: u32,
+    pub height: u32,
+    pub is_minimized: bool,
+    pub is_maximized: bool,
+    pub is_fullscreen: bool,
+    pub is_focused: bool,
+    pub is_xwayland: bool,
+    pub kind: WindowKind,
+    pub skip_taskbar: bool,
+    pub skip_pager: bool,
+    pub is_visible: bool,
+    pub owner_id: Option<u32>,
+}
+
+impl ImplWindow {
+    pub fn backend() -> Backend {
+        Backend::DisplayMedia
+    }
+
+    pub fn all() -> XCapResult<Vec<ImplWindow>> {
+        Ok(ImplWindow::all_with_errors()?.into_logged_items())
+    }
+
+    pub fn all_with_override_redirect() -> XCapResult<Vec<ImplWindow>> {
+        ImplWindow::all()
+    }
+
+    pub fn all_with_errors() -> XCapResult<Enumeration<ImplWindow>> {
+        Ok(Enumeration::default())
+    }
+}
+
+impl ImplWindow {
+    pub fn exe_path(&self) -> XCapResult<PathBuf> {
+        Err(XCapError::new("Processes are not visible in the browser"))
+    }
+
+    pub fn cmdline(&self) -> XCapResult<Vec<String>> {
+        Err(XCapError::new("Processes are not visible in the browser"))
+    }
+
+    pub fn process_info(&self) -> XCapResult<ProcessInfo> {
+        Err(XCapError::new("Processes are not visible in the browser"))
+    }
+}
+
+impl ImplWindow {
+    pub fn capture_image(&self) -> XCapResult<RgbaImage> {
+        Err(XCapError::new(
+            "Window capture is not supported in the browser, use Monitor::video_recorder",
+        ))
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        Ok(thumbnail(self.capture_image()?, max_width, max_height))
+    }
+}
+
+impl ImplWindow {
+    pub fn is_valid(&self) -> bool {
+        false
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn scroll(&self, _lines: i32) -> XCapResult<()> {
+        Err(XCapError::new(
+            "Input can not be synthesized in the browser",
+        ))
+    }
+
+    pub fn shape(&self) -> XCapResult<WindowShape> {
+        Ok(WindowShape::rectangle(self.width, self.height))
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl ImplWindow {
+    pub fn raw_window_handle(&self) -> XCapResult<raw_window_handle::RawWindowHandle> {
+        Err(XCapError::new("Browser windows have no raw window handle"))
+    }
+}
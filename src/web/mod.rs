@@ -0,0 +1,21 @@
+pub mod impl_monitor;
+pub mod impl_pointer;
+pub mod impl_vblank;
+pub mod impl_video_recorder;
+pub mod impl_watcher;
+pub mod impl_window;
+
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::DomException;
+
+use crate::error::{Remediation, XCapError};
+
+pub(super) fn js_error(err: JsValue) -> XCapError {
+    // 用户拒绝了共享屏幕的对话框
+    match err.dyn_ref::<DomException>() {
+        Some(exception) if exception.name() == "NotAllowedError" => {
+            XCapError::permission_denied(exception.message(), Remediation::SharingDialog)
+        }
+        _ => XCapError::new(format!("{:?}", err)),
+    }
+}
@@ -1,7 +1,241 @@
-use image::RgbaImage;
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
 
-use crate::{error::XCapResult, platform::impl_window::ImplWindow, Monitor};
+#[cfg(feature = "image")]
+use image::Rgba32FImage;
+use image::{ImageFormat, RgbaImage};
 
+#[cfg(feature = "image")]
+use crate::color::to_linear_image;
+use crate::{
+    encode::{encode_image, save_image, EncodeOptions},
+    enumeration::Enumeration,
+    error::{XCapError, XCapResult},
+    platform::impl_window::ImplWindow,
+    video_recorder::{capture_burst, Frame},
+    watcher::{focus_stats, FocusStats},
+    CaptureOptions, Monitor, Rect, Region,
+};
+
+/// The role of a window, from `_NET_WM_WINDOW_TYPE` on X11, the window class and styles on
+/// Windows and the window level on macOS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WindowKind {
+    #[default]
+    Normal,
+    Dialog,
+    /// Palettes and tool windows.
+    Utility,
+    Toolbar,
+    /// Dropdown, popup and combo box menus.
+    Menu,
+    /// Docks, panels, taskbars and the menu bar.
+    Dock,
+    Desktop,
+    Splash,
+    Tooltip,
+    Notification,
+}
+
+/// The CPU architecture a process runs as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcessArch {
+    X86,
+    X86_64,
+    Arm,
+    Arm64,
+    Unknown,
+}
+
+impl ProcessArch {
+    pub fn is_64bit(&self) -> bool {
+        matches!(self, ProcessArch::X86_64 | ProcessArch::Arm64)
+    }
+}
+
+/// How the process owning a window runs, see [`Window::process_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcessInfo {
+    pub arch: ProcessArch,
+    /// Whether the process is confined: the App Sandbox on macOS, an AppContainer on Windows,
+    /// Flatpak or Snap on Linux.
+    pub is_sandboxed: bool,
+    /// Whether the process runs translated from another architecture: Rosetta on macOS, x86
+    /// and x64 emulation on Windows on ARM. Always false on Linux.
+    pub is_translated: bool,
+}
+
+/// What identifies a window across restarts of its application, when its id and pid change.
+/// Take it with [`Window::fingerprint`], store the fields and find the window again with
+/// [`Window::from_fingerprint`]. Matching is best effort: two windows of the same app with
+/// similar titles can be mixed up.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WindowFingerprint {
+    /// The executable path, the app name when the path cannot be read.
+    pub app: String,
+    /// The title with every run of digits replaced by `#`, so counters and clocks in it do not
+    /// matter.
+    pub title_pattern: String,
+    pub kind: WindowKind,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowFingerprint {
+    /// How well `other` matches, 0 when it belongs to another app. Higher is better.
+    pub fn score(&self, other: &WindowFingerprint) -> u32 {
+        if self.app != other.app {
+            return 0;
+        }
+
+        // 标题最能区分同一应用的多个窗口，位置和大小在重启后常常会变
+        let mut score = 1;
+        if self.title_pattern == other.title_pattern {
+            score += 8;
+        }
+        if self.kind == other.kind {
+            score += 4;
+        }
+        if (self.width, self.height) == (other.width, other.height) {
+            score += 2;
+        }
+        if (self.x, self.y) == (other.x, other.y) {
+            score += 1;
+        }
+
+        score
+    }
+}
+
+fn title_pattern(title: &str) -> String {
+    let mut pattern = String::with_capacity(title.len());
+    for c in title.trim().chars() {
+        if !c.is_ascii_digit() {
+            pattern.push(c);
+        } else if !pattern.ends_with('#') {
+            pattern.push('#');
+        }
+    }
+
+    pattern
+}
+
+/// Whether a window was on screen when it was captured. Backends deliver stale or black
+/// content for hidden windows, consumers can pause encoding or show a placeholder instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    /// Covered entirely by the windows above it.
+    Occluded,
+    Minimized,
+    /// Outside of all monitors, on another virtual desktop or not mapped.
+    OffScreen,
+}
+
+/// The visibility of `target` on `screens`, below the `above` windows. The rectangles are
+/// split into a grid along all their edges, `target` is occluded when every cell of it on a
+/// screen is covered.
+fn visibility_of(target: Rect, screens: &[Rect], above: &[Rect]) -> Visibility {
+    let on_screen: Vec<Rect> = screens
+        .iter()
+        .filter_map(|screen| screen.intersection(&target))
+        .collect();
+    if on_screen.is_empty() {
+        return Visibility::OffScreen;
+    }
+
+    let mut xs = vec![target.x, target.right()];
+    let mut ys = vec![target.y, target.bottom()];
+    for rect in on_screen.iter().chain(above) {
+        xs.extend([rect.x, rect.right()]);
+        ys.extend([rect.y, rect.bottom()]);
+    }
+    for edges in [&mut xs, &mut ys] {
+        edges.sort_unstable();
+        edges.dedup();
+    }
+
+    for y in ys.windows(2) {
+        for x in xs.windows(2) {
+            let (cell_x, cell_y) = (x[0], y[0]);
+            let exposed = on_screen.iter().any(|rect| rect.contains(cell_x, cell_y))
+                && !above.iter().any(|rect| rect.contains(cell_x, cell_y));
+            if exposed {
+                return Visibility::Visible;
+            }
+        }
+    }
+
+    Visibility::Occluded
+}
+
+/// The parts of a window that are drawn and that receive input, as rectangles relative to its
+/// top left corner. Rectangular windows have a single rectangle covering the whole window.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WindowShape {
+    /// The size of the window the shape belongs to.
+    pub width: u32,
+    pub height: u32,
+    /// The visible parts, e.g. without the cut off rounded corners.
+    pub bounding: Vec<Region>,
+    /// The parts receiving clicks, empty for click-through windows.
+    pub input: Vec<Region>,
+}
+
+impl WindowShape {
+    /// The shape of a plain rectangular window.
+    pub(crate) fn rectangle(width: u32, height: u32) -> WindowShape {
+        let whole = vec![Region::new(0, 0, width, height)];
+
+        WindowShape {
+            width,
+            height,
+            bounding: whole.clone(),
+            input: whole,
+        }
+    }
+
+    /// The window lets all clicks through to the windows below.
+    pub fn is_click_through(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    /// Make the pixels of a capture of the window outside its bounding shape transparent.
+    /// Captures scaled differently from the window, e.g. on HiDPI monitors, are scaled too.
+    #[cfg(feature = "image")]
+    pub fn apply_mask(&self, image: &mut RgbaImage) {
+        let (image_width, image_height) = image.dimensions();
+        let scale_x = image_width as f64 / self.width.max(1) as f64;
+        let scale_y = image_height as f64 / self.height.max(1) as f64;
+
+        let regions: Vec<Region> = self
+            .bounding
+            .iter()
+            .map(|region| {
+                let x = (region.x as f64 * scale_x).floor() as u32;
+                let y = (region.y as f64 * scale_y).floor() as u32;
+                let right = ((region.x + region.width) as f64 * scale_x).ceil() as u32;
+                let bottom = ((region.y + region.height) as f64 * scale_y).ceil() as u32;
+                Region::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+            })
+            .collect();
+
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if !regions.iter().any(|region| region.contains(x, y)) {
+                pixel.0[3] = 0;
+            }
+        }
+    }
+}
+
+/// A window, cheap to clone. On X11 every window enumerated together shares one connection,
+/// so capturing it again and again does not connect to the X server each time.
 #[derive(Debug, Clone)]
 pub struct Window {
     pub(crate) impl_window: ImplWindow,
@@ -15,6 +249,11 @@ impl Window {
 
 impl Window {
     /// List all windows, sorted by z coordinate.
+    ///
+    /// On Wayland with the `foreign-toplevel` feature, native Wayland windows are listed after
+    /// the XWayland ones. The compositor does not expose their position, size or pid, so they
+    /// are reported as zero sized windows at the origin of their monitor. They can be captured
+    /// with the `ext-image-copy-capture` feature when the compositor supports it.
     pub fn all() -> XCapResult<Vec<Window>> {
         let windows = ImplWindow::all()?
             .iter()
@@ -23,6 +262,122 @@ impl Window {
 
         Ok(windows)
     }
+
+    /// Like [`Window::all`], but also returns the windows that could not be inspected, e.g.
+    /// because they were destroyed while being listed, instead of only logging them. Fails
+    /// only when windows can not be listed at all.
+    pub fn all_with_errors() -> XCapResult<Enumeration<Window>> {
+        Ok(ImplWindow::all_with_errors()?.map(Window::new))
+    }
+
+    /// The windows containing the virtual screen point, topmost first. Minimized windows and
+    /// windows not on screen are skipped.
+    pub fn all_at_point(x: i32, y: i32) -> XCapResult<Vec<Window>> {
+        let mut windows: Vec<Window> = Window::all()?
+            .into_iter()
+            .filter(|window| {
+                window.is_visible()
+                    && Rect::new(window.x(), window.y(), window.width(), window.height())
+                        .contains(x, y)
+            })
+            .collect();
+        windows.sort_by_key(|window| -window.z());
+
+        Ok(windows)
+    }
+
+    /// The topmost window under the virtual screen point, e.g. for click to select a window.
+    pub fn from_point(x: i32, y: i32) -> XCapResult<Window> {
+        Window::all_at_point(x, y)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| XCapError::new(format!("No window at ({}, {})", x, y)))
+    }
+
+    /// List the windows of the process `pid`, sorted by z coordinate.
+    pub fn by_pid(pid: u32) -> XCapResult<Vec<Window>> {
+        let windows = Window::all()?
+            .into_iter()
+            .filter(|window| window.pid() == pid)
+            .collect();
+
+        Ok(windows)
+    }
+
+    /// Wait until the process `pid` shows a window and return the topmost one, errors when
+    /// `timeout` elapses first. Useful right after launching an application.
+    pub fn wait_for_window(pid: u32, timeout: Duration) -> XCapResult<Window> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(window) = Window::by_pid(pid)?.into_iter().next() {
+                return Ok(window);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(XCapError::new(format!(
+                    "Wait for window of process {} timed out",
+                    pid
+                )));
+            }
+            thread::sleep(remaining.min(Duration::from_millis(100)));
+        }
+    }
+
+    /// The window matching `fingerprint` best, preferring the topmost one on ties. Errors
+    /// when no window of the app is open.
+    pub fn from_fingerprint(fingerprint: &WindowFingerprint) -> XCapResult<Window> {
+        let mut best: Option<(u32, Window)> = None;
+        for window in Window::all()? {
+            let score = fingerprint.score(&window.fingerprint());
+            if score > best.as_ref().map_or(0, |(best_score, _)| *best_score) {
+                best = Some((score, window));
+            }
+        }
+
+        best.map(|(_, window)| window)
+            .ok_or_else(|| XCapError::new(format!("No window of {} found", fingerprint.app)))
+    }
+
+    /// The windows owned by this window, directly or through other owned windows, sorted by z
+    /// coordinate. Together with the window itself they form one logical unit, such as an
+    /// editor and its open dialogs.
+    pub fn owned_windows(&self) -> XCapResult<Vec<Window>> {
+        let windows = Window::all()?;
+
+        let mut owners = vec![self.id()];
+        let mut owned_windows = Vec::new();
+        // 对话框也可能拥有对话框，逐层展开
+        while let Some(owner) = owners.pop() {
+            for window in &windows {
+                if window.owner_id() == Some(owner)
+                    && !owned_windows.iter().any(|w: &Window| w.id() == window.id())
+                {
+                    owners.push(window.id());
+                    owned_windows.push(window.clone());
+                }
+            }
+        }
+        owned_windows.sort_by_key(|window| -window.z());
+
+        Ok(owned_windows)
+    }
+}
+
+/// The native handle of the window, for passing it to crates that take a raw window handle.
+///
+/// This is an X11 window id on Linux, an `HWND` on Windows and the content `NSView` on macOS.
+/// Native Wayland windows have no handle, and on macOS only the windows of the current process
+/// can be converted, from the main thread. The handle is not owned: it is only valid while the
+/// window exists.
+#[cfg(feature = "raw-window-handle")]
+impl TryFrom<&Window> for raw_window_handle::RawWindowHandle {
+    type Error = XCapError;
+
+    fn try_from(window: &Window) -> XCapResult<Self> {
+        window.impl_window.raw_window_handle()
+    }
 }
 
 impl Window {
@@ -38,10 +393,77 @@ impl Window {
     pub fn title(&self) -> &str {
         &self.impl_window.title
     }
+    /// The raw bytes of the window title, for titles that are not valid text and were decoded
+    /// lossily by [`Window::title`]. The raw `WM_NAME` on X11, UTF-16LE on Windows, and UTF-8
+    /// elsewhere.
+    pub fn title_bytes(&self) -> &[u8] {
+        &self.impl_window.title_bytes
+    }
     /// The window process id
     pub fn pid(&self) -> u32 {
         self.impl_window.pid
     }
+    /// The path of the executable of the window process.
+    pub fn exe_path(&self) -> XCapResult<PathBuf> {
+        self.impl_window.exe_path()
+    }
+    /// The command line of the window process, the first argument is usually the executable.
+    pub fn cmdline(&self) -> XCapResult<Vec<String>> {
+        self.impl_window.cmdline()
+    }
+    /// What identifies the window after its application restarts, see
+    /// [`Window::from_fingerprint`].
+    pub fn fingerprint(&self) -> WindowFingerprint {
+        let app = match self.exe_path() {
+            Ok(exe_path) => exe_path.to_string_lossy().into_owned(),
+            Err(_) => self.app_name().to_string(),
+        };
+
+        WindowFingerprint {
+            app,
+            title_pattern: title_pattern(self.title()),
+            kind: self.kind(),
+            x: self.x(),
+            y: self.y(),
+            width: self.width(),
+            height: self.height(),
+        }
+    }
+
+    /// The architecture and confinement of the window process, e.g. to decide how to attach
+    /// a debugger or inject input.
+    pub fn process_info(&self) -> XCapResult<ProcessInfo> {
+        self.impl_window.process_info()
+    }
+    /// The window role, screenshot pickers usually only show [`WindowKind::Normal`] and
+    /// [`WindowKind::Dialog`] windows.
+    pub fn kind(&self) -> WindowKind {
+        self.impl_window.kind
+    }
+    /// Whether the taskbar hides the window: `_NET_WM_STATE_SKIP_TASKBAR` on X11, tool and
+    /// owned windows without `WS_EX_APPWINDOW` on Windows, and windows outside the normal
+    /// level or of applications without a Dock icon on macOS.
+    pub fn skip_taskbar(&self) -> bool {
+        self.impl_window.skip_taskbar
+    }
+    /// Whether pagers and window switchers hide the window: `_NET_WM_STATE_SKIP_PAGER` on
+    /// X11, the same rule as [`Window::skip_taskbar`] for Alt-Tab on Windows, and windows
+    /// outside the normal level, which Mission Control skips, on macOS.
+    pub fn skip_pager(&self) -> bool {
+        self.impl_window.skip_pager
+    }
+    /// Whether the window is actually on screen: mapped, not minimized, on the current
+    /// desktop and not fully transparent. Unlike [`Window::is_minimized`] this is also false
+    /// for windows on other virtual desktops, which capture as empty images.
+    pub fn is_visible(&self) -> bool {
+        self.impl_window.is_visible
+    }
+    /// The id of the window owning this one, from `WM_TRANSIENT_FOR` on X11 and the owner
+    /// window on Windows, usually the main window of a dialog. Always `None` on macOS and for
+    /// native Wayland windows.
+    pub fn owner_id(&self) -> Option<u32> {
+        self.impl_window.owner_id
+    }
     /// The window current monitor
     pub fn current_monitor(&self) -> Monitor {
         Monitor::new(self.impl_window.current_monitor.to_owned())
@@ -74,14 +496,265 @@ impl Window {
     pub fn is_maximized(&self) -> bool {
         self.impl_window.is_maximized
     }
+    /// The window is full screen, e.g. a game or a video player.
+    pub fn is_fullscreen(&self) -> bool {
+        self.impl_window.is_fullscreen
+    }
+    /// When the window last had the focus and for how long in total, tracked while a
+    /// [`crate::Watcher`] runs. `None` if no watcher saw the window focused.
+    pub fn focus_stats(&self) -> Option<FocusStats> {
+        focus_stats(self.id())
+    }
     /// The window is focused.
     pub fn is_focused(&self) -> bool {
         self.impl_window.is_focused
     }
+    /// The window is an X11 client running under XWayland, always `false` outside Linux.
+    ///
+    /// On a Wayland session only XWayland windows are listed by [`Window::all`], and
+    /// [`Window::capture_image`] reads them through the X server. Native Wayland toplevels
+    /// are not visible to X11, capture them through the ScreenCast portal instead.
+    pub fn is_xwayland(&self) -> bool {
+        self.impl_window.is_xwayland
+    }
+    /// The visible and clickable parts of the window, from the SHAPE extension on X11 and the
+    /// window region and `WS_EX_TRANSPARENT` on Windows. macOS does not expose window shapes,
+    /// the whole window is reported, its rounded corners are transparent in captures anyway.
+    pub fn shape(&self) -> XCapResult<WindowShape> {
+        self.impl_window.shape()
+    }
 }
 
 impl Window {
+    /// Whether the window still exists. Captures of a destroyed window fail with
+    /// [`XCapError::SourceGone`].
+    pub fn is_valid(&self) -> bool {
+        self.impl_window.is_valid()
+    }
+
+    /// Backend errors are opaque, check whether they were caused by a destroyed window.
+    fn check_gone(&self, err: XCapError) -> XCapError {
+        match self.is_valid() {
+            true => err,
+            false => XCapError::SourceGone,
+        }
+    }
+
+    #[cfg(feature = "image")]
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
-        self.impl_window.capture_image()
+        self.capture_rgba()
+    }
+
+    pub(crate) fn capture_rgba(&self) -> XCapResult<RgbaImage> {
+        self.impl_window
+            .capture_image()
+            .map_err(|err| self.check_gone(err))
+    }
+
+    /// Capture a downscaled preview of the window that fits into `max_width` x `max_height`,
+    /// keeping the aspect ratio. The backend scales natively where it can, which is much
+    /// cheaper than capturing the full image and resizing it.
+    #[cfg(feature = "image")]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        self.impl_window
+            .capture_thumbnail(max_width, max_height)
+            .map_err(|err| self.check_gone(err))
+    }
+
+    /// Capture image of the window as linear light `f32` values.
+    #[cfg(feature = "image")]
+    pub fn capture_image_linear(&self) -> XCapResult<Rgba32FImage> {
+        Ok(to_linear_image(
+            &self.capture_rgba()?,
+            self.current_monitor().transfer_function(),
+        ))
+    }
+
+    /// Capture image of the window and save it, the format is inferred from the file extension.
+    pub fn capture_to_file<P: AsRef<Path>>(&self, path: P) -> XCapResult<()> {
+        self.capture_to_file_with(path, &EncodeOptions::default())
+    }
+
+    pub fn capture_to_file_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &EncodeOptions,
+    ) -> XCapResult<()> {
+        save_image(&self.capture_rgba()?, path.as_ref(), options)
+    }
+
+    /// Capture image of the window and encode it as PNG.
+    pub fn capture_png(&self) -> XCapResult<Vec<u8>> {
+        encode_image(
+            &self.capture_rgba()?,
+            ImageFormat::Png,
+            &EncodeOptions::default(),
+        )
+    }
+
+    /// Capture image of the window and encode it as JPEG with `quality` between 1 and 100.
+    #[cfg(feature = "jpeg")]
+    pub fn capture_jpeg(&self, quality: u8) -> XCapResult<Vec<u8>> {
+        encode_image(
+            &self.capture_rgba()?,
+            ImageFormat::Jpeg,
+            &EncodeOptions { quality },
+        )
+    }
+
+    /// Capture image of the window and encode it as lossless WebP, the bundled encoder has no
+    /// lossy mode.
+    #[cfg(feature = "webp")]
+    pub fn capture_webp_lossless(&self) -> XCapResult<Vec<u8>> {
+        encode_image(
+            &self.capture_rgba()?,
+            ImageFormat::WebP,
+            &EncodeOptions::default(),
+        )
+    }
+
+    /// Whether the window is currently on screen, from a fresh list of windows and monitors.
+    pub fn visibility(&self) -> XCapResult<Visibility> {
+        let windows = Window::all()?;
+        let window = windows
+            .iter()
+            .find(|window| window.id() == self.id())
+            .ok_or(XCapError::SourceGone)?;
+
+        if window.is_minimized() {
+            return Ok(Visibility::Minimized);
+        }
+        if !window.is_visible() {
+            return Ok(Visibility::OffScreen);
+        }
+
+        let rect = |x, y, width, height| Rect::new(x, y, width, height);
+        let screens: Vec<Rect> = Monitor::all()?
+            .iter()
+            .map(|monitor| rect(monitor.x(), monitor.y(), monitor.width(), monitor.height()))
+            .collect();
+        let above: Vec<Rect> = windows
+            .iter()
+            .filter(|other| other.z() > window.z() && other.is_visible() && !other.is_minimized())
+            .map(|other| rect(other.x(), other.y(), other.width(), other.height()))
+            .collect();
+
+        Ok(visibility_of(
+            rect(window.x(), window.y(), window.width(), window.height()),
+            &screens,
+            &above,
+        ))
+    }
+
+    /// Capture the window as a raw RGBA [`Frame`], without exposing `image` types. The frame
+    /// is tagged with the [`Visibility`] of the window, which lists all windows once more.
+    pub fn capture_frame(&self) -> XCapResult<Frame> {
+        let image = self.capture_rgba()?;
+        let (width, height) = image.dimensions();
+        let visibility = self.visibility().unwrap_or_else(|err| {
+            log::debug!("Get window visibility failed: {}", err);
+            Visibility::Visible
+        });
+
+        Ok(Frame::new(width, height, image.into_raw())
+            .with_color_space(self.current_monitor().color_space())
+            .with_visibility(visibility))
+    }
+
+    /// Like [`Window::capture_frame`], converted as `options` say.
+    pub fn capture_frame_with(&self, options: &CaptureOptions) -> XCapResult<Frame> {
+        options.apply(self.capture_frame()?)
+    }
+
+    /// Capture `count` frames of the window, `interval` apart, one [`Window::capture_frame`]
+    /// each, stamped with the time the capture started. Windows have no [`crate::VideoRecorder`]
+    /// stream to sample, unlike [`Monitor::capture_burst`] on Windows.
+    pub fn capture_burst(&self, count: usize, interval: Duration) -> XCapResult<Vec<Frame>> {
+        capture_burst(count, interval, || self.capture_frame())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn occluded_by_windows_above() {
+        let screens = [Rect::new(0, 0, 100, 100), Rect::new(100, 0, 100, 100)];
+        let target = Rect::new(80, 10, 40, 40);
+        let left = Rect::new(0, 0, 100, 50);
+        let right = Rect::new(100, 0, 50, 50);
+
+        assert_eq!(
+            visibility_of(target, &screens, &[left]),
+            Visibility::Visible
+        );
+        assert_eq!(
+            visibility_of(target, &screens, &[left, right]),
+            Visibility::Occluded
+        );
+        // 屏幕外的部分不算露出来
+        assert_eq!(
+            visibility_of(
+                Rect::new(180, 10, 40, 40),
+                &screens,
+                &[Rect::new(150, 0, 50, 50)]
+            ),
+            Visibility::Occluded
+        );
+        assert_eq!(
+            visibility_of(Rect::new(300, 0, 10, 10), &screens, &[]),
+            Visibility::OffScreen
+        );
+    }
+
+    #[test]
+    fn match_fingerprint_after_restart() {
+        let fingerprint = WindowFingerprint {
+            app: "/usr/bin/editor".to_string(),
+            title_pattern: title_pattern("notes.txt (3 unread) - Editor 2"),
+            kind: WindowKind::Normal,
+            x: 0,
+            y: 0,
+            width: 800,
+            height: 600,
+        };
+        assert_eq!(fingerprint.title_pattern, "notes.txt (# unread) - Editor #");
+
+        let moved = WindowFingerprint {
+            title_pattern: title_pattern("notes.txt (12 unread) - Editor 2"),
+            x: 40,
+            ..fingerprint.clone()
+        };
+        let other_document = WindowFingerprint {
+            title_pattern: title_pattern("todo.txt - Editor 2"),
+            ..fingerprint.clone()
+        };
+        assert!(fingerprint.score(&moved) > fingerprint.score(&other_document));
+
+        let other_app = WindowFingerprint {
+            app: "/usr/bin/browser".to_string(),
+            ..fingerprint.clone()
+        };
+        assert_eq!(fingerprint.score(&other_app), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn mask_scaled_capture() {
+        // 窗口左上角被裁掉，截图是窗口的两倍大
+        let shape = WindowShape {
+            width: 4,
+            height: 4,
+            bounding: vec![Region::new(2, 0, 2, 2), Region::new(0, 2, 4, 2)],
+            input: Vec::new(),
+        };
+        let mut image = RgbaImage::from_pixel(8, 8, image::Rgba([255, 255, 255, 255]));
+        shape.apply_mask(&mut image);
+
+        assert_eq!(image.get_pixel(3, 3).0[3], 0);
+        assert_eq!(image.get_pixel(4, 3).0[3], 255);
+        assert_eq!(image.get_pixel(0, 4).0[3], 255);
+        assert!(shape.is_click_through());
     }
 }
@@ -2,21 +2,28 @@ use std::{ffi::c_void, mem};
 
 use image::{DynamicImage, RgbaImage};
 use scopeguard::guard;
-use windows::Win32::{
-    Foundation::HWND,
-    Graphics::{
-        Dwm::DwmIsCompositionEnabled,
-        Gdi::{
-            BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject,
-            GetCurrentObject, GetDIBits, GetObjectW, GetWindowDC, ReleaseDC, SelectObject, BITMAP,
-            BITMAPINFO, BITMAPINFOHEADER, DIB_RGB_COLORS, HBITMAP, HDC, OBJ_BITMAP, SRCCOPY,
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::HWND,
+        Graphics::{
+            Dwm::DwmIsCompositionEnabled,
+            Gdi::{
+                BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject,
+                GetCurrentObject, GetDIBits, GetObjectW, GetWindowDC, ReleaseDC, SelectObject,
+                SetBrushOrgEx, SetStretchBltMode, StretchBlt, BITMAP, BITMAPINFO, BITMAPINFOHEADER,
+                DIB_RGB_COLORS, HALFTONE, HBITMAP, HDC, OBJ_BITMAP, SRCCOPY,
+            },
         },
+        Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
+        UI::WindowsAndMessaging::{FindWindowW, GetDesktopWindow, GetWindowInfo, WINDOWINFO},
     },
-    Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
-    UI::WindowsAndMessaging::{GetDesktopWindow, WINDOWINFO},
 };
 
-use crate::error::{XCapError, XCapResult};
+use crate::{
+    error::{XCapError, XCapResult},
+    report::capture_path,
+};
 
 use super::utils::{bgra_to_rgba_image, get_os_major_version};
 
@@ -65,6 +72,22 @@ fn to_rgba_image(
 
 #[allow(unused)]
 pub fn capture_monitor(x: i32, y: i32, width: i32, height: i32) -> XCapResult<RgbaImage> {
+    capture_path(
+        "gdi-bitblt",
+        capture_monitor_scaled(x, y, width, height, width, height),
+    )
+}
+
+/// Capture the area of the desktop and let GDI scale it down to `dst_width` x `dst_height`.
+#[allow(unused)]
+pub fn capture_monitor_scaled(
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    dst_width: i32,
+    dst_height: i32,
+) -> XCapResult<RgbaImage> {
     unsafe {
         let hwnd = GetDesktopWindow();
         let scope_guard_hdc_desktop_window = guard(GetWindowDC(Some(hwnd)), |val| {
@@ -85,7 +108,7 @@ pub fn capture_monitor(x: i32, y: i32, width: i32, height: i32) -> XCapResult<Rg
         );
 
         let scope_guard_h_bitmap = guard(
-            CreateCompatibleBitmap(*scope_guard_hdc_desktop_window, width, height),
+            CreateCompatibleBitmap(*scope_guard_hdc_desktop_window, dst_width, dst_height),
             |val| {
                 if DeleteObject(val.into()).as_bool() {
                     log::error!("DeleteObject {:?} failed", val);
@@ -97,21 +120,45 @@ pub fn capture_monitor(x: i32, y: i32, width: i32, height: i32) -> XCapResult<Rg
         SelectObject(*scope_guard_mem, (*scope_guard_h_bitmap).into());
 
         // 拷贝原始图像到内存
-        // 这里不需要缩放图片，所以直接使用BitBlt
-        // 如需要缩放，则使用 StretchBlt
-        BitBlt(
-            *scope_guard_mem,
-            0,
-            0,
-            width,
-            height,
-            Some(*scope_guard_hdc_desktop_window),
-            x,
-            y,
-            SRCCOPY,
-        )?;
+        // 不需要缩放图片时直接使用 BitBlt，否则使用 StretchBlt
+        if dst_width == width && dst_height == height {
+            BitBlt(
+                *scope_guard_mem,
+                0,
+                0,
+                width,
+                height,
+                Some(*scope_guard_hdc_desktop_window),
+                x,
+                y,
+                SRCCOPY,
+            )?;
+        } else {
+            // HALFTONE 模式缩放质量更好，设置后需要重置画刷原点
+            SetStretchBltMode(*scope_guard_mem, HALFTONE);
+            SetBrushOrgEx(*scope_guard_mem, 0, 0, None).ok()?;
+            StretchBlt(
+                *scope_guard_mem,
+                0,
+                0,
+                dst_width,
+                dst_height,
+                Some(*scope_guard_hdc_desktop_window),
+                x,
+                y,
+                width,
+                height,
+                SRCCOPY,
+            )
+            .ok()?;
+        }
 
-        to_rgba_image(*scope_guard_mem, *scope_guard_h_bitmap, width, height)
+        to_rgba_image(
+            *scope_guard_mem,
+            *scope_guard_h_bitmap,
+            dst_width,
+            dst_height,
+        )
     }
 }
 
@@ -171,6 +218,7 @@ pub fn capture_window(
         let previous_object = SelectObject(*scope_guard_hdc_mem, (*scope_guard_h_bitmap).into());
 
         let mut is_success = false;
+        let mut path = "gdi-print-window-full-content";
 
         // https://webrtc.googlesource.com/src.git/+/refs/heads/main/modules/desktop_capture/win/window_capturer_win_gdi.cc#301
         if get_os_major_version() >= 8 {
@@ -178,14 +226,17 @@ pub fn capture_window(
         }
 
         if !is_success && DwmIsCompositionEnabled()?.as_bool() {
+            path = "gdi-print-window";
             is_success = PrintWindow(hwnd, *scope_guard_hdc_mem, PRINT_WINDOW_FLAGS(0)).as_bool();
         }
 
         if !is_success {
+            path = "gdi-print-window-4";
             is_success = PrintWindow(hwnd, *scope_guard_hdc_mem, PRINT_WINDOW_FLAGS(4)).as_bool();
         }
 
         if !is_success {
+            path = "gdi-bitblt-window";
             is_success = BitBlt(
                 *scope_guard_hdc_mem,
                 0,
@@ -202,7 +253,10 @@ pub fn capture_window(
 
         SelectObject(*scope_guard_hdc_mem, previous_object);
 
-        let image = to_rgba_image(*scope_guard_hdc_mem, *scope_guard_h_bitmap, width, height)?;
+        let image = capture_path(
+            path,
+            to_rgba_image(*scope_guard_hdc_mem, *scope_guard_h_bitmap, width, height),
+        )?;
 
         let mut rc_client = window_info.rcClient;
 
@@ -216,3 +270,30 @@ pub fn capture_window(
             .to_rgba8())
     }
 }
+
+/// Capture the area of the Explorer desktop window, which draws the wallpaper and the desktop
+/// icons below every other window.
+#[cfg_attr(not(feature = "image"), allow(dead_code))]
+pub fn capture_desktop(x: i32, y: i32, width: u32, height: u32) -> XCapResult<RgbaImage> {
+    unsafe {
+        let hwnd = FindWindowW(w!("Progman"), PCWSTR::null())?;
+        let mut window_info = WINDOWINFO {
+            cbSize: mem::size_of::<WINDOWINFO>() as u32,
+            ..WINDOWINFO::default()
+        };
+        GetWindowInfo(hwnd, &mut window_info)?;
+
+        // Progman 覆盖整个虚拟屏幕，裁剪出显示器的区域
+        let image = capture_window(hwnd, 1.0, &window_info)?;
+        let rc_client = window_info.rcClient;
+
+        Ok(DynamicImage::ImageRgba8(image)
+            .crop(
+                (x - rc_client.left).max(0) as u32,
+                (y - rc_client.top).max(0) as u32,
+                width,
+                height,
+            )
+            .to_rgba8())
+    }
+}
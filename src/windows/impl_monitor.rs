@@ -5,23 +5,48 @@ use scopeguard::guard;
 use windows::{
     core::{s, w, HRESULT, PCWSTR},
     Win32::{
-        Foundation::{BOOL, LPARAM, POINT, RECT, TRUE},
+        Devices::Display::{
+            DestroyPhysicalMonitors, GetMonitorBrightness, GetNumberOfPhysicalMonitorsFromHMONITOR,
+            GetPhysicalMonitorsFromHMONITOR, PHYSICAL_MONITOR,
+        },
+        Foundation::{BOOL, HANDLE, LPARAM, POINT, RECT, TRUE},
         Graphics::Gdi::{
-            CreateDCW, DeleteDC, EnumDisplayMonitors, EnumDisplaySettingsW, GetDeviceCaps,
-            GetMonitorInfoW, MonitorFromPoint, DESKTOPHORZRES, DEVMODEW, DMDO_180, DMDO_270,
-            DMDO_90, DMDO_DEFAULT, ENUM_CURRENT_SETTINGS, HDC, HMONITOR, HORZRES, MONITORINFO,
-            MONITORINFOEXW, MONITOR_DEFAULTTONULL,
+            CreateDCW, DeleteDC, EnumDisplayDevicesW, EnumDisplayMonitors, EnumDisplaySettingsW,
+            GetDeviceCaps, GetMonitorInfoW, MonitorFromPoint, DESKTOPHORZRES, DEVMODEW,
+            DISPLAY_DEVICEW, DMDO_180, DMDO_270, DMDO_90, DMDO_DEFAULT, ENUM_CURRENT_SETTINGS,
+            ENUM_DISPLAY_SETTINGS_MODE, HDC, HMONITOR, HORZRES, MONITORINFO, MONITORINFOEXW,
+            MONITOR_DEFAULTTONULL,
+        },
+        System::{
+            LibraryLoader::GetProcAddress,
+            StationsAndDesktops::{
+                CloseDesktop, GetUserObjectInformationW, OpenInputDesktop, DESKTOP_CONTROL_FLAGS,
+                DESKTOP_READOBJECTS, UOI_NAME,
+            },
+            Threading::GetCurrentProcess,
+        },
+        UI::{
+            ColorSystem::GetDeviceGammaRamp,
+            WindowsAndMessaging::{EDD_GET_DEVICE_INTERFACE_NAME, MONITORINFOF_PRIMARY},
         },
-        System::{LibraryLoader::GetProcAddress, Threading::GetCurrentProcess},
-        UI::WindowsAndMessaging::MONITORINFOF_PRIMARY,
     },
 };
 
-use crate::error::{XCapError, XCapResult};
+use crate::{
+    backend::Backend,
+    capabilities::Inhibition,
+    color::{ColorSpace, GammaRamp},
+    custom::CustomMonitor,
+    error::{XCapError, XCapResult},
+    gpu::GpuDevice,
+    layout::Rect,
+    utils::thumbnail_size,
+    VideoMode,
+};
 
 use super::{
-    capture::capture_monitor,
-    impl_video_recorder::ImplVideoRecorder,
+    capture::{capture_desktop, capture_monitor, capture_monitor_scaled},
+    impl_video_recorder::{protected_content_masked, ImplVideoRecorder},
     utils::{get_monitor_name, get_process_is_dpi_awareness, load_library},
 };
 
@@ -44,8 +69,14 @@ pub(crate) struct ImplMonitor {
     pub scale_factor: f32,
     pub frequency: f32,
     pub is_primary: bool,
+    pub color_space: ColorSpace,
 }
 
+// SAFETY: HMONITOR is an opaque id of the display valid in the whole process, not a pointer
+// xcap dereferences, and the monitor APIs called with it have no thread affinity. The other
+// fields are plain data copied out of MONITORINFOEXW.
+unsafe impl Send for ImplMonitor {}
+
 extern "system" fn monitor_enum_proc(
     h_monitor: HMONITOR,
     _: HDC,
@@ -140,6 +171,34 @@ fn get_scale_factor(h_monitor: HMONITOR, monitor_info_ex_w: MONITORINFOEXW) -> X
     Ok(scale_factor)
 }
 
+/// Whether the input desktop is not the default one, e.g. the secure desktop of a UAC prompt or
+/// the lock screen, which a normal process can not open at all.
+fn is_secure_desktop() -> bool {
+    unsafe {
+        let Ok(h_desktop) = OpenInputDesktop(DESKTOP_CONTROL_FLAGS(0), false, DESKTOP_READOBJECTS)
+        else {
+            return true;
+        };
+        let h_desktop = guard(h_desktop, |val| {
+            if let Err(err) = CloseDesktop(val) {
+                log::error!("CloseDesktop failed: {}", err);
+            }
+        });
+
+        let mut name = [0u16; 256];
+        let result = GetUserObjectInformationW(
+            HANDLE(h_desktop.0),
+            UOI_NAME,
+            Some(name.as_mut_ptr().cast()),
+            mem::size_of_val(&name) as u32,
+            None,
+        );
+        let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+
+        result.is_ok() && !String::from_utf16_lossy(&name[..len]).eq_ignore_ascii_case("Default")
+    }
+}
+
 impl ImplMonitor {
     pub fn new(h_monitor: HMONITOR) -> XCapResult<ImplMonitor> {
         let mut monitor_info_ex_w = MONITORINFOEXW::default();
@@ -184,9 +243,69 @@ impl ImplMonitor {
             scale_factor,
             frequency: dev_mode_w.dmDisplayFrequency as f32,
             is_primary: monitor_info_ex_w.monitorInfo.dwFlags == MONITORINFOF_PRIMARY,
+            // GDI 与 DXGI 的 BGRA8 输出都是 sRGB 编码
+            color_space: ColorSpace::Srgb,
         })
     }
 
+    pub fn backend() -> Backend {
+        Backend::Gdi
+    }
+
+    pub fn inhibitions() -> Vec<Inhibition> {
+        let mut inhibitions = Vec::new();
+        if is_secure_desktop() {
+            // 安全桌面上也无法复制输出，不用再检查受保护的内容
+            inhibitions.push(Inhibition::SecureDesktop);
+            return inhibitions;
+        }
+
+        let protected = ImplMonitor::all()
+            .unwrap_or_default()
+            .iter()
+            .any(|impl_monitor| {
+                protected_content_masked(impl_monitor.h_monitor).unwrap_or_else(|err| {
+                    log::debug!("Check protected content failed: {}", err);
+                    false
+                })
+            });
+        if protected {
+            inhibitions.push(Inhibition::ProtectedContent);
+        }
+
+        inhibitions
+    }
+
+    /// A monitor of a custom backend, without a `HMONITOR`.
+    pub fn from_custom(custom_monitor: &CustomMonitor) -> ImplMonitor {
+        ImplMonitor {
+            h_monitor: HMONITOR::default(),
+            monitor_info_ex_w: MONITORINFOEXW::default(),
+            id: custom_monitor.id,
+            name: custom_monitor.name.clone(),
+            x: custom_monitor.x,
+            y: custom_monitor.y,
+            width: custom_monitor.width,
+            height: custom_monitor.height,
+            rotation: 0.0,
+            scale_factor: custom_monitor.scale_factor,
+            frequency: custom_monitor.frequency,
+            is_primary: custom_monitor.is_primary,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn all_with_backend(backend: Backend) -> XCapResult<Vec<ImplMonitor>> {
+        if backend != Backend::Gdi {
+            return Err(XCapError::new(format!(
+                "{:?} backend is not available",
+                backend
+            )));
+        }
+
+        ImplMonitor::all()
+    }
+
     pub fn all() -> XCapResult<Vec<ImplMonitor>> {
         let hmonitors_mut_ptr: *mut Vec<HMONITOR> = Box::into_raw(Box::default());
 
@@ -227,11 +346,231 @@ impl ImplMonitor {
 }
 
 impl ImplMonitor {
+    pub fn is_valid(&self) -> bool {
+        let mut monitor_info = MONITORINFO {
+            cbSize: mem::size_of::<MONITORINFO>() as u32,
+            ..MONITORINFO::default()
+        };
+
+        unsafe { GetMonitorInfoW(self.h_monitor, &mut monitor_info).as_bool() }
+    }
+
     pub fn capture_image(&self) -> XCapResult<RgbaImage> {
         capture_monitor(self.x, self.y, self.width as i32, self.height as i32)
     }
 
+    /// No native exclusion, [`Monitor`](crate::Monitor) composites the window captures instead.
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_excluding(&self, _window_ids: &[u32]) -> XCapResult<Option<RgbaImage>> {
+        Ok(None)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_desktop_only(&self) -> XCapResult<RgbaImage> {
+        capture_desktop(self.x, self.y, self.width, self.height)
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_root_background(&self, _rect: Rect) -> XCapResult<Option<RgbaImage>> {
+        Ok(None)
+    }
+
+    pub fn workarea(&self) -> XCapResult<Rect> {
+        let mut monitor_info = MONITORINFO {
+            cbSize: mem::size_of::<MONITORINFO>() as u32,
+            ..MONITORINFO::default()
+        };
+        unsafe { GetMonitorInfoW(self.h_monitor, &mut monitor_info).ok()? };
+
+        // rcWork 不包含任务栏和停靠的应用栏
+        let RECT {
+            left,
+            top,
+            right,
+            bottom,
+        } = monitor_info.rcWork;
+
+        Ok(Rect::new(
+            left,
+            top,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        ))
+    }
+
+    pub fn stable_id(&self) -> String {
+        let mut display_device = DISPLAY_DEVICEW {
+            cb: mem::size_of::<DISPLAY_DEVICEW>() as u32,
+            ..DISPLAY_DEVICEW::default()
+        };
+
+        // 以适配器名查询时，第 0 个设备就是接在它上面的显示器，DeviceID 为其设备接口路径
+        let is_success = unsafe {
+            EnumDisplayDevicesW(
+                PCWSTR(self.monitor_info_ex_w.szDevice.as_ptr()),
+                0,
+                &mut display_device,
+                EDD_GET_DEVICE_INTERFACE_NAME,
+            )
+            .as_bool()
+        };
+        let device_path = String::from_utf16_lossy(&display_device.DeviceID)
+            .trim_end_matches('\0')
+            .to_string();
+
+        match is_success && !device_path.is_empty() {
+            true => device_path,
+            false => String::from_utf16_lossy(&self.monitor_info_ex_w.szDevice)
+                .trim_end_matches('\0')
+                .to_string(),
+        }
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        let (width, height) = thumbnail_size(self.width, self.height, max_width, max_height);
+
+        capture_monitor_scaled(
+            self.x,
+            self.y,
+            self.width as i32,
+            self.height as i32,
+            width as i32,
+            height as i32,
+        )
+    }
+
+    pub fn is_mirror_of(&self, other: &ImplMonitor) -> bool {
+        // 复制模式下同一显卡的显示器合并为一个 HMONITOR，跨显卡复制时各自的桌面区域完全相同
+        self.id != other.id
+            && (self.x, self.y, self.width, self.height)
+                == (other.x, other.y, other.width, other.height)
+    }
+
+    pub fn supported_modes(&self) -> XCapResult<Vec<VideoMode>> {
+        let current = get_dev_mode_w(&self.monitor_info_ex_w)?;
+        let sz_device = self.monitor_info_ex_w.szDevice.as_ptr();
+
+        let mut video_modes: Vec<VideoMode> = Vec::new();
+        for index in 0.. {
+            let mut dev_mode_w = DEVMODEW {
+                dmSize: mem::size_of::<DEVMODEW>() as u16,
+                ..DEVMODEW::default()
+            };
+            let is_success = unsafe {
+                EnumDisplaySettingsW(
+                    PCWSTR(sz_device),
+                    ENUM_DISPLAY_SETTINGS_MODE(index),
+                    &mut dev_mode_w,
+                )
+                .as_bool()
+            };
+            if !is_success {
+                break;
+            }
+
+            let video_mode = VideoMode {
+                width: dev_mode_w.dmPelsWidth,
+                height: dev_mode_w.dmPelsHeight,
+                refresh_rate: dev_mode_w.dmDisplayFrequency as f32,
+                is_current: dev_mode_w.dmPelsWidth == current.dmPelsWidth
+                    && dev_mode_w.dmPelsHeight == current.dmPelsHeight
+                    && dev_mode_w.dmDisplayFrequency == current.dmDisplayFrequency,
+                is_native: false,
+            };
+
+            // 同一分辨率会按色深和缩放方式重复出现
+            let is_duplicate = video_modes.iter().any(|m| {
+                (m.width, m.height, m.refresh_rate)
+                    == (video_mode.width, video_mode.height, video_mode.refresh_rate)
+            });
+            if !is_duplicate {
+                video_modes.push(video_mode);
+            }
+        }
+
+        // GDI 不提供面板的原生分辨率，最大的模式通常就是原生模式
+        let native = video_modes
+            .iter()
+            .map(|m| (m.width as u64 * m.height as u64, m.refresh_rate as u32))
+            .max();
+        for video_mode in &mut video_modes {
+            let key = (
+                video_mode.width as u64 * video_mode.height as u64,
+                video_mode.refresh_rate as u32,
+            );
+            video_mode.is_native = Some(key) == native;
+        }
+
+        Ok(video_modes)
+    }
+
+    pub fn brightness(&self) -> XCapResult<f32> {
+        unsafe {
+            let mut count = 0;
+            GetNumberOfPhysicalMonitorsFromHMONITOR(self.h_monitor, &mut count)?;
+            let mut physical_monitors = vec![PHYSICAL_MONITOR::default(); count as usize];
+            GetPhysicalMonitorsFromHMONITOR(self.h_monitor, &mut physical_monitors)?;
+            let physical_monitors = guard(physical_monitors, |val| {
+                if let Err(err) = DestroyPhysicalMonitors(&val) {
+                    log::error!("DestroyPhysicalMonitors failed: {}", err);
+                }
+            });
+
+            // 镜像时一个 HMONITOR 对应多个物理显示器，取第一个支持 DDC/CI 的
+            for physical_monitor in physical_monitors.iter() {
+                let (mut min, mut current, mut max) = (0, 0, 0);
+                let result = GetMonitorBrightness(
+                    physical_monitor.hPhysicalMonitor,
+                    &mut min,
+                    &mut current,
+                    &mut max,
+                );
+                if result != 0 && max > min {
+                    return Ok(current.saturating_sub(min) as f32 / (max - min) as f32);
+                }
+            }
+        }
+
+        Err(XCapError::new(format!(
+            "Monitor {} does not support DDC/CI brightness",
+            self.name
+        )))
+    }
+
+    pub fn gamma_ramp(&self) -> XCapResult<GammaRamp> {
+        unsafe {
+            let scope_guard_hdc = guard(
+                CreateDCW(
+                    PCWSTR(self.monitor_info_ex_w.szDevice.as_ptr()),
+                    PCWSTR(self.monitor_info_ex_w.szDevice.as_ptr()),
+                    PCWSTR(ptr::null()),
+                    None,
+                ),
+                |val| {
+                    if !DeleteDC(val).as_bool() {
+                        log::error!("DeleteDC {:?} failed", val)
+                    }
+                },
+            );
+
+            // 红、绿、蓝各 256 项
+            let mut ramp = [[0u16; 256]; 3];
+            GetDeviceGammaRamp(*scope_guard_hdc, ramp.as_mut_ptr().cast()).ok()?;
+
+            Ok(GammaRamp {
+                red: ramp[0].to_vec(),
+                green: ramp[1].to_vec(),
+                blue: ramp[2].to_vec(),
+            })
+        }
+    }
+
     pub fn video_recorder(&self) -> XCapResult<ImplVideoRecorder> {
         ImplVideoRecorder::new(self.h_monitor)
     }
+
+    pub fn video_recorder_on(&self, gpu_device: &GpuDevice) -> XCapResult<ImplVideoRecorder> {
+        ImplVideoRecorder::with_device(self.h_monitor, gpu_device.d3d11_device.clone())
+    }
 }
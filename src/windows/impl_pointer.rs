@@ -0,0 +1,26 @@
+use windows::Win32::{Foundation::POINT, UI::WindowsAndMessaging::GetCursorPos};
+
+use crate::error::XCapResult;
+
+/// Windows moves one cursor with every mouse.
+#[derive(Debug, Clone)]
+pub(crate) struct ImplPointer {
+    pub id: u32,
+    pub name: String,
+}
+
+impl ImplPointer {
+    pub fn all() -> XCapResult<Vec<ImplPointer>> {
+        Ok(vec![ImplPointer {
+            id: 0,
+            name: String::from("Cursor"),
+        }])
+    }
+
+    pub fn position(&self) -> XCapResult<(i32, i32)> {
+        let mut point = POINT::default();
+        unsafe { GetCursorPos(&mut point)? };
+
+        Ok((point.x, point.y))
+    }
+}
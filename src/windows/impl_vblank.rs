@@ -0,0 +1,46 @@
+use windows::Win32::Graphics::Dxgi::{CreateDXGIFactory1, IDXGIFactory1, IDXGIOutput};
+
+use crate::error::{XCapError, XCapResult};
+
+use super::impl_monitor::ImplMonitor;
+
+/// Waits for the vblank of the DXGI output showing a monitor.
+#[derive(Debug)]
+pub(crate) struct ImplVblank {
+    output: IDXGIOutput,
+}
+
+unsafe impl Send for ImplVblank {}
+
+impl ImplVblank {
+    pub fn new(impl_monitor: &ImplMonitor) -> XCapResult<ImplVblank> {
+        unsafe {
+            let factory = CreateDXGIFactory1::<IDXGIFactory1>()?;
+
+            let mut adapter_index = 0;
+            while let Ok(adapter) = factory.EnumAdapters1(adapter_index) {
+                adapter_index += 1;
+
+                let mut output_index = 0;
+                while let Ok(output) = adapter.EnumOutputs(output_index) {
+                    output_index += 1;
+
+                    if output.GetDesc()?.Monitor == impl_monitor.h_monitor {
+                        return Ok(ImplVblank { output });
+                    }
+                }
+            }
+        }
+
+        Err(XCapError::new(format!(
+            "No DXGI output shows monitor {}",
+            impl_monitor.name
+        )))
+    }
+
+    pub fn wait(&self) -> XCapResult<()> {
+        unsafe { self.output.WaitForVBlank()? };
+
+        Ok(())
+    }
+}
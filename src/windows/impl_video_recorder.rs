@@ -1,19 +1,33 @@
-use std::{slice, sync::Arc};
+use std::{
+    ffi::c_void,
+    slice,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
 
+use image::RgbaImage;
 use windows::{
-    core::Interface,
+    core::{Interface, HRESULT},
     Win32::{
         Foundation::HMODULE,
         Graphics::{
             Direct3D::D3D_DRIVER_TYPE_HARDWARE,
             Direct3D11::{
                 D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Resource,
-                ID3D11Texture2D, D3D11_CPU_ACCESS_READ, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
-                D3D11_CREATE_DEVICE_SINGLETHREADED, D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ,
-                D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+                ID3D11Texture2D, D3D11_BIND_SHADER_RESOURCE, D3D11_CPU_ACCESS_READ,
+                D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_CREATE_DEVICE_SINGLETHREADED,
+                D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ, D3D11_RESOURCE_MISC_SHARED,
+                D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11_USAGE_STAGING,
             },
             Dxgi::{
+                Common::{
+                    DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R10G10B10A2_UNORM,
+                    DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_MODE_ROTATION_ROTATE180,
+                    DXGI_MODE_ROTATION_ROTATE270, DXGI_MODE_ROTATION_ROTATE90,
+                },
                 IDXGIDevice, IDXGIOutput1, IDXGIOutputDuplication, IDXGIResource,
+                DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET,
                 DXGI_ERROR_WAIT_TIMEOUT, DXGI_OUTDUPL_FRAME_INFO,
             },
             Gdi::HMONITOR,
@@ -22,11 +36,15 @@ use windows::{
 };
 
 use crate::{
+    color::ColorSpace,
+    gpu::GpuTexture,
+    report::pixel_format,
+    utils::rotate_to_screen,
     video_recorder::{Frame, RecorderWaker},
     XCapError, XCapResult,
 };
 
-use super::utils::bgra_to_rgba;
+use super::utils::{bgra_to_rgba, map_access_denied};
 
 pub fn texture_to_frame(
     d3d_device: &ID3D11Device,
@@ -71,92 +89,272 @@ pub fn texture_to_frame(
             source_desc.Width,
             source_desc.Height,
             bgra_to_rgba(bgra.to_owned()),
-        ))
+        )
+        .with_color_space(ColorSpace::Srgb))
     }
 }
 
+/// Copy the duplicated `source_texture` to a shareable texture, the duplicated one is only valid
+/// until the frame is released.
+fn copy_to_texture(
+    d3d_device: &ID3D11Device,
+    d3d_context: &ID3D11DeviceContext,
+    source_texture: ID3D11Texture2D,
+) -> XCapResult<GpuTexture> {
+    unsafe {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        source_texture.GetDesc(&mut desc);
+        desc.BindFlags = D3D11_BIND_SHADER_RESOURCE.0 as u32;
+        desc.MiscFlags = D3D11_RESOURCE_MISC_SHARED.0 as u32;
+        desc.Usage = D3D11_USAGE_DEFAULT;
+        desc.CPUAccessFlags = 0;
+
+        let texture = {
+            let mut texture = None;
+            d3d_device.CreateTexture2D(&desc, None, Some(&mut texture))?;
+            texture.ok_or(XCapError::new("CreateTexture2D failed"))?
+        };
+        d3d_context.CopyResource(Some(&texture.cast()?), Some(&source_texture.cast()?));
+
+        Ok(GpuTexture {
+            width: desc.Width,
+            height: desc.Height,
+            timestamp: SystemTime::now(),
+            texture,
+        })
+    }
+}
+
+/// How many degrees clockwise the duplicated image must be rotated to match the screen, the
+/// duplication delivers the output in its native orientation.
+fn duplication_rotation(duplication: &IDXGIOutputDuplication) -> f32 {
+    match unsafe { duplication.GetDesc() }.Rotation {
+        DXGI_MODE_ROTATION_ROTATE90 => 90.0,
+        DXGI_MODE_ROTATION_ROTATE180 => 180.0,
+        DXGI_MODE_ROTATION_ROTATE270 => 270.0,
+        _ => 0.0,
+    }
+}
+
+fn rotate_frame(frame: Frame, rotation: f32) -> XCapResult<Frame> {
+    if rotation == 0.0 {
+        return Ok(frame);
+    }
+
+    let image = RgbaImage::from_raw(frame.width, frame.height, frame.raw)
+        .ok_or_else(|| XCapError::new("Frame size does not match its data"))?;
+    let image = rotate_to_screen(image, rotation);
+
+    Ok(Frame::new(image.width(), image.height(), image.into_raw())
+        .with_color_space(frame.color_space))
+}
+
+/// How often a lost duplication is recreated before the error is returned.
+const RECOVER_ATTEMPTS: u32 = 10;
+
+/// Duplicate the output showing `h_monitor`.
+fn duplicate_output(
+    d3d_device: &ID3D11Device,
+    h_monitor: HMONITOR,
+) -> XCapResult<IDXGIOutputDuplication> {
+    unsafe {
+        let dxgi_device = d3d_device.cast::<IDXGIDevice>()?;
+        let adapter = dxgi_device.GetAdapter()?;
+
+        let mut output_index = 0;
+        loop {
+            let output = adapter.EnumOutputs(output_index)?;
+            output_index += 1;
+            if output.GetDesc()?.Monitor != h_monitor {
+                continue;
+            }
+
+            let output1 = output.cast::<IDXGIOutput1>()?;
+            // 安全桌面（UAC、锁屏）上没有权限复制输出
+            let duplication = output1.DuplicateOutput(&dxgi_device).map_err(|err| {
+                map_access_denied(err, "duplicate the output of the secure desktop")
+            })?;
+
+            let format = duplication.GetDesc().ModeDesc.Format;
+            let format = match format {
+                DXGI_FORMAT_B8G8R8A8_UNORM => String::from("B8G8R8A8_UNORM"),
+                DXGI_FORMAT_R10G10B10A2_UNORM => String::from("R10G10B10A2_UNORM"),
+                DXGI_FORMAT_R16G16B16A16_FLOAT => String::from("R16G16B16A16_FLOAT"),
+                format => format!("DXGI_FORMAT {}", format.0),
+            };
+            pixel_format("dxgi-duplication", format);
+
+            return Ok(duplication);
+        }
+    }
+}
+
+/// Whether the next duplicated frame of `h_monitor` has protected content masked out.
+pub(super) fn protected_content_masked(h_monitor: HMONITOR) -> XCapResult<bool> {
+    let recorder = ImplVideoRecorder::new(h_monitor)?;
+    let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+    let mut resource: Option<IDXGIResource> = None;
+
+    unsafe {
+        match recorder
+            .duplication
+            .AcquireNextFrame(100, &mut frame_info, &mut resource)
+        {
+            Ok(()) => recorder.duplication.ReleaseFrame()?,
+            Err(err) if err.code() == DXGI_ERROR_WAIT_TIMEOUT => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(frame_info.ProtectedContentMaskedOut.as_bool())
+}
+
 #[derive(Debug, Clone)]
 pub struct ImplVideoRecorder {
+    // HMONITOR 是指针，不能跨线程，按数值保存
+    h_monitor: usize,
     d3d_device: ID3D11Device,
     d3d_context: ID3D11DeviceContext,
     duplication: IDXGIOutputDuplication,
     recorder_waker: Arc<RecorderWaker>,
+    raw_orientation: bool,
+    // 应用注册的设备，设备丢失时由应用重建
+    shared_device: bool,
 }
 
 impl ImplVideoRecorder {
     pub fn new(h_monitor: HMONITOR) -> XCapResult<Self> {
+        Self::with_waker(h_monitor, None, Arc::new(RecorderWaker::new()))
+    }
+
+    /// Duplicate the output on a device registered by the application.
+    pub fn with_device(h_monitor: HMONITOR, d3d_device: ID3D11Device) -> XCapResult<Self> {
+        Self::with_waker(h_monitor, Some(d3d_device), Arc::new(RecorderWaker::new()))
+    }
+
+    /// Duplicate the output again, keeping the started or stopped state of `self`.
+    pub fn rebuild(&self) -> XCapResult<Self> {
+        let h_monitor = HMONITOR(self.h_monitor as *mut c_void);
+        let d3d_device = self.shared_device.then(|| self.d3d_device.clone());
+        let recorder = Self::with_waker(h_monitor, d3d_device, self.recorder_waker.clone())?;
+
+        Ok(recorder.with_raw_orientation(self.raw_orientation))
+    }
+
+    pub fn with_raw_orientation(mut self, raw_orientation: bool) -> Self {
+        self.raw_orientation = raw_orientation;
+        self
+    }
+
+    fn with_waker(
+        h_monitor: HMONITOR,
+        d3d_device: Option<ID3D11Device>,
+        recorder_waker: Arc<RecorderWaker>,
+    ) -> XCapResult<Self> {
         unsafe {
-            let mut d3d_device = None;
-            D3D11CreateDevice(
-                None,
-                D3D_DRIVER_TYPE_HARDWARE,
-                HMODULE::default(),
-                D3D11_CREATE_DEVICE_BGRA_SUPPORT | D3D11_CREATE_DEVICE_SINGLETHREADED,
-                None,
-                D3D11_SDK_VERSION,
-                Some(&mut d3d_device),
-                None,
-                None,
-            )?;
-
-            let d3d_device = d3d_device.ok_or(XCapError::new("Call D3D11CreateDevice failed"))?;
-            let dxgi_device = d3d_device.cast::<IDXGIDevice>()?;
+            let shared_device = d3d_device.is_some();
+            let d3d_device = match d3d_device {
+                Some(d3d_device) => d3d_device,
+                None => {
+                    let mut d3d_device = None;
+                    D3D11CreateDevice(
+                        None,
+                        D3D_DRIVER_TYPE_HARDWARE,
+                        HMODULE::default(),
+                        D3D11_CREATE_DEVICE_BGRA_SUPPORT | D3D11_CREATE_DEVICE_SINGLETHREADED,
+                        None,
+                        D3D11_SDK_VERSION,
+                        Some(&mut d3d_device),
+                        None,
+                        None,
+                    )?;
+
+                    d3d_device.ok_or(XCapError::new("Call D3D11CreateDevice failed"))?
+                }
+            };
             let d3d_context = d3d_device.GetImmediateContext()?;
+            let duplication = duplicate_output(&d3d_device, h_monitor)?;
+
+            Ok(Self {
+                h_monitor: h_monitor.0 as usize,
+                d3d_device,
+                d3d_context,
+                duplication,
+                recorder_waker,
+                raw_orientation: false,
+                shared_device,
+            })
+        }
+    }
 
-            let adapter = dxgi_device.GetAdapter()?;
+    /// Recover from a lost duplication in place: mode changes, fullscreen exclusive apps and
+    /// the secure desktop only invalidate the duplication, driver resets the whole device.
+    /// The output may not be available for a moment, so this retries for up to a second.
+    fn recover(&self, code: HRESULT) -> XCapResult<Self> {
+        if self.shared_device && code != DXGI_ERROR_ACCESS_LOST {
+            return Err(XCapError::new(
+                "The registered GPU device was removed, register a new one",
+            ));
+        }
 
-            let mut output_index = 0;
-            loop {
-                let output = adapter.EnumOutputs(output_index)?;
-                output_index += 1;
-                let output_desc = output.GetDesc()?;
+        let h_monitor = HMONITOR(self.h_monitor as *mut c_void);
+        let mut attempt = 0;
 
-                let output1 = output.cast::<IDXGIOutput1>()?;
-                let duplication = output1.DuplicateOutput(&dxgi_device)?;
+        loop {
+            let result = if code == DXGI_ERROR_ACCESS_LOST {
+                duplicate_output(&self.d3d_device, h_monitor).map(|duplication| Self {
+                    duplication,
+                    ..self.clone()
+                })
+            } else {
+                self.rebuild()
+            };
 
-                if output_desc.Monitor == h_monitor {
-                    return Ok(Self {
-                        d3d_device,
-                        d3d_context,
-                        duplication,
-                        recorder_waker: Arc::new(RecorderWaker::new()),
-                    });
+            attempt += 1;
+            match result {
+                Err(err) if attempt < RECOVER_ATTEMPTS => {
+                    log::debug!("Recover desktop duplication failed: {}", err);
+                    thread::sleep(Duration::from_millis(100));
                 }
+                result => return result,
             }
         }
     }
 
-    pub fn on_frame<F>(&self, on_frame: F) -> XCapResult<()>
+    /// Call `on_texture` with every duplicated frame until the stream stops.
+    fn on_duplicated<F>(&self, mut on_texture: F) -> XCapResult<()>
     where
-        F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+        F: FnMut(&Self, ID3D11Texture2D) -> XCapResult<()>,
     {
-        let duplication = self.duplication.clone();
-        let d3d_device = self.d3d_device.clone();
-        let d3d_context = self.d3d_context.clone();
-        let recorder_waker = self.recorder_waker.clone();
+        let mut recorder = self.clone();
 
         loop {
-            recorder_waker.wait()?;
+            recorder.recorder_waker.wait()?;
 
             let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
             let mut resource: Option<IDXGIResource> = None;
 
             unsafe {
+                let duplication = &recorder.duplication;
                 if let Err(err) = duplication.AcquireNextFrame(200, &mut frame_info, &mut resource)
                 {
                     // 尝试释放当前帧，不然不能获取到下一帧数据
                     let _ = duplication.ReleaseFrame();
-                    if err.code() != DXGI_ERROR_WAIT_TIMEOUT {
-                        break Err::<(), XCapError>(XCapError::new("DXGI_ERROR_UNSUPPORTED"));
+                    match err.code() {
+                        DXGI_ERROR_WAIT_TIMEOUT => {}
+                        code @ (DXGI_ERROR_ACCESS_LOST
+                        | DXGI_ERROR_DEVICE_REMOVED
+                        | DXGI_ERROR_DEVICE_RESET) => {
+                            log::info!("Desktop duplication lost: {}, recreating it", err);
+                            recorder = recorder.recover(code)?;
+                        }
+                        _ => break Err::<(), XCapError>(err.into()),
                     }
                 } else {
                     // 如何确定 AcquireNextFrame 执行成功
                     if frame_info.LastPresentTime != 0 {
                         let resource = resource.ok_or(XCapError::new("AcquireNextFrame failed"))?;
-                        let source_texture = resource.cast::<ID3D11Texture2D>()?;
-                        let frame = texture_to_frame(&d3d_device, &d3d_context, source_texture)?;
-
-                        on_frame(frame)?;
+                        on_texture(&recorder, resource.cast::<ID3D11Texture2D>()?)?;
                     }
 
                     // 最后释放帧，不然获取不到当前帧的数据
@@ -165,6 +363,35 @@ impl ImplVideoRecorder {
             }
         }
     }
+
+    pub fn on_frame<F>(&self, on_frame: F) -> XCapResult<()>
+    where
+        F: Fn(Frame) -> XCapResult<()> + Send + 'static,
+    {
+        self.on_duplicated(|recorder, source_texture| {
+            let mut frame =
+                texture_to_frame(&recorder.d3d_device, &recorder.d3d_context, source_texture)?;
+            if !recorder.raw_orientation {
+                frame = rotate_frame(frame, duplication_rotation(&recorder.duplication))?;
+            }
+
+            on_frame(frame)
+        })
+    }
+
+    pub fn on_texture<F>(&self, on_texture: F) -> XCapResult<()>
+    where
+        F: Fn(GpuTexture) -> XCapResult<()> + Send + 'static,
+    {
+        self.on_duplicated(|recorder, source_texture| {
+            on_texture(copy_to_texture(
+                &recorder.d3d_device,
+                &recorder.d3d_context,
+                source_texture,
+            )?)
+        })
+    }
+
     pub fn start(&self) -> XCapResult<()> {
         self.recorder_waker.wake()?;
 
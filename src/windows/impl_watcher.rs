@@ -0,0 +1,126 @@
+use std::{
+    cell::RefCell,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, WPARAM},
+    System::Threading::GetCurrentThreadId,
+    UI::{
+        Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK},
+        WindowsAndMessaging::{
+            DispatchMessageW, GetAncestor, GetMessageW, PostThreadMessageW, TranslateMessage,
+            CHILDID_SELF, EVENT_MAX, EVENT_MIN, GA_ROOT, MSG, OBJID_WINDOW, WINEVENT_OUTOFCONTEXT,
+            WINEVENT_SKIPOWNPROCESS, WM_QUIT,
+        },
+    },
+};
+
+thread_local! {
+    static EVENT_SENDER: RefCell<Option<Sender<()>>> = const { RefCell::new(None) };
+}
+
+unsafe extern "system" fn win_event_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    // 只关心顶层窗口本身的事件，忽略子控件、光标、caret 等
+    if id_object != OBJID_WINDOW.0 || id_child != CHILDID_SELF as i32 || hwnd.is_invalid() {
+        return;
+    }
+    if GetAncestor(hwnd, GA_ROOT) != hwnd {
+        return;
+    }
+
+    EVENT_SENDER.with(|sender| {
+        if let Some(sender) = sender.borrow().as_ref() {
+            let _ = sender.send(());
+        }
+    });
+}
+
+/// Wakes the [`Watcher`](crate::Watcher) up on WinEvents, the hook runs on its own thread
+/// with a message loop.
+pub(crate) struct ImplWatcher {
+    receiver: Receiver<()>,
+    thread_id: Option<u32>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+impl ImplWatcher {
+    pub fn new() -> ImplWatcher {
+        let (sender, receiver) = mpsc::channel();
+        let (thread_id_sender, thread_id_receiver) = mpsc::channel();
+
+        let join_handle = thread::spawn(move || unsafe {
+            EVENT_SENDER.with(|event_sender| *event_sender.borrow_mut() = Some(sender));
+
+            let hook = SetWinEventHook(
+                EVENT_MIN,
+                EVENT_MAX,
+                None,
+                Some(win_event_proc),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+            );
+            if hook.is_invalid() {
+                log::error!("SetWinEventHook failed, fallback to polling");
+                return;
+            }
+
+            let _ = thread_id_sender.send(GetCurrentThreadId());
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+
+            let _ = UnhookWinEvent(hook);
+        });
+
+        ImplWatcher {
+            receiver,
+            thread_id: thread_id_receiver.recv().ok(),
+            join_handle: Some(join_handle),
+        }
+    }
+
+    /// Block until a WinEvent arrives or `timeout` elapses, returns whether something changed.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        if self.thread_id.is_none() {
+            thread::sleep(timeout);
+            return false;
+        }
+
+        match self.receiver.recv_timeout(timeout) {
+            Ok(()) => {
+                while self.receiver.try_recv().is_ok() {}
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl Drop for ImplWatcher {
+    fn drop(&mut self) {
+        if let Some(thread_id) = self.thread_id {
+            unsafe {
+                let _ = PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+            }
+        }
+
+        if let Some(join_handle) = self.join_handle.take() {
+            let _ = join_handle.join();
+        }
+    }
+}
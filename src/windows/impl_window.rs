@@ -1,31 +1,68 @@
 use core::slice;
-use std::{cmp::Ordering, ffi::c_void, mem, ptr};
+use std::{
+    cmp::Ordering,
+    ffi::{c_void, OsString},
+    mem,
+    os::windows::ffi::OsStringExt,
+    path::PathBuf,
+    ptr,
+};
 
 use image::RgbaImage;
+use scopeguard::guard;
 use widestring::U16CString;
 use windows::{
-    core::{HSTRING, PCWSTR},
+    core::{HSTRING, PCWSTR, PWSTR},
+    Wdk::System::Threading::{NtQueryInformationProcess, ProcessCommandLineInformation},
     Win32::{
-        Foundation::{GetLastError, BOOL, HANDLE, HWND, LPARAM, MAX_PATH, RECT, TRUE, WPARAM},
+        Foundation::{
+            CloseHandle, GetLastError, LocalFree, BOOL, HANDLE, HLOCAL, HWND, LPARAM, MAX_PATH,
+            RECT, TRUE, UNICODE_STRING, WPARAM,
+        },
         Graphics::{
             Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS},
-            Gdi::{IsRectEmpty, MonitorFromWindow, MONITOR_DEFAULTTONEAREST},
+            Gdi::{
+                CreateRectRgn, DeleteObject, GetRegionData, GetWindowRgn, IsRectEmpty,
+                MonitorFromWindow, MONITOR_DEFAULTTONEAREST, RGNDATA, RGNDATAHEADER, RGN_ERROR,
+            },
         },
+        Security::{GetTokenInformation, TokenIsAppContainer, TOKEN_QUERY},
         Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW},
         System::{
             ProcessStatus::{GetModuleBaseNameW, GetModuleFileNameExW},
-            Threading::{GetCurrentProcess, PROCESS_QUERY_LIMITED_INFORMATION},
+            SystemInformation::{
+                IMAGE_FILE_MACHINE, IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM64,
+                IMAGE_FILE_MACHINE_ARMNT, IMAGE_FILE_MACHINE_I386, IMAGE_FILE_MACHINE_UNKNOWN,
+            },
+            Threading::{
+                GetCurrentProcess, GetProcessInformation, IsWow64Process2, OpenProcessToken,
+                ProcessMachineTypeInfo, QueryFullProcessImageNameW, PROCESS_MACHINE_INFORMATION,
+                PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+        },
+        UI::Input::KeyboardAndMouse::{
+            SendInput, INPUT, INPUT_0, INPUT_MOUSE, MOUSEEVENTF_WHEEL, MOUSEINPUT,
         },
+        UI::Shell::CommandLineToArgvW,
         UI::WindowsAndMessaging::{
-            EnumWindows, GetClassNameW, GetForegroundWindow, GetWindowInfo, GetWindowLongPtrW,
-            GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible, IsZoomed,
-            SendMessageTimeoutW, GWL_EXSTYLE, SMTO_NORMAL, WINDOWINFO, WINDOW_EX_STYLE, WM_GETTEXT,
-            WM_GETTEXTLENGTH, WS_EX_TOOLWINDOW,
+            EnumWindows, GetClassNameW, GetForegroundWindow, GetLayeredWindowAttributes, GetWindow,
+            GetWindowInfo, GetWindowLongPtrW, GetWindowThreadProcessId, IsIconic, IsWindow,
+            IsWindowVisible, IsZoomed, SendMessageTimeoutW, SetCursorPos, GWL_EXSTYLE, GW_OWNER,
+            LAYERED_WINDOW_ATTRIBUTES_FLAGS, LWA_ALPHA, SMTO_NORMAL, WHEEL_DELTA, WINDOWINFO,
+            WINDOW_EX_STYLE, WM_GETTEXT, WM_GETTEXTLENGTH, WS_CAPTION, WS_EX_APPWINDOW,
+            WS_EX_LAYERED, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT,
         },
     },
 };
 
-use crate::{error::XCapResult, platform::utils::log_last_error};
+use crate::{
+    backend::Backend,
+    enumeration::Enumeration,
+    error::{XCapError, XCapResult},
+    platform::utils::log_last_error,
+    utils::thumbnail,
+    ProcessArch, ProcessInfo, Region, WindowKind, WindowShape,
+};
 
 use super::{
     capture::capture_window,
@@ -40,6 +77,7 @@ pub(crate) struct ImplWindow {
     pub window_info: WINDOWINFO,
     pub id: u32,
     pub title: String,
+    pub title_bytes: Vec<u8>,
     pub app_name: String,
     pub pid: u32,
     pub current_monitor: ImplMonitor,
@@ -50,9 +88,22 @@ pub(crate) struct ImplWindow {
     pub height: u32,
     pub is_minimized: bool,
     pub is_maximized: bool,
+    pub is_fullscreen: bool,
     pub is_focused: bool,
+    pub is_xwayland: bool,
+    pub kind: WindowKind,
+    pub skip_taskbar: bool,
+    pub skip_pager: bool,
+    pub is_visible: bool,
+    pub owner_id: Option<u32>,
 }
 
+// SAFETY: HWND is a handle into the window manager's table valid in the whole session, not a
+// pointer xcap dereferences. xcap only queries and captures the window, which any thread may
+// do, it never sends messages that must come from the owning thread. The other fields are
+// plain data and an ImplMonitor, which is Send.
+unsafe impl Send for ImplWindow {}
+
 fn is_window_cloaked(hwnd: HWND) -> bool {
     unsafe {
         let mut cloaked = 0u32;
@@ -73,6 +124,88 @@ fn is_window_cloaked(hwnd: HWND) -> bool {
     }
 }
 
+/// Visible, not minimized, not cloaked (other virtual desktops are cloaked) and not a fully
+/// transparent layered window.
+fn is_window_visible(hwnd: HWND, window_info: &WINDOWINFO) -> bool {
+    unsafe {
+        if !IsWindowVisible(hwnd).as_bool() || IsIconic(hwnd).as_bool() || is_window_cloaked(hwnd) {
+            return false;
+        }
+
+        if !window_info.dwExStyle.contains(WS_EX_LAYERED) {
+            return true;
+        }
+
+        let mut alpha = 255u8;
+        let mut flags = LAYERED_WINDOW_ATTRIBUTES_FLAGS::default();
+        // UpdateLayeredWindow 创建的窗口会失败，此时按可见处理
+        if GetLayeredWindowAttributes(hwnd, None, Some(&mut alpha), Some(&mut flags)).is_err() {
+            return true;
+        }
+
+        !flags.contains(LWA_ALPHA) || alpha != 0
+    }
+}
+
+fn get_class_name(hwnd: HWND) -> String {
+    unsafe {
+        let mut lp_class_name = [0u16; MAX_PATH as usize];
+        let lp_class_name_length = GetClassNameW(hwnd, &mut lp_class_name) as usize;
+
+        U16CString::from_vec_truncate(&lp_class_name[0..lp_class_name_length])
+            .to_string()
+            .unwrap_or_default()
+    }
+}
+
+/// Whether the taskbar and Alt-Tab skip the window.
+fn is_skip_taskbar(hwnd: HWND, window_info: &WINDOWINFO) -> bool {
+    let ex_style = window_info.dwExStyle;
+    if ex_style.contains(WS_EX_APPWINDOW) {
+        return false;
+    }
+
+    // 工具窗口和有 owner 的窗口不显示在任务栏
+    let has_owner = unsafe { GetWindow(hwnd, GW_OWNER) }.is_ok_and(|owner| !owner.is_invalid());
+    ex_style.contains(WS_EX_TOOLWINDOW) || has_owner
+}
+
+/// Borderless and covering its whole monitor, like games and full screen video players.
+fn is_fullscreen(window_info: &WINDOWINFO, impl_monitor: &ImplMonitor) -> bool {
+    let rc_window = window_info.rcWindow;
+    // 最大化窗口有标题栏，并且不盖住任务栏
+    !window_info.dwStyle.contains(WS_CAPTION)
+        && rc_window.left <= impl_monitor.x
+        && rc_window.top <= impl_monitor.y
+        && rc_window.right >= impl_monitor.x + impl_monitor.width as i32
+        && rc_window.bottom >= impl_monitor.y + impl_monitor.height as i32
+}
+
+fn get_window_kind(hwnd: HWND, window_info: &WINDOWINFO) -> WindowKind {
+    match get_class_name(hwnd).as_str() {
+        "Shell_TrayWnd" | "Shell_SecondaryTrayWnd" => return WindowKind::Dock,
+        "Progman" | "WorkerW" => return WindowKind::Desktop,
+        "tooltips_class32" => return WindowKind::Tooltip,
+        // 系统菜单和对话框的窗口类
+        "#32768" => return WindowKind::Menu,
+        "#32770" => return WindowKind::Dialog,
+        _ => {}
+    }
+
+    let ex_style = window_info.dwExStyle;
+    if ex_style.contains(WS_EX_TOOLWINDOW) {
+        return WindowKind::Utility;
+    }
+
+    // 有 owner 且不强制显示在任务栏的窗口，一般是对话框
+    let has_owner = unsafe { GetWindow(hwnd, GW_OWNER) }.is_ok_and(|owner| !owner.is_invalid());
+    if has_owner && !ex_style.contains(WS_EX_APPWINDOW) {
+        return WindowKind::Dialog;
+    }
+
+    WindowKind::Normal
+}
+
 // https://webrtc.googlesource.com/src.git/+/refs/heads/main/modules/desktop_capture/win/window_capture_utils.cc#52
 fn is_valid_window(hwnd: HWND) -> bool {
     unsafe {
@@ -88,15 +221,7 @@ fn is_valid_window(hwnd: HWND) -> bool {
         //   return TRUE;
         // }
 
-        let mut lp_class_name = [0u16; MAX_PATH as usize];
-        let lp_class_name_length = GetClassNameW(hwnd, &mut lp_class_name) as usize;
-        if lp_class_name_length < 1 {
-            return false;
-        }
-
-        let class_name = U16CString::from_vec_truncate(&lp_class_name[0..lp_class_name_length])
-            .to_string()
-            .unwrap_or_default();
+        let class_name = get_class_name(hwnd);
         if class_name.is_empty() {
             return false;
         }
@@ -162,7 +287,7 @@ unsafe extern "system" fn enum_windows_proc(hwnd: HWND, state: LPARAM) -> BOOL {
     TRUE
 }
 
-fn get_window_title(hwnd: HWND) -> XCapResult<String> {
+fn get_window_title(hwnd: HWND) -> XCapResult<U16CString> {
     const TIMEOUT_MS: u32 = 500;
     // suggested by https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getwindowtexta#remarks
     unsafe {
@@ -193,9 +318,8 @@ fn get_window_title(hwnd: HWND) -> XCapResult<String> {
             TIMEOUT_MS,
             None,
         );
-        let window_title = U16CString::from_vec_truncate(wide_buffer).to_string()?;
-
-        Ok(window_title)
+        // 标题可能含有不成对的代理项，由调用方决定如何转换
+        Ok(U16CString::from_vec_truncate(wide_buffer))
     }
 }
 
@@ -332,7 +456,13 @@ impl ImplWindow {
 
             GetWindowInfo(hwnd, &mut window_info)?;
 
-            let title = get_window_title(hwnd)?;
+            let wide_title = get_window_title(hwnd)?;
+            let title = wide_title.to_string_lossy();
+            let title_bytes = wide_title
+                .as_slice()
+                .iter()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect();
             let pid = get_window_pid(hwnd);
             let app_name = get_app_name(pid)?;
 
@@ -340,16 +470,26 @@ impl ImplWindow {
             let rc_client = window_info.rcClient;
             let is_minimized = IsIconic(hwnd).as_bool();
             let is_maximized = IsZoomed(hwnd).as_bool();
+            let current_monitor = ImplMonitor::new(h_monitor)?;
+            let is_fullscreen = !is_minimized && is_fullscreen(&window_info, &current_monitor);
             let is_focused = GetForegroundWindow() == hwnd;
+            let kind = get_window_kind(hwnd, &window_info);
+            let skip_taskbar = is_skip_taskbar(hwnd, &window_info);
+            let is_visible = is_window_visible(hwnd, &window_info);
+            let owner_id = GetWindow(hwnd, GW_OWNER)
+                .ok()
+                .filter(|owner| !owner.is_invalid())
+                .map(|owner| owner.0 as u32);
 
             Ok(ImplWindow {
                 hwnd,
                 window_info,
                 id: hwnd.0 as u32,
                 title,
+                title_bytes,
                 app_name,
                 pid,
-                current_monitor: ImplMonitor::new(h_monitor)?,
+                current_monitor,
                 x: rc_client.left,
                 y: rc_client.top,
                 z,
@@ -357,12 +497,32 @@ impl ImplWindow {
                 height: (rc_client.bottom - rc_client.top) as u32,
                 is_minimized,
                 is_maximized,
+                is_fullscreen,
                 is_focused,
+                is_xwayland: false,
+                kind,
+                skip_taskbar,
+                skip_pager: skip_taskbar,
+                is_visible,
+                owner_id,
             })
         }
     }
 
+    pub fn backend() -> Backend {
+        Backend::Gdi
+    }
+
     pub fn all() -> XCapResult<Vec<ImplWindow>> {
+        Ok(ImplWindow::all_with_errors()?.into_logged_items())
+    }
+
+    pub fn all_with_override_redirect() -> XCapResult<Vec<ImplWindow>> {
+        // EnumWindows 已经包含菜单和弹出窗口
+        ImplWindow::all()
+    }
+
+    pub fn all_with_errors() -> XCapResult<Enumeration<ImplWindow>> {
         // (HWND, i32) 表示当前窗口以及层级，既（窗口，层级 z），i32 表示 max_z_order，既最大的窗口的 z 顺序
         // 窗口当前层级为 max_z_order - z
         let hwnds_mut_ptr: *mut (Vec<(HWND, i32)>, i32) = Box::into_raw(Box::default());
@@ -373,19 +533,173 @@ impl ImplWindow {
             Box::from_raw(hwnds_mut_ptr)
         };
 
-        let mut impl_windows = Vec::new();
+        let mut enumeration = Enumeration::default();
 
         let max_z_order = hwnds.1;
 
         for &(hwnd, z) in hwnds.0.iter() {
-            if let Ok(impl_window) = ImplWindow::new(hwnd, max_z_order - z) {
-                impl_windows.push(impl_window);
-            } else {
-                log::error!("ImplWindow::new({:?}) failed", hwnd);
+            match ImplWindow::new(hwnd, max_z_order - z) {
+                Ok(impl_window) => enumeration.items.push(impl_window),
+                Err(err) => enumeration.push_error(Some(hwnd.0 as u32), err),
+            }
+        }
+
+        Ok(enumeration)
+    }
+}
+
+fn get_exe_path(pid: u32) -> XCapResult<PathBuf> {
+    let scope_guard_handle = open_process(PROCESS_QUERY_LIMITED_INFORMATION, false, pid)?;
+
+    // 长路径最多 32767 个字符
+    let mut exe_path = vec![0u16; 32768];
+    let mut size = exe_path.len() as u32;
+    unsafe {
+        QueryFullProcessImageNameW(
+            *scope_guard_handle,
+            PROCESS_NAME_WIN32,
+            PWSTR(exe_path.as_mut_ptr()),
+            &mut size,
+        )?;
+    }
+
+    Ok(PathBuf::from(OsString::from_wide(
+        &exe_path[..size as usize],
+    )))
+}
+
+fn machine_arch(machine: IMAGE_FILE_MACHINE) -> ProcessArch {
+    match machine {
+        IMAGE_FILE_MACHINE_I386 => ProcessArch::X86,
+        IMAGE_FILE_MACHINE_AMD64 => ProcessArch::X86_64,
+        IMAGE_FILE_MACHINE_ARMNT => ProcessArch::Arm,
+        IMAGE_FILE_MACHINE_ARM64 => ProcessArch::Arm64,
+        _ => ProcessArch::Unknown,
+    }
+}
+
+fn get_process_info(pid: u32) -> XCapResult<ProcessInfo> {
+    let scope_guard_handle = open_process(PROCESS_QUERY_LIMITED_INFORMATION, false, pid)?;
+
+    let mut process_machine = IMAGE_FILE_MACHINE_UNKNOWN;
+    let mut native_machine = IMAGE_FILE_MACHINE_UNKNOWN;
+    unsafe {
+        IsWow64Process2(
+            *scope_guard_handle,
+            &mut process_machine,
+            Some(&mut native_machine),
+        )?;
+    }
+
+    // 非 WOW64 进程返回 UNKNOWN，ProcessMachineTypeInfo（Windows 11）还能识别 ARM64 上模拟的 x64 进程
+    let mut machine_information = PROCESS_MACHINE_INFORMATION::default();
+    let machine = match unsafe {
+        GetProcessInformation(
+            *scope_guard_handle,
+            ProcessMachineTypeInfo,
+            &mut machine_information as *mut PROCESS_MACHINE_INFORMATION as *mut c_void,
+            mem::size_of::<PROCESS_MACHINE_INFORMATION>() as u32,
+        )
+    } {
+        Ok(()) => machine_information.ProcessMachine,
+        Err(_) if process_machine != IMAGE_FILE_MACHINE_UNKNOWN => process_machine,
+        Err(_) => native_machine,
+    };
+    let arch = machine_arch(machine);
+
+    let is_sandboxed = unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(*scope_guard_handle, TOKEN_QUERY, &mut token)?;
+        let token = guard(token, |token| {
+            if let Err(err) = CloseHandle(token) {
+                log::error!("CloseHandle {:?} failed {:?}", token, err);
             }
+        });
+
+        let mut is_app_container = 0u32;
+        let mut length = 0;
+        GetTokenInformation(
+            *token,
+            TokenIsAppContainer,
+            Some(&mut is_app_container as *mut u32 as *mut c_void),
+            mem::size_of::<u32>() as u32,
+            &mut length,
+        )?;
+
+        is_app_container != 0
+    };
+
+    Ok(ProcessInfo {
+        arch,
+        is_sandboxed,
+        is_translated: machine_arch(native_machine) == ProcessArch::Arm64
+            && matches!(arch, ProcessArch::X86 | ProcessArch::X86_64),
+    })
+}
+
+fn get_cmdline(pid: u32) -> XCapResult<Vec<String>> {
+    let scope_guard_handle = open_process(PROCESS_QUERY_LIMITED_INFORMATION, false, pid)?;
+
+    unsafe {
+        // 第一次调用只为获取需要的长度，需要 Windows 8.1 及以上
+        let mut length = 0;
+        let _ = NtQueryInformationProcess(
+            *scope_guard_handle,
+            ProcessCommandLineInformation,
+            ptr::null_mut(),
+            0,
+            &mut length,
+        );
+        if length == 0 {
+            return Err(XCapError::new("NtQueryInformationProcess failed"));
+        }
+
+        // 按 UNICODE_STRING 的指针对齐
+        let mut buffer = vec![0u64; (length as usize).div_ceil(8)];
+        NtQueryInformationProcess(
+            *scope_guard_handle,
+            ProcessCommandLineInformation,
+            buffer.as_mut_ptr().cast(),
+            length,
+            &mut length,
+        )
+        .ok()?;
+
+        let unicode_string = &*(buffer.as_ptr() as *const UNICODE_STRING);
+        if unicode_string.Length == 0 {
+            return Ok(Vec::new());
         }
+        let command_line =
+            slice::from_raw_parts(unicode_string.Buffer.0, unicode_string.Length as usize / 2);
+        let command_line = U16CString::from_vec_truncate(command_line);
+
+        let mut argc = 0;
+        let argv = CommandLineToArgvW(PCWSTR(command_line.as_ptr()), &mut argc);
+        if argv.is_null() {
+            return Err(XCapError::new("CommandLineToArgvW failed"));
+        }
+
+        let args = slice::from_raw_parts(argv, argc as usize)
+            .iter()
+            .map(|arg| arg.to_string().unwrap_or_default())
+            .collect();
+        let _ = LocalFree(Some(HLOCAL(argv.cast())));
 
-        Ok(impl_windows)
+        Ok(args)
+    }
+}
+
+impl ImplWindow {
+    pub fn exe_path(&self) -> XCapResult<PathBuf> {
+        get_exe_path(self.pid)
+    }
+
+    pub fn cmdline(&self) -> XCapResult<Vec<String>> {
+        get_cmdline(self.pid)
+    }
+
+    pub fn process_info(&self) -> XCapResult<ProcessInfo> {
+        get_process_info(self.pid)
     }
 }
 
@@ -408,4 +722,128 @@ impl ImplWindow {
 
         capture_window(self.hwnd, scale_factor, &self.window_info)
     }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn capture_thumbnail(&self, max_width: u32, max_height: u32) -> XCapResult<RgbaImage> {
+        // PrintWindow 只能按窗口原始大小绘制，只能截图后再缩放
+        Ok(thumbnail(self.capture_image()?, max_width, max_height))
+    }
+}
+
+impl ImplWindow {
+    pub fn is_valid(&self) -> bool {
+        unsafe { IsWindow(Some(self.hwnd)).as_bool() }
+    }
+
+    #[cfg_attr(not(feature = "image"), allow(dead_code))]
+    pub fn scroll(&self, lines: i32) -> XCapResult<()> {
+        unsafe {
+            // 滚轮消息发给光标下的窗口，先把光标移到窗口中间
+            SetCursorPos(
+                self.x + self.width as i32 / 2,
+                self.y + self.height as i32 / 2,
+            )?;
+
+            let input = INPUT {
+                r#type: INPUT_MOUSE,
+                Anonymous: INPUT_0 {
+                    mi: MOUSEINPUT {
+                        // 负数向下滚动
+                        mouseData: (-lines * WHEEL_DELTA as i32) as u32,
+                        dwFlags: MOUSEEVENTF_WHEEL,
+                        ..Default::default()
+                    },
+                },
+            };
+
+            if SendInput(&[input], mem::size_of::<INPUT>() as i32) != 1 {
+                return Err(XCapError::new(format!(
+                    "SendInput failed: {:?}",
+                    GetLastError()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ImplWindow {
+    /// The window region set with `SetWindowRgn`, relative to the window rectangle, most
+    /// windows have none. Layered windows with `WS_EX_TRANSPARENT` let all clicks through.
+    pub fn shape(&self) -> XCapResult<WindowShape> {
+        let mut shape = WindowShape::rectangle(self.width, self.height);
+        if self.window_info.dwExStyle.contains(WS_EX_LAYERED)
+            && self.window_info.dwExStyle.contains(WS_EX_TRANSPARENT)
+        {
+            shape.input.clear();
+        }
+
+        unsafe {
+            let hrgn = CreateRectRgn(0, 0, 0, 0);
+            let hrgn = guard(hrgn, |val| {
+                if !DeleteObject(val.into()).as_bool() {
+                    log::error!("DeleteObject {:?} failed", val);
+                }
+            });
+            if GetWindowRgn(self.hwnd, *hrgn) == RGN_ERROR {
+                return Ok(shape);
+            }
+
+            let size = GetRegionData(*hrgn, 0, None);
+            // RGNDATA 后面紧跟着 nCount 个 RECT，按 RGNDATA 对齐分配
+            let mut buffer =
+                vec![RGNDATA::default(); (size as usize).div_ceil(mem::size_of::<RGNDATA>())];
+            if GetRegionData(*hrgn, size, Some(buffer.as_mut_ptr())) == 0 {
+                return Err(XCapError::new("GetRegionData failed"));
+            }
+
+            let header: RGNDATAHEADER = buffer[0].rdh;
+            let rects = slice::from_raw_parts(
+                buffer[0].Buffer.as_ptr() as *const RECT,
+                header.nCount as usize,
+            );
+            // 窗口区域相对窗口左上角，换算到客户区
+            let rc_window = self.window_info.rcWindow;
+            let offset_x = self.x - rc_window.left;
+            let offset_y = self.y - rc_window.top;
+            let regions: Vec<Region> = rects
+                .iter()
+                .filter_map(|rect| {
+                    let left = (rect.left - offset_x).max(0) as u32;
+                    let top = (rect.top - offset_y).max(0) as u32;
+                    let right = (rect.right - offset_x).max(0) as u32;
+                    let bottom = (rect.bottom - offset_y).max(0) as u32;
+                    Region::new(
+                        left,
+                        top,
+                        right.saturating_sub(left),
+                        bottom.saturating_sub(top),
+                    )
+                    .clamp(self.width, self.height)
+                })
+                .collect();
+
+            if !shape.input.is_empty() {
+                shape.input = regions.clone();
+            }
+            shape.bounding = regions;
+        }
+
+        Ok(shape)
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl ImplWindow {
+    pub fn raw_window_handle(&self) -> XCapResult<raw_window_handle::RawWindowHandle> {
+        use std::num::NonZeroIsize;
+
+        use raw_window_handle::{RawWindowHandle, Win32WindowHandle};
+
+        let hwnd = NonZeroIsize::new(self.hwnd.0 as isize)
+            .ok_or(XCapError::new("Window handle is null"))?;
+
+        Ok(RawWindowHandle::Win32(Win32WindowHandle::new(hwnd)))
+    }
 }
@@ -2,5 +2,8 @@ mod capture;
 mod utils;
 
 pub mod impl_monitor;
+pub mod impl_pointer;
+pub mod impl_vblank;
 pub mod impl_video_recorder;
+pub mod impl_watcher;
 pub mod impl_window;
@@ -13,7 +13,7 @@ use windows::{
             DISPLAYCONFIG_SOURCE_DEVICE_NAME, DISPLAYCONFIG_TARGET_DEVICE_NAME,
             QDC_ONLY_ACTIVE_PATHS,
         },
-        Foundation::{CloseHandle, FreeLibrary, GetLastError, HANDLE, HMODULE},
+        Foundation::{CloseHandle, FreeLibrary, GetLastError, E_ACCESSDENIED, HANDLE, HMODULE},
         Graphics::Gdi::MONITORINFOEXW,
         System::{
             LibraryLoader::{GetProcAddress, LoadLibraryW},
@@ -23,7 +23,10 @@ use windows::{
     },
 };
 
-use crate::{error::XCapResult, XCapError};
+use crate::{
+    error::{Remediation, XCapResult},
+    XCapError,
+};
 
 pub(super) fn get_build_number() -> u32 {
     unsafe {
@@ -145,13 +148,23 @@ pub(super) fn load_library(
     }
 }
 
+/// `E_ACCESSDENIED` means the target runs at a higher integrity level than the app.
+pub(super) fn map_access_denied<S: ToString>(err: windows::core::Error, reason: S) -> XCapError {
+    if err.code() == E_ACCESSDENIED {
+        XCapError::permission_denied(reason, Remediation::IntegrityLevel)
+    } else {
+        err.into()
+    }
+}
+
 pub(super) fn open_process(
     dw_desired_access: PROCESS_ACCESS_RIGHTS,
     b_inherit_handle: bool,
     dw_process_id: u32,
 ) -> XCapResult<ScopeGuard<HANDLE, impl FnOnce(HANDLE)>> {
     unsafe {
-        let handle = OpenProcess(dw_desired_access, b_inherit_handle, dw_process_id)?;
+        let handle = OpenProcess(dw_desired_access, b_inherit_handle, dw_process_id)
+            .map_err(|err| map_access_denied(err, format!("open process {}", dw_process_id)))?;
 
         if handle.is_invalid() {
             return Err(XCapError::new(format!(
@@ -0,0 +1,388 @@
+//! A harness for golden image tests. Every test starts its own headless Xvfb server, paints
+//! known patterns with plain X11 requests, captures them through the public API, and compares
+//! the captures with the PNG files in `tests/golden`.
+//!
+//! Tests are skipped when Xvfb is not installed, set `XCAP_GOLDEN_REQUIRED=1` to fail instead,
+//! e.g. on CI. Set `XCAP_UPDATE_GOLDEN=1` to overwrite the golden images with the captures.
+
+use std::{
+    env,
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind},
+    os::fd::FromRawFd,
+    path::PathBuf,
+    process::{self, Child, Command, Stdio},
+    sync::Mutex,
+};
+
+use image::{Rgba, RgbaImage};
+use xcb::{
+    x::{
+        Atom, ChangeProperty, ChangeWindowAttributes, ClearArea, CreateGc, CreatePixmap,
+        CreateWindow, Cw, Drawable, Gcontext, GetInputFocus, ImageFormat, ImageOrder, InternAtom,
+        MapWindow, Pixmap, PropMode, PutImage, Window, WindowClass, ATOM_CARDINAL, ATOM_STRING,
+        ATOM_WINDOW, ATOM_WM_NAME, COPY_FROM_PARENT,
+    },
+    Connection, Xid,
+};
+
+pub const SCREEN_WIDTH: u32 = 320;
+pub const SCREEN_HEIGHT: u32 = 240;
+
+// DISPLAY 是进程级的环境变量，测试需要一个一个执行
+static X_SERVER_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct XServer {
+    child: Child,
+    conn: Connection,
+    root: Window,
+    depth: u8,
+    /// The channel masks of the root visual.
+    masks: [u32; 3],
+    bits_per_pixel: u8,
+    byte_order: ImageOrder,
+    clients: Vec<Window>,
+}
+
+impl XServer {
+    fn start(depth: u8) -> Option<XServer> {
+        let mut fds = [0; 2];
+        assert_eq!(
+            unsafe { libc::pipe(fds.as_mut_ptr()) },
+            0,
+            "Create pipe failed"
+        );
+        let [read_fd, write_fd] = fds;
+
+        // -displayfd 让 Xvfb 自己挑一个空闲的 display，准备好后写回编号
+        let child = Command::new("Xvfb")
+            .args([
+                "-displayfd",
+                &write_fd.to_string(),
+                "-screen",
+                "0",
+                &format!("{}x{}x{}", SCREEN_WIDTH, SCREEN_HEIGHT, depth),
+                "-nolisten",
+                "tcp",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let reader = unsafe {
+            libc::close(write_fd);
+            File::from_raw_fd(read_fd)
+        };
+
+        let child = match child {
+            Ok(child) => child,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                if env::var_os("XCAP_GOLDEN_REQUIRED").is_some() {
+                    panic!("Xvfb is required but not installed");
+                }
+                eprintln!("Xvfb is not installed, skipping golden image test");
+                return None;
+            }
+            Err(err) => panic!("Start Xvfb failed: {}", err),
+        };
+
+        let mut display = String::new();
+        BufReader::new(reader)
+            .read_line(&mut display)
+            .expect("Read Xvfb display failed");
+        let display = format!(":{}", display.trim());
+        assert_ne!(display, ":", "Xvfb exited before it was ready");
+
+        // 强制使用 X11 后端
+        env::set_var("DISPLAY", &display);
+        env::set_var("XDG_SESSION_TYPE", "x11");
+        env::remove_var("WAYLAND_DISPLAY");
+
+        let (conn, screen_num) =
+            Connection::connect(Some(&display)).expect("Connect to Xvfb failed");
+        let setup = conn.get_setup();
+        let screen = setup
+            .roots()
+            .nth(screen_num as usize)
+            .expect("Xvfb has no screen");
+        let visual = screen
+            .allowed_depths()
+            .flat_map(|depth| depth.visuals())
+            .find(|visual| visual.visual_id() == screen.root_visual())
+            .expect("Root visual not found");
+        let bits_per_pixel = setup
+            .pixmap_formats()
+            .iter()
+            .find(|format| format.depth() == screen.root_depth())
+            .expect("Pixmap format not found")
+            .bits_per_pixel();
+
+        Some(XServer {
+            root: screen.root(),
+            depth: screen.root_depth(),
+            masks: [visual.red_mask(), visual.green_mask(), visual.blue_mask()],
+            bits_per_pixel,
+            byte_order: setup.image_byte_order(),
+            child,
+            conn,
+            clients: Vec::new(),
+        })
+    }
+
+    fn encode_pixel(&self, pixel: &Rgba<u8>) -> u32 {
+        self.masks
+            .iter()
+            .zip(pixel.0)
+            .map(|(mask, channel)| {
+                let bits = mask.count_ones();
+                (channel as u32 >> (8 - bits)) << mask.trailing_zeros()
+            })
+            .fold(0, |value, channel| value | channel)
+    }
+
+    /// A pixmap of the root depth holding `image`.
+    fn create_pixmap(&self, image: &RgbaImage) -> Pixmap {
+        let pixmap = self.conn.generate_id();
+        let gc: Gcontext = self.conn.generate_id();
+        self.conn.send_request(&CreatePixmap {
+            depth: self.depth,
+            pid: pixmap,
+            drawable: Drawable::Window(self.root),
+            width: image.width() as u16,
+            height: image.height() as u16,
+        });
+        self.conn.send_request(&CreateGc {
+            cid: gc,
+            drawable: Drawable::Pixmap(pixmap),
+            value_list: &[],
+        });
+
+        let bytes_per_pixel = self.bits_per_pixel as usize / 8;
+        // 分段上传，避免超过请求的最大长度
+        for (index, rows) in image
+            .as_raw()
+            .chunks(image.width() as usize * 4 * 32)
+            .enumerate()
+        {
+            let mut data = Vec::with_capacity(rows.len() / 4 * bytes_per_pixel);
+            for pixel in rows.chunks_exact(4) {
+                let value = self.encode_pixel(&Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]));
+                match self.byte_order {
+                    ImageOrder::LsbFirst => {
+                        data.extend_from_slice(&value.to_le_bytes()[..bytes_per_pixel])
+                    }
+                    ImageOrder::MsbFirst => {
+                        data.extend_from_slice(&value.to_be_bytes()[4 - bytes_per_pixel..])
+                    }
+                }
+            }
+
+            self.conn.send_request(&PutImage {
+                format: ImageFormat::ZPixmap,
+                drawable: Drawable::Pixmap(pixmap),
+                gc,
+                width: image.width() as u16,
+                height: (rows.len() / 4 / image.width() as usize) as u16,
+                dst_x: 0,
+                dst_y: (index * 32) as i16,
+                left_pad: 0,
+                depth: self.depth,
+                data: &data,
+            });
+        }
+
+        pixmap
+    }
+
+    fn intern_atom(&self, name: &str) -> Atom {
+        let cookie = self.conn.send_request(&InternAtom {
+            only_if_exists: false,
+            name: name.as_bytes(),
+        });
+        self.conn
+            .wait_for_reply(cookie)
+            .expect("Intern atom failed")
+            .atom()
+    }
+
+    fn sync(&self) {
+        self.conn.flush().expect("Flush X connection failed");
+        // GetInputFocus 的回复说明之前的请求都已经处理完
+        let cookie = self.conn.send_request(&GetInputFocus {});
+        self.conn
+            .wait_for_reply(cookie)
+            .expect("Sync with Xvfb failed");
+    }
+
+    /// Fill the whole screen with `image`.
+    pub fn paint_root(&self, image: &RgbaImage) {
+        let pixmap = self.create_pixmap(image);
+        self.conn.send_request(&ChangeWindowAttributes {
+            window: self.root,
+            value_list: &[Cw::BackPixmap(pixmap)],
+        });
+        self.conn.send_request(&ClearArea {
+            exposures: false,
+            window: self.root,
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        });
+        self.sync();
+    }
+
+    /// Map a window showing `image` at `(x, y)`. There is no window manager, so the window is
+    /// also added to `_NET_CLIENT_LIST_STACKING` for xcap to list it.
+    pub fn map_window(&mut self, title: &str, x: i16, y: i16, image: &RgbaImage) -> u32 {
+        let pixmap = self.create_pixmap(image);
+        let window: Window = self.conn.generate_id();
+        self.conn.send_request(&CreateWindow {
+            depth: self.depth,
+            wid: window,
+            parent: self.root,
+            x,
+            y,
+            width: image.width() as u16,
+            height: image.height() as u16,
+            border_width: 0,
+            class: WindowClass::InputOutput,
+            visual: COPY_FROM_PARENT,
+            value_list: &[Cw::BackPixmap(pixmap)],
+        });
+        self.conn.send_request(&ChangeProperty {
+            mode: PropMode::Replace,
+            window,
+            property: ATOM_WM_NAME,
+            r#type: ATOM_STRING,
+            data: title.as_bytes(),
+        });
+        self.conn.send_request(&ChangeProperty {
+            mode: PropMode::Replace,
+            window,
+            property: self.intern_atom("_NET_WM_PID"),
+            r#type: ATOM_CARDINAL,
+            data: &[process::id()],
+        });
+        self.conn.send_request(&MapWindow { window });
+
+        self.clients.push(window);
+        self.conn.send_request(&ChangeProperty {
+            mode: PropMode::Replace,
+            window: self.root,
+            property: self.intern_atom("_NET_CLIENT_LIST_STACKING"),
+            r#type: ATOM_WINDOW,
+            data: &self.clients,
+        });
+        self.sync();
+
+        window.resource_id()
+    }
+}
+
+impl Drop for XServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Run `test` against a fresh Xvfb server of `depth` bits, unless Xvfb is not installed.
+pub fn with_x_server(depth: u8, test: impl FnOnce(&mut XServer)) {
+    let _guard = X_SERVER_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some(mut x_server) = XServer::start(depth) {
+        test(&mut x_server);
+    }
+}
+
+/// Color bars over a gray ramp, framed by a one pixel red border with a green marker in the top
+/// left corner. Swapped channels change the bars, flips move the marker, off-by-one crops lose
+/// the border.
+pub fn test_card(width: u32, height: u32) -> RgbaImage {
+    const BARS: [[u8; 3]; 8] = [
+        [255, 255, 255],
+        [255, 255, 0],
+        [0, 255, 255],
+        [0, 255, 0],
+        [255, 0, 255],
+        [255, 0, 0],
+        [0, 0, 255],
+        [0, 0, 0],
+    ];
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let [r, g, b] = if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            [255, 0, 0]
+        } else if x < 9 && y < 9 {
+            [0, 255, 0]
+        } else if y < height * 2 / 3 {
+            BARS[(x * BARS.len() as u32 / width) as usize]
+        } else {
+            let gray = (x * 255 / (width - 1)) as u8;
+            [gray, gray, gray]
+        };
+
+        Rgba([r, g, b, 255])
+    })
+}
+
+/// A checkerboard of 8 pixel squares, as a background that differs from [`test_card`].
+pub fn checkerboard(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| match (x / 8 + y / 8) % 2 {
+        0 => Rgba([32, 64, 96, 255]),
+        _ => Rgba([224, 192, 160, 255]),
+    })
+}
+
+/// Compare `actual` with `tests/golden/<name>.png`, every channel may differ by `tolerance`.
+/// On mismatch the capture is saved next to the test binaries for inspection.
+pub fn assert_golden(name: &str, actual: &RgbaImage, tolerance: u8) {
+    let golden_path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden"]
+        .iter()
+        .collect::<PathBuf>()
+        .join(format!("{}.png", name));
+
+    if env::var_os("XCAP_UPDATE_GOLDEN").is_some() {
+        actual.save(&golden_path).expect("Save golden image failed");
+        return;
+    }
+
+    let expected = image::open(&golden_path)
+        .unwrap_or_else(|err| panic!("Open {} failed: {}", golden_path.display(), err))
+        .to_rgba8();
+    let actual_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+
+    if actual.dimensions() != expected.dimensions() {
+        actual.save(&actual_path).expect("Save capture failed");
+        panic!(
+            "{}: captured {:?}, expected {:?}, see {}",
+            name,
+            actual.dimensions(),
+            expected.dimensions(),
+            actual_path.display()
+        );
+    }
+
+    let mismatches: Vec<_> = actual
+        .enumerate_pixels()
+        .zip(expected.pixels())
+        .filter(|((.., a), b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > tolerance))
+        .map(|((x, y, a), b)| (x, y, a.0, b.0))
+        .collect();
+
+    if let Some((x, y, actual_pixel, expected_pixel)) = mismatches.first() {
+        actual.save(&actual_path).expect("Save capture failed");
+        panic!(
+            "{}: {} pixels differ, first at ({}, {}) is {:?} instead of {:?}, see {}",
+            name,
+            mismatches.len(),
+            x,
+            y,
+            actual_pixel,
+            expected_pixel,
+            actual_path.display()
+        );
+    }
+}
@@ -0,0 +1,44 @@
+#![cfg(all(target_os = "linux", feature = "image"))]
+
+mod common;
+
+use common::{assert_golden, checkerboard, test_card, with_x_server, SCREEN_HEIGHT, SCREEN_WIDTH};
+use xcap::{Monitor, Window};
+
+#[test]
+fn monitor_24bit() {
+    with_x_server(24, |x_server| {
+        x_server.paint_root(&test_card(SCREEN_WIDTH, SCREEN_HEIGHT));
+
+        let monitor = Monitor::all().unwrap().remove(0);
+        assert_golden("monitor", &monitor.capture_image().unwrap(), 0);
+    });
+}
+
+#[test]
+fn monitor_16bit() {
+    with_x_server(16, |x_server| {
+        x_server.paint_root(&test_card(SCREEN_WIDTH, SCREEN_HEIGHT));
+
+        // RGB565 丢掉了低位
+        let monitor = Monitor::all().unwrap().remove(0);
+        assert_golden("monitor", &monitor.capture_image().unwrap(), 8);
+    });
+}
+
+#[test]
+fn window_24bit() {
+    with_x_server(24, |x_server| {
+        x_server.paint_root(&checkerboard(SCREEN_WIDTH, SCREEN_HEIGHT));
+        let id = x_server.map_window("xcap golden", 37, 21, &test_card(120, 90));
+
+        let window = Window::all()
+            .unwrap()
+            .into_iter()
+            .find(|window| window.id() == id)
+            .expect("Window not listed");
+        assert_eq!(window.title(), "xcap golden");
+        assert_eq!((window.x(), window.y()), (37, 21));
+        assert_golden("window", &window.capture_image().unwrap(), 0);
+    });
+}